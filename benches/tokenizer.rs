@@ -0,0 +1,44 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::hint::black_box;
+use std::str::FromStr;
+use transdoc::syntax::Chapter;
+use transdoc::tokenizer::get_tokens;
+
+/// Chapter of plain prose with a gloss and a translation per sentence,
+/// the text the run-of-characters fast path is for
+fn chapter(sentences: usize) -> String {
+    let mut src = String::from("title = Bench\n\n");
+    for i in 0..sentences {
+        src.push_str(&format!(
+            "@ {i}\nThe quick brown fox jumps over the << lazy = slow >> dog, again and again.\n---\nEl rápido zorro marrón salta sobre el perro perezoso.\n\n"
+        ));
+    }
+    src
+}
+
+fn tokenize(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tokenize");
+    for n in [100, 1000] {
+        let src = chapter(n);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &src, |b, src| {
+            b.iter(|| get_tokens(black_box(src)).len())
+        });
+    }
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for n in [100, 1000] {
+        let src = chapter(n);
+        group.throughput(Throughput::Bytes(src.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(n), &src, |b, src| {
+            b.iter(|| Chapter::from_str(black_box(src)).unwrap().sentences.len())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tokenize, parse);
+criterion_main!(benches);
@@ -0,0 +1,196 @@
+use crate::syntax::{Chapter, Sentence, Translation};
+
+/// Attr naming who made a translation
+pub const TRANSLATOR_ATTR: &str = "translator";
+
+/// Number of characters to insert, delete or replace to turn one text
+/// into the other
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let next = (diag + usize::from(ca != *cb))
+                .min(row[j] + 1)
+                .min(row[j + 1] + 1);
+            diag = row[j + 1];
+            row[j + 1] = next;
+        }
+    }
+    row[b.len()]
+}
+
+/// How far apart two translations of a sentence are
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub label: String,
+    pub distance: usize,
+    /// From 0 to 1, one minus the distance over the longer length
+    pub similarity: f64,
+    /// Length of the second translation over the first's
+    pub length_ratio: f64,
+}
+
+impl Divergence {
+    pub fn new(label: &str, first: &str, second: &str) -> Self {
+        let distance = edit_distance(first, second);
+        let (l1, l2) = (first.chars().count(), second.chars().count());
+        Self {
+            label: label.to_string(),
+            distance,
+            similarity: 1.0 - distance as f64 / l1.max(l2).max(1) as f64,
+            length_ratio: l2 as f64 / l1.max(1) as f64,
+        }
+    }
+}
+
+/// Translation of the sentence to the language, with its key
+fn translation<'a>(
+    chap: &Chapter,
+    s: &'a Sentence,
+    lang: &str,
+) -> Option<(&'a String, &'a Translation)> {
+    s.translations
+        .iter()
+        .enumerate()
+        .find(|(i, (k, _))| chap.translation_language(*i, k) == lang)
+        .map(|(_, kt)| kt)
+}
+
+/// Divergence of the translations to the language of the sentences both
+/// chapters translate, matched by label
+pub fn agreement(first: &Chapter, second: &Chapter, lang: &str) -> Vec<Divergence> {
+    first
+        .sentences
+        .iter()
+        .filter_map(|s| {
+            let (_, a) = translation(first, s, lang)?;
+            let other = second.sentences.iter().find(|o| o.label == s.label)?;
+            let (_, b) = translation(second, other, lang)?;
+            Some(Divergence::new(&s.label, &a.content, &b.content))
+        })
+        .collect()
+}
+
+/// The first chapter with the translations of the second one to the
+/// language added as alternatives labelled `language-name`, an alias of
+/// the language, and each translation's `translator` attr set to the name
+/// of its file
+pub fn merge_alternatives(
+    first: &Chapter,
+    second: &Chapter,
+    lang: &str,
+    names: (&str, &str),
+) -> Chapter {
+    let mut merged = first.clone();
+    let alt = format!("{lang}-{}", names.1);
+    let mut added = false;
+    for s in &mut merged.sentences {
+        let Some(other) = second.sentences.iter().find(|o| o.label == s.label) else {
+            continue;
+        };
+        let Some((_, b)) = translation(second, other, lang) else {
+            continue;
+        };
+        let mut b = b.clone();
+        b.attrs
+            .entry(TRANSLATOR_ATTR.to_string())
+            .or_insert_with(|| names.1.to_string());
+        let key = translation(first, s, lang).map(|(k, _)| k.clone());
+        match key {
+            Some(k) => {
+                let a = s.translations.get_mut(&k).expect("key of the sentence");
+                if a.content == b.content {
+                    continue;
+                }
+                a.attrs
+                    .entry(TRANSLATOR_ATTR.to_string())
+                    .or_insert_with(|| names.0.to_string());
+                // after the positional translations so they keep their place
+                s.translations.insert(alt.clone(), b);
+                added = true;
+            }
+            None => {
+                s.translations.insert(lang.to_string(), b);
+            }
+        }
+    }
+    if added {
+        let key = format!("{lang}.aliases");
+        let aliases = match merged.attrs.get(&key) {
+            Some(a) if !a.trim().is_empty() => format!("{}, {alt}", a.trim()),
+            _ => alt,
+        };
+        merged.attrs.insert(key, aliases);
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("kitten", "sitting", 3)]
+    #[case("", "abc", 3)]
+    #[case("नमस्ते", "नमस्ते", 0)]
+    #[case("flaw", "lawn", 2)]
+    fn edit_distance_test(#[case] a: &str, #[case] b: &str, #[case] expected: usize) {
+        assert_eq!(edit_distance(a, b), expected);
+        assert_eq!(edit_distance(b, a), expected);
+    }
+
+    const FIRST: &str =
+        "tranlations = english\n\n@ 1\na\n---\nthe cat\n\n@ 2\nb\n---\nsame\n\n@ 3\nc\n";
+    const SECOND: &str = "@ 1\na\n--- english\nthe cats\n\n@ 2\nb\n--- english\nsame\n\n@ 3\nc\n--- english\nnew\n\n@ 4\nd\n--- english\nextra\n";
+
+    #[test]
+    fn agreement_test() {
+        let first = Chapter::from_str(FIRST).unwrap();
+        let second = Chapter::from_str(SECOND).unwrap();
+        let divergence = agreement(&first, &second, "english");
+        assert_eq!(divergence.len(), 2);
+        assert_eq!(divergence[0].distance, 1);
+        assert_eq!(divergence[0].similarity, 1.0 - 1.0 / 8.0);
+        assert_eq!(divergence[0].length_ratio, 8.0 / 7.0);
+        assert_eq!(divergence[1].similarity, 1.0);
+    }
+
+    #[test]
+    fn merge_alternatives_test() {
+        let first = Chapter::from_str(FIRST).unwrap();
+        let second = Chapter::from_str(SECOND).unwrap();
+        let merged = merge_alternatives(&first, &second, "english", ("ann", "bob"));
+        let merged = Chapter::from_str(&merged.source()).unwrap();
+        assert_eq!(merged.attrs["english.aliases"], "english-bob");
+        let tls = |i: usize| -> Vec<(String, String, String)> {
+            let s = &merged.sentences[i];
+            s.translations
+                .iter()
+                .enumerate()
+                .map(|(j, (k, t))| {
+                    (
+                        merged.translation_language(j, k),
+                        t.content.clone(),
+                        t.attrs.get(TRANSLATOR_ATTR).cloned().unwrap_or_default(),
+                    )
+                })
+                .collect()
+        };
+        let tl = |l: &str, c: &str, t: &str| (l.to_string(), c.to_string(), t.to_string());
+        assert_eq!(
+            tls(0),
+            vec![
+                tl("english", "the cat", "ann"),
+                tl("english", "the cats", "bob")
+            ]
+        );
+        assert_eq!(tls(1), vec![tl("english", "same", "")]);
+        assert_eq!(tls(2), vec![tl("english", "new", "bob")]);
+        assert_eq!(merged.sentences.len(), 3);
+    }
+}
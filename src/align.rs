@@ -0,0 +1,216 @@
+#[cfg(feature = "html")]
+use crate::syntax::OrgFragment;
+use crate::syntax::Sentence;
+#[cfg(feature = "html")]
+use std::collections::HashMap;
+use std::str::FromStr;
+
+/// Translation attr aligning its words with the original's
+pub const ALIGN_ATTR: &str = "align";
+
+/// Word alignment of a translation with its original, like `1-1, 2+3-2`:
+/// pairs of original and translation words counted from 1, with `+`
+/// joining the words of a phrase. Words are separated by whitespace, and a
+/// glossed word or plugin counts as the words of its text.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Alignment(pub Vec<Link>);
+
+/// Words of the original and of the translation linked together
+pub type Link = (Vec<usize>, Vec<usize>);
+
+impl FromStr for Alignment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words = |side: &str| -> Result<Vec<usize>, String> {
+            side.split('+')
+                .map(|w| {
+                    w.trim()
+                        .parse()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("{w:?} isn't a word number"))
+                })
+                .collect()
+        };
+        s.split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                let (org, tl) = p
+                    .split_once('-')
+                    .ok_or_else(|| format!("{p:?} isn't a pair like 2-3"))?;
+                Ok((words(org)?, words(tl)?))
+            })
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+/// Link groups of the aligned words by word index, for the original and
+/// each translation of a sentence
+#[cfg(feature = "html")]
+#[derive(Debug, Default)]
+pub(crate) struct Links {
+    pub original: HashMap<usize, Vec<String>>,
+    pub translations: Vec<HashMap<usize, Vec<String>>>,
+}
+
+#[cfg(feature = "html")]
+impl Links {
+    pub fn is_empty(&self) -> bool {
+        self.original.is_empty()
+    }
+}
+
+/// Text with the aligned words wrapped in spans naming their link groups,
+/// and the number of words in it; words are counted from `first`
+#[cfg(feature = "html")]
+pub(crate) fn wrap_words(
+    text: &str,
+    first: usize,
+    groups: &HashMap<usize, Vec<String>>,
+) -> (String, usize) {
+    let mut out = String::with_capacity(text.len());
+    let mut count = 0;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let space = rest.len() - rest.trim_start().len();
+        out.push_str(&rest[..space]);
+        rest = &rest[space..];
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            break;
+        }
+        out.push_str(&wrap(&rest[..end], groups.get(&(first + count))));
+        count += 1;
+        rest = &rest[end..];
+    }
+    (out, count)
+}
+
+/// HTML of a fragment of the original with its aligned words wrapped, and
+/// the number of words in it
+#[cfg(feature = "html")]
+pub(crate) fn wrap_fragment(
+    frag: &OrgFragment,
+    html: String,
+    first: usize,
+    groups: &HashMap<usize, Vec<String>>,
+) -> (String, usize) {
+    if let OrgFragment::Simple(s) = frag {
+        return wrap_words(s, first, groups);
+    }
+    let count = frag.text().split_whitespace().count();
+    let mut linked: Vec<String> = (first..first + count)
+        .filter_map(|i| groups.get(&i))
+        .flatten()
+        .cloned()
+        .collect();
+    linked.sort();
+    linked.dedup();
+    match linked.is_empty() {
+        true => (html, count),
+        false => (wrap(&html, Some(&linked)), count),
+    }
+}
+
+#[cfg(feature = "html")]
+fn wrap(html: &str, groups: Option<&Vec<String>>) -> String {
+    match groups {
+        Some(g) => format!("<span data-align=\"{}\">{html}</span>", g.join(" ")),
+        None => html.to_string(),
+    }
+}
+
+impl Sentence {
+    /// Word alignments of the translations with an `align` attr, by the
+    /// index of the translation
+    pub fn alignments(&self) -> Vec<(usize, Result<Alignment, String>)> {
+        self.translations
+            .values()
+            .enumerate()
+            .filter_map(|(i, t)| Some((i, t.attrs.get(ALIGN_ATTR)?.parse())))
+            .collect()
+    }
+
+    /// Number of words of the original, as alignments count them
+    pub fn original_words(&self) -> usize {
+        self.original
+            .iter()
+            .map(|o| o.text().split_whitespace().count())
+            .sum()
+    }
+
+    #[cfg(feature = "html")]
+    pub(crate) fn links(&self) -> Links {
+        let mut links = Links {
+            translations: vec![HashMap::new(); self.translations.len()],
+            ..Default::default()
+        };
+        for (t, alignment) in self.alignments() {
+            let Ok(alignment) = alignment else {
+                continue;
+            };
+            for (p, (org, tl)) in alignment.0.iter().enumerate() {
+                let group = format!("a{t}-{p}");
+                for w in org {
+                    links.original.entry(w - 1).or_default().push(group.clone());
+                }
+                for w in tl {
+                    links.translations[t]
+                        .entry(w - 1)
+                        .or_default()
+                        .push(group.clone());
+                }
+            }
+        }
+        links
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "html")]
+    use crate::syntax::Chapter;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("1-1, 2+3-2", Ok(vec![(vec![1], vec![1]), (vec![2, 3], vec![2])]))]
+    #[case(" 1-2 ,", Ok(vec![(vec![1], vec![2])]))]
+    #[case("1-0", Err(()))]
+    #[case("1", Err(()))]
+    #[case("a-1", Err(()))]
+    fn parse_test(#[case] src: &str, #[case] expected: Result<Vec<Link>, ()>) {
+        assert_eq!(
+            src.parse::<Alignment>().map_err(|_| ()),
+            expected.map(Alignment)
+        );
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn wrap_words_test() {
+        let groups = HashMap::from([(1, vec!["a0-0".to_string()])]);
+        assert_eq!(
+            wrap_words(" I  have\ta dog ", 0, &groups),
+            (
+                " I  <span data-align=\"a0-0\">have</span>\ta dog ".to_string(),
+                4
+            )
+        );
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn html_test() {
+        let chap = Chapter::from_str(
+            "@ 1\nich habe << einen Hund = a dog >>\n---\nI have a dog\nalign = 1-1, 2-2, 3+4-3+4\n",
+        )
+        .unwrap();
+        let html = chap.sentence_html(&chap.sentences[0], &Default::default());
+        assert!(html.contains("<span data-align=\"a0-0\">ich</span> <span data-align=\"a0-1\">habe</span> <span data-align=\"a0-2\"><span"));
+        assert!(html.contains("<div class=\"tl\"><span data-align=\"a0-0\">I</span> <span data-align=\"a0-1\">have</span> <span data-align=\"a0-2\">a</span> <span data-align=\"a0-2\">dog</span></div>"));
+    }
+}
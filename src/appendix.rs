@@ -0,0 +1,190 @@
+use crate::output::GENERATED_MARKER;
+use crate::syntax::Chapter;
+use crate::tokenizer::{get_tokens_with, TokenType};
+
+/// Attrs holding translator's notes unless told otherwise
+pub const NOTE_ATTRS: &[&str] = &["note", "comment"];
+
+/// A note about a chapter or one of its sentences
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation {
+    /// Sentence it is about, `None` for the chapter
+    pub label: Option<String>,
+    /// Where it was written, like `note`, `note (english)` or `comment`
+    pub source: String,
+    pub text: String,
+}
+
+impl Chapter {
+    /// Notes of the chapter and its sentences in the order of the
+    /// sentences: the note attrs of the chapter, sentences and
+    /// translations, the comments of the source and the reader's notes.
+    ///
+    /// Comments on consecutive lines make one note, which belongs to the
+    /// sentence it is in or after, or to the one on the next line; those
+    /// before the first sentence belong to the chapter.
+    pub fn annotations(&self, src: Option<&str>, comment: &str, keys: &[&str]) -> Vec<Annotation> {
+        let note = |label: Option<&str>, source: String, text: &str| Annotation {
+            label: label.map(String::from),
+            source,
+            text: text.trim().to_string(),
+        };
+        let comments = src.map(|s| comments(s, comment)).unwrap_or_default();
+        let sentence_of = |offset: usize, leads: bool| match leads {
+            true => self.sentences.iter().position(|s| s.span.start > offset),
+            false => self.sentences.iter().rposition(|s| s.span.start <= offset),
+        };
+        let mut notes = Vec::new();
+        for (k, v) in self
+            .attrs
+            .iter()
+            .filter(|(k, _)| keys.contains(&k.as_str()))
+        {
+            notes.push(note(None, k.clone(), v));
+        }
+        for (offset, text, leads) in &comments {
+            if sentence_of(*offset, *leads).is_none() {
+                notes.push(note(None, "comment".into(), text));
+            }
+        }
+        for (i, s) in self.sentences.iter().enumerate() {
+            let label = Some(s.label.as_str());
+            for (k, v) in s
+                .orgattrs
+                .iter()
+                .filter(|(k, _)| keys.contains(&k.as_str()))
+            {
+                notes.push(note(label, k.clone(), v));
+            }
+            for (j, (key, t)) in s.translations.iter().enumerate() {
+                let lang = self.translation_language(j, key);
+                for (k, v) in t.attrs.iter().filter(|(k, _)| keys.contains(&k.as_str())) {
+                    notes.push(note(label, format!("{k} ({lang})"), v));
+                }
+            }
+            for (offset, text, leads) in &comments {
+                if sentence_of(*offset, *leads) == Some(i) {
+                    notes.push(note(label, "comment".into(), text));
+                }
+            }
+            if let Some(n) = self.notes.get(&s.label)
+                && !n.text.trim().is_empty()
+            {
+                notes.push(note(label, "reader's note".into(), &n.text));
+            }
+        }
+        notes.retain(|n| !n.text.is_empty());
+        notes
+    }
+}
+
+/// Comments of the source with the offset they start at and whether the
+/// line after them starts a sentence, the ones on consecutive lines joined
+fn comments(src: &str, comment: &str) -> Vec<(usize, String, bool)> {
+    let mut found: Vec<(usize, String, bool)> = Vec::new();
+    let mut joins = false;
+    for t in get_tokens_with(src, comment) {
+        match t.ty {
+            TokenType::Comment => {
+                let text = t.content[comment.len()..].trim();
+                match found.last_mut() {
+                    Some((_, prev, _)) if joins => {
+                        prev.push(' ');
+                        prev.push_str(text);
+                    }
+                    _ => found.push((t.offset, text.to_string(), false)),
+                }
+                joins = true;
+            }
+            // a comment on the next line continues it
+            TokenType::WhiteSpace => (),
+            TokenType::NewLine if joins => (),
+            TokenType::At if joins && src[..t.offset].ends_with('\n') => {
+                if let Some(last) = found.last_mut() {
+                    last.2 = true;
+                }
+                joins = false;
+            }
+            _ => joins = false,
+        }
+        if t.ty == TokenType::NewLine && src[..t.offset].ends_with('\n') {
+            // a blank line ends it
+            joins = false;
+        }
+    }
+    found
+}
+
+/// Markdown document of the notes of the chapters, with a section for each
+/// chapter with notes and a heading for each sentence with some
+pub fn markdown(title: &str, chapters: &[(String, Vec<Annotation>)]) -> String {
+    let mut md = format!("{GENERATED_MARKER}\n# {title}\n");
+    for (chapter, notes) in chapters.iter().filter(|(_, n)| !n.is_empty()) {
+        md.push_str(&format!("\n## {chapter}\n"));
+        let mut label = None;
+        let mut first = true;
+        for n in notes {
+            if n.label != label || first {
+                if let Some(l) = &n.label {
+                    md.push_str(&format!("\n### @{l}\n"));
+                }
+                md.push('\n');
+                label = n.label.clone();
+                first = false;
+            }
+            md.push_str(&format!(
+                "- *{}:* {}\n",
+                n.source,
+                n.text.replace('\n', " ")
+            ));
+        }
+    }
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const CHAP: &str = "title = One\ntranlations = english\nnote = Translated from the 1905 edition\n# which is\n# shorter\n\n@ 1\nhola # a greeting\nnote = informal\n---\nhello\nnote = or hi\n\n# about the next one\n@ 2\nadios\ncomment = formal\n\n@ 3\nnada\n";
+
+    #[test]
+    fn annotations_test() {
+        let chap = Chapter::parse_with(CHAP, "#").unwrap();
+        let notes = chap.annotations(Some(CHAP), "#", NOTE_ATTRS);
+        let got: Vec<(Option<&str>, &str, &str)> = notes
+            .iter()
+            .map(|n| (n.label.as_deref(), n.source.as_str(), n.text.as_str()))
+            .collect();
+        assert_eq!(
+            got,
+            [
+                (None, "note", "Translated from the 1905 edition"),
+                (None, "comment", "which is shorter"),
+                (Some("1"), "note", "informal"),
+                (Some("1"), "note (english)", "or hi"),
+                (Some("1"), "comment", "a greeting"),
+                (Some("2"), "comment", "formal"),
+                (Some("2"), "comment", "about the next one"),
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_test() {
+        let chap = Chapter::from_str("@ 1\nhola\nnote = informal\n").unwrap();
+        let notes = chap.annotations(None, "#", NOTE_ATTRS);
+        let empty = Chapter::from_str("@ 1\nx\n").unwrap();
+        assert_eq!(
+            markdown(
+                "Notes",
+                &[
+                    ("One".into(), notes),
+                    ("Two".into(), empty.annotations(None, "#", NOTE_ATTRS))
+                ]
+            ),
+            "<!-- Generated by transdoc -->\n# Notes\n\n## One\n\n### @1\n\n- *note:* informal\n"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use crate::syntax::Chapter;
+use indexmap::IndexMap;
+
+/// Change to the attrs of sentences or translations
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrEdit {
+    /// Sets the key to the value, adding it if missing
+    Set(String, String),
+    Remove(String),
+    /// Renames the first key to the second, keeping its place
+    Rename(String, String),
+}
+
+impl AttrEdit {
+    /// Whether the keys and value can be written back to the file as an
+    /// attr line
+    pub fn check(&self) -> Result<(), String> {
+        let (keys, value) = match self {
+            Self::Set(k, v) => (vec![k], Some(v)),
+            Self::Remove(k) => (vec![k], None),
+            Self::Rename(k, to) => (vec![k, to], None),
+        };
+        for k in keys {
+            if k.is_empty() || k.contains(|c: char| c == '=' || c.is_whitespace()) {
+                return Err(format!("Invalid attr key {k:?}"));
+            }
+        }
+        if let Some(v) = value
+            && v.contains(['\n', '\r'])
+        {
+            return Err(format!("Attr value {v:?} spans lines"));
+        }
+        Ok(())
+    }
+
+    /// Applies the edit, giving whether the attrs changed
+    pub fn apply(&self, attrs: &mut IndexMap<String, String>) -> bool {
+        match self {
+            Self::Set(k, v) => attrs.insert(k.clone(), v.clone()).as_ref() != Some(v),
+            Self::Remove(k) => attrs.shift_remove(k).is_some(),
+            Self::Rename(k, to) => match attrs.shift_remove_full(k) {
+                Some((i, _, v)) => {
+                    attrs.shift_remove(to);
+                    attrs.shift_insert(i.min(attrs.len()), to.clone(), v);
+                    k != to
+                }
+                None => false,
+            },
+        }
+    }
+}
+
+/// Sentences and translations an attr edit applies to, all of them by default
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AttrTarget {
+    /// Only the sentences with these labels
+    pub labels: Vec<String>,
+    /// Only the sentences with any of these tags
+    pub tags: Vec<String>,
+    /// The translations to this language instead of the sentences
+    pub lang: Option<String>,
+}
+
+impl Chapter {
+    /// Applies the edit to the targeted attrs, giving the indices of the
+    /// sentences that changed
+    pub fn edit_attrs(&mut self, target: &AttrTarget, edit: &AttrEdit) -> Vec<usize> {
+        let tagged = self.tagged(&target.tags);
+        let mut modified = Vec::new();
+        for i in 0..self.sentences.len() {
+            let s = &self.sentences[i];
+            if (!target.labels.is_empty() && !target.labels.contains(&s.label))
+                || (!target.tags.is_empty() && !tagged.contains(&i))
+            {
+                continue;
+            }
+            let changed = match &target.lang {
+                None => edit.apply(&mut self.sentences[i].orgattrs),
+                Some(lang) => {
+                    let keys: Vec<String> = s
+                        .translations
+                        .keys()
+                        .enumerate()
+                        .filter(|(j, k)| self.translation_language(*j, k) == *lang)
+                        .map(|(_, k)| k.clone())
+                        .collect();
+                    let mut changed = false;
+                    for k in keys {
+                        if let Some(t) = self.sentences[i].translations.get_mut(&k) {
+                            changed |= edit.apply(&mut t.attrs);
+                        }
+                    }
+                    changed
+                }
+            };
+            if changed {
+                modified.push(i);
+            }
+        }
+        modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str = "tranlations = english, german\n\n@ 1\na\ntags = x\n---\nA\n---\nB\nstatus = draft\n\n@ 2\nb\n--- german\nC\n";
+
+    #[rstest]
+    #[case(
+        AttrTarget { lang: Some("german".into()), ..Default::default() },
+        AttrEdit::Set("status".into(), "reviewed".into()),
+        vec![0, 1]
+    )]
+    #[case(
+        AttrTarget { lang: Some("german".into()), labels: vec!["2".into()], ..Default::default() },
+        AttrEdit::Set("status".into(), "reviewed".into()),
+        vec![1]
+    )]
+    #[case(
+        AttrTarget { lang: Some("german".into()), ..Default::default() },
+        AttrEdit::Set("status".into(), "draft".into()),
+        vec![1]
+    )]
+    #[case(
+        AttrTarget { lang: Some("english".into()), ..Default::default() },
+        AttrEdit::Remove("status".into()),
+        vec![]
+    )]
+    #[case(
+        AttrTarget { lang: Some("german".into()), ..Default::default() },
+        AttrEdit::Rename("status".into(), "state".into()),
+        vec![0]
+    )]
+    #[case(
+        AttrTarget { tags: vec!["x".into()], ..Default::default() },
+        AttrEdit::Set("level".into(), "A1".into()),
+        vec![0]
+    )]
+    fn edit_attrs_test(
+        #[case] target: AttrTarget,
+        #[case] edit: AttrEdit,
+        #[case] expected: Vec<usize>,
+    ) {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        let modified = chap.edit_attrs(&target, &edit);
+        assert_eq!(modified, expected);
+        let edited = Chapter::from_str(&chap.splice_sentences(CHAP, &modified)).unwrap();
+        assert_eq!(edited, chap);
+    }
+
+    #[rstest]
+    #[case(AttrEdit::Set("a".into(), "b c".into()), true)]
+    #[case(AttrEdit::Set("a b".into(), "c".into()), false)]
+    #[case(AttrEdit::Set("a".into(), "b\nc = d".into()), false)]
+    #[case(AttrEdit::Rename("a".into(), "b=".into()), false)]
+    #[case(AttrEdit::Remove("".into()), false)]
+    fn check_test(#[case] edit: AttrEdit, #[case] valid: bool) {
+        assert_eq!(edit.check().is_ok(), valid);
+    }
+
+    #[test]
+    fn rename_keeps_place_test() {
+        let mut attrs: IndexMap<String, String> = [("a", "1"), ("b", "2"), ("c", "3")]
+            .map(|(k, v)| (k.into(), v.into()))
+            .into();
+        assert!(AttrEdit::Rename("b".into(), "d".into()).apply(&mut attrs));
+        assert_eq!(attrs.keys().collect::<Vec<_>>(), vec!["a", "d", "c"]);
+        assert!(AttrEdit::Rename("a".into(), "c".into()).apply(&mut attrs));
+        assert_eq!(attrs.keys().collect::<Vec<_>>(), vec!["c", "d"]);
+    }
+}
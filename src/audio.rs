@@ -0,0 +1,199 @@
+use crate::errors::{Diagnostic, Severity};
+use crate::output::GENERATED_MARKER;
+use crate::parser::resolve_path;
+use crate::syntax::{Chapter, Sentence};
+use crate::typed::{parse_duration, parse_list};
+use indexmap::IndexMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Attr of an original or a translation naming its recording
+pub const AUDIO_ATTR: &str = "audio";
+
+/// Recording of one sentence in one language
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clip {
+    pub label: String,
+    pub file: PathBuf,
+    /// Length of the recording, needed to place chapter markers
+    pub duration: Option<Duration>,
+}
+
+/// Recordings of the sentences of a chapter by language
+pub type Clips = IndexMap<String, Vec<Clip>>;
+
+/// Recording of an `audio = file.mp3, 3.2s` attr, the duration optional
+pub fn parse_audio(value: &str) -> Result<(&str, Option<Duration>), String> {
+    match parse_list(value)[..] {
+        [file] => Ok((file, None)),
+        [file, duration] => Ok((file, Some(parse_duration(duration)?))),
+        _ => Err("give the audio file and optionally its duration, like `01.mp3, 3.2s`".into()),
+    }
+}
+
+impl Chapter {
+    /// Recordings of the sentences by language, the originals first, with
+    /// the files relative to the chapter's directory, and warnings about
+    /// the attrs that can't be read
+    pub fn audio_clips(&self, dir: &Path) -> (Clips, Vec<Diagnostic>) {
+        let mut clips = Clips::new();
+        let mut problems = Vec::new();
+        let mut add = |lang: String, s: &Sentence, value: Option<&String>| {
+            let Some(value) = value else {
+                return;
+            };
+            match parse_audio(value) {
+                Ok((file, duration)) => clips.entry(lang).or_default().push(Clip {
+                    label: s.label.clone(),
+                    file: resolve_path(dir, file),
+                    duration,
+                }),
+                Err(e) => problems.push(Diagnostic {
+                    severity: Severity::Warning,
+                    line: s.span.line,
+                    col: s.span.col,
+                    message: format!("Attr audio = {value} of @{}: {e}", s.label),
+                    ..Default::default()
+                }),
+            }
+        };
+        for s in &self.sentences {
+            add(self.language.clone(), s, s.orgattrs.get(AUDIO_ATTR));
+            for (i, (key, t)) in s.translations.iter().enumerate() {
+                add(
+                    self.translation_language(i, key),
+                    s,
+                    t.attrs.get(AUDIO_ATTR),
+                );
+            }
+        }
+        (clips, problems)
+    }
+}
+
+/// Extended M3U playlist of the recordings
+pub fn playlist(title: &str, clips: &[Clip]) -> String {
+    let mut m3u = format!("#EXTM3U\n# {GENERATED_MARKER}\n#PLAYLIST:{title}\n");
+    for c in clips {
+        let secs = c.duration.map_or(-1, |d| d.as_secs_f64().round() as i64);
+        m3u.push_str(&format!(
+            "#EXTINF:{secs},@{}\n{}\n",
+            c.label,
+            c.file.to_string_lossy()
+        ));
+    }
+    m3u
+}
+
+/// List of the recordings of the chapters for ffmpeg's concat demuxer,
+/// playing them one after the other as one file
+pub fn concat_list<'a>(clips: impl IntoIterator<Item = &'a Clip>) -> String {
+    let mut list = format!("ffconcat version 1.0\n# {GENERATED_MARKER}\n");
+    for c in clips {
+        // quoted, a quote closes the string, is escaped and opens it again
+        let file = c.file.to_string_lossy().replace('\'', r"'\''");
+        list.push_str(&format!("file '{file}'\n"));
+        if let Some(d) = c.duration {
+            list.push_str(&format!("duration {:.3}\n", d.as_secs_f64()));
+        }
+    }
+    list
+}
+
+/// ffmpeg metadata marking where each chapter starts in the concatenated
+/// recordings, `None` when a recording has no duration to place them by
+pub fn chapter_markers(title: &str, chapters: &[(String, Vec<Clip>)]) -> Option<String> {
+    let escape = |s: &str| {
+        s.chars().fold(String::new(), |mut e, c| {
+            if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+                e.push('\\');
+            }
+            e.push(c);
+            e
+        })
+    };
+    let mut meta = format!(
+        ";FFMETADATA1\n; {GENERATED_MARKER}\ntitle={}\n",
+        escape(title)
+    );
+    let mut start = Duration::ZERO;
+    for (name, clips) in chapters {
+        let length: Duration = clips.iter().map(|c| c.duration).sum::<Option<Duration>>()?;
+        meta.push_str(&format!(
+            "\n[CHAPTER]\nTIMEBASE=1/1000\nSTART={}\nEND={}\ntitle={}\n",
+            start.as_millis(),
+            (start + length).as_millis(),
+            escape(name)
+        ));
+        start += length;
+    }
+    Some(meta)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("01.mp3", Some(("01.mp3", None)))]
+    #[case(" 01.mp3 , 1.5s", Some(("01.mp3", Some(Duration::from_millis(1500)))))]
+    #[case("01.mp3, soon", None)]
+    #[case("", None)]
+    fn parse_audio_test(#[case] value: &str, #[case] expected: Option<(&str, Option<Duration>)>) {
+        assert_eq!(parse_audio(value).ok(), expected);
+    }
+
+    fn clip(label: &str, file: &str, secs: Option<u64>) -> Clip {
+        Clip {
+            label: label.into(),
+            file: file.into(),
+            duration: secs.map(Duration::from_secs),
+        }
+    }
+
+    #[test]
+    fn clips_test() {
+        let chap = Chapter::from_str(
+            "language = german\ntranlations = english\n\n@ 1\nHallo\naudio = de/1.mp3, 2s\n---\nHello\naudio = en/1.mp3\n\n@ 2\nTschüss\naudio = de/2.mp3, x\n---\nBye\n",
+        )
+        .unwrap();
+        let (clips, problems) = chap.audio_clips(Path::new("book"));
+        assert_eq!(clips.keys().collect::<Vec<_>>(), ["german", "english"]);
+        assert_eq!(clips["german"], [clip("1", "book/de/1.mp3", Some(2))]);
+        assert_eq!(clips["english"], [clip("1", "book/en/1.mp3", None)]);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, 11);
+    }
+
+    #[test]
+    fn playlist_test() {
+        let clips = [clip("1", "a.mp3", Some(2)), clip("2", "it's.mp3", None)];
+        assert_eq!(
+            playlist("Ch 1", &clips),
+            "#EXTM3U\n# <!-- Generated by transdoc -->\n#PLAYLIST:Ch 1\n#EXTINF:2,@1\na.mp3\n#EXTINF:-1,@2\nit's.mp3\n"
+        );
+        assert_eq!(
+            concat_list(&clips),
+            "ffconcat version 1.0\n# <!-- Generated by transdoc -->\nfile 'a.mp3'\nduration 2.000\nfile 'it'\\''s.mp3'\n"
+        );
+    }
+
+    #[test]
+    fn markers_test() {
+        let chapters = vec![
+            (
+                "One".to_string(),
+                vec![clip("1", "a", Some(2)), clip("2", "b", Some(3))],
+            ),
+            ("Two; end".to_string(), vec![clip("1", "c", Some(1))]),
+        ];
+        assert_eq!(
+            chapter_markers("Book", &chapters).unwrap(),
+            ";FFMETADATA1\n; <!-- Generated by transdoc -->\ntitle=Book\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=0\nEND=5000\ntitle=One\n\n[CHAPTER]\nTIMEBASE=1/1000\nSTART=5000\nEND=6000\ntitle=Two\\; end\n"
+        );
+        let unknown = vec![("One".to_string(), vec![clip("1", "a", None)])];
+        assert_eq!(chapter_markers("Book", &unknown), None);
+    }
+}
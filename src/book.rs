@@ -0,0 +1,480 @@
+use crate::html::{escape_attr, Edition, RepeatGloss};
+use crate::intern::intern;
+use crate::outline::ContentsEntry;
+use crate::output::GENERATED_MARKER;
+use crate::stats::Difficulty;
+use crate::syntax::{Chapter, Meanings, OrgFragment, Provenance};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Book manifest, a JSON file listing the chapters in reading order
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Book {
+    #[serde(default)]
+    pub title: String,
+    /// Chapter files, relative to the manifest
+    pub chapters: Vec<PathBuf>,
+    /// Make the words glossed inline in a chapter dictionary entries of
+    /// the chapters after it
+    #[serde(default)]
+    pub inherit_glosses: bool,
+    /// How words glossed in earlier chapters or before on the page are shown
+    #[serde(default)]
+    pub repeat_gloss: RepeatGloss,
+    /// Address the book is published at, an Atom feed of the chapters with
+    /// a `date` attr is written when given
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Author of the book named in the feed, its title if not given
+    #[serde(default)]
+    pub author: Option<String>,
+    /// Number the chapters in reading order, and their sections and
+    /// sentences after them
+    #[serde(default)]
+    pub numbered: bool,
+    /// Editions to build, each in its directory; a full one in the output
+    /// directory when none are given
+    #[serde(default)]
+    pub outputs: Vec<Output>,
+    /// Frequency list to grade the chapters against, for badging them in
+    /// the index and ordering them from the easiest
+    #[serde(default)]
+    pub difficulty: Option<Grading>,
+}
+
+/// How the chapters of a graded reader are scored and ordered
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Grading {
+    /// Frequency list relative to the manifest, one word per line with the
+    /// most frequent first
+    pub frequency: PathBuf,
+    /// Size of the frequency band counted as easy words
+    #[serde(default = "default_top")]
+    pub top: usize,
+    /// Order the chapters from the easiest to the hardest instead of as
+    /// listed
+    #[serde(default)]
+    pub sort: bool,
+}
+
+fn default_top() -> usize {
+    2000
+}
+
+impl Grading {
+    /// Badge of a chapter with the difficulty in the table of contents
+    pub fn badge(&self, difficulty: &Difficulty) -> String {
+        format!("{:.0}% in top {}", difficulty.coverage(), self.top)
+    }
+}
+
+/// Edition of the book built into a directory
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Output {
+    /// Directory relative to the output directory
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub edition: Edition,
+}
+
+impl Book {
+    /// Manifest from the file, its chapter paths made relative to the
+    /// current directory
+    pub fn load(file: &Path) -> Result<Self, String> {
+        let text = crate::text::read_text(file)?;
+        let mut book: Self = serde_json::from_str(&text).map_err(|e| e.to_string())?;
+        let dir = file.parent().unwrap_or(Path::new(""));
+        book.chapters = book.chapters.iter().map(|c| dir.join(c)).collect();
+        if let Some(grading) = &mut book.difficulty {
+            grading.frequency = dir.join(&grading.frequency);
+        }
+        Ok(book)
+    }
+
+    /// Editions to build, the full one alone by default
+    pub fn outputs(&self) -> Vec<Output> {
+        match self.outputs.is_empty() {
+            true => vec![Output::default()],
+            false => self.outputs.clone(),
+        }
+    }
+}
+
+/// Where a word was glossed for the first time
+#[derive(Debug, Clone, PartialEq)]
+pub struct Introduced {
+    pub chapter: String,
+    pub label: String,
+}
+
+/// Words glossed inline in the chapters read so far
+#[derive(Debug, Clone, Default)]
+pub struct Glossary {
+    pub entries: IndexMap<Arc<str>, (Meanings, Introduced)>,
+    /// Language of the first chapter, which the table is sorted for
+    pub language: String,
+}
+
+impl Glossary {
+    /// Adds the words glossed inline in the chapter that weren't glossed
+    /// before; call it before processing the chapter, as that turns
+    /// dictionary lookups into glosses too
+    pub fn learn(&mut self, chap: &Chapter, name: &str) {
+        if self.language.is_empty() {
+            self.language = chap.language.clone();
+        }
+        for s in &chap.sentences {
+            for o in &s.original {
+                if let OrgFragment::Meaning(w, m) = o
+                    && !self.entries.contains_key(w.as_str())
+                {
+                    let intro = Introduced {
+                        chapter: name.to_string(),
+                        label: s.label.clone(),
+                    };
+                    self.entries.insert(intern(w), (m.clone(), intro));
+                }
+            }
+        }
+    }
+
+    /// Adds the words to the dictionary of the chapter, its own entries
+    /// take precedence
+    pub fn teach(&self, chap: &mut Chapter) {
+        for (w, (m, intro)) in &self.entries {
+            if !chap.dictionary.contains_key(w) {
+                chap.dictionary.insert(w.clone(), m.clone());
+                chap.provenance.insert(
+                    w.clone(),
+                    Provenance::Chapter {
+                        chapter: intro.chapter.clone(),
+                        label: intro.label.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    /// Table of the words in the order of the language with the chapter
+    /// they were introduced in
+    pub fn html(&self) -> String {
+        let mut html = String::from(
+            "<h3>Vocabulary</h3><table><tr><th>Word</th> <th>Meanings</th> <th>Introduced in</th></tr>",
+        );
+        let mut entries: Vec<_> = self.entries.iter().collect();
+        crate::collate::sort_by_word(&mut entries, &self.language, |(w, _)| w);
+        for (w, (m, intro)) in entries {
+            html.push_str(&format!(
+                "<tr><td>{w}</td><td>{}</td><td>{} @ {}</td></tr>",
+                m.join("; "),
+                intro.chapter,
+                intro.label
+            ));
+        }
+        html.push_str("</table>");
+        html
+    }
+}
+
+/// Table of contents of the book linking the chapter pages, with the
+/// vocabulary when glosses are inherited and the feed when there is one
+pub fn index_html(
+    title: &str,
+    chapters: &[ContentsEntry],
+    glossary: Option<&Glossary>,
+    feed: Option<&str>,
+) -> String {
+    let items: String = chapters.iter().map(ContentsEntry::html).collect();
+    // numbered chapters show their own numbers
+    let list = match chapters.iter().any(|c| c.number.is_some()) {
+        true => "<ol style=\"list-style: none\">",
+        false => "<ol>",
+    };
+    format!(
+        "{GENERATED_MARKER}\n<html>\n<title> {title} </title>\n{}<body>\n<h2> {title} </h2>\n{list}{items}</ol>\n{}</body></html>",
+        feed.map(|f| format!(
+            "<link rel=\"alternate\" type=\"application/atom+xml\" href=\"{f}\">\n"
+        ))
+        .unwrap_or_default(),
+        glossary.map(Glossary::html).unwrap_or_default()
+    )
+}
+
+/// Chapter as listed in the feed
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEntry {
+    pub title: String,
+    /// Page relative to the book address
+    pub href: String,
+    /// `YYYY-MM-DD` or an RFC 3339 timestamp
+    pub date: String,
+    pub excerpt: String,
+}
+
+/// Atom timestamp of a `YYYY-MM-DD` date or RFC 3339 timestamp
+pub fn atom_date(date: &str) -> Option<String> {
+    atom_time(date).map(|(d, _)| d)
+}
+
+/// Atom timestamp of the date with its seconds since the Unix epoch, for
+/// ordering timestamps with different offsets
+fn atom_time(date: &str) -> Option<(String, i64)> {
+    let date = date.trim();
+    let ymd = date.get(..10)?;
+    let days = epoch_days(ymd)?;
+    let time = match &date[10..] {
+        "" => return Some((format!("{ymd}T00:00:00Z"), days * 86400)),
+        time => time.strip_prefix('T')?,
+    };
+    let [h, m, s] = fields(time.get(..8)?, ':', &[2, 2, 2])?[..] else {
+        return None;
+    };
+    // 60 seconds for a leap second
+    if h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    let mut rest = &time[8..];
+    if let Some(fraction) = rest.strip_prefix('.') {
+        rest = fraction.trim_start_matches(|c: char| c.is_ascii_digit());
+        if rest.len() == fraction.len() {
+            return None;
+        }
+    }
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let sign = match rest.get(..1)? {
+                "+" => 1,
+                "-" => -1,
+                _ => return None,
+            };
+            let [oh, om] = fields(&rest[1..], ':', &[2, 2])?[..] else {
+                return None;
+            };
+            if oh > 23 || om > 59 {
+                return None;
+            }
+            sign * (oh * 3600 + om * 60)
+        }
+    };
+    Some((
+        date.to_string(),
+        days * 86400 + h * 3600 + m * 60 + s - offset,
+    ))
+}
+
+/// Numbers separated by `sep`, each with exactly the given number of digits
+fn fields(text: &str, sep: char, widths: &[usize]) -> Option<Vec<i64>> {
+    let parts: Vec<&str> = text.split(sep).collect();
+    if parts.len() != widths.len() {
+        return None;
+    }
+    parts
+        .iter()
+        .zip(widths)
+        .map(|(p, w)| {
+            if p.len() == *w && p.bytes().all(|b| b.is_ascii_digit()) {
+                p.parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Days since the Unix epoch of a `YYYY-MM-DD` date that exists
+fn epoch_days(ymd: &str) -> Option<i64> {
+    let [y, m, d] = fields(ymd, '-', &[4, 2, 2])?[..] else {
+        return None;
+    };
+    let leap = y % 4 == 0 && (y % 100 != 0 || y % 400 == 0);
+    let month_days = match m {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        1..=12 => 31,
+        _ => return None,
+    };
+    if d < 1 || d > month_days {
+        return None;
+    }
+    // after Howard Hinnant's `days_from_civil`
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    Some(era * 146_097 + doe - 719_468)
+}
+
+/// Atom feed of the chapters, the newest first
+pub fn atom_feed(title: &str, url: &str, author: &str, entries: &[FeedEntry]) -> String {
+    let url = url.trim_end_matches('/');
+    let mut dated: Vec<(String, i64, &FeedEntry)> = entries
+        .iter()
+        .filter_map(|e| {
+            let (date, secs) = atom_time(&e.date)?;
+            Some((date, secs, e))
+        })
+        .collect();
+    dated.sort_by_key(|d| std::cmp::Reverse(d.1));
+    let updated = dated
+        .first()
+        .map_or("1970-01-01T00:00:00Z", |(d, _, _)| d.as_str());
+    let mut xml = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n{GENERATED_MARKER}\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n<title>{}</title>\n<id>{url}/</id>\n<link href=\"{url}/\"/>\n<updated>{updated}</updated>\n<author><name>{}</name></author>\n",
+        escape_attr(title),
+        escape_attr(author),
+        url = escape_attr(url)
+    );
+    for (date, _, e) in dated {
+        let link = escape_attr(&format!("{url}/{}", e.href));
+        xml.push_str(&format!(
+            "<entry>\n<title>{}</title>\n<id>{link}</id>\n<link href=\"{link}\"/>\n<updated>{date}</updated>\n<summary>{}</summary>\n</entry>\n",
+            escape_attr(&e.title),
+            escape_attr(&e.excerpt)
+        ));
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("2024-03-01", Some("2024-03-01T00:00:00Z"))]
+    #[case(" 2024-03-01T10:00:00+05:45 ", Some("2024-03-01T10:00:00+05:45"))]
+    #[case("2024-3-1", None)]
+    #[case("March 2024", None)]
+    #[case("2024-03-01 10:00", None)]
+    #[case("2024-13-45", None)]
+    #[case("2023-02-29", None)]
+    #[case("2024-02-29", Some("2024-02-29T00:00:00Z"))]
+    #[case("2024-03-01T24:00:00Z", None)]
+    #[case("2024-03-01T10:00:00.25Z", Some("2024-03-01T10:00:00.25Z"))]
+    #[case("2024-03-01T10:00:00", None)]
+    #[case("2024-03-01T10:00:00+5:45", None)]
+    fn atom_date_test(#[case] date: &str, #[case] expected: Option<&str>) {
+        assert_eq!(atom_date(date).as_deref(), expected);
+    }
+
+    #[test]
+    fn atom_feed_test() {
+        let entry = |title: &str, date: &str| FeedEntry {
+            title: title.to_string(),
+            href: format!("{title}.html"),
+            date: date.to_string(),
+            excerpt: "a < b".to_string(),
+        };
+        let feed = atom_feed(
+            "Book & co",
+            "https://example.com/book/",
+            "Ana",
+            &[
+                entry("one", "2024-01-01"),
+                entry("two", "2024-02-01"),
+                entry("draft", ""),
+                entry("east", "2024-03-01T10:00:00+05:45"),
+                entry("west", "2024-03-01T01:00:00-05:00"),
+                entry("q&a", "2023-12-01"),
+            ],
+        );
+        assert!(feed.contains("<title>Book &amp; co</title>"));
+        assert!(feed.contains("<author><name>Ana</name></author>"));
+        assert!(feed.contains("<updated>2024-03-01T01:00:00-05:00</updated>\n<author>"));
+        assert!(feed.contains("<link href=\"https://example.com/book/two.html\"/>"));
+        assert!(feed.contains("<summary>a &lt; b</summary>"));
+        assert!(feed.find("two.html") < feed.find("one.html"));
+        assert!(!feed.contains("draft"));
+        assert!(feed.find("west.html") < feed.find("east.html"));
+        assert!(feed.contains("<link href=\"https://example.com/book/q&amp;a.html\"/>"));
+    }
+
+    #[test]
+    fn glossary_test() {
+        let first = Chapter::from_str("@ 1\n<< a = x >> << b = y >>\n").unwrap();
+        let mut second =
+            Chapter::from_str("@ 1\n<< a >>\n\n@ 2\n<< b >> << c = z >> << a = w >>\n").unwrap();
+        second
+            .dictionary
+            .insert("b".into(), crate::intern::meanings(&["own"]));
+        let mut glossary = Glossary::default();
+        glossary.learn(&first, "one");
+        glossary.teach(&mut second);
+        glossary.learn(&second, "two");
+        second.process();
+        let meaning = |i: usize, j: usize| match &second.sentences[i].original[j] {
+            OrgFragment::Meaning(_, m) => m.join("; "),
+            o => panic!("{o:?} is not glossed"),
+        };
+        assert_eq!(meaning(0, 0), "x");
+        assert_eq!(meaning(1, 0), "own");
+        assert_eq!(
+            second.provenance["a"].to_string(),
+            "learned from sentence @1 of one"
+        );
+        assert_eq!(
+            second.provenance["c"].to_string(),
+            "learned from sentence @2"
+        );
+        assert!(!second.provenance.contains_key("b"));
+        let words: Vec<(&str, &str, &str)> = glossary
+            .entries
+            .iter()
+            .map(|(w, (_, i))| (w.as_ref(), i.chapter.as_str(), i.label.as_str()))
+            .collect();
+        assert_eq!(
+            words,
+            vec![("a", "one", "1"), ("b", "one", "1"), ("c", "two", "2")]
+        );
+    }
+
+    #[test]
+    fn outputs_test() {
+        let book: Book = serde_json::from_str(r#"{"chapters": []}"#).unwrap();
+        assert_eq!(book.outputs(), vec![Output::default()]);
+        let book: Book = serde_json::from_str(
+            r#"{"chapters": [], "outputs": [{"dir": "."}, {"dir": "reader", "edition": "reader"}]}"#,
+        )
+        .unwrap();
+        let editions: Vec<Edition> = book.outputs().iter().map(|o| o.edition).collect();
+        assert_eq!(editions, vec![Edition::Full, Edition::Reader]);
+    }
+
+    #[test]
+    fn grading_test() {
+        let book: Book =
+            serde_json::from_str(r#"{"chapters": [], "difficulty": {"frequency": "ne.txt"}}"#)
+                .unwrap();
+        let grading = book.difficulty.unwrap();
+        assert_eq!((grading.top, grading.sort), (2000, false));
+        let difficulty = Difficulty {
+            tokens: 40,
+            in_band: 36,
+            out_of_list: 2,
+        };
+        assert_eq!(grading.badge(&difficulty), "90% in top 2000");
+    }
+
+    #[test]
+    fn glossary_order_test() {
+        let chap = Chapter::from_str(
+            "language = spanish\n\n@ 1\n<< ola = wave >> << ñu = gnu >> << árbol = tree >> << nube = cloud >>\n",
+        )
+        .unwrap();
+        let mut glossary = Glossary::default();
+        glossary.learn(&chap, "one");
+        let html = glossary.html();
+        let order: Vec<usize> = ["árbol", "nube", "ñu", "ola"]
+            .iter()
+            .map(|w| html.find(&format!("<td>{w}</td>")).unwrap())
+            .collect();
+        assert!(order.is_sorted());
+    }
+}
@@ -0,0 +1,160 @@
+use crate::syntax::{Chapter, Sentence};
+use indexmap::IndexMap;
+use std::path::{Path, PathBuf};
+
+/// Extension of the file next to a chapter with the checksums of its
+/// sentences as they were last recorded
+pub const CHECKSUMS_EXTENSION: &str = "checksums";
+
+/// Start of the comment of a page with the checksum of its chapter
+const PAGE_CHECKSUM: &str = "<!-- transdoc checksum ";
+/// Start of the comment of a page with the hash of the options it was built with
+const PAGE_OPTIONS: &str = "<!-- transdoc options ";
+
+/// 64 bit FNV-1a, stable across versions and platforms unlike the std hashers
+pub(crate) fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |h, b| {
+        (h ^ b as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn hex(text: &str) -> String {
+    format!("{:016x}", fnv1a(text))
+}
+
+impl Sentence {
+    /// Hash of the original, its attrs and the translations, ignoring the
+    /// label, comments and spacing, for telling whether the sentence changed
+    pub fn checksum(&self) -> String {
+        // the label is what the checksums are kept by
+        let src = self.source();
+        let lines: Vec<&str> = src.lines().skip(1).map(str::trim).collect();
+        hex(&lines.join("\n"))
+    }
+}
+
+impl Chapter {
+    /// Hash of the attrs and sentences, ignoring comments and spacing
+    pub fn checksum(&self) -> String {
+        hex(&self.source())
+    }
+
+    /// Checksum of each sentence by label
+    pub fn checksums(&self) -> IndexMap<String, String> {
+        self.sentences
+            .iter()
+            .map(|s| (s.label.clone(), s.checksum()))
+            .collect()
+    }
+
+    /// Labels of the sentences that are new or changed since the checksums
+    /// were recorded
+    pub fn changed_since(&self, recorded: &IndexMap<String, String>) -> Vec<&str> {
+        self.sentences
+            .iter()
+            .filter(|s| recorded.get(&s.label) != Some(&s.checksum()))
+            .map(|s| s.label.as_str())
+            .collect()
+    }
+}
+
+/// Checksums file of the chapter file, `chapter.checksums` next to it
+pub fn sidecar(chapter: &Path) -> PathBuf {
+    chapter.with_extension(CHECKSUMS_EXTENSION)
+}
+
+/// Checksums by label from a checksums file of `@label checksum` lines,
+/// `#` lines being comments
+pub fn parse(src: &str) -> Result<IndexMap<String, String>, String> {
+    let mut sums = IndexMap::new();
+    for (i, line) in src.lines().enumerate() {
+        if line.starts_with('#') || line.trim().is_empty() {
+            continue;
+        }
+        match line
+            .trim()
+            .strip_prefix('@')
+            .and_then(|l| l.rsplit_once(' '))
+        {
+            Some((label, sum)) if !label.trim().is_empty() => {
+                sums.insert(label.trim().to_string(), sum.to_string());
+            }
+            _ => return Err(format!("Line {} isn't `@label checksum`", i + 1)),
+        }
+    }
+    Ok(sums)
+}
+
+/// Writes checksums as a checksums file
+pub fn source(sums: &IndexMap<String, String>) -> String {
+    sums.iter().map(|(l, s)| format!("@{l} {s}\n")).collect()
+}
+
+/// Hash of a description of the options pages are built with
+pub fn options_hash(options: &str) -> String {
+    hex(options)
+}
+
+/// Page with comments holding the checksum of its chapter and the hash of
+/// the options it was built with after the generated marker
+pub fn with_page_checksum(html: &str, checksum: &str, options: &str) -> String {
+    let marker = crate::output::GENERATED_MARKER;
+    html.replacen(
+        marker,
+        &format!("{marker}\n{PAGE_CHECKSUM}{checksum} -->\n{PAGE_OPTIONS}{options} -->"),
+        1,
+    )
+}
+
+/// Checksum of the chapter a page was built from, if it was recorded
+pub fn page_checksum(html: &str) -> Option<&str> {
+    page_comment(html, PAGE_CHECKSUM)
+}
+
+/// Hash of the options a page was built with, if it was recorded
+pub fn page_options(html: &str) -> Option<&str> {
+    page_comment(html, PAGE_OPTIONS)
+}
+
+fn page_comment<'a>(html: &'a str, start: &str) -> Option<&'a str> {
+    let at = html.find(start)? + start.len();
+    html[at..].split_once(" -->").map(|(value, _)| value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("@ 1\na\n", "@ 1\na\n", true)]
+    #[case("@ 1\na\n", "# comment\n@ 2\na   # note\n", true)]
+    #[case("@ 1\na\n", "@ 1\na\ntags = x\n", false)]
+    #[case("@ 1\na\n---\nA\n", "@ 1\na\n---\nB\n", false)]
+    fn checksum_test(#[case] a: &str, #[case] b: &str, #[case] same: bool) {
+        let a = Chapter::from_str(a).unwrap();
+        let b = Chapter::from_str(b).unwrap();
+        assert_eq!(a.sentences[0].checksum() == b.sentences[0].checksum(), same);
+    }
+
+    #[test]
+    fn sidecar_test() {
+        let old = Chapter::from_str("@ 1\na\n\n@ 2\nb\n").unwrap();
+        let recorded = parse(&format!("# recorded\n{}", source(&old.checksums()))).unwrap();
+        assert_eq!(recorded, old.checksums());
+        let new = Chapter::from_str("@ 1\na\n\n@ 2\nB\n\n@ 3\nc\n").unwrap();
+        assert_eq!(new.changed_since(&recorded), ["2", "3"]);
+        assert!(parse("1 abc\n").is_err());
+    }
+
+    #[test]
+    fn page_test() {
+        let html = format!("{}\n<html>", crate::output::GENERATED_MARKER);
+        let page = with_page_checksum(&html, "0123abcd", "4567ef");
+        assert_eq!(page_checksum(&page), Some("0123abcd"));
+        assert_eq!(page_options(&page), Some("4567ef"));
+        assert_eq!(page_checksum(&html), None);
+        assert_eq!(page_options(&html), None);
+    }
+}
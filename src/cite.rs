@@ -0,0 +1,110 @@
+#[cfg(feature = "html")]
+use crate::html::escape_attr;
+#[cfg(feature = "html")]
+use crate::label::Label;
+use crate::syntax::Chapter;
+#[cfg(feature = "html")]
+use crate::syntax::{HtmlOptions, Sentence};
+
+impl Chapter {
+    /// Reference to the sentence like `ch3:@42`, after the chapter's `cite`
+    /// attr, or just `@42` without one
+    pub fn citation(&self, label: &str) -> String {
+        match self.attrs.get("cite").map(|c| c.trim()) {
+            Some(c) if !c.is_empty() => format!("{c}:@{label}"),
+            _ => format!("@{label}"),
+        }
+    }
+
+    /// Citation of the sentence with its original, the chapter's title and
+    /// the page of the original if known, like
+    /// `ch3:@42 "Hola mundo" (The Title, p. 37)`
+    pub fn cite(&self, label: &str) -> Option<String> {
+        let i = self.sentences.iter().position(|s| s.label == label)?;
+        let page = match self.pages()[i] {
+            Some(p) => format!(", p. {p}"),
+            None => String::new(),
+        };
+        Some(format!(
+            "{} \"{}\" ({}{page})",
+            self.citation(label),
+            self.sentences[i].text().trim(),
+            self.title.trim()
+        ))
+    }
+
+    /// Whether the sentences show their labels, from the `numbered` attr
+    /// or the renderer option
+    #[cfg(feature = "html")]
+    pub fn numbered(&self, opts: &HtmlOptions) -> bool {
+        self.attr_bool("numbered").unwrap_or(opts.numbered)
+    }
+
+    /// Visible label of the sentence copying its citation when clicked, for
+    /// the `{{number}}` placeholder of numbered chapters; its outline
+    /// number is shown instead when given
+    #[cfg(feature = "html")]
+    pub(crate) fn number_html(
+        &self,
+        sentence: &Sentence,
+        outline: Option<&str>,
+        opts: &HtmlOptions,
+    ) -> String {
+        if !self.numbered(opts) {
+            return String::new();
+        }
+        let label = Label::from(sentence.label.as_str());
+        let cite = escape_attr(&self.citation(&sentence.label));
+        format!(
+            "<a class=\"num\" href=\"#{}\" title=\"Copy {cite}\" data-cite=\"{cite}\" onclick=\"navigator.clipboard.writeText(this.dataset.cite)\">{}</a>",
+            label.anchor(),
+            escape_attr(&outline.map_or_else(|| label.to_string(), String::from))
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case(
+        "cite = ch3\ntitle = Hola\n\n@ 42\nhola << mundo = world >>\n",
+        "ch3:@42 \"hola mundo\" (Hola)"
+    )]
+    #[case("@ 42\nhola\n", "@42 \"hola\" (Unnamed Chapter)")]
+    #[case(
+        "title = Hola\n\n@ 41\nadios\npage = 37\n\n@ 42\nhola\n",
+        "@42 \"hola\" (Hola, p. 37)"
+    )]
+    fn cite_test(#[case] src: &str, #[case] expected: &str) {
+        let chap = Chapter::from_str(src).unwrap();
+        assert_eq!(chap.cite("42").unwrap(), expected);
+        assert_eq!(chap.cite("43"), None);
+    }
+
+    #[cfg(feature = "html")]
+    #[rstest]
+    #[case(
+        "cite = ch3\n\n@ 1\na\n",
+        true,
+        Some("<a class=\"num\" href=\"#line-1\" title=\"Copy ch3:@1\"")
+    )]
+    #[case("cite = ch3\n\n@ 1\na\n", false, None)]
+    #[case("numbered = yes\n\n@ 1\na\n", false, Some("data-cite=\"@1\""))]
+    #[case("numbered = no\n\n@ 1\na\n", true, None)]
+    fn numbered_test(#[case] src: &str, #[case] numbered: bool, #[case] expected: Option<&str>) {
+        let chap = Chapter::from_str(src).unwrap();
+        let opts = HtmlOptions {
+            numbered,
+            ..Default::default()
+        };
+        let html = chap.sentence_html(&chap.sentences[0], &opts);
+        match expected {
+            Some(e) => assert!(html.contains(e), "{html}"),
+            None => assert!(!html.contains("class=\"num\""), "{html}"),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+
+/// Rules of a language that differ from ignoring case and diacritics
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Tailoring {
+    #[default]
+    Root,
+    /// Katakana sorts with hiragana, and small and voiced kana with the
+    /// plain ones, in the order of the gojūon
+    Japanese,
+    /// `ñ` is a letter of its own after `n`
+    Spanish,
+}
+
+impl Tailoring {
+    /// Rules of the language by its name or ISO 639 code, like the
+    /// `language` attr of a chapter (`japanese`, `ja`, `es-MX`)
+    pub fn for_language(language: &str) -> Self {
+        let lang = language.trim().to_lowercase();
+        let code = lang.split(['-', '_']).next().unwrap_or_default();
+        match code {
+            "ja" | "jpn" | "japanese" => Self::Japanese,
+            "es" | "spa" | "spanish" | "español" | "espanol" => Self::Spanish,
+            _ => Self::Root,
+        }
+    }
+}
+
+/// Key of the word at the primary level: lowercased, without diacritics
+/// and with the tailoring of the language
+fn primary(word: &str, tailoring: Tailoring) -> String {
+    let mut key = String::with_capacity(word.len());
+    let mut chars = word.nfd().flat_map(char::to_lowercase).peekable();
+    while let Some(c) = chars.next() {
+        match (tailoring, c) {
+            (_, c) if is_combining_mark(c) => (),
+            (Tailoring::Spanish, 'n') if chars.peek() == Some(&'\u{303}') => {
+                // after every word starting with n and before o
+                key.push('n');
+                key.push(char::MAX);
+            }
+            (Tailoring::Japanese, c) => key.push(kana(c)),
+            _ => key.push(c),
+        }
+    }
+    key
+}
+
+/// Plain hiragana of the kana, the combining voicing marks already being
+/// split off by the decomposition
+fn kana(c: char) -> char {
+    let c = match c {
+        // katakana ァ to ヶ
+        '\u{30A1}'..='\u{30F6}' => char::from_u32(c as u32 - 0x60).unwrap_or(c),
+        _ => c,
+    };
+    match c {
+        'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'っ' | 'ゃ' | 'ゅ' | 'ょ' | 'ゎ' => {
+            char::from_u32(c as u32 + 1).unwrap_or(c)
+        }
+        'ゕ' => 'か',
+        'ゖ' => 'け',
+        _ => c,
+    }
+}
+
+/// Sort key of the word in the language: words equal but for diacritics
+/// go by them, and then by case
+pub fn sort_key(word: &str, language: &str) -> (String, String, String) {
+    let tailoring = Tailoring::for_language(language);
+    (
+        primary(word, tailoring),
+        word.nfd().flat_map(char::to_lowercase).collect(),
+        word.to_string(),
+    )
+}
+
+/// Sorts the items by their words the way a dictionary of the language
+/// orders them
+pub fn sort_by_word<T>(items: &mut [T], language: &str, word: impl Fn(&T) -> &str) {
+    items.sort_by_cached_key(|i| sort_key(word(i), language));
+}
+
+/// Sorts the words the way a dictionary of the language orders them
+pub fn sort_words<S: AsRef<str>>(words: &mut [S], language: &str) {
+    sort_by_word(words, language, |w| w.as_ref());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("english", &["Zebra", "apple", "éclair", "eclair", "Eclair"], &["apple", "Eclair", "eclair", "éclair", "Zebra"])]
+    #[case("es", &["ola", "ñu", "nube", "árbol", "nz"], &["árbol", "nube", "nz", "ñu", "ola"])]
+    #[case("spanish", &["canon", "cañón", "cana"], &["cana", "canon", "cañón"])]
+    #[case("japanese", &["ネコ", "いぬ", "がっこう", "かさ", "カキ"], &["いぬ", "カキ", "かさ", "がっこう", "ネコ"])]
+    #[case("ja-JP", &["きゃく", "きやく", "キャク"], &["きゃく", "きやく", "キャク"])]
+    fn sort_test(#[case] lang: &str, #[case] words: &[&str], #[case] sorted: &[&str]) {
+        let mut words = words.to_vec();
+        sort_words(&mut words, lang);
+        assert_eq!(words, sorted);
+    }
+
+    #[rstest]
+    #[case("Japanese", Tailoring::Japanese)]
+    #[case("jpn", Tailoring::Japanese)]
+    #[case("es_MX", Tailoring::Spanish)]
+    #[case("nepali", Tailoring::Root)]
+    #[case("", Tailoring::Root)]
+    fn tailoring_test(#[case] lang: &str, #[case] expected: Tailoring) {
+        assert_eq!(Tailoring::for_language(lang), expected);
+    }
+}
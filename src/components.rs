@@ -4,7 +4,7 @@ use crate::{
 };
 use nom::{
     branch::alt,
-    combinator::value,
+    combinator::{eof, value},
     multi::{many0, many1},
     sequence::{preceded, terminated},
     Parser,
@@ -22,6 +22,22 @@ pub fn string_val(inp: TokenList<'_>) -> MatchRes<'_, String> {
         .parse(inp)
 }
 
+/// Text that may contain any symbol except line ends and `}}`
+pub fn inline_text(inp: TokenList<'_>) -> MatchRes<'_, String> {
+    many1(alt((
+        character,
+        space,
+        equal,
+        semicolon,
+        at,
+        dash,
+        angle_start,
+        angle_end,
+    )))
+    .map(|tk| tk.iter().map(|t| t.content).collect())
+    .parse(inp)
+}
+
 macro_rules! one_token {
     ($name:ident, $ty:pat) => {
         pub fn $name(inp: TokenList<'_>) -> MatchRes<'_, &Token<'_>> {
@@ -45,6 +61,8 @@ one_token!(space, TokenType::WhiteSpace);
 one_token!(comment, TokenType::Comment);
 one_token!(angle_start, TokenType::AngleStart);
 one_token!(angle_end, TokenType::AngleEnd);
+one_token!(brace_start, TokenType::BraceStart);
+one_token!(brace_end, TokenType::BraceEnd);
 one_token!(at, TokenType::At);
 one_token!(equal, TokenType::Equal);
 one_token!(semicolon, TokenType::Semicolon);
@@ -115,8 +133,13 @@ pub fn many0_newlines(inp: TokenList<'_>) -> MatchRes<'_, ()> {
     value((), many0(alt((space, newline, comment)))).parse(inp)
 }
 
+/// Matches one or more line ends, the end of input counts as one
 pub fn many1_newlines(inp: TokenList<'_>) -> MatchRes<'_, ()> {
-    value((), many1(maybe_space(alt((newline, comment))))).parse(inp)
+    alt((
+        value((), many1(maybe_space(alt((newline, comment))))),
+        value((), maybe_space(eof)),
+    ))
+    .parse(inp)
 }
 
 #[cfg(test)]
@@ -0,0 +1,132 @@
+use crate::syntax::{Chapter, OrgFragment};
+use std::borrow::Cow;
+
+/// Start of a clause only shown to readers of one language,
+/// `<<?english (a greeting)>>`
+pub const CONDITIONAL_START: &str = "<<?";
+const CONDITIONAL_END: &str = ">>";
+
+/// Language and text of the clause inside `<<?` and `>>`
+pub fn split_conditional(inner: &str) -> (String, String) {
+    let inner = inner.trim();
+    match inner.split_once(char::is_whitespace) {
+        Some((lang, text)) => (lang.to_string(), text.trim().to_string()),
+        None => (inner.to_string(), String::new()),
+    }
+}
+
+/// Text with the conditional clauses for the languages `keep` accepts
+/// replaced by their text and the others removed, with the space before
+/// them
+pub fn resolve(text: &str, keep: impl Fn(&str) -> bool) -> Cow<'_, str> {
+    if !text.contains(CONDITIONAL_START) {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(CONDITIONAL_START) {
+        let after = &rest[start + CONDITIONAL_START.len()..];
+        let Some(end) = after.find(CONDITIONAL_END) else {
+            break;
+        };
+        out.push_str(&rest[..start]);
+        let (lang, clause) = split_conditional(&after[..end]);
+        if keep(&lang) {
+            out.push_str(&clause);
+        } else if out.ends_with(char::is_whitespace) {
+            out.pop();
+        }
+        rest = &after[end + CONDITIONAL_END.len()..];
+    }
+    out.push_str(rest);
+    Cow::Owned(out)
+}
+
+impl Chapter {
+    /// Resolves the conditional clauses for readers of the languages: the
+    /// clauses for them are kept and the others removed. Translations keep
+    /// the clauses for their own language when no language is given.
+    pub fn for_languages(&mut self, languages: &[String]) {
+        let mut sentences = std::mem::take(&mut self.sentences);
+        let targets: Vec<String> = languages
+            .iter()
+            .map(|l| self.canonical_language(l))
+            .collect();
+        let keeps = |lang: &str, own: Option<&str>| {
+            let lang = self.canonical_language(lang);
+            if targets.is_empty() {
+                own == Some(lang.as_str())
+            } else {
+                targets.contains(&lang)
+            }
+        };
+        for s in &mut sentences {
+            let mut original: Vec<OrgFragment> = Vec::with_capacity(s.original.len());
+            for o in std::mem::take(&mut s.original) {
+                match o {
+                    OrgFragment::Conditional(lang, text) if keeps(&lang, None) => {
+                        original.push(OrgFragment::Simple(text))
+                    }
+                    OrgFragment::Conditional(..) => {
+                        if let Some(OrgFragment::Simple(prev)) = original.last_mut()
+                            && prev.ends_with(char::is_whitespace)
+                        {
+                            prev.pop();
+                        }
+                    }
+                    o => original.push(o),
+                }
+            }
+            s.original = original;
+            for (i, (k, t)) in s.translations.iter_mut().enumerate() {
+                let own = self.translation_language(i, k);
+                if let Cow::Owned(c) = resolve(&t.content, |l| keeps(l, Some(&own))) {
+                    t.content = c;
+                }
+            }
+        }
+        self.sentences = sentences;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("a <<?en x>> b", &["en"], "a x b")]
+    #[case("a <<?en x>> b", &["de"], "a b")]
+    #[case("a <<?en x y>>.", &["de"], "a.")]
+    #[case("<<?en>>a <<?de z", &["de"], "a <<?de z")]
+    fn resolve_test(#[case] text: &str, #[case] keep: &[&str], #[case] expected: &str) {
+        assert_eq!(resolve(text, |l| keep.contains(&l)), expected);
+    }
+
+    const CHAP: &str = "tranlations = english, german\nenglish.aliases = en\n\n@ 1\nnamaste << ?en (a greeting) >>!\n---\nhello <<?en (informal)>> there\n---\nhallo <<?en (informal)>> du\n";
+
+    #[rstest]
+    #[case(&[], "namaste!", "hello (informal) there", "hallo du")]
+    #[case(&["english"], "namaste (a greeting)!", "hello (informal) there", "hallo (informal) du")]
+    #[case(&["german"], "namaste!", "hello there", "hallo du")]
+    fn for_languages_test(
+        #[case] languages: &[&str],
+        #[case] original: &str,
+        #[case] english: &str,
+        #[case] german: &str,
+    ) {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        assert_eq!(Chapter::from_str(&chap.source()).unwrap(), chap);
+        let languages: Vec<String> = languages.iter().map(|l| l.to_string()).collect();
+        chap.for_languages(&languages);
+        let s = &chap.sentences[0];
+        assert_eq!(s.text(), original);
+        let tls: Vec<&str> = s
+            .translations
+            .values()
+            .map(|t| t.content.as_str())
+            .collect();
+        assert_eq!(tls, vec![english, german]);
+    }
+}
@@ -0,0 +1,179 @@
+use crate::intern;
+use crate::numbers::digit;
+use crate::syntax::{Chapter, OrgFragment};
+use regex::Regex;
+use std::str::FromStr;
+
+/// Unit of the originals with its value in one the reader knows, an entry
+/// of the `convert` chapter attr like `ri: 3.9 km` or `ryo: ~300000 yen`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conversion {
+    pub unit: String,
+    pub factor: f64,
+    pub target: String,
+    /// Marked with `~`, for values like currencies that can only be guessed
+    pub approximate: bool,
+}
+
+impl FromStr for Conversion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Conversion {s:?} isn't like `ri: 3.9 km`");
+        let (unit, value) = s.split_once(':').ok_or_else(err)?;
+        let value = value.trim();
+        let (approximate, value) = match value.strip_prefix('~') {
+            Some(v) => (true, v.trim_start()),
+            None => (false, value),
+        };
+        let end = value
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(value.len());
+        let factor: f64 = value[..end].parse().map_err(|_| err())?;
+        let unit = unit.trim();
+        if unit.is_empty() {
+            return Err(err());
+        }
+        Ok(Self {
+            unit: unit.to_string(),
+            factor,
+            target: value[end..].trim().to_string(),
+            approximate,
+        })
+    }
+}
+
+impl Conversion {
+    /// Gloss of the amount of this unit
+    pub fn gloss(&self, amount: f64) -> String {
+        let value = format!("{:.2}", amount * self.factor);
+        let value = value.trim_end_matches('0').trim_end_matches('.');
+        let about = if self.approximate { "about " } else { "" };
+        if self.target.is_empty() {
+            format!("{about}{value}")
+        } else {
+            format!("{about}{value} {}", self.target)
+        }
+    }
+}
+
+/// Value of a number written with digits of any script, commas between
+/// the thousands and a `.` before the decimals
+fn amount(text: &str) -> Option<f64> {
+    let ascii: String = text
+        .chars()
+        .filter(|c| *c != ',')
+        .map(|c| digit(c).map(|(d, _)| char::from(b'0' + d)).unwrap_or(c))
+        .collect();
+    ascii.parse().ok()
+}
+
+impl Chapter {
+    /// Units of the `convert` attr, the entries separated by commas
+    pub fn conversions(&self) -> Vec<Conversion> {
+        let Some(attr) = self.attrs.get("convert") else {
+            return Vec::new();
+        };
+        attr.split(',')
+            .filter(|e| !e.trim().is_empty())
+            .filter_map(|e| {
+                e.parse()
+                    .map_err(|e| tracing::warn!("{e}, it is ignored"))
+                    .ok()
+            })
+            .collect()
+    }
+
+    /// Glosses the amounts of the `convert` attr units in the text of the
+    /// originals, like `3 ri`, with their converted value
+    pub fn convert_units(&mut self) {
+        let conversions = self.conversions();
+        if conversions.is_empty() {
+            return;
+        }
+        let units: Vec<String> = conversions.iter().map(|c| regex::escape(&c.unit)).collect();
+        let re = Regex::new(&format!(
+            r"(\d+(?:,\d{{3}})*(?:\.\d+)?)\s?({})\b",
+            units.join("|")
+        ))
+        .expect("escaped units");
+        for s in &mut self.sentences {
+            let mut original = Vec::with_capacity(s.original.len());
+            for o in std::mem::take(&mut s.original) {
+                let OrgFragment::Simple(text) = o else {
+                    original.push(o);
+                    continue;
+                };
+                let mut last = 0;
+                for m in re.captures_iter(&text) {
+                    let whole = m.get(0).expect("match");
+                    let (Some(amount), Some(conv)) =
+                        (amount(&m[1]), conversions.iter().find(|c| c.unit == m[2]))
+                    else {
+                        continue;
+                    };
+                    if whole.start() > last {
+                        original.push(OrgFragment::Simple(text[last..whole.start()].to_string()));
+                    }
+                    original.push(OrgFragment::Meaning(
+                        whole.as_str().to_string(),
+                        intern::meanings(&[conv.gloss(amount)]),
+                    ));
+                    last = whole.end();
+                }
+                if last < text.len() {
+                    original.push(OrgFragment::Simple(text[last..].to_string()));
+                }
+            }
+            s.original = original;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("ri: 3.9 km", 3.0, "11.7 km")]
+    #[case("ryo: ~300000 yen", 0.5, "about 150000 yen")]
+    #[case("shaku:0.303", 2.0, "0.61")]
+    fn conversion_test(#[case] entry: &str, #[case] amount: f64, #[case] expected: &str) {
+        assert_eq!(Conversion::from_str(entry).unwrap().gloss(amount), expected);
+    }
+
+    #[rstest]
+    #[case("ri 3.9 km")]
+    #[case("ri: km")]
+    #[case(": 3 km")]
+    fn bad_conversion_test(#[case] entry: &str) {
+        assert!(Conversion::from_str(entry).is_err());
+    }
+
+    #[test]
+    fn convert_units_test() {
+        let mut chap = Chapter::from_str(
+            "convert = ri: 3.9 km, ryo: ~300000 yen, bad\n\n@ 1\nHe walked 3 ri, paid 1.5ryo and 1,000 ryo, three ri and 2 rice << ri = x >>\n",
+        )
+        .unwrap();
+        chap.process();
+        let glosses: Vec<(String, String)> = chap.sentences[0]
+            .original
+            .iter()
+            .filter_map(|o| match o {
+                OrgFragment::Meaning(w, _) => Some((w.clone(), o.gloss()?)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            glosses,
+            vec![
+                ("3 ri".to_string(), "11.7 km".to_string()),
+                ("1.5ryo".to_string(), "about 450000 yen".to_string()),
+                ("1,000 ryo".to_string(), "about 300000000 yen".to_string()),
+                ("ri".to_string(), "x".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,217 @@
+/// Part of a diff between two sequences
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Change<T> {
+    Same(T),
+    Removed(T),
+    Added(T),
+}
+
+/// Shortest edit turning one sequence into the other, by Myers' algorithm;
+/// removals come before the additions replacing them
+pub fn diff<'a, T: PartialEq>(a: &'a [T], b: &'a [T]) -> Vec<Change<&'a T>> {
+    // the common start and end are left out of the search
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let (ma, mb) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let mut changes: Vec<Change<&T>> = a[..prefix].iter().map(Change::Same).collect();
+    changes.extend(middle(ma, mb));
+    changes.extend(a[a.len() - suffix..].iter().map(Change::Same));
+    changes
+}
+
+fn middle<'a, T: PartialEq>(a: &'a [T], b: &'a [T]) -> Vec<Change<&'a T>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = n + m;
+    let at = |k: isize| (k + max) as usize;
+    // furthest x reached on each diagonal k = x - y, kept for each d
+    let mut v = vec![0isize; 2 * max as usize + 2];
+    let mut trace = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v.clone());
+        for k in (-d..=d).step_by(2) {
+            let mut x = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+                v[at(k + 1)]
+            } else {
+                v[at(k - 1)] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[at(k)] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+        }
+    }
+    let (mut x, mut y) = (n, m);
+    let mut changes = Vec::new();
+    for (d, v) in trace.iter().enumerate().rev() {
+        let d = d as isize;
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && v[at(k - 1)] < v[at(k + 1)]) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let prev_x = v[at(prev_k)];
+        let prev_y = prev_x - prev_k;
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            changes.push(Change::Same(&a[x as usize]));
+        }
+        if d > 0 {
+            if x == prev_x {
+                changes.push(Change::Added(&b[prev_y as usize]));
+            } else {
+                changes.push(Change::Removed(&a[prev_x as usize]));
+            }
+        }
+        (x, y) = (prev_x, prev_y);
+    }
+    changes.reverse();
+    changes
+}
+
+/// Lines of unchanged text around the changes of a unified diff
+const CONTEXT: usize = 3;
+
+/// Unified diff of two versions of a file, like `diff -u` prints them,
+/// with the removed lines in red and the added ones in green when colored;
+/// empty when they are the same
+pub fn unified(old: &str, new: &str, name: &str, color: bool) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let changes = diff(&a, &b);
+    // lines of each version before each change
+    let mut before = Vec::with_capacity(changes.len() + 1);
+    let (mut i, mut j) = (0, 0);
+    for c in &changes {
+        before.push((i, j));
+        match c {
+            Change::Same(_) => (i, j) = (i + 1, j + 1),
+            Change::Removed(_) => i += 1,
+            Change::Added(_) => j += 1,
+        }
+    }
+    before.push((i, j));
+    let edited: Vec<usize> = (0..changes.len())
+        .filter(|c| !matches!(changes[*c], Change::Same(_)))
+        .collect();
+    if edited.is_empty() {
+        return String::new();
+    }
+    let (red, green, reset) = match color {
+        true => ("\x1b[31m", "\x1b[32m", "\x1b[0m"),
+        false => ("", "", ""),
+    };
+    let mut out = format!("--- {name}\n+++ {name}\n");
+    let mut start = 0;
+    while start < edited.len() {
+        // changes closer than twice the context share a hunk
+        let mut end = start;
+        while end + 1 < edited.len() && edited[end + 1] - edited[end] <= 2 * CONTEXT + 1 {
+            end += 1;
+        }
+        let first = edited[start].saturating_sub(CONTEXT);
+        let last = (edited[end] + CONTEXT + 1).min(changes.len());
+        let (old_start, new_start) = before[first];
+        let (old_end, new_end) = before[last];
+        let range = |s: usize, e: usize| match e - s {
+            0 => format!("{s},0"),
+            1 => format!("{}", s + 1),
+            n => format!("{},{n}", s + 1),
+        };
+        out.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_end),
+            range(new_start, new_end)
+        ));
+        for c in &changes[first..last] {
+            let (sign, line, (on, off)) = match c {
+                Change::Same(l) => (' ', l, ("", "")),
+                Change::Removed(l) => ('-', l, (red, reset)),
+                Change::Added(l) => ('+', l, (green, reset)),
+            };
+            out.push_str(&format!("{on}{sign}{}{off}\n", line.trim_end_matches('\n')));
+            if !line.ends_with('\n') {
+                out.push_str("\\ No newline at end of file\n");
+            }
+        }
+        start = end + 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("abcabba", "cbabac")]
+    #[case("", "abc")]
+    #[case("abc", "")]
+    #[case("same", "same")]
+    #[case("xaby", "xcdy")]
+    fn diff_test(#[case] a: &str, #[case] b: &str) {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        let changes = diff(&a, &b);
+        let old: Vec<char> = changes
+            .iter()
+            .filter_map(|c| match c {
+                Change::Same(x) | Change::Removed(x) => Some(**x),
+                Change::Added(_) => None,
+            })
+            .collect();
+        let new: Vec<char> = changes
+            .iter()
+            .filter_map(|c| match c {
+                Change::Same(x) | Change::Added(x) => Some(**x),
+                Change::Removed(_) => None,
+            })
+            .collect();
+        assert_eq!((old, new), (a, b));
+    }
+
+    #[test]
+    fn shortest_test() {
+        let a: Vec<char> = "abcabba".chars().collect();
+        let b: Vec<char> = "cbabac".chars().collect();
+        let edits = diff(&a, &b)
+            .iter()
+            .filter(|c| !matches!(c, Change::Same(_)))
+            .count();
+        assert_eq!(edits, 5);
+    }
+
+    #[rstest]
+    #[case("a\nb\nc\n", "a\nb\nc\n", "")]
+    #[case(
+        "a\nb\nc\n",
+        "a\nB\nc\n",
+        "--- f\n+++ f\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n"
+    )]
+    #[case(
+        "a\n",
+        "a\nb",
+        "--- f\n+++ f\n@@ -1 +1,2 @@\n a\n+b\n\\ No newline at end of file\n"
+    )]
+    #[case("", "a\n", "--- f\n+++ f\n@@ -0,0 +1 @@\n+a\n")]
+    #[case(
+        "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n",
+        "0\n1\n2\n3\n4\n5\n6\n7\n8\n9\n",
+        "--- f\n+++ f\n@@ -1,3 +1,4 @@\n+0\n 1\n 2\n 3\n@@ -7,4 +8,3 @@\n 7\n 8\n 9\n-10\n"
+    )]
+    fn unified_test(#[case] old: &str, #[case] new: &str, #[case] expected: &str) {
+        assert_eq!(unified(old, new, "f", false), expected);
+    }
+}
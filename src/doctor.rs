@@ -0,0 +1,222 @@
+use crate::errors::{Diagnostic, Severity};
+#[cfg(feature = "html")]
+use crate::html::local_assets;
+#[cfg(feature = "html")]
+use crate::parser::resolve_path;
+#[cfg(feature = "html")]
+use crate::plugin::Plugins;
+#[cfg(feature = "html")]
+use crate::syntax::OrgFragment;
+use crate::syntax::{Chapter, Sentence};
+use std::path::Path;
+
+/// Cargo features transdoc was built with
+pub fn features() -> Vec<&'static str> {
+    [
+        ("cli", cfg!(feature = "cli")),
+        ("cjk", cfg!(feature = "cjk")),
+        ("encrypt", cfg!(feature = "encrypt")),
+        ("html", cfg!(feature = "html")),
+        ("import", cfg!(feature = "import")),
+        ("kindle", cfg!(feature = "kindle")),
+        ("serve", cfg!(feature = "serve")),
+        ("site", cfg!(feature = "site")),
+        ("tui", cfg!(feature = "tui")),
+    ]
+    .into_iter()
+    .filter(|(_, on)| *on)
+    .map(|(f, _)| f)
+    .collect()
+}
+
+fn missing(s: Option<&Sentence>, message: String) -> Diagnostic {
+    Diagnostic {
+        severity: Severity::Warning,
+        line: s.map_or(0, |s| s.span.line),
+        col: s.map_or(0, |s| s.span.col),
+        message,
+        ..Default::default()
+    }
+}
+
+impl Chapter {
+    /// Files the recordings and, with the `html` feature, the built in
+    /// fragment plugins, like `{{img:cat.png}}`, refer to that
+    /// don't exist, relative to the directory, and template files that
+    /// don't, relative to the current one like the renderer reads them
+    pub fn missing_assets(&self, dir: &Path) -> Vec<Diagnostic> {
+        let (clips, mut diags) = self.audio_clips(dir);
+        for c in clips.values().flatten().filter(|c| !c.file.is_file()) {
+            let s = self.sentences.iter().find(|s| s.label == c.label);
+            diags.push(missing(
+                s,
+                format!(
+                    "Recording {} of @{} doesn't exist, fix the path of its audio attr",
+                    c.file.to_string_lossy(),
+                    c.label
+                ),
+            ));
+        }
+        #[cfg(feature = "html")]
+        let plugins = Plugins::default();
+        #[cfg(feature = "html")]
+        for s in &self.sentences {
+            let html: String = s
+                .original
+                .iter()
+                .filter_map(|o| match o {
+                    OrgFragment::Plugin(n, c) => Some(plugins.html(n, c)),
+                    _ => None,
+                })
+                .collect();
+            for f in local_assets(&html) {
+                if !resolve_path(dir, f).exists() {
+                    diags.push(missing(
+                        Some(s),
+                        format!("File {f} used in @{} doesn't exist", s.label),
+                    ));
+                }
+            }
+        }
+        for (k, v) in &self.attrs {
+            if let Some(name) = k.strip_prefix("template.")
+                && !Path::new(v).is_file()
+            {
+                diags.push(missing(
+                    None,
+                    format!(
+                        "Template {name:?} file {v} doesn't exist, the default one is used instead"
+                    ),
+                ));
+            }
+        }
+        diags
+    }
+}
+
+/// Tally of the problems found checking a project
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Health {
+    pub chapters: usize,
+    /// Chapters with errors, or only with warnings
+    pub broken: usize,
+    pub warned: usize,
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+impl Health {
+    fn count(&mut self, diags: &[Diagnostic]) -> (usize, usize) {
+        let errors = diags
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count();
+        self.errors += errors;
+        self.warnings += diags.len() - errors;
+        (errors, diags.len() - errors)
+    }
+
+    /// Adds the problems of a chapter
+    pub fn chapter(&mut self, diags: &[Diagnostic]) {
+        self.chapters += 1;
+        match self.count(diags) {
+            (0, 0) => (),
+            (0, _) => self.warned += 1,
+            _ => self.broken += 1,
+        }
+    }
+
+    /// Adds problems of the project rather than one of its chapters
+    pub fn project(&mut self, diags: &[Diagnostic]) {
+        self.count(diags);
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.errors == 0
+    }
+}
+
+impl std::fmt::Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let plural = |n: usize, what: &str| match n {
+            1 => format!("1 {what}"),
+            n => format!("{n} {what}s"),
+        };
+        let checked = plural(self.chapters, "chapter");
+        match (self.errors, self.warnings) {
+            (0, 0) => write!(f, "Healthy: {checked} checked, no problems"),
+            (0, w) => {
+                write!(f, "Usable: {checked} checked, {}", plural(w, "warning"))?;
+                if self.warned > 0 {
+                    write!(f, " in {}", plural(self.warned, "chapter"))?;
+                }
+                write!(f, "; the pages build but may not look as intended")
+            }
+            (e, w) => {
+                write!(
+                    f,
+                    "Broken: {checked} checked, {} and {}",
+                    plural(e, "error"),
+                    plural(w, "warning")
+                )?;
+                if self.broken > 0 {
+                    write!(f, ", {} with errors", plural(self.broken, "chapter"))?;
+                }
+                write!(f, "; fix the errors first")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    #[cfg(feature = "html")]
+    use std::str::FromStr;
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn missing_assets_test() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        let chap = Chapter::from_str(
+            "template.fig = fig.html\n\n@ 1\nhola {{img:README.md|readme}} {{img:nope.png}}\naudio = missing.mp3\n",
+        )
+        .unwrap();
+        let messages: Vec<String> = chap
+            .missing_assets(dir)
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(messages.len(), 3, "{messages:?}");
+        assert!(messages[0].starts_with("Recording "));
+        assert!(
+            messages[0]
+                .ends_with("missing.mp3 of @1 doesn't exist, fix the path of its audio attr")
+        );
+        assert_eq!(messages[1], "File nope.png used in @1 doesn't exist");
+        assert_eq!(
+            messages[2],
+            "Template \"fig\" file fig.html doesn't exist, the default one is used instead"
+        );
+    }
+
+    fn diag(severity: Severity) -> Diagnostic {
+        Diagnostic {
+            severity,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case(&[], "Healthy: 2 chapters checked, no problems")]
+    #[case(&[Severity::Warning], "Usable: 2 chapters checked, 1 warning in 1 chapter; the pages build but may not look as intended")]
+    #[case(&[Severity::Error, Severity::Warning], "Broken: 2 chapters checked, 1 error and 1 warning, 1 chapter with errors; fix the errors first")]
+    fn health_test(#[case] first: &[Severity], #[case] expected: &str) {
+        let mut health = Health::default();
+        health.chapter(&first.iter().map(|s| diag(*s)).collect::<Vec<_>>());
+        health.chapter(&[]);
+        assert_eq!(health.to_string(), expected);
+        assert_eq!(health.is_ok(), !first.contains(&Severity::Error));
+    }
+}
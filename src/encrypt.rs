@@ -0,0 +1,175 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use sha2::Sha256;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// PBKDF2 rounds deriving the key from the passphrase
+pub const ITERATIONS: u32 = 200_000;
+
+const SALT: usize = 16;
+const NONCE: usize = 12;
+
+/// Encrypts translations with a key derived from a passphrase, for pages
+/// that decrypt them in the browser when given it.
+///
+/// The key comes from PBKDF2-HMAC-SHA256 of the passphrase with a random
+/// salt, and each text is sealed with AES-256-GCM, both of which WebCrypto
+/// can undo. The salt is new for every sealer, so the nonces only have to
+/// be unique under its key and are counted up from zero. It keeps drafts
+/// from being read by anyone finding the page, it isn't meant to hold
+/// against someone guessing weak passphrases.
+pub struct Sealer {
+    salt: [u8; SALT],
+    iterations: u32,
+    cipher: Aes256Gcm,
+    sealed: AtomicU64,
+}
+
+impl std::fmt::Debug for Sealer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Sealer")
+            .field("iterations", &self.iterations)
+            .finish_non_exhaustive()
+    }
+}
+
+fn random<const N: usize>() -> Result<[u8; N], String> {
+    let mut bytes = [0u8; N];
+    getrandom::fill(&mut bytes).map_err(|e| format!("No random numbers to encrypt with: {e}"))?;
+    Ok(bytes)
+}
+
+/// Key PBKDF2-HMAC-SHA256 derives from the passphrase and salt
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> Key<Aes256Gcm> {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(passphrase.as_bytes(), salt, iterations).into()
+}
+
+impl Sealer {
+    pub fn new(passphrase: &str) -> Result<Self, String> {
+        Self::with_iterations(passphrase, ITERATIONS)
+    }
+
+    pub fn with_iterations(passphrase: &str, iterations: u32) -> Result<Self, String> {
+        if passphrase.is_empty() {
+            return Err("The passphrase is empty".into());
+        }
+        let salt = random()?;
+        Ok(Self {
+            salt,
+            iterations,
+            cipher: Aes256Gcm::new(&derive_key(passphrase, &salt, iterations)),
+            sealed: AtomicU64::new(0),
+        })
+    }
+
+    /// Next nonce of the key, the count of texts sealed before
+    fn nonce(&self) -> [u8; NONCE] {
+        let mut nonce = [0u8; NONCE];
+        let count = self.sealed.fetch_add(1, Ordering::Relaxed);
+        nonce[NONCE - 8..].copy_from_slice(&count.to_be_bytes());
+        nonce
+    }
+
+    /// `nonce || ciphertext || tag` of the text, in base64
+    fn seal(&self, text: &str) -> String {
+        let nonce = self.nonce();
+        let mut sealed = nonce.to_vec();
+        sealed.extend(
+            self.cipher
+                .encrypt(Nonce::from_slice(&nonce), text.as_bytes())
+                .expect("texts are far shorter than AES-GCM can seal"),
+        );
+        BASE64.encode(sealed)
+    }
+
+    /// Element the page puts the HTML back in place of once decrypted
+    pub fn seal_html(&self, html: &str) -> String {
+        format!(
+            "<div class=\"sealed\" data-sealed=\"{}\"></div>",
+            self.seal(html)
+        )
+    }
+
+    /// Passphrase form decrypting the sealed elements of the page
+    pub fn form_html(&self) -> String {
+        UNSEAL
+            .replace("{{salt}}", &BASE64.encode(self.salt))
+            .replace("{{iterations}}", &self.iterations.to_string())
+    }
+}
+
+/// Asks for the passphrase and puts the decrypted HTML in place of the
+/// sealed elements, see `Sealer`
+const UNSEAL: &str = r#"<form class="unseal" data-salt="{{salt}}" data-iterations="{{iterations}}">
+<label>Passphrase <input type="password" name="pass" autocomplete="off"></label>
+<button>Show the translations</button> <output name="msg"></output>
+</form>
+<script>
+document.querySelector('form.unseal').addEventListener('submit', async ev => {
+    ev.preventDefault();
+    const form = ev.target;
+    const subtle = crypto.subtle;
+    const bytes = s => Uint8Array.from(atob(s), c => c.charCodeAt(0));
+    const pass = await subtle.importKey('raw', new TextEncoder().encode(form.pass.value),
+        'PBKDF2', false, ['deriveKey']);
+    const key = await subtle.deriveKey({name: 'PBKDF2', hash: 'SHA-256',
+        salt: bytes(form.dataset.salt), iterations: +form.dataset.iterations}, pass,
+        {name: 'AES-GCM', length: 256}, false, ['decrypt']);
+    for (const el of document.querySelectorAll('[data-sealed]')) {
+        const data = bytes(el.dataset.sealed);
+        let text;
+        try {
+            text = await subtle.decrypt({name: 'AES-GCM', iv: data.slice(0, 12)}, key,
+                data.slice(12));
+        } catch {
+            form.msg.value = 'Wrong passphrase';
+            return;
+        }
+        el.outerHTML = new TextDecoder().decode(text);
+    }
+    form.hidden = true;
+});
+</script>
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open(sealer: &Sealer, sealed: &str) -> Option<String> {
+        let data = BASE64.decode(sealed).unwrap();
+        let (nonce, text) = data.split_at(NONCE);
+        let opened = sealer.cipher.decrypt(Nonce::from_slice(nonce), text).ok()?;
+        Some(String::from_utf8(opened).unwrap())
+    }
+
+    #[test]
+    fn derive_key_test() {
+        // RFC 7914 PBKDF2-HMAC-SHA256 test vector
+        let key: String = derive_key("passwd", b"salt", 1)
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect();
+        assert_eq!(
+            key,
+            "55ac046e56e3089fec1691c22544b605f94185216dde0465e68b9d57c20dacbc"
+        );
+    }
+
+    #[test]
+    fn seal_test() {
+        let sealer = Sealer::with_iterations("secret", 10).unwrap();
+        let html = "<div class=\"tl\">a translation</div>";
+        let (first, second) = (sealer.seal(html), sealer.seal(html));
+        assert_ne!(first, second);
+        assert_eq!(open(&sealer, &first).as_deref(), Some(html));
+        assert_eq!(open(&sealer, &second).as_deref(), Some(html));
+        // another passphrase, or the same one with another salt, can't open it
+        let other = Sealer::with_iterations("secret", 10).unwrap();
+        assert_eq!(open(&other, &first), None);
+        assert!(!sealer.seal_html(html).contains("translation"));
+        assert!(Sealer::new("").is_err());
+    }
+}
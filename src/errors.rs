@@ -58,6 +58,116 @@ impl ParseError {
         }
         msg
     }
+
+    /// Single line `file:line:col: error: message` form for tools to parse
+    pub fn line_msg(&self, filename: &str) -> String {
+        self.diagnostic(Some(filename)).gcc()
+    }
+
+    pub fn diagnostic(&self, filename: Option<&str>) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            file: filename.map(String::from),
+            line: self.line,
+            col: self.col,
+            message: self.ty.message(),
+            linestr: Some(self.linestr.clone()),
+            rule: None,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Default, PartialOrd, Eq, Ord)]
+pub enum Severity {
+    #[default]
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// A problem found in a file, with its position when known
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub file: Option<String>,
+    /// Line and column from 1, 0 when the problem is with the whole file
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+    /// Source line the problem is in
+    pub linestr: Option<String>,
+    /// Lint rule that found it, whose level the project can set; problems
+    /// without one, like syntax errors, are always reported
+    pub rule: Option<&'static str>,
+}
+
+impl Diagnostic {
+    /// Problem with the whole file, like it not being readable
+    pub fn file(severity: Severity, file: &str, message: String) -> Self {
+        Self {
+            severity,
+            file: Some(file.to_string()),
+            message,
+            ..Default::default()
+        }
+    }
+
+    /// `file:line:col: severity: message` line as printed by gcc
+    pub fn gcc(&self) -> String {
+        let mut msg = String::new();
+        if let Some(f) = &self.file {
+            msg.push_str(&format!("{f}:"));
+        }
+        if self.line > 0 {
+            msg.push_str(&format!("{}:{}:", self.line, self.col));
+        }
+        if !msg.is_empty() {
+            msg.push(' ');
+        }
+        msg.push_str(&format!("{}: {}", self.severity, self.message));
+        if let Some(rule) = self.rule {
+            msg.push_str(&format!(" [{rule}]"));
+        }
+        msg
+    }
+
+    /// Multi line form with the source line and a marker under the problem
+    pub fn human(&self) -> String {
+        let sev = match self.severity {
+            Severity::Warning => "Warning",
+            Severity::Error => "Error",
+        };
+        let sev = match self.rule {
+            Some(rule) => format!("{sev}[{rule}]"),
+            None => sev.to_string(),
+        };
+        if self.line == 0 {
+            return match &self.file {
+                Some(f) => format!("{sev}: {}\n  -> {f}", self.message),
+                None => format!("{sev}: {}", self.message),
+            };
+        }
+        let mut msg = format!("{sev} at Line {} Column {}\n", self.line, self.col);
+        if let Some(f) = &self.file {
+            msg.push_str(&format!("  -> {}:{}:{}\n", f, self.line, self.col));
+        }
+        match &self.linestr {
+            Some(l) => {
+                msg.push_str(&format!("  {l}\n"));
+                msg.push_str(&format!("  {: >2$} {}", "^", self.message, self.col));
+            }
+            None => msg.push_str(&format!("  {}", self.message)),
+        }
+        msg
+    }
 }
 
 pub type MatchRes<'a, T> = IResult<TokenList<'a>, T, MatchErr<'a>>;
@@ -157,3 +267,31 @@ impl<'a> nom::error::ParseError<TokenList<'a>> for MatchErr<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(Some("a.chapter"), 3, 5, "a.chapter:3:5: error: bad")]
+    #[case(Some("a.chapter"), 0, 0, "a.chapter: error: bad")]
+    #[case(None, 2, 1, "2:1: error: bad")]
+    fn gcc_test(
+        #[case] file: Option<&str>,
+        #[case] line: usize,
+        #[case] col: usize,
+        #[case] expected: &str,
+    ) {
+        let diag = Diagnostic {
+            severity: Severity::Error,
+            file: file.map(String::from),
+            line,
+            col,
+            message: "bad".into(),
+            linestr: None,
+            rule: None,
+        };
+        assert_eq!(diag.gcc(), expected);
+    }
+}
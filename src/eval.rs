@@ -0,0 +1,237 @@
+use crate::syntax::Chapter;
+use std::collections::HashMap;
+use std::hash::Hash;
+
+/// Longest character n-grams chrF counts
+const CHRF_ORDER: usize = 6;
+/// Weight of the recall against the precision in chrF
+const CHRF_BETA: f64 = 2.0;
+/// Longest word n-grams BLEU counts
+const BLEU_ORDER: usize = 4;
+
+/// Matched, candidate and reference n-grams of each length
+#[derive(Debug, Clone, Default, PartialEq)]
+struct NgramStats(Vec<(usize, usize, usize)>);
+
+impl NgramStats {
+    fn new<T: Hash + Eq>(candidate: &[T], reference: &[T], order: usize) -> Self {
+        Self(
+            (1..=order)
+                .map(|n| {
+                    let cand = ngrams(candidate, n);
+                    let refs = ngrams(reference, n);
+                    let matched = cand
+                        .iter()
+                        .map(|(g, c)| (*c).min(refs.get(g).copied().unwrap_or_default()))
+                        .sum();
+                    (
+                        matched,
+                        candidate.len().saturating_sub(n - 1),
+                        reference.len().saturating_sub(n - 1),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn add(&mut self, other: &Self) {
+        if self.0.is_empty() {
+            self.0 = other.0.clone();
+            return;
+        }
+        for (a, b) in self.0.iter_mut().zip(&other.0) {
+            a.0 += b.0;
+            a.1 += b.1;
+            a.2 += b.2;
+        }
+    }
+}
+
+fn ngrams<T: Hash + Eq>(items: &[T], n: usize) -> HashMap<&[T], usize> {
+    let mut counts = HashMap::new();
+    for g in items.windows(n) {
+        *counts.entry(g).or_default() += 1;
+    }
+    counts
+}
+
+/// Counts both scores are computed from, which add up over sentences
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvalStats {
+    chars: NgramStats,
+    words: NgramStats,
+    candidate_len: usize,
+    reference_len: usize,
+}
+
+impl EvalStats {
+    pub fn new(candidate: &str, reference: &str) -> Self {
+        let chars = |t: &str| -> Vec<char> { t.chars().filter(|c| !c.is_whitespace()).collect() };
+        let words = |t: &str| -> Vec<String> { t.split_whitespace().map(String::from).collect() };
+        let (cw, rw) = (words(candidate), words(reference));
+        Self {
+            chars: NgramStats::new(&chars(candidate), &chars(reference), CHRF_ORDER),
+            words: NgramStats::new(&cw, &rw, BLEU_ORDER),
+            candidate_len: cw.len(),
+            reference_len: rw.len(),
+        }
+    }
+
+    pub fn add(&mut self, other: &Self) {
+        self.chars.add(&other.chars);
+        self.words.add(&other.words);
+        self.candidate_len += other.candidate_len;
+        self.reference_len += other.reference_len;
+    }
+
+    /// Character n-gram F-score from 0 to 100, chrF
+    pub fn chrf(&self) -> f64 {
+        let (mut precision, mut recall, mut orders) = (0.0, 0.0, 0);
+        for &(matched, cand, refs) in &self.chars.0 {
+            if cand == 0 || refs == 0 {
+                continue;
+            }
+            precision += matched as f64 / cand as f64;
+            recall += matched as f64 / refs as f64;
+            orders += 1;
+        }
+        if orders == 0 {
+            return 0.0;
+        }
+        let (p, r) = (precision / orders as f64, recall / orders as f64);
+        let b2 = CHRF_BETA * CHRF_BETA;
+        if p + r == 0.0 {
+            0.0
+        } else {
+            100.0 * (1.0 + b2) * p * r / (b2 * p + r)
+        }
+    }
+
+    /// Word n-gram precision with a brevity penalty from 0 to 100, like
+    /// BLEU with add-one smoothing of the longer n-grams
+    pub fn bleu(&self) -> f64 {
+        if self.candidate_len == 0 {
+            return 0.0;
+        }
+        let mut log_sum = 0.0;
+        for (i, &(matched, cand, _)) in self.words.0.iter().enumerate() {
+            let (m, c) = if i == 0 {
+                (matched as f64, cand as f64)
+            } else {
+                (matched as f64 + 1.0, cand as f64 + 1.0)
+            };
+            if m == 0.0 {
+                return 0.0;
+            }
+            log_sum += (m / c).ln();
+        }
+        let ratio = self.candidate_len as f64 / self.reference_len.max(1) as f64;
+        let brevity = if ratio < 1.0 {
+            (1.0 - 1.0 / ratio).exp()
+        } else {
+            1.0
+        };
+        100.0 * brevity * (log_sum / BLEU_ORDER as f64).exp()
+    }
+}
+
+/// Scores of a sentence's translation against its reference
+#[derive(Debug, Clone, PartialEq)]
+pub struct SentenceEval {
+    pub label: String,
+    pub stats: EvalStats,
+}
+
+/// Scores of a chapter's translations to a language against references
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvalReport {
+    pub sentences: Vec<SentenceEval>,
+    /// Labels of the sentences without a translation to the language
+    pub missing: Vec<String>,
+    /// Counts of all the sentences together
+    pub total: EvalStats,
+}
+
+impl Chapter {
+    /// Compares the translations to the language with the references, one
+    /// for each sentence in order
+    pub fn eval(&self, lang: &str, references: &[String]) -> Result<EvalReport, String> {
+        if references.len() != self.sentences.len() {
+            return Err(format!(
+                "{} references for {} sentences, give one line for each sentence",
+                references.len(),
+                self.sentences.len()
+            ));
+        }
+        let mut report = EvalReport::default();
+        for (s, reference) in self.sentences.iter().zip(references) {
+            let translation = s
+                .translations
+                .iter()
+                .enumerate()
+                .find(|(i, (k, _))| self.translation_language(*i, k) == lang)
+                .map(|(_, (_, t))| t.content.as_str());
+            let Some(translation) = translation else {
+                report.missing.push(s.label.clone());
+                continue;
+            };
+            let stats = EvalStats::new(translation, reference);
+            report.total.add(&stats);
+            report.sentences.push(SentenceEval {
+                label: s.label.clone(),
+                stats,
+            });
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("the cat sat on the mat", "the cat sat on the mat", 100.0, 100.0)]
+    #[case("", "the cat", 0.0, 0.0)]
+    #[case("dog", "the cat", 0.0, 0.0)]
+    fn scores_test(
+        #[case] candidate: &str,
+        #[case] reference: &str,
+        #[case] chrf: f64,
+        #[case] bleu: f64,
+    ) {
+        let stats = EvalStats::new(candidate, reference);
+        assert!((stats.chrf() - chrf).abs() < 1e-9, "{}", stats.chrf());
+        assert!((stats.bleu() - bleu).abs() < 1e-9, "{}", stats.bleu());
+    }
+
+    #[test]
+    fn closer_scores_higher_test() {
+        let reference = "the cat sat on the mat";
+        let close = EvalStats::new("the cat sat on a mat", reference);
+        let far = EvalStats::new("a dog lay on the rug", reference);
+        assert!(close.chrf() > far.chrf());
+        assert!(close.bleu() > far.bleu());
+        assert!(close.chrf() < 100.0 && close.bleu() < 100.0);
+    }
+
+    #[test]
+    fn eval_test() {
+        let chap = Chapter::from_str(
+            "tranlations = english\n\n@ 1\na\n---\nthe cat sat\n\n@ 2\nb\n\n@ 3\nc\n--- english\nhello\n",
+        )
+        .unwrap();
+        let refs: Vec<String> = ["the cat sat", "x", "hello there"].map(String::from).into();
+        let report = chap.eval("english", &refs).unwrap();
+        assert_eq!(report.missing, vec!["2"]);
+        let labels: Vec<&str> = report.sentences.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, vec!["1", "3"]);
+        assert_eq!(report.sentences[0].stats.chrf(), 100.0);
+        let mut total = report.sentences[0].stats.clone();
+        total.add(&report.sentences[1].stats);
+        assert_eq!(report.total, total);
+        assert!(chap.eval("english", &refs[..2]).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+use crate::attrs::AttrEdit;
+use crate::syntax::Chapter;
+use regex::Regex;
+use std::collections::HashSet;
+use std::str::FromStr;
+
+/// Operation of a filter script
+#[derive(Debug, Clone)]
+pub enum FilterOp {
+    /// Drops the sentences with the tag, `drop tag draft`
+    DropTag(String),
+    /// Removes the translations to the language and its chapter attrs,
+    /// `strip language german`
+    StripLanguage(String),
+    /// Renames an attr of the chapter, the sentences and the translations,
+    /// `rename attr status state`
+    RenameAttr(String, String),
+    /// Rewrites the labels the regex matches with the replacement, which
+    /// can use its groups like `$1`, `relabel ^(\d+)$ ch1-$1`
+    Relabel(Regex, String),
+}
+
+impl FromStr for FilterOp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        match words[..] {
+            ["drop", "tag", tag] => Ok(Self::DropTag(tag.to_string())),
+            ["strip", "language", lang] => Ok(Self::StripLanguage(lang.to_string())),
+            ["rename", "attr", from, to] => {
+                let edit = AttrEdit::Rename(from.to_string(), to.to_string());
+                edit.check()?;
+                Ok(Self::RenameAttr(from.to_string(), to.to_string()))
+            }
+            ["relabel", pattern, replacement] => Regex::new(pattern)
+                .map(|re| Self::Relabel(re, replacement.to_string()))
+                .map_err(|e| format!("Invalid label pattern {pattern:?}: {e}")),
+            _ => Err(format!(
+                "Unknown operation {s:?}, use drop tag, strip language, rename attr or relabel"
+            )),
+        }
+    }
+}
+
+/// Operations applied to a chapter in order, one on each line of a filter
+/// script, with `#` lines as comments
+#[derive(Debug, Clone, Default)]
+pub struct FilterScript(pub Vec<FilterOp>);
+
+impl FromStr for FilterScript {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+            .map(|(i, l)| l.parse().map_err(|e| format!("Line {}: {e}", i + 1)))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl Chapter {
+    /// Applies the operations of the script in order
+    pub fn filter(&mut self, script: &FilterScript) -> Result<(), String> {
+        for op in &script.0 {
+            match op {
+                FilterOp::DropTag(tag) => {
+                    self.retain_sentences(|_, s| !s.tags().contains(&tag.as_str()))
+                }
+                FilterOp::StripLanguage(lang) => self.strip_language(lang),
+                FilterOp::RenameAttr(from, to) => {
+                    let edit = AttrEdit::Rename(from.clone(), to.clone());
+                    edit.apply(&mut self.attrs);
+                    for s in &mut self.sentences {
+                        edit.apply(&mut s.orgattrs);
+                        for t in s.translations.values_mut() {
+                            edit.apply(&mut t.attrs);
+                        }
+                    }
+                }
+                FilterOp::Relabel(re, replacement) => {
+                    let mut seen = HashSet::new();
+                    for s in &mut self.sentences {
+                        s.label = re
+                            .replace(&s.label, replacement.as_str())
+                            .trim()
+                            .to_string();
+                        if s.label.is_empty() || !seen.insert(s.label.clone()) {
+                            return Err(format!(
+                                "Relabelling gives the label {:?} to more than one sentence",
+                                s.label
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes the translations to the language, the language from the
+    /// `translations` attr and its `language.key` attrs
+    fn strip_language(&mut self, lang: &str) {
+        let lang = self.canonical_language(lang);
+        if self.tl_languages.iter().any(|l| l.trim() == lang) {
+            // the positional translations after it would shift languages
+            self.label_translations();
+            self.tl_languages.retain(|l| l.trim() != lang);
+            let names: Vec<&str> = self.tl_languages.iter().map(|l| l.trim()).collect();
+            let key = self.translations_key();
+            if names.is_empty() {
+                self.attrs.shift_remove(key);
+            } else {
+                self.attrs.insert(key.to_string(), names.join(", "));
+            }
+        }
+        let mut sentences = std::mem::take(&mut self.sentences);
+        for s in &mut sentences {
+            let keys: Vec<String> = s
+                .translations
+                .keys()
+                .enumerate()
+                .filter(|(i, k)| self.translation_language(*i, k) == lang)
+                .map(|(_, k)| k.clone())
+                .collect();
+            for k in keys {
+                s.translations.shift_remove(&k);
+            }
+        }
+        self.sentences = sentences;
+        let prefix = format!("{lang}.");
+        self.attrs.retain(|k, _| !k.starts_with(&prefix));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const CHAP: &str = "tranlations = english, german\ngerman.translator = Bob\nstatus = draft\n\n@ 1\na\ntags = draft\n---\nA\n---\nB\n\n@ 2\nb\nstatus = done\n---\nC\n---\nD\nstatus = draft\n";
+
+    fn filtered(script: &str) -> Result<Chapter, String> {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.filter(&script.parse()?)?;
+        Ok(Chapter::from_str(&chap.source()).unwrap())
+    }
+
+    #[test]
+    fn drop_tag_test() {
+        let chap = filtered("# drafts aren't shared\ndrop tag draft\n").unwrap();
+        let labels: Vec<&str> = chap.sentences.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, vec!["2"]);
+    }
+
+    #[test]
+    fn strip_language_test() {
+        let chap = filtered("strip language english").unwrap();
+        assert_eq!(chap.attrs["tranlations"], "german");
+        assert_eq!(chap.attrs["german.translator"], "Bob");
+        let tls: Vec<(String, String)> = chap.sentences[1]
+            .translations
+            .iter()
+            .enumerate()
+            .map(|(i, (k, t))| (chap.translation_language(i, k), t.content.clone()))
+            .collect();
+        assert_eq!(tls, vec![("german".to_string(), "D".to_string())]);
+        let chap = filtered("strip language german").unwrap();
+        assert!(!chap.attrs.contains_key("german.translator"));
+        assert_eq!(chap.sentences[0].translations.len(), 1);
+    }
+
+    #[test]
+    fn rename_attr_test() {
+        let chap = filtered("rename attr status state").unwrap();
+        assert_eq!(chap.attrs["state"], "draft");
+        assert_eq!(chap.sentences[1].orgattrs["state"], "done");
+        assert_eq!(chap.sentences[1].translations["1"].attrs["state"], "draft");
+        assert!(!chap.attrs.contains_key("status"));
+    }
+
+    #[rstest]
+    #[case(r"relabel ^(\d+)$ ch1-$1", Ok(vec!["ch1-1", "ch1-2"]))]
+    #[case(r"relabel .* x", Err(()))]
+    fn relabel_test(#[case] script: &str, #[case] expected: Result<Vec<&str>, ()>) {
+        let labels = filtered(script)
+            .map(|c| {
+                c.sentences
+                    .iter()
+                    .map(|s| s.label.clone())
+                    .collect::<Vec<_>>()
+            })
+            .map_err(|_| ());
+        assert_eq!(
+            labels,
+            expected.map(|l| l.into_iter().map(String::from).collect())
+        );
+    }
+
+    #[rstest]
+    #[case("drop sentence 1")]
+    #[case("rename attr status")]
+    #[case("relabel ( x")]
+    fn bad_script_test(#[case] script: &str) {
+        assert!(FilterScript::from_str(script).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::output::GENERATED_MARKER;
+use crate::stats::FrequencyList;
+use crate::syntax::Chapter;
+use crate::text::escape_attr;
+use crate::words::words;
+use indexmap::{IndexMap, IndexSet};
+use std::str::FromStr;
+
+/// File format of a vocabulary graph
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GraphFormat {
+    /// Graphviz
+    #[default]
+    Dot,
+    GraphMl,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "dot" | "gv" => Ok(Self::Dot),
+            "graphml" => Ok(Self::GraphMl),
+            _ => Err(format!("Unknown format {s:?}, use dot or graphml")),
+        }
+    }
+}
+
+/// What the edges of a vocabulary graph join
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GraphKind {
+    /// Words to the chapters using them
+    #[default]
+    Chapters,
+    /// Words used in the same sentence
+    Cooccurrence,
+}
+
+impl FromStr for GraphKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "chapters" => Ok(Self::Chapters),
+            "cooccurrence" | "co-occurrence" => Ok(Self::Cooccurrence),
+            _ => Err(format!("Unknown graph {s:?}, use chapters or cooccurrence")),
+        }
+    }
+}
+
+/// Which words of the originals make it into a graph
+#[derive(Debug, Clone, Default)]
+pub struct WordFilter {
+    /// Words used fewer times across the chapters are left out
+    pub min_count: usize,
+    /// Words within this many of the most frequent ones are left out, like
+    /// articles and pronouns that would join everything
+    pub skip_top: usize,
+    pub frequency: Option<FrequencyList>,
+}
+
+impl WordFilter {
+    fn skips(&self, word: &str) -> bool {
+        self.frequency
+            .as_ref()
+            .and_then(|f| f.rank(word))
+            .is_some_and(|r| r <= self.skip_top)
+    }
+}
+
+/// A node of a vocabulary graph
+#[derive(Debug, Clone, PartialEq)]
+pub struct Node {
+    pub label: String,
+    /// `word` or `chapter`
+    pub kind: &'static str,
+    /// Uses of the word, or words of the chapter
+    pub count: usize,
+}
+
+/// Words of a book and how they are used together, by the index of their
+/// nodes, with the number of times as the weight of the edges
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct VocabGraph {
+    pub nodes: Vec<Node>,
+    pub edges: IndexMap<(usize, usize), usize>,
+    pub directed: bool,
+}
+
+/// Words of each sentence of the chapters lowercased, and their counts
+fn sentence_words(
+    chapters: &[&Chapter],
+    filter: &WordFilter,
+) -> (Vec<Vec<Vec<String>>>, IndexMap<String, usize>) {
+    let mut counts: IndexMap<String, usize> = IndexMap::new();
+    let sentences: Vec<Vec<Vec<String>>> = chapters
+        .iter()
+        .map(|c| {
+            c.sentences
+                .iter()
+                .map(|s| {
+                    words(&s.text())
+                        .map(str::to_lowercase)
+                        .filter(|w| !filter.skips(w))
+                        .inspect(|w| *counts.entry(w.clone()).or_default() += 1)
+                        .collect()
+                })
+                .collect()
+        })
+        .collect();
+    counts.retain(|_, n| *n >= filter.min_count);
+    (sentences, counts)
+}
+
+impl VocabGraph {
+    pub fn new(kind: GraphKind, chapters: &[&Chapter], filter: &WordFilter) -> Self {
+        let (sentences, counts) = sentence_words(chapters, filter);
+        let mut graph = Self {
+            nodes: counts
+                .iter()
+                .map(|(w, n)| Node {
+                    label: w.clone(),
+                    kind: "word",
+                    count: *n,
+                })
+                .collect(),
+            edges: IndexMap::new(),
+            directed: kind == GraphKind::Chapters,
+        };
+        for (chap, sentences) in chapters.iter().zip(&sentences) {
+            let chap_node = graph.nodes.len();
+            if kind == GraphKind::Chapters {
+                graph.nodes.push(Node {
+                    label: chap.title.clone(),
+                    kind: "chapter",
+                    count: sentences.iter().map(Vec::len).sum(),
+                });
+            }
+            for words in sentences {
+                let ids = words.iter().filter_map(|w| counts.get_index_of(w));
+                match kind {
+                    GraphKind::Chapters => {
+                        for w in ids {
+                            *graph.edges.entry((w, chap_node)).or_default() += 1;
+                        }
+                    }
+                    GraphKind::Cooccurrence => {
+                        let ids: IndexSet<usize> = ids.collect();
+                        for (i, a) in ids.iter().enumerate() {
+                            for b in ids.iter().skip(i + 1) {
+                                let edge = (*a.min(b), *a.max(b));
+                                *graph.edges.entry(edge).or_default() += 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn write(&self, format: GraphFormat) -> String {
+        match format {
+            GraphFormat::Dot => self.dot(),
+            GraphFormat::GraphMl => self.graphml(),
+        }
+    }
+
+    /// Graphviz source, the chapters drawn as boxes and the weights as the
+    /// width of the edges
+    pub fn dot(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let (graph, arrow) = match self.directed {
+            true => ("digraph", "->"),
+            false => ("graph", "--"),
+        };
+        let mut dot = format!("// {GENERATED_MARKER}\n{graph} vocabulary {{\n");
+        for (i, n) in self.nodes.iter().enumerate() {
+            let shape = match n.kind {
+                "chapter" => ", shape=box",
+                _ => "",
+            };
+            dot.push_str(&format!(
+                "  n{i} [label={}, count={}{shape}];\n",
+                quote(&n.label),
+                n.count
+            ));
+        }
+        for ((a, b), w) in &self.edges {
+            dot.push_str(&format!(
+                "  n{a} {arrow} n{b} [weight={w}, penwidth={w}];\n"
+            ));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// GraphML with the label, kind and count of the nodes and the weight
+    /// of the edges as data
+    pub fn graphml(&self) -> String {
+        let mut xml = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+{GENERATED_MARKER}
+<graphml xmlns="http://graphml.graphdrawing.org/xmlns">
+  <key id="label" for="node" attr.name="label" attr.type="string"/>
+  <key id="kind" for="node" attr.name="kind" attr.type="string"/>
+  <key id="count" for="node" attr.name="count" attr.type="int"/>
+  <key id="weight" for="edge" attr.name="weight" attr.type="int"/>
+"#,
+        );
+        let default = match self.directed {
+            true => "directed",
+            false => "undirected",
+        };
+        xml.push_str(&format!(
+            "  <graph id=\"vocabulary\" edgedefault=\"{default}\">\n"
+        ));
+        for (i, n) in self.nodes.iter().enumerate() {
+            xml.push_str(&format!(
+                "    <node id=\"n{i}\"><data key=\"label\">{}</data><data key=\"kind\">{}</data><data key=\"count\">{}</data></node>\n",
+                escape_attr(&n.label),
+                n.kind,
+                n.count
+            ));
+        }
+        for ((a, b), w) in &self.edges {
+            xml.push_str(&format!(
+                "    <edge source=\"n{a}\" target=\"n{b}\"><data key=\"weight\">{w}</data></edge>\n"
+            ));
+        }
+        xml.push_str("  </graph>\n</graphml>\n");
+        xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn chapters() -> Vec<Chapter> {
+        vec![
+            Chapter::from_str("title = One\n\n@ 1\nThe cat sat\n\n@ 2\nthe cat ran\n").unwrap(),
+            Chapter::from_str("title = \"Two\"\n\n@ 1\na dog ran\n").unwrap(),
+        ]
+    }
+
+    #[rstest]
+    #[case(GraphKind::Chapters, 1, 0, 8, 7)]
+    #[case(GraphKind::Chapters, 2, 0, 5, 4)]
+    #[case(GraphKind::Cooccurrence, 2, 0, 3, 3)]
+    #[case(GraphKind::Cooccurrence, 1, 1, 5, 5)]
+    fn graph_test(
+        #[case] kind: GraphKind,
+        #[case] min_count: usize,
+        #[case] skip_top: usize,
+        #[case] nodes: usize,
+        #[case] edges: usize,
+    ) {
+        let chapters = chapters();
+        let chapters: Vec<&Chapter> = chapters.iter().collect();
+        let filter = WordFilter {
+            min_count,
+            skip_top,
+            frequency: Some(FrequencyList::parse("the\na\n")),
+        };
+        let graph = VocabGraph::new(kind, &chapters, &filter);
+        assert_eq!((graph.nodes.len(), graph.edges.len()), (nodes, edges));
+    }
+
+    #[test]
+    fn write_test() {
+        let chapters = chapters();
+        let chapters: Vec<&Chapter> = chapters.iter().collect();
+        let filter = WordFilter {
+            min_count: 2,
+            ..Default::default()
+        };
+        let graph = VocabGraph::new(GraphKind::Chapters, &chapters, &filter);
+        assert_eq!(
+            graph.dot(),
+            "// <!-- Generated by transdoc -->\ndigraph vocabulary {\n  n0 [label=\"the\", count=2];\n  n1 [label=\"cat\", count=2];\n  n2 [label=\"ran\", count=2];\n  n3 [label=\"One\", count=6, shape=box];\n  n4 [label=\"\\\"Two\\\"\", count=3, shape=box];\n  n0 -> n3 [weight=2, penwidth=2];\n  n1 -> n3 [weight=2, penwidth=2];\n  n2 -> n3 [weight=1, penwidth=1];\n  n2 -> n4 [weight=1, penwidth=1];\n}\n"
+        );
+        let xml = graph.graphml();
+        assert!(xml.contains("<data key=\"label\">&quot;Two&quot;</data>"));
+        assert!(
+            xml.contains("<edge source=\"n2\" target=\"n4\"><data key=\"weight\">1</data></edge>")
+        );
+        assert!(xml.contains("edgedefault=\"directed\""));
+    }
+}
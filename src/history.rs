@@ -0,0 +1,224 @@
+use crate::diff::{self, Change};
+use crate::syntax::Chapter;
+use indexmap::IndexMap;
+use std::path::{Path, PathBuf};
+
+/// Extension of the file next to a chapter with the earlier translations
+pub const HISTORY_EXTENSION: &str = "history";
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const STRIKE: &str = "\x1b[9m";
+const RESET: &str = "\x1b[0m";
+
+/// History file of the chapter file, `chapter.history` next to it
+pub fn sidecar(chapter: &Path) -> PathBuf {
+    chapter.with_extension(HISTORY_EXTENSION)
+}
+
+/// Revisions of the translations, oldest first, by sentence label and
+/// language
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History(pub IndexMap<(String, String), Vec<String>>);
+
+impl History {
+    /// History from a history file, where `@ label language` lines start
+    /// the revisions of a translation, `---` lines separate them and `#`
+    /// lines are comments; revision lines that would look like one of them
+    /// are escaped with a `\`
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let mut history = Self::default();
+        let mut current: Option<(String, String)> = None;
+        for (i, line) in src.lines().enumerate() {
+            if line.starts_with('#') {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('@') {
+                let Some((label, lang)) = header.trim().rsplit_once(char::is_whitespace) else {
+                    return Err(format!("Line {} should be `@ label language`", i + 1));
+                };
+                let key = (label.trim().to_string(), lang.to_string());
+                history
+                    .0
+                    .entry(key.clone())
+                    .or_default()
+                    .push(String::new());
+                current = Some(key);
+                continue;
+            }
+            match current.as_ref().and_then(|k| history.0.get_mut(k)) {
+                Some(revisions) if line.trim() == "---" => revisions.push(String::new()),
+                Some(revisions) => {
+                    let text = revisions.last_mut().expect("pushed with the header");
+                    text.push_str(line.strip_prefix('\\').unwrap_or(line));
+                    text.push('\n');
+                }
+                None if line.trim().is_empty() => (),
+                None => {
+                    return Err(format!(
+                        "Line {} isn't after a `@ label language` line",
+                        i + 1
+                    ));
+                }
+            }
+        }
+        for revisions in history.0.values_mut() {
+            for r in revisions.iter_mut() {
+                *r = r.trim().to_string();
+            }
+            revisions.retain(|r| !r.is_empty());
+        }
+        Ok(history)
+    }
+
+    /// Text of a history file with the revisions
+    pub fn source(&self) -> String {
+        let mut src = String::new();
+        for ((label, lang), revisions) in &self.0 {
+            if !src.is_empty() {
+                src.push('\n');
+            }
+            src.push_str(&format!("@ {label} {lang}\n"));
+            let revisions: Vec<String> = revisions.iter().map(|r| escape(r)).collect();
+            src.push_str(&revisions.join("\n---\n"));
+            src.push('\n');
+        }
+        src
+    }
+}
+
+/// Revision with a `\` before the lines that would be read as a header,
+/// separator or comment, or that start with a `\` themselves
+fn escape(revision: &str) -> String {
+    revision
+        .lines()
+        .map(|l| {
+            if l.starts_with(['#', '@', '\\']) || l.trim() == "---" {
+                format!("\\{l}")
+            } else {
+                l.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+impl Chapter {
+    /// Adds the translations that differ from their last revision to the
+    /// history, giving how many were added
+    pub fn record_revisions(&self, history: &mut History) -> usize {
+        let mut added = 0;
+        for s in &self.sentences {
+            for (i, (k, t)) in s.translations.iter().enumerate() {
+                let text = t.content.trim();
+                if text.is_empty() {
+                    continue;
+                }
+                let key = (s.label.clone(), self.translation_language(i, k));
+                let revisions = history.0.entry(key).or_default();
+                if revisions.last().map(String::as_str) != Some(text) {
+                    revisions.push(text.to_string());
+                    added += 1;
+                }
+            }
+        }
+        added
+    }
+}
+
+/// Changes turning the words of one text into the other's
+pub fn word_diff<'a>(old: &'a str, new: &'a str) -> Vec<Change<&'a str>> {
+    let a: Vec<&str> = old.split_whitespace().collect();
+    let b: Vec<&str> = new.split_whitespace().collect();
+    diff::diff(&a, &b)
+        .into_iter()
+        .map(|c| match c {
+            Change::Same(w) => Change::Same(*w),
+            Change::Removed(w) => Change::Removed(*w),
+            Change::Added(w) => Change::Added(*w),
+        })
+        .collect()
+}
+
+/// Word diff as text, removed words in struck through red and added ones
+/// in green, or as `[-removed-]` and `{+added+}` without colors
+pub fn diff_text(changes: &[Change<&str>], color: bool) -> String {
+    changes
+        .iter()
+        .map(|c| match (c, color) {
+            (Change::Same(w), _) => w.to_string(),
+            (Change::Removed(w), true) => format!("{RED}{STRIKE}{w}{RESET}"),
+            (Change::Added(w), true) => format!("{GREEN}{w}{RESET}"),
+            (Change::Removed(w), false) => format!("[-{w}-]"),
+            (Change::Added(w), false) => format!("{{+{w}+}}"),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const HISTORY: &str =
+        "# reviewed in class\n@ 1 english\nI has a dog\n---\nI have a dog\n\n@ 2 a b german\nx\n";
+
+    #[test]
+    fn parse_test() {
+        let history = History::parse(HISTORY).unwrap();
+        let entries: Vec<(&str, &str, usize)> = history
+            .0
+            .iter()
+            .map(|((l, g), r)| (l.as_str(), g.as_str(), r.len()))
+            .collect();
+        assert_eq!(entries, vec![("1", "english", 2), ("2 a b", "german", 1)]);
+        assert_eq!(History::parse(&history.source()).unwrap(), history);
+    }
+
+    #[rstest]
+    #[case("#1 hit song")]
+    #[case("@ home\nthere")]
+    #[case("first\n---\nsecond")]
+    #[case("\\n is a newline")]
+    fn escape_test(#[case] revision: &str) {
+        let mut history = History::default();
+        history.0.insert(
+            ("1".to_string(), "english".to_string()),
+            vec![revision.to_string(), "last".to_string()],
+        );
+        assert_eq!(History::parse(&history.source()).unwrap(), history);
+    }
+
+    #[rstest]
+    #[case("text\n@ 1 english\n")]
+    #[case("@ 1\nx\n")]
+    fn bad_history_test(#[case] src: &str) {
+        assert!(History::parse(src).is_err());
+    }
+
+    #[test]
+    fn record_test() {
+        let mut history = History::parse(HISTORY).unwrap();
+        let chap = Chapter::from_str(
+            "tranlations = english\n\n@ 1\na\n---\nI have a dog\n\n@ 3\nc\n---\nC\n",
+        )
+        .unwrap();
+        assert_eq!(chap.record_revisions(&mut history), 1);
+        assert_eq!(chap.record_revisions(&mut history), 0);
+        assert_eq!(
+            history.0[&("3".to_string(), "english".to_string())],
+            vec!["C"]
+        );
+    }
+
+    #[rstest]
+    #[case("I has a dog", "I have a dog", "I [-has-] {+have+} a dog")]
+    #[case("a b c", "a c d", "a [-b-] c {+d+}")]
+    #[case("", "new text", "{+new+} {+text+}")]
+    #[case("same", "same", "same")]
+    fn diff_test(#[case] old: &str, #[case] new: &str, #[case] expected: &str) {
+        assert_eq!(diff_text(&word_diff(old, new), false), expected);
+    }
+}
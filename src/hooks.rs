@@ -0,0 +1,120 @@
+use crate::plugin::run_piped;
+use crate::syntax::Chapter;
+use std::process::Command;
+
+/// Transformation of a chapter run before or after processing
+pub type Hook = Box<dyn Fn(&mut Chapter) -> Result<(), String>>;
+
+/// Hooks around `Chapter::process`, run in the order they were added
+#[derive(Default)]
+pub struct Hooks {
+    pre: Vec<Hook>,
+    post: Vec<Hook>,
+}
+
+impl Hooks {
+    /// Runs before the dictionary is applied
+    pub fn pre(&mut self, hook: Hook) {
+        self.pre.push(hook);
+    }
+
+    /// Runs after the dictionary is applied
+    pub fn post(&mut self, hook: Hook) {
+        self.post.push(hook);
+    }
+}
+
+/// External command given the chapter as JSON on stdin, printing the
+/// transformed chapter as JSON on stdout, like pandoc filters
+pub fn filter_command(command: &str) -> Option<Hook> {
+    let mut parts = command.split_whitespace().map(String::from);
+    let program = parts.next()?;
+    let args: Vec<String> = parts.collect();
+    Some(Box::new(move |chap: &mut Chapter| {
+        let err = |e: &dyn std::fmt::Display| format!("Filter {program:?}: {e}");
+        let json = chap.to_json(false);
+        let out =
+            run_piped(Command::new(&program).args(&args), json.as_bytes()).map_err(|e| err(&e))?;
+        if !out.status.success() {
+            return Err(err(&format!("exited with {}", out.status)));
+        }
+        let mut out =
+            Chapter::from_json(&String::from_utf8_lossy(&out.stdout)).map_err(|e| err(&e))?;
+        // regexes and the directory don't go through JSON
+        out.patterns = std::mem::take(&mut chap.patterns);
+        out.dir = std::mem::take(&mut chap.dir);
+        *chap = out;
+        Ok(())
+    }))
+}
+
+impl Chapter {
+    /// `process` with the hooks run around it
+    pub fn process_with(&mut self, hooks: &Hooks) -> Result<(), String> {
+        for h in &hooks.pre {
+            h(self)?;
+        }
+        self.process();
+        for h in &hooks.post {
+            h(self)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::OrgFragment;
+    use std::str::FromStr;
+
+    #[test]
+    fn hooks_order_test() {
+        let mut chap = Chapter::from_str("@ 1\n<< a >>\n").unwrap();
+        chap.dictionary
+            .insert("a".into(), crate::intern::meanings(&["b"]));
+        let mut hooks = Hooks::default();
+        hooks.pre(Box::new(|c| {
+            // still a lookup before processing
+            assert!(matches!(
+                c.sentences[0].original[0],
+                OrgFragment::DictLookup(_)
+            ));
+            c.title = "Pre".into();
+            Ok(())
+        }));
+        hooks.post(Box::new(|c| {
+            assert!(matches!(
+                c.sentences[0].original[0],
+                OrgFragment::Meaning(..)
+            ));
+            c.title.push_str(" Post");
+            Ok(())
+        }));
+        chap.process_with(&hooks).unwrap();
+        assert_eq!(chap.title, "Pre Post");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn filter_command_test() {
+        let mut chap = Chapter::from_str("title = T\n\n@ 1\nabc\n").unwrap();
+        let mut hooks = Hooks::default();
+        hooks.pre(filter_command("sed s/abc/xyz/").unwrap());
+        chap.process_with(&hooks).unwrap();
+        assert_eq!(chap.sentences[0].text(), "xyz");
+
+        // a chapter bigger than a pipe holds
+        let src: String = (1..3000)
+            .map(|i| format!("@ {i}\nabc {i}\n---\nxyz\n"))
+            .collect();
+        let mut big = Chapter::from_str(&src).unwrap();
+        let mut hooks = Hooks::default();
+        hooks.pre(filter_command("cat").unwrap());
+        big.process_with(&hooks).unwrap();
+        assert_eq!(big.sentences.len(), 2999);
+
+        hooks.post(filter_command("false").unwrap());
+        assert!(chap.process_with(&hooks).is_err());
+    }
+}
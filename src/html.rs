@@ -0,0 +1,681 @@
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Whitespace layout of the rendered HTML
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum HtmlStyle {
+    /// As the renderer writes it
+    #[default]
+    Raw,
+    /// Block elements on their own lines, indented by nesting
+    Pretty,
+    /// Whitespace between elements and in the CSS removed
+    Minify,
+}
+
+impl HtmlStyle {
+    pub fn apply(&self, html: &str) -> String {
+        match self {
+            Self::Raw => html.to_string(),
+            Self::Pretty => pretty(html),
+            Self::Minify => minify(html),
+        }
+    }
+}
+
+pub use crate::text::escape_attr;
+
+/// How the meanings of glossed words are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum GlossMode {
+    /// In a tooltip when hovering the word
+    #[default]
+    Tooltip,
+    /// In small text after the word
+    Small,
+    /// In brackets after the word
+    Bracket,
+    /// Hidden until the word is clicked
+    Hidden,
+}
+
+impl FromStr for GlossMode {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "tooltip" => Ok(Self::Tooltip),
+            "small" => Ok(Self::Small),
+            "bracket" => Ok(Self::Bracket),
+            "hidden" => Ok(Self::Hidden),
+            _ => Err(format!(
+                "unknown gloss mode {s:?}, expected tooltip, small, bracket or hidden"
+            )),
+        }
+    }
+}
+
+impl GlossMode {
+    /// The glossed word as HTML
+    pub fn html(&self, word: &str, meanings: &str) -> String {
+        match self {
+            // the tooltip isn't read by screen readers, the hidden text is
+            Self::Tooltip => format!(
+                "<span title=\"{meanings}\">{word}<span class=\"sr-only\"> ({meanings})</span></span>"
+            ),
+            Self::Small => format!("<span>{word}<small class=\"gloss\">{meanings}</small></span>"),
+            Self::Bracket => format!("<span>{word} [{meanings}]</span>"),
+            Self::Hidden => format!(
+                "<span class=\"gloss-toggle\" role=\"button\" tabindex=\"0\" aria-expanded=\"false\" onclick=\"this.setAttribute('aria-expanded', this.classList.toggle('shown'))\" onkeydown=\"if (event.key === 'Enter' || event.key === ' ') {{ event.preventDefault(); this.click(); }}\">{word}<small class=\"gloss\">{meanings}</small></span>"
+            ),
+        }
+    }
+}
+
+/// What the gloss of a word shows and in what order, a format like
+/// `{meanings} ({source})` with the fields in braces
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlossFormat(Vec<GlossPart>);
+
+#[derive(Debug, Clone, PartialEq)]
+enum GlossPart {
+    Text(String),
+    Field(GlossField),
+}
+
+/// What a gloss can show
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlossField {
+    /// The word as in the original
+    Word,
+    /// Every meaning, separated by `; `
+    Meanings,
+    /// The first meaning
+    Meaning,
+    /// Where the dictionary entry came from, empty for inline glosses
+    Source,
+}
+
+impl GlossField {
+    const NAMES: [(&str, Self); 4] = [
+        ("word", Self::Word),
+        ("meanings", Self::Meanings),
+        ("meaning", Self::Meaning),
+        ("source", Self::Source),
+    ];
+}
+
+impl Default for GlossFormat {
+    fn default() -> Self {
+        Self(vec![GlossPart::Field(GlossField::Meanings)])
+    }
+}
+
+impl FromStr for GlossFormat {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = Vec::new();
+        let mut rest = s.trim();
+        while let Some(start) = rest.find('{') {
+            if start > 0 {
+                parts.push(GlossPart::Text(rest[..start].to_string()));
+            }
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| format!("unclosed {{ in gloss format {s:?}"))?;
+            let name = rest[start + 1..start + end].trim();
+            let field = GlossField::NAMES
+                .iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, f)| *f)
+                .ok_or_else(|| {
+                    format!(
+                        "unknown gloss field {name:?}, expected word, meanings, meaning or source"
+                    )
+                })?;
+            parts.push(GlossPart::Field(field));
+            rest = &rest[start + end + 1..];
+        }
+        if !rest.is_empty() {
+            parts.push(GlossPart::Text(rest.to_string()));
+        }
+        if !parts.iter().any(|p| matches!(p, GlossPart::Field(_))) {
+            return Err(format!(
+                "gloss format {s:?} has no fields like {{meanings}}"
+            ));
+        }
+        Ok(Self(parts))
+    }
+}
+
+impl GlossFormat {
+    /// Gloss of the word, escaped as a whole to go in an attribute
+    pub fn render(&self, word: &str, meanings: &[&str], source: Option<&str>) -> String {
+        let gloss: String = self
+            .0
+            .iter()
+            .map(|p| match p {
+                GlossPart::Text(t) => t.clone(),
+                GlossPart::Field(GlossField::Word) => word.to_string(),
+                GlossPart::Field(GlossField::Meanings) => meanings.join("; "),
+                GlossPart::Field(GlossField::Meaning) => {
+                    meanings.first().copied().unwrap_or_default().to_string()
+                }
+                GlossPart::Field(GlossField::Source) => source.unwrap_or_default().to_string(),
+            })
+            .collect();
+        escape_attr(&gloss)
+    }
+}
+
+/// How glosses of words glossed before on the page are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepeatGloss {
+    /// Like the first one
+    #[default]
+    Full,
+    /// Underlined, without the meanings
+    Marker,
+    /// As plain text
+    None,
+}
+
+impl FromStr for RepeatGloss {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "full" => Ok(Self::Full),
+            "marker" => Ok(Self::Marker),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "unknown repeat gloss mode {s:?}, expected full, marker or none"
+            )),
+        }
+    }
+}
+
+/// What a page has besides the originals
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edition {
+    /// The translations too
+    #[default]
+    Full,
+    /// Only the originals with their glosses and the dictionary, a glossed
+    /// reader for studying without the translations
+    Reader,
+}
+
+impl FromStr for Edition {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "full" => Ok(Self::Full),
+            "reader" => Ok(Self::Reader),
+            _ => Err(format!("unknown edition {s:?}, expected full or reader")),
+        }
+    }
+}
+
+/// Elements that start a new line when pretty printing; the rest are
+/// kept with the text around them as the whitespace there matters
+const BLOCK_TAGS: [&str; 25] = [
+    "html",
+    "head",
+    "title",
+    "body",
+    "style",
+    "script",
+    "meta",
+    "link",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "p",
+    "div",
+    "blockquote",
+    "table",
+    "tr",
+    "th",
+    "td",
+    "ul",
+    "ol",
+    "li",
+    "br",
+];
+
+const VOID_TAGS: [&str; 4] = ["meta", "link", "br", "hr"];
+
+#[derive(Debug, PartialEq)]
+enum Piece<'a> {
+    Tag(&'a str),
+    Text(&'a str),
+    /// Contents of `<style>` and `<script>`
+    Raw(&'a str),
+}
+
+/// Lowercase name of the tag and whether it closes an element
+fn tag_name(tag: &str) -> (String, bool) {
+    let inner = tag.trim_start_matches('<');
+    let (closing, inner) = match inner.strip_prefix('/') {
+        Some(i) => (true, i),
+        None => (false, inner),
+    };
+    let name = inner
+        .split(|c: char| c.is_whitespace() || c == '>' || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+    (name, closing)
+}
+
+fn is_block(piece: Option<&Piece>) -> bool {
+    match piece {
+        Some(Piece::Tag(t)) => BLOCK_TAGS.contains(&tag_name(t).0.as_str()),
+        _ => false,
+    }
+}
+
+/// Byte offset of the ASCII needle in the text, ignoring ASCII case, so
+/// it is an offset of the text itself
+fn find_ignore_case(text: &str, needle: &str) -> Option<usize> {
+    text.as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+fn pieces(html: &str) -> Vec<Piece<'_>> {
+    let mut out = Vec::new();
+    let mut rest = html;
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            out.push(Piece::Text(rest));
+            break;
+        };
+        if start > 0 {
+            out.push(Piece::Text(&rest[..start]));
+        }
+        let end = rest[start..]
+            .find('>')
+            .map(|e| start + e + 1)
+            .unwrap_or(rest.len());
+        let tag = &rest[start..end];
+        out.push(Piece::Tag(tag));
+        rest = &rest[end..];
+        let (name, closing) = tag_name(tag);
+        if !closing && (name == "style" || name == "script") {
+            let close = find_ignore_case(rest, &format!("</{name}")).unwrap_or(rest.len());
+            if close > 0 {
+                out.push(Piece::Raw(&rest[..close]));
+            }
+            rest = &rest[close..];
+        }
+    }
+    out
+}
+
+fn collapse_space(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            space = true;
+        } else {
+            if space {
+                out.push(' ');
+                space = false;
+            }
+            out.push(c);
+        }
+    }
+    if space {
+        out.push(' ');
+    }
+    out
+}
+
+/// Text with its whitespace collapsed and trimmed next to block elements
+fn layout_text(pieces: &[Piece], i: usize, text: &str) -> String {
+    let mut text = collapse_space(text);
+    if i == 0 || is_block(pieces.get(i - 1)) {
+        text = text.trim_start().to_string();
+    }
+    if is_block(pieces.get(i + 1)) || i + 1 == pieces.len() {
+        text = text.trim_end().to_string();
+    }
+    text
+}
+
+fn minify_css(css: &str) -> String {
+    let css = collapse_space(css);
+    let css = css.trim();
+    let mut out = String::with_capacity(css.len());
+    let mut depth = 0;
+    for (i, c) in css.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ' ' => {
+                let prev = out.chars().last();
+                let next = css[i + 1..].chars().next();
+                if [prev, next]
+                    .iter()
+                    .any(|c| matches!(c, Some('{' | '}' | ';' | ',')))
+                {
+                    continue;
+                }
+                // in selectors the space before `:` is a descendant combinator
+                let declaration = depth > 0
+                    && css[i..]
+                        .chars()
+                        .find(|c| matches!(c, '{' | '}' | ';'))
+                        != Some('{');
+                if declaration && [prev, next].contains(&Some(':')) {
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        out.push(c);
+    }
+    out.replace(";}", "}")
+}
+
+/// HTML with the whitespace that doesn't change how it shows removed
+pub fn minify(html: &str) -> String {
+    let pieces = pieces(html);
+    let mut out = String::with_capacity(html.len());
+    let mut in_style = false;
+    for (i, p) in pieces.iter().enumerate() {
+        match p {
+            Piece::Tag(t) => {
+                in_style = tag_name(t) == ("style".to_string(), false);
+                out.push_str(t);
+            }
+            Piece::Text(t) => out.push_str(&layout_text(&pieces, i, t)),
+            Piece::Raw(r) if in_style => out.push_str(&minify_css(r)),
+            Piece::Raw(r) => {
+                let lines: Vec<&str> = r.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+                out.push_str(&lines.join("\n"));
+            }
+        }
+    }
+    out
+}
+
+/// HTML with block elements on their own lines, indented by nesting
+pub fn pretty(html: &str) -> String {
+    const INDENT: &str = "  ";
+    let pieces = pieces(html);
+    let mut out = String::with_capacity(html.len() * 2);
+    let mut depth: usize = 0;
+    // whether the last thing written ends a line
+    let mut line_start = true;
+    let newline = |out: &mut String, depth: usize| {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&INDENT.repeat(depth));
+    };
+    for (i, p) in pieces.iter().enumerate() {
+        match p {
+            Piece::Tag(t) if is_block(Some(p)) => {
+                let (name, closing) = tag_name(t);
+                if closing {
+                    depth = depth.saturating_sub(1);
+                }
+                newline(&mut out, depth);
+                out.push_str(t);
+                if !closing && !VOID_TAGS.contains(&name.as_str()) && !t.ends_with("/>") {
+                    depth += 1;
+                }
+                line_start = true;
+            }
+            Piece::Tag(t) => {
+                if line_start {
+                    newline(&mut out, depth);
+                    line_start = false;
+                }
+                out.push_str(t);
+            }
+            Piece::Text(t) => {
+                let text = layout_text(&pieces, i, t);
+                if text.is_empty() {
+                    continue;
+                }
+                if line_start {
+                    newline(&mut out, depth);
+                    line_start = false;
+                }
+                out.push_str(&text);
+            }
+            Piece::Raw(r) => {
+                let mut nesting: usize = 0;
+                for line in r.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                    if line.starts_with('}') {
+                        nesting = nesting.saturating_sub(1);
+                    }
+                    newline(&mut out, depth + nesting);
+                    out.push_str(line);
+                    if line.ends_with('{') {
+                        nesting += 1;
+                    }
+                }
+                line_start = true;
+            }
+        }
+    }
+    out.push('\n');
+    out
+}
+
+/// Value of the attribute in a start tag
+fn tag_attr<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let pat = format!(" {name}=\"");
+    let start = tag.find(&pat)? + pat.len();
+    let len = tag[start..].find('"')?;
+    Some(&tag[start..start + len])
+}
+
+/// Files on disk the HTML refers to: sources of images and media, and
+/// stylesheets, leaving out addresses, anchors and data URLs
+pub fn local_assets(html: &str) -> Vec<&str> {
+    fn asset(tag: &str) -> Option<&str> {
+        match tag_name(tag).0.as_str() {
+            "img" | "audio" | "video" | "source" | "track" | "script" => tag_attr(tag, "src"),
+            "link" => tag_attr(tag, "href"),
+            _ => None,
+        }
+    }
+    pieces(html)
+        .into_iter()
+        .filter_map(|p| match p {
+            Piece::Tag(tag) => asset(tag),
+            _ => None,
+        })
+        .filter(|f| {
+            !(f.is_empty()
+                || f.contains("://")
+                || f.starts_with(['#', '/'])
+                || f.starts_with("data:")
+                || f.starts_with("mailto:"))
+        })
+        .collect()
+}
+
+/// Accessibility problems of the page: no language, images without alt
+/// text and links without text
+pub fn a11y_check(html: &str) -> Vec<String> {
+    let pieces = pieces(html);
+    let mut problems = Vec::new();
+    for (i, p) in pieces.iter().enumerate() {
+        let Piece::Tag(tag) = p else { continue };
+        match tag_name(tag) {
+            (name, false) if name == "html" && tag_attr(tag, "lang").is_none() => {
+                problems.push("Page has no language, set the lang chapter attr".to_string())
+            }
+            (name, false) if name == "img" && tag_attr(tag, "alt").is_none() => {
+                problems.push(format!(
+                    "Image {:?} has no alt text",
+                    tag_attr(tag, "src").unwrap_or_default()
+                ))
+            }
+            (name, false) if name == "a" && tag_attr(tag, "aria-label").is_none() => {
+                let text = pieces[i + 1..]
+                    .iter()
+                    .take_while(
+                        |p| !matches!(p, Piece::Tag(t) if tag_name(t) == ("a".to_string(), true)),
+                    )
+                    .any(|p| match p {
+                        Piece::Text(t) => !t.trim().is_empty(),
+                        Piece::Tag(t) => tag_attr(t, "alt").is_some_and(|a| !a.is_empty()),
+                        Piece::Raw(_) => false,
+                    });
+                if !text {
+                    problems.push(format!(
+                        "Link {:?} has no text",
+                        tag_attr(tag, "href").unwrap_or_default()
+                    ));
+                }
+            }
+            _ => (),
+        }
+    }
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("<p>\n  a  <span>b</span>\n c </p>", "<p>a <span>b</span> c</p>")]
+    #[case("<div> x </div>\n<div>y</div>", "<div>x</div><div>y</div>")]
+    #[case(
+        "<style>\n .tl {\n  color: red;\n  margin: 0 1em;\n }\n</style>",
+        "<style>.tl{color:red;margin:0 1em}</style>"
+    )]
+    #[case(
+        "<style>div :first-child { color : red }</style>",
+        "<style>div :first-child{color:red}</style>"
+    )]
+    #[case(
+        "<style>@media print { p :hover { margin : 0 } }</style>",
+        "<style>@media print{p :hover{margin:0}}</style>"
+    )]
+    fn minify_test(#[case] html: &str, #[case] expected: &str) {
+        assert_eq!(minify(html), expected);
+    }
+
+    #[rstest]
+    #[case("<script>var s = \"İİİ\";</script><p>a</p>")]
+    #[case("<SCRIPT>ſİ</Script><p>a</p>")]
+    #[case("<style>p::after { content: \"İ\" }</STYLE><p>a</p>")]
+    fn non_ascii_raw_test(#[case] html: &str) {
+        let raw: Vec<&str> = pieces(html)
+            .into_iter()
+            .filter_map(|p| match p {
+                Piece::Raw(r) => Some(r),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(raw.len(), 1);
+        assert!(!raw[0].contains('<'));
+        assert!(pretty(html).contains("</p>"));
+        assert!(minify(html).ends_with("<p>a</p>"));
+    }
+
+    #[rstest]
+    #[case(
+        "<html><body><p>a <span>b</span></p></body></html>",
+        "<html>\n  <body>\n    <p>\n      a <span>b</span>\n    </p>\n  </body>\n</html>\n"
+    )]
+    #[case(
+        "<style> .a {\ncolor: red;\n}</style>",
+        "<style>\n  .a {\n    color: red;\n  }\n</style>\n"
+    )]
+    #[case("<br><p>x</p>", "<br>\n<p>\n  x\n</p>\n")]
+    fn pretty_test(#[case] html: &str, #[case] expected: &str) {
+        assert_eq!(pretty(html), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "tooltip",
+        r#"<span title="m">w<span class="sr-only"> (m)</span></span>"#
+    )]
+    #[case("small", r#"<span>w<small class="gloss">m</small></span>"#)]
+    #[case("bracket", "<span>w [m]</span>")]
+    #[case(
+        "hidden",
+        r#"<span class="gloss-toggle" role="button" tabindex="0" aria-expanded="false" onclick="this.setAttribute('aria-expanded', this.classList.toggle('shown'))" onkeydown="if (event.key === 'Enter' || event.key === ' ') { event.preventDefault(); this.click(); }">w<small class="gloss">m</small></span>"#
+    )]
+    fn gloss_mode_test(#[case] mode: &str, #[case] expected: &str) {
+        assert_eq!(GlossMode::from_str(mode).unwrap().html("w", "m"), expected);
+    }
+
+    #[rstest]
+    #[case("{meanings}", Some("a; b"))]
+    #[case("{meaning} ({source})", Some("a (dict.txt:3)"))]
+    #[case("{ word }: {meanings} \"", Some("w: a; b &quot;"))]
+    #[case("{reading}", None)]
+    #[case("{meanings", None)]
+    #[case("meanings", None)]
+    fn gloss_format_test(#[case] format: &str, #[case] expected: Option<&str>) {
+        let format = GlossFormat::from_str(format);
+        assert_eq!(
+            format
+                .ok()
+                .map(|f| f.render("w", &["a", "b"], Some("dict.txt:3")))
+                .as_deref(),
+            expected
+        );
+    }
+
+    #[test]
+    fn gloss_escape_test() {
+        let format = GlossFormat::from_str("{word}: {meanings} ({source})").unwrap();
+        let gloss = format.render("<b>w</b>", &["say \"hi\"", "a<b"], Some("d\"x.txt:1"));
+        assert_eq!(
+            gloss,
+            "&lt;b&gt;w&lt;/b&gt;: say &quot;hi&quot;; a&lt;b (d&quot;x.txt:1)"
+        );
+        let html = GlossMode::Tooltip.html("w", &gloss);
+        assert!(html.starts_with("<span title=\"&lt;b&gt;w&lt;/b&gt;: say &quot;hi&quot;;"));
+    }
+
+    #[rstest]
+    #[case(r#"<html lang="ne"><img src="a.png" alt="A"><a href="b">B</a></html>"#, vec![])]
+    #[case("<html><body></body></html>", vec!["Page has no language, set the lang chapter attr"])]
+    #[case(r#"<img src="a.png">"#, vec![r#"Image "a.png" has no alt text"#])]
+    #[case(r#"<a href="b"> </a><a href="c"><img src="c.png" alt="C"></a>"#, vec![r#"Link "b" has no text"#])]
+    #[case(r#"<a href="b" aria-label="B"></a>"#, vec![])]
+    fn a11y_check_test(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(a11y_check(html), expected);
+    }
+
+    #[rstest]
+    #[case(r#"<img src="fig/1.png" alt="A"><a href="b.html">B</a>"#, vec!["fig/1.png"])]
+    #[case(r#"<audio src="https://x.org/a.mp3"></audio><img src="data:image/png;base64,AA">"#, vec![])]
+    #[case(r#"<link rel="stylesheet" href="style.css"><source src="a.ogg">"#, vec!["style.css", "a.ogg"])]
+    fn local_assets_test(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(local_assets(html), expected);
+    }
+
+    #[test]
+    fn pieces_test() {
+        assert_eq!(
+            pieces("<style>a<b</style>t"),
+            vec![
+                Piece::Tag("<style>"),
+                Piece::Raw("a<b"),
+                Piece::Tag("</style>"),
+                Piece::Text("t")
+            ]
+        );
+    }
+}
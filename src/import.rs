@@ -0,0 +1,409 @@
+use crate::syntax::{Chapter, OrgFragment, Sentence, Span, TRANSLATIONS_ATTR, Translation};
+use indexmap::IndexMap;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::BufRead;
+
+/// Replaces the characters that are syntax in chapter files with look-alikes
+pub fn plain_text(s: &str) -> String {
+    s.replace("<<", "«")
+        .replace(">>", "»")
+        .replace("---", "—")
+        .replace(['\n', '\r', '\t'], " ")
+        .replace('=', "＝")
+        .replace(';', "；")
+        .replace('#', "＃")
+        .replace('@', "＠")
+        .trim()
+        .to_string()
+}
+
+/// Splits text into sentences at line ends and sentence ending punctuation
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    for line in text.lines() {
+        let mut start = 0;
+        for (i, c) in line.char_indices() {
+            if matches!(c, '.' | '!' | '?' | '。' | '！' | '？' | '।' | '॥') {
+                let end = i + c.len_utf8();
+                // keep runs like "..." or "?!" together
+                if line[end..].starts_with(|n: char| n.is_alphanumeric() || n.is_whitespace())
+                    || end == line.len()
+                {
+                    sentences.push(&line[start..end]);
+                    start = end;
+                }
+            }
+        }
+        sentences.push(&line[start..]);
+    }
+    sentences
+        .into_iter()
+        .map(plain_text)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Chapter with one untranslated sentence per sentence of the text
+pub fn text_chapter(title: &str, language: Option<&str>, text: &str) -> Chapter {
+    let mut attrs = IndexMap::new();
+    attrs.insert("title".to_string(), plain_text(title));
+    if let Some(l) = language {
+        attrs.insert("language".to_string(), plain_text(l));
+    }
+    let sentences = split_sentences(text)
+        .into_iter()
+        .enumerate()
+        .map(|(i, s)| Sentence {
+            label: format!("s{}", i + 1),
+            original: vec![OrgFragment::Simple(s)],
+            orgattrs: IndexMap::new(),
+            translations: IndexMap::new(),
+            span: Span::default(),
+        })
+        .collect();
+    Chapter::new(attrs, sentences)
+}
+
+/// Sentence with a single translation labelled with its language
+pub fn pair_sentence(label: String, original: &str, language: &str, translation: &str) -> Sentence {
+    let mut translations = IndexMap::new();
+    translations.insert(
+        language.to_string(),
+        Translation {
+            content: plain_text(translation),
+            attrs: IndexMap::new(),
+        },
+    );
+    Sentence {
+        label,
+        original: vec![OrgFragment::Simple(plain_text(original))],
+        orgattrs: IndexMap::new(),
+        translations,
+        span: Span::default(),
+    }
+}
+
+/// Chapter of sentence pairs from `language` to `target`
+pub fn pair_chapter(
+    title: &str,
+    language: &str,
+    target: &str,
+    sentences: Vec<Sentence>,
+) -> Chapter {
+    let mut attrs = IndexMap::new();
+    attrs.insert("title".to_string(), plain_text(title));
+    attrs.insert("language".to_string(), plain_text(language));
+    attrs.insert(TRANSLATIONS_ATTR.to_string(), plain_text(target));
+    Chapter::new(attrs, sentences)
+}
+
+/// Sentence pairs from the Tatoeba `sentences.tsv` and `links.tsv` exports.
+///
+/// Only the first linked translation of each sentence is kept, sentences
+/// are labelled with their Tatoeba id.
+pub fn tatoeba(
+    sentences: impl BufRead,
+    links: impl BufRead,
+    language: &str,
+    target: &str,
+    limit: Option<usize>,
+) -> Result<Vec<Sentence>, String> {
+    let mut originals: IndexMap<String, String> = IndexMap::new();
+    let mut targets: HashMap<String, String> = HashMap::new();
+    for line in sentences.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let mut cols = line.splitn(3, '\t');
+        if let (Some(id), Some(lang), Some(text)) = (cols.next(), cols.next(), cols.next()) {
+            if lang == language {
+                originals.insert(id.to_string(), text.to_string());
+            } else if lang == target {
+                targets.insert(id.to_string(), text.to_string());
+            }
+        }
+    }
+    let mut pairs: HashMap<String, String> = HashMap::new();
+    for line in links.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if let Some((a, b)) = line.split_once('\t')
+            && originals.contains_key(a)
+            && !pairs.contains_key(a)
+            && let Some(t) = targets.get(b)
+        {
+            pairs.insert(a.to_string(), t.clone());
+        }
+    }
+    Ok(originals
+        .into_iter()
+        .filter_map(|(id, org)| {
+            let tl = pairs.get(&id)?;
+            Some(pair_sentence(id, &org, target, tl))
+        })
+        .take(limit.unwrap_or(usize::MAX))
+        .collect())
+}
+
+/// Sentence pairs from line aligned OPUS Moses files, labelled by line
+/// number. Files with different line counts are not aligned and give an
+/// error.
+pub fn moses(
+    originals: impl BufRead,
+    translations: impl BufRead,
+    target: &str,
+    limit: Option<usize>,
+) -> Result<Vec<Sentence>, String> {
+    let originals: Vec<String> = originals
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    let translations: Vec<String> = translations
+        .lines()
+        .collect::<Result<_, _>>()
+        .map_err(|e| e.to_string())?;
+    if originals.len() != translations.len() {
+        return Err(format!(
+            "Original has {} lines but translation has {}, the files are not aligned",
+            originals.len(),
+            translations.len()
+        ));
+    }
+    Ok(originals
+        .iter()
+        .zip(&translations)
+        .enumerate()
+        .filter(|(_, (org, tl))| !org.trim().is_empty() && !tl.trim().is_empty())
+        .map(|(i, (org, tl))| pair_sentence((i + 1).to_string(), org, target, tl))
+        .take(limit.unwrap_or(usize::MAX))
+        .collect())
+}
+
+/// Looked up words from a Kindle vocabulary builder CSV export.
+///
+/// Both the word as it appeared and its stem are taken, from the `word` and
+/// `stem` columns of the header, or the first column when there is none.
+pub fn kindle_csv(data: &str, language: Option<&str>) -> Result<Vec<String>, String> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let mut rows = rdr.records();
+    let mut columns = vec![0];
+    let mut lang_col = None;
+    let mut first = true;
+    let mut words = Vec::new();
+    while let Some(row) = rows.next().transpose().map_err(|e| e.to_string())? {
+        if first {
+            first = false;
+            let names: Vec<String> = row.iter().map(|c| c.trim().to_lowercase()).collect();
+            let found: Vec<usize> = (0..names.len())
+                .filter(|&i| names[i] == "word" || names[i] == "stem")
+                .collect();
+            if !found.is_empty() {
+                columns = found;
+                lang_col = names.iter().position(|n| n == "lang" || n == "language");
+                continue;
+            }
+        }
+        if let (Some(l), Some(c)) = (language, lang_col)
+            && row.get(c).is_some_and(|v| v.trim() != l)
+        {
+            continue;
+        }
+        words.extend(
+            columns
+                .iter()
+                .filter_map(|&c| row.get(c))
+                .map(plain_text)
+                .filter(|w| !w.is_empty()),
+        );
+    }
+    crate::collate::sort_words(&mut words, language.unwrap_or_default());
+    words.dedup();
+    Ok(words)
+}
+
+/// Looked up words and their stems from a Kindle `vocab.db`
+#[cfg(feature = "kindle")]
+pub fn kindle_db(file: &std::path::Path, language: Option<&str>) -> Result<Vec<String>, String> {
+    let conn =
+        rusqlite::Connection::open_with_flags(file, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+            .map_err(|e| e.to_string())?;
+    let mut stmt = conn
+        .prepare("SELECT word, stem FROM WORDS WHERE ?1 IS NULL OR lang = ?1")
+        .map_err(|e| e.to_string())?;
+    let rows = stmt
+        .query_map([language], |r| {
+            Ok((
+                r.get::<_, Option<String>>(0)?,
+                r.get::<_, Option<String>>(1)?,
+            ))
+        })
+        .map_err(|e| e.to_string())?;
+    let mut words = Vec::new();
+    for row in rows {
+        let (word, stem) = row.map_err(|e| e.to_string())?;
+        words.extend(
+            [word, stem]
+                .into_iter()
+                .flatten()
+                .map(|w| plain_text(&w))
+                .filter(|w| !w.is_empty()),
+        );
+    }
+    crate::collate::sort_words(&mut words, language.unwrap_or_default());
+    words.dedup();
+    Ok(words)
+}
+
+#[derive(Deserialize)]
+struct LingqLesson {
+    title: String,
+    #[serde(alias = "content")]
+    text: String,
+    #[serde(alias = "language_code")]
+    language: Option<String>,
+}
+
+/// Chapter from a LingQ lesson JSON export
+pub fn lingq_lesson(json: &str) -> Result<Chapter, String> {
+    let lesson: LingqLesson = serde_json::from_str(json).map_err(|e| e.to_string())?;
+    Ok(text_chapter(
+        &lesson.title,
+        lesson.language.as_deref(),
+        &lesson.text,
+    ))
+}
+
+/// Dictionary entries from a term list export (LingQ CSV, LWT TSV).
+///
+/// The term and meaning columns are found from the header when there is
+/// one, otherwise the first two columns are used. Meanings are split at any
+/// of the `separators`.
+pub fn term_list(
+    data: &str,
+    delimiter: u8,
+    separators: &[char],
+) -> Result<IndexMap<String, Vec<String>>, String> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let mut rows = rdr.records();
+    let mut columns = (0, 1);
+    let mut dict = IndexMap::new();
+    let mut first = true;
+    while let Some(row) = rows.next().transpose().map_err(|e| e.to_string())? {
+        if first {
+            first = false;
+            let names: Vec<String> = row.iter().map(|c| c.trim().to_lowercase()).collect();
+            let term = names.iter().position(|n| n == "term" || n == "word");
+            let meaning = names.iter().position(|n| {
+                ["hint", "meaning", "translation", "definition"].contains(&n.as_str())
+            });
+            if let Some(t) = term {
+                columns = (t, meaning.unwrap_or(t + 1));
+                continue;
+            }
+        }
+        let term = plain_text(row.get(columns.0).unwrap_or_default());
+        let meanings: Vec<String> = row
+            .get(columns.1)
+            .unwrap_or_default()
+            .split(separators)
+            .map(plain_text)
+            .filter(|m| !m.is_empty())
+            .collect();
+        if term.is_empty() || meanings.is_empty() {
+            continue;
+        }
+        dict.entry(term).or_insert_with(Vec::new).extend(meanings);
+    }
+    Ok(dict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("One. Two! Three", vec!["One.", "Two!", "Three"])]
+    #[case("Wait... what?\nNew line", vec!["Wait...", "what?", "New line"])]
+    #[case("今日は。元気？", vec!["今日は。", "元気？"])]
+    #[case("a = b; #3", vec!["a ＝ b； ＃3"])]
+    fn split_sentences_test(#[case] txt: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(split_sentences(txt), expected);
+    }
+
+    #[rstest]
+    #[case("term,hint\nhund,dog\nkatze,cat; kitty\n", b',', 2)]
+    #[case("Hund\tdog / hound\t\t\nKatze\tcat\n", b'\t', 2)]
+    #[case("Word,Status,Meaning\nhund,1,dog\n,,\n", b',', 1)]
+    fn term_list_test(#[case] txt: &str, #[case] delim: u8, #[case] count: usize) {
+        let dict = term_list(txt, delim, &[';', '/']).unwrap();
+        assert_eq!(dict.len(), count);
+    }
+
+    #[test]
+    fn tatoeba_test() {
+        let sentences =
+            "1\tjpn\t猫です。\n2\teng\tIt's a cat.\n3\tjpn\t犬\n4\tdeu\tHund\n5\teng\tA cat.\n";
+        let links = "1\t2\n2\t1\n1\t5\n3\t4\n";
+        let pairs = tatoeba(sentences.as_bytes(), links.as_bytes(), "jpn", "eng", None).unwrap();
+        assert_eq!(pairs.len(), 1);
+        assert_eq!(pairs[0].label, "1");
+        assert_eq!(pairs[0].translations["eng"].content, "It's a cat.");
+    }
+
+    #[test]
+    fn moses_test() {
+        let pairs = moses("a\n\nc\n".as_bytes(), "A\nB\nC\n".as_bytes(), "en", None).unwrap();
+        assert_eq!(pairs.len(), 2);
+        assert_eq!(pairs[1].label, "3");
+        let chap = pair_chapter("T", "xx", "en", pairs);
+        let reparsed = Chapter::from_str(&chap.source()).unwrap();
+        assert_eq!(reparsed.sentences, chap.sentences);
+        assert_eq!(chap.tl_languages, ["en"]);
+        assert_eq!(reparsed.tl_languages, ["en"]);
+        assert_eq!(
+            moses("a\nb\n".as_bytes(), "A\n".as_bytes(), "en", None).unwrap_err(),
+            "Original has 2 lines but translation has 1, the files are not aligned"
+        );
+    }
+
+    #[rstest]
+    #[case("Word,Stem,Lang,Usage\nran,run,en,He ran.\nHunde,Hund,de,x\n", Some("en"), vec!["ran", "run"])]
+    #[case("ran\nrun\nran\n", None, vec!["ran", "run"])]
+    fn kindle_csv_test(#[case] txt: &str, #[case] lang: Option<&str>, #[case] expected: Vec<&str>) {
+        assert_eq!(kindle_csv(txt, lang).unwrap(), expected);
+    }
+
+    #[cfg(feature = "kindle")]
+    #[test]
+    fn kindle_db_test() {
+        let file = std::env::temp_dir().join(format!("transdoc-kindle-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&file);
+        let conn = rusqlite::Connection::open(&file).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE WORDS (id TEXT, word TEXT, stem TEXT, lang TEXT);
+             INSERT INTO WORDS VALUES ('en:ran', 'ran', 'run', 'en'), ('de:Hunde', 'Hunde', 'Hund', 'de');",
+        )
+        .unwrap();
+        drop(conn);
+        assert_eq!(kindle_db(&file, Some("de")).unwrap(), vec!["Hund", "Hunde"]);
+        assert_eq!(kindle_db(&file, None).unwrap().len(), 4);
+        std::fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn lingq_lesson_test() {
+        let json = r#"{"title": "Lesson 1", "text": "Hallo Welt. Wie geht's?", "language": "de"}"#;
+        let chap = lingq_lesson(json).unwrap();
+        assert_eq!(chap.sentences.len(), 2);
+        let reparsed = Chapter::from_str(&chap.source()).unwrap();
+        assert_eq!(reparsed.sentences, chap.sentences);
+        assert_eq!(reparsed.language, "de");
+    }
+}
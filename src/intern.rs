@@ -0,0 +1,112 @@
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock, Mutex};
+
+/// Meanings of a word, shared between the dictionary and its glosses
+pub type Meanings = Arc<[Arc<str>]>;
+
+/// Keeps one copy of each string and meaning list handed to it
+#[derive(Debug, Default)]
+pub struct Interner {
+    strings: HashSet<Arc<str>>,
+    meanings: HashSet<Meanings>,
+    /// Entries kept at the last pruning
+    kept: usize,
+}
+
+impl Interner {
+    pub fn intern(&mut self, s: &str) -> Arc<str> {
+        if let Some(i) = self.strings.get(s) {
+            return i.clone();
+        }
+        let i: Arc<str> = s.into();
+        self.strings.insert(i.clone());
+        i
+    }
+
+    pub fn meanings<S: AsRef<str>>(&mut self, meanings: &[S]) -> Meanings {
+        let m: Meanings = meanings.iter().map(|m| self.intern(m.as_ref())).collect();
+        if let Some(i) = self.meanings.get(&m) {
+            return i.clone();
+        }
+        self.meanings.insert(m.clone());
+        m
+    }
+
+    /// Drops the strings and meaning lists only the interner still holds
+    pub fn prune(&mut self) {
+        self.meanings.retain(|m| Arc::strong_count(m) > 1);
+        self.strings.retain(|s| Arc::strong_count(s) > 1);
+        self.kept = self.len();
+    }
+
+    pub fn len(&self) -> usize {
+        self.strings.len() + self.meanings.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Prunes once the entries doubled since the last time, so chapters
+    /// and dictionaries dropped don't stay around for the whole process
+    fn prune_grown(&mut self) {
+        if self.len() > (2 * self.kept).max(PRUNE_MIN) {
+            self.prune();
+        }
+    }
+}
+
+/// Entries below which the interner isn't pruned
+const PRUNE_MIN: usize = 4096;
+
+/// Interner shared by the chapters of a process, so chapters of a book
+/// using the same dictionary share its words and meanings; what no chapter
+/// holds anymore is dropped as it grows
+static INTERNER: LazyLock<Mutex<Interner>> = LazyLock::new(Default::default);
+
+pub fn intern(s: &str) -> Arc<str> {
+    let mut interner = INTERNER.lock().expect("interner poisoned");
+    interner.prune_grown();
+    interner.intern(s)
+}
+
+pub fn meanings<S: AsRef<str>>(meanings: &[S]) -> Meanings {
+    let mut interner = INTERNER.lock().expect("interner poisoned");
+    interner.prune_grown();
+    interner.meanings(meanings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interner_test() {
+        let mut interner = Interner::default();
+        let a = interner.meanings(&["flower", "blossom"]);
+        let b = interner.meanings(&["flower".to_string(), "blossom".to_string()]);
+        assert!(Arc::ptr_eq(&a, &b));
+        let c = interner.meanings(&["flower"]);
+        assert!(!Arc::ptr_eq(&a, &c));
+        assert!(Arc::ptr_eq(&a[0], &c[0]));
+        assert!(Arc::ptr_eq(&interner.intern("flower"), &c[0]));
+    }
+
+    #[test]
+    fn prune_test() {
+        let mut interner = Interner::default();
+        let kept = interner.meanings(&["flower", "blossom"]);
+        drop(interner.meanings(&["tree"]));
+        drop(interner.intern("leaf"));
+        assert_eq!(interner.len(), 6);
+        interner.prune();
+        assert_eq!(interner.len(), 3);
+        assert!(Arc::ptr_eq(
+            &interner.meanings(&["flower", "blossom"]),
+            &kept
+        ));
+        drop(kept);
+        interner.prune();
+        assert!(interner.is_empty());
+    }
+}
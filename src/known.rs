@@ -0,0 +1,182 @@
+use crate::syntax::Chapter;
+use crate::text;
+use crate::words::{is_known, parse_word_list, words};
+use indexmap::IndexMap;
+use std::collections::HashSet;
+use std::path::Path;
+#[cfg(feature = "html")]
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Known words by proficiency level, the easiest level first
+pub type KnownLevels = IndexMap<String, HashSet<String>>;
+
+/// Word lists of a directory of known words, one file per level named
+/// after it like `N5.txt`. Levels are ordered like `order` and the ones
+/// it doesn't name after them like `A1 < A2 < B1`.
+pub fn load_levels(dir: &Path, order: &[&str]) -> Result<KnownLevels, String> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).map_err(|e| e.to_string())? {
+        let path = entry.map_err(|e| e.to_string())?.path();
+        let hidden = path
+            .file_name()
+            .is_some_and(|n| n.to_string_lossy().starts_with('.'));
+        if path.is_file()
+            && !hidden
+            && let Some(stem) = path.file_stem()
+        {
+            files.push((stem.to_string_lossy().to_string(), path));
+        }
+    }
+    files.sort_by(|(a, _), (b, _)| {
+        let rank = |l: &str| order.iter().position(|o| *o == l).unwrap_or(order.len());
+        (rank(a), a.len(), a).cmp(&(rank(b), b.len(), b))
+    });
+    let mut levels = KnownLevels::new();
+    for (level, path) in files {
+        let words =
+            text::read_text(&path).map_err(|e| format!("{}: {e}", path.to_string_lossy()))?;
+        levels.insert(level, parse_word_list(&words));
+    }
+    Ok(levels)
+}
+
+/// Level the word is known at, the easiest one listing it
+pub fn level_of<'a>(levels: &'a KnownLevels, word: &str) -> Option<&'a str> {
+    levels
+        .iter()
+        .find(|(_, w)| is_known(w, word))
+        .map(|(l, _)| l.as_str())
+}
+
+/// Class of the words known at the level, like `known-n5`
+pub fn level_class(level: &str) -> String {
+    let id: String = level
+        .chars()
+        .map(|c| match c.is_alphanumeric() {
+            true => c.to_ascii_lowercase(),
+            false => '-',
+        })
+        .collect();
+    format!("known-{id}")
+}
+
+/// HTML text with the known words wrapped in spans of their level's class,
+/// leaving tags and character references alone
+#[cfg(feature = "html")]
+pub(crate) fn wrap_known(html: &str, levels: &KnownLevels) -> String {
+    let mut out = String::with_capacity(html.len());
+    let (mut in_tag, mut in_ref) = (false, false);
+    for seg in html.split_word_bounds() {
+        match seg {
+            "<" => in_tag = true,
+            ">" => in_tag = false,
+            "&" => in_ref = true,
+            ";" => in_ref = false,
+            _ if !in_tag && !in_ref && seg.chars().any(char::is_alphanumeric) => {
+                if let Some(level) = level_of(levels, seg) {
+                    out.push_str(&format!(
+                        "<span class=\"{}\" title=\"{level}\">{seg}</span>",
+                        level_class(level)
+                    ));
+                    continue;
+                }
+            }
+            _ => (),
+        }
+        out.push_str(seg);
+    }
+    out
+}
+
+impl Chapter {
+    /// Percentage of the words of the originals known at each level or an
+    /// easier one
+    pub fn level_coverage(&self) -> Vec<(&str, f64)> {
+        let mut known = vec![0usize; self.known_levels.len()];
+        let mut tokens = 0;
+        for s in &self.sentences {
+            for w in words(&s.text()) {
+                tokens += 1;
+                if let Some(i) = self.known_levels.values().position(|l| is_known(l, w)) {
+                    known[i] += 1;
+                }
+            }
+        }
+        let mut total = 0;
+        self.known_levels
+            .keys()
+            .zip(known)
+            .map(|(level, n)| {
+                total += n;
+                let share = match tokens {
+                    0 => 100.0,
+                    t => total as f64 * 100.0 / t as f64,
+                };
+                (level.as_str(), share)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "html")]
+    use crate::syntax::HtmlOptions;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    fn levels_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("transdoc-{name}-{}", std::process::id()));
+        let levels = dir.join("levels");
+        std::fs::create_dir_all(&levels).unwrap();
+        std::fs::write(levels.join("N5.txt"), "neko\ninu\n").unwrap();
+        std::fs::write(levels.join("N4.txt"), "hashiru # to run\n").unwrap();
+        std::fs::write(levels.join(".hidden"), "x\n").unwrap();
+        dir
+    }
+
+    #[rstest]
+    #[case(&[], vec!["N4", "N5"])]
+    #[case(&["N5", "N4"], vec!["N5", "N4"])]
+    #[case(&["N5"], vec!["N5", "N4"])]
+    fn load_test(#[case] order: &[&str], #[case] expected: Vec<&str>) {
+        let dir = levels_dir(&format!("levels-{}", order.len()));
+        let levels = load_levels(&dir.join("levels"), order).unwrap();
+        assert_eq!(levels.keys().collect::<Vec<_>>(), expected);
+        assert_eq!(level_of(&levels, "Hashiru"), Some("N4"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn chapter_test() {
+        let dir = levels_dir("known");
+        let chap = Chapter::parse_in(
+            "known_words = levels\nknown_levels = N5, N4\n\n@ 1\nneko hashiru <b>inu</b> tori\n\n@ 2\nneko tori\n",
+            "#",
+            &dir,
+        )
+        .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(chap.known_words.contains("inu"));
+        assert_eq!(chap.level_coverage(), vec![("N5", 37.5), ("N4", 50.0)]);
+        #[cfg(feature = "html")]
+        let html = chap.sentence_html(&chap.sentences[0], &HtmlOptions::default());
+        #[cfg(feature = "html")]
+        assert!(html.contains(
+            "<span class=\"known-n5\" title=\"N5\">neko</span> <span class=\"known-n4\" title=\"N4\">hashiru</span> <b><span class=\"known-n5\" title=\"N5\">inu</span></b> tori"
+        ), "{html}");
+    }
+
+    #[test]
+    fn no_levels_test() {
+        let chap = Chapter::from_str("@ 1\nneko\n").unwrap();
+        assert_eq!(chap.level_coverage(), vec![]);
+        #[cfg(feature = "html")]
+        assert!(
+            !chap
+                .sentence_html(&chap.sentences[0], &HtmlOptions::default())
+                .contains("known-")
+        );
+    }
+}
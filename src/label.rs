@@ -0,0 +1,233 @@
+use crate::syntax::Chapter;
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+/// Run of digits or of other characters in a label
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum Part {
+    Number(u64),
+    Text(String),
+}
+
+/// Sentence label split into its numbers and the text between them, so
+/// numeric (`10`), dotted (`3.12`) and prefixed (`p2-s5`) labels sort by
+/// their numbers: `2 < 10`, `3.9 < 3.12` and `p2-s5 < p10-s1`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Label {
+    parts: Vec<Part>,
+    raw: String,
+}
+
+impl From<&str> for Label {
+    fn from(s: &str) -> Self {
+        let raw = s.trim().trim_start_matches('@').trim();
+        let mut parts = Vec::new();
+        let mut rest = raw;
+        while let Some(c) = rest.chars().next() {
+            let digit = c.is_ascii_digit();
+            let end = rest
+                .find(|c: char| c.is_ascii_digit() != digit)
+                .unwrap_or(rest.len());
+            parts.push(match rest[..end].parse() {
+                Ok(n) if digit => Part::Number(n),
+                _ => Part::Text(rest[..end].to_string()),
+            });
+            rest = &rest[end..];
+        }
+        Self {
+            parts,
+            raw: raw.to_string(),
+        }
+    }
+}
+
+impl FromStr for Label {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let label = Self::from(s);
+        match label.raw.is_empty() {
+            true => Err(format!("Label {s:?} is empty")),
+            false => Ok(label),
+        }
+    }
+}
+
+impl std::fmt::Display for Label {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl Ord for Label {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // `01` and `1` have the same parts
+        self.parts
+            .cmp(&other.parts)
+            .then_with(|| self.raw.cmp(&other.raw))
+    }
+}
+
+impl PartialOrd for Label {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Label {
+    /// Only digits, like `12`
+    pub fn is_numeric(&self) -> bool {
+        matches!(self.parts[..], [Part::Number(_)])
+    }
+
+    /// The label as it goes in element ids, with the whitespace that ids
+    /// can't have replaced by `-`
+    pub fn id(&self) -> String {
+        self.raw
+            .chars()
+            .map(|c| if c.is_whitespace() { '-' } else { c })
+            .collect()
+    }
+
+    /// id of the sentence's element on the page
+    pub fn anchor(&self) -> String {
+        format!("line-{}", self.id())
+    }
+}
+
+/// Labels from one to another like `@10..@25`, with either end left open
+/// like `@10..`; a single label is a range of itself
+#[derive(Debug, Clone, PartialEq)]
+pub struct LabelRange {
+    pub start: Option<Label>,
+    pub end: Option<Label>,
+}
+
+impl FromStr for LabelRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let end = |l: &str| Some(Label::from(l)).filter(|l| !l.raw.is_empty());
+        let range = match s.split_once("..") {
+            Some((a, b)) => Self {
+                start: end(a),
+                end: end(b.trim_start_matches('=')),
+            },
+            None => Self {
+                start: end(s),
+                end: end(s),
+            },
+        };
+        if range.start.is_none() && range.end.is_none() {
+            return Err(format!("Label range {s:?} isn't like @10..@25"));
+        }
+        Ok(range)
+    }
+}
+
+impl LabelRange {
+    /// Whether the label is between the ends, both included
+    pub fn contains(&self, label: &Label) -> bool {
+        self.start.as_ref().is_none_or(|s| s <= label)
+            && self.end.as_ref().is_none_or(|e| label <= e)
+    }
+}
+
+impl Chapter {
+    /// Indices of the sentences with labels in any of the ranges
+    pub fn in_ranges(&self, ranges: &[LabelRange]) -> Vec<usize> {
+        self.sentences
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| {
+                let label = Label::from(s.label.as_str());
+                ranges.iter().any(|r| r.contains(&label))
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Keeps only the sentences with labels in any of the ranges, or all of
+    /// them when there are no ranges
+    pub fn select(&mut self, ranges: &[LabelRange]) {
+        if ranges.is_empty() {
+            return;
+        }
+        self.retain_sentences(|_, s| {
+            let label = Label::from(s.label.as_str());
+            ranges.iter().any(|r| r.contains(&label))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("2", "10")]
+    #[case("3.9", "3.12")]
+    #[case("3.12", "4")]
+    #[case("p2-s5", "p10-s1")]
+    #[case("p2-s5", "p2-s10")]
+    #[case("01", "1")]
+    #[case("10", "a")]
+    #[case("intro", "outro")]
+    fn order_test(#[case] a: &str, #[case] b: &str) {
+        assert!(Label::from(a) < Label::from(b));
+        assert!(Label::from(b) > Label::from(a));
+    }
+
+    #[rstest]
+    #[case("@ 12", "12", true)]
+    #[case("3.12", "3.12", false)]
+    #[case("my label", "my-label", false)]
+    fn label_test(#[case] label: &str, #[case] id: &str, #[case] numeric: bool) {
+        let label = Label::from(label);
+        assert_eq!(label.id(), id);
+        assert_eq!(label.anchor(), format!("line-{id}"));
+        assert_eq!(label.is_numeric(), numeric);
+    }
+
+    #[rstest]
+    #[case("@10..@25", vec!["10", "12", "25"])]
+    #[case("10..", vec!["10", "12", "25", "100"])]
+    #[case("..@9", vec!["2", "3.5"])]
+    #[case("@12", vec!["12"])]
+    #[case("3..4", vec!["3.5"])]
+    fn range_test(#[case] range: &str, #[case] expected: Vec<&str>) {
+        let chap =
+            Chapter::from_str("@ 2\na\n\n@ 10\nb\n\n@ 3.5\nc\n\n@ 12\nd\n\n@ 25\ne\n\n@ 100\nf\n")
+                .unwrap();
+        let labels: Vec<&str> = chap
+            .in_ranges(&[range.parse().unwrap()])
+            .into_iter()
+            .map(|i| chap.sentences[i].label.as_str())
+            .collect();
+        let mut expected = expected;
+        let order: Vec<&str> = chap.sentences.iter().map(|s| s.label.as_str()).collect();
+        expected.sort_by_key(|l| order.iter().position(|o| o == l));
+        assert_eq!(labels, expected);
+    }
+
+    #[rstest]
+    #[case(vec![], vec!["1", "2", "10"])]
+    #[case(vec!["@2..", "@1"], vec!["1", "2", "10"])]
+    #[case(vec!["@2..@9"], vec!["2"])]
+    #[case(vec!["@3"], vec![])]
+    fn select_test(#[case] ranges: Vec<&str>, #[case] expected: Vec<&str>) {
+        let mut chap = Chapter::from_str("@ 1\na\n\n@ 2\nb\n\n@ 10\nc\n").unwrap();
+        let ranges: Vec<LabelRange> = ranges.iter().map(|r| r.parse().unwrap()).collect();
+        chap.select(&ranges);
+        let labels: Vec<&str> = chap.sentences.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, expected);
+    }
+
+    #[rstest]
+    #[case("..")]
+    #[case("")]
+    fn bad_range_test(#[case] range: &str) {
+        assert!(LabelRange::from_str(range).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+pub mod agree;
+pub mod align;
+pub mod appendix;
+pub mod attrs;
+pub mod audio;
+#[cfg(feature = "html")]
+pub mod book;
+pub mod checksum;
+pub mod cite;
+pub mod collate;
+pub mod components;
+pub mod conditional;
+pub mod convert;
+pub mod diff;
+pub mod doctor;
+#[cfg(feature = "encrypt")]
+pub mod encrypt;
+pub mod errors;
+pub mod eval;
+pub mod filter;
+pub mod graph;
+pub mod history;
+pub mod hooks;
+#[cfg(feature = "html")]
+pub mod html;
+#[cfg(feature = "import")]
+pub mod import;
+pub mod intern;
+pub mod known;
+pub mod label;
+pub mod lints;
+pub mod lrc;
+pub mod merge;
+pub mod notes;
+pub mod numbers;
+pub mod outline;
+pub mod output;
+pub mod page;
+pub mod parser;
+pub mod plugin;
+pub mod rename;
+pub mod reorder;
+pub mod review;
+pub mod scaffold;
+pub mod schema;
+pub mod select;
+#[cfg(feature = "serve")]
+pub mod serve;
+#[cfg(feature = "site")]
+pub mod site;
+pub mod skip;
+pub mod snippet;
+pub mod splice;
+pub mod stale;
+#[cfg(feature = "html")]
+pub mod stamp;
+pub mod stats;
+pub mod syntax;
+pub mod template;
+pub mod term;
+pub mod text;
+pub mod tokenizer;
+pub mod typed;
+pub mod undo;
+pub mod validate;
+pub mod vars;
+pub mod widget;
+pub mod words;
@@ -0,0 +1,282 @@
+use crate::errors::{Diagnostic, Severity};
+use indexmap::IndexMap;
+#[cfg(feature = "cli")]
+use std::path::Path;
+use std::str::FromStr;
+
+/// Project configuration file, read from the current directory
+pub const CONFIG_FILE: &str = "transdoc.toml";
+
+/// Group of every warning, like rustc's
+pub const WARNINGS: &str = "warnings";
+
+/// Rules of the checks, their level unless configured and what they find
+pub const RULES: &[(&str, LintLevel, &str)] = &[
+    (
+        "attr-type",
+        LintLevel::Warn,
+        "attr values of the wrong type",
+    ),
+    (
+        "changed-original",
+        LintLevel::Warn,
+        "originals changed since they were translated",
+    ),
+    (
+        "dictionary",
+        LintLevel::Warn,
+        "dictionaries that can't be read or have bad entries",
+    ),
+    (
+        "duplicate-label",
+        LintLevel::Deny,
+        "sentences with the label of an earlier one",
+    ),
+    (
+        "empty-pair",
+        LintLevel::Warn,
+        "empty originals or translations, with --alignment",
+    ),
+    (
+        "invalid-alignment",
+        LintLevel::Warn,
+        "word alignments that can't be read or are past the words",
+    ),
+    (
+        "invisible-character",
+        LintLevel::Warn,
+        "zero width and other invisible characters",
+    ),
+    (
+        "known-words",
+        LintLevel::Warn,
+        "known words lists that can't be read",
+    ),
+    (
+        "length-ratio",
+        LintLevel::Warn,
+        "translations of unusual length, with --alignment",
+    ),
+    (
+        "repeated-translation",
+        LintLevel::Warn,
+        "one translation given to different originals, with --alignment",
+    ),
+    (
+        "sense-out-of-range",
+        LintLevel::Warn,
+        "senses past the meanings of the dictionary entry",
+    ),
+    (
+        "stale-translation",
+        LintLevel::Warn,
+        "translations marked stale",
+    ),
+    (
+        "trailing-whitespace",
+        LintLevel::Warn,
+        "spaces and tabs at the end of lines",
+    ),
+    (
+        "undefined-variable",
+        LintLevel::Warn,
+        "variables used but not defined",
+    ),
+    (
+        "unknown-template",
+        LintLevel::Warn,
+        "sentence templates that don't exist",
+    ),
+    (
+        "unknown-translation-label",
+        LintLevel::Warn,
+        "translation labels not in the translations attr",
+    ),
+    (
+        "unlabeled-translation",
+        LintLevel::Warn,
+        "translations without a language label in chapters with several languages",
+    ),
+    (
+        "untranslated",
+        LintLevel::Warn,
+        "sentences without a translation, when asked for",
+    ),
+];
+
+/// What becomes of the problems a rule finds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    /// Left out
+    Allow,
+    /// Reported as warnings
+    Warn,
+    /// Reported as errors, failing the command
+    Deny,
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "allow" => Ok(Self::Allow),
+            "warn" => Ok(Self::Warn),
+            "deny" => Ok(Self::Deny),
+            _ => Err(format!("Unknown lint level {s:?}, use allow, warn or deny")),
+        }
+    }
+}
+
+impl std::fmt::Display for LintLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.pad(match self {
+            Self::Allow => "allow",
+            Self::Warn => "warn",
+            Self::Deny => "deny",
+        })
+    }
+}
+
+/// Levels of the lint rules set by the project, the others keeping theirs
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Lints {
+    levels: IndexMap<String, LintLevel>,
+}
+
+impl Lints {
+    /// Levels from the `[lints]` table of a `transdoc.toml`, like
+    /// `unlabeled-translation = "deny"` or `warnings = "deny"`
+    #[cfg(feature = "cli")]
+    pub fn parse(src: &str) -> Result<Self, String> {
+        let doc: toml_edit::DocumentMut = src.parse().map_err(|e| format!("{e}"))?;
+        let mut lints = Self::default();
+        let Some(table) = doc.get("lints") else {
+            return Ok(lints);
+        };
+        let table = table
+            .as_table_like()
+            .ok_or("lints is not a table of rules and their levels")?;
+        for (rule, level) in table.iter() {
+            let level = level
+                .as_str()
+                .ok_or_else(|| format!("Level of lint {rule} is not a string like \"deny\""))?;
+            lints.set(rule, level.parse()?)?;
+        }
+        Ok(lints)
+    }
+
+    /// Levels of the configuration file, none if there is no such file
+    #[cfg(feature = "cli")]
+    pub fn load(file: &Path) -> Result<Self, String> {
+        if !file.is_file() {
+            return Ok(Self::default());
+        }
+        crate::text::read_text(file)
+            .and_then(|t| Self::parse(&t))
+            .map_err(|e| format!("{}: {e}", file.to_string_lossy()))
+    }
+
+    pub fn set(&mut self, rule: &str, level: LintLevel) -> Result<(), String> {
+        if rule != WARNINGS && !RULES.iter().any(|(r, _, _)| *r == rule) {
+            return Err(format!(
+                "Unknown lint {rule:?}, `transdoc lints` lists them"
+            ));
+        }
+        self.levels.insert(rule.to_string(), level);
+        Ok(())
+    }
+
+    /// Whether any rule, or `warnings`, is denied
+    pub fn denies(&self) -> bool {
+        self.levels.values().any(|l| *l == LintLevel::Deny)
+    }
+
+    /// Level of the rule, as configured or its default
+    pub fn level(&self, rule: &str) -> Option<LintLevel> {
+        self.levels.get(rule).copied().or_else(|| {
+            RULES
+                .iter()
+                .find(|(r, _, _)| *r == rule)
+                .map(|(_, l, _)| *l)
+        })
+    }
+
+    /// The problems at the levels of their rules: the ones allowed left
+    /// out, the denied ones made errors. The `warnings` level then applies
+    /// to every warning, so denying it fails on any problem
+    pub fn apply(&self, diags: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diags
+            .into_iter()
+            .filter_map(|mut d| {
+                match d.rule.and_then(|r| self.levels.get(r)) {
+                    Some(LintLevel::Allow) => return None,
+                    Some(LintLevel::Warn) => d.severity = Severity::Warning,
+                    Some(LintLevel::Deny) => d.severity = Severity::Error,
+                    None => (),
+                }
+                if d.severity == Severity::Warning {
+                    match self.levels.get(WARNINGS) {
+                        Some(LintLevel::Allow) => return None,
+                        Some(LintLevel::Deny) => d.severity = Severity::Error,
+                        _ => (),
+                    }
+                }
+                Some(d)
+            })
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "cli"))]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn diag(rule: Option<&'static str>, severity: Severity) -> Diagnostic {
+        Diagnostic {
+            severity,
+            rule,
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case("", vec![Some(Severity::Warning), Some(Severity::Error), Some(Severity::Warning)])]
+    #[case("[lints]\nunlabeled-translation = \"deny\"\n", vec![Some(Severity::Error), Some(Severity::Error), Some(Severity::Warning)])]
+    #[case("[lints]\nunlabeled-translation = \"allow\"\nduplicate-label = \"warn\"\n", vec![None, Some(Severity::Warning), Some(Severity::Warning)])]
+    #[case("[lints]\nwarnings = \"deny\"\n", vec![Some(Severity::Error), Some(Severity::Error), Some(Severity::Error)])]
+    #[case("[lints]\nwarnings = \"deny\"\nunlabeled-translation = \"allow\"\n", vec![None, Some(Severity::Error), Some(Severity::Error)])]
+    fn apply_test(#[case] config: &str, #[case] expected: Vec<Option<Severity>>) {
+        let lints = Lints::parse(config).unwrap();
+        let diags = vec![
+            diag(Some("unlabeled-translation"), Severity::Warning),
+            diag(Some("duplicate-label"), Severity::Error),
+            diag(None, Severity::Warning),
+        ];
+        let got: Vec<Option<Severity>> = diags
+            .into_iter()
+            .map(|d| lints.apply(vec![d]).first().map(|d| d.severity))
+            .collect();
+        assert_eq!(got, expected);
+    }
+
+    #[rstest]
+    #[case("[lints]\nunlabeled = \"deny\"\n")]
+    #[case("[lints]\nstale-translation = \"forbid\"\n")]
+    #[case("[lints]\nstale-translation = 1\n")]
+    #[case("lints = 3\n")]
+    #[case("[lints\n")]
+    fn parse_error_test(#[case] config: &str) {
+        assert!(Lints::parse(config).is_err());
+    }
+
+    #[test]
+    fn level_test() {
+        let lints = Lints::parse("[lints]\nstale-translation = \"deny\"\n").unwrap();
+        assert_eq!(lints.level("stale-translation"), Some(LintLevel::Deny));
+        assert_eq!(lints.level("duplicate-label"), Some(LintLevel::Deny));
+        assert_eq!(lints.level("trailing-whitespace"), Some(LintLevel::Warn));
+        assert_eq!(lints.level("nope"), None);
+    }
+}
@@ -0,0 +1,112 @@
+use crate::errors::{Diagnostic, Severity};
+use crate::syntax::{Chapter, Sentence};
+use std::time::Duration;
+
+/// Sentence attr with the time the sentence starts at in its recording
+pub const TIME_ATTR: &str = "time";
+
+/// Tag naming the program that wrote the lyrics
+pub const CREATOR_TAG: &str = "[re:transdoc]";
+
+/// `[mm:ss.xx]` time tag of LRC lyrics
+pub fn time_tag(time: Duration) -> String {
+    let centis = time.as_millis() / 10;
+    format!(
+        "[{:02}:{:02}.{:02}]",
+        centis / 6000,
+        centis / 100 % 60,
+        centis % 100
+    )
+}
+
+impl Chapter {
+    /// Timed lyrics of the sentences with a `time` attr, a line for each of
+    /// the languages at each time, the original's by its language, or just
+    /// the originals when none are given; with warnings about the
+    /// sentences left out
+    pub fn lrc(&self, languages: &[String]) -> (String, Vec<Diagnostic>) {
+        let mut lrc = format!("[ti:{}]\n{CREATOR_TAG}\n", one_line(&self.title));
+        let mut warnings = Vec::new();
+        let warn = |s: &Sentence, message: String| Diagnostic {
+            severity: Severity::Warning,
+            line: s.span.line,
+            col: s.span.col,
+            message,
+            ..Default::default()
+        };
+        for s in &self.sentences {
+            let Some(value) = s.orgattrs.get(TIME_ATTR) else {
+                warnings.push(warn(s, format!("@{} has no time, left out", s.label)));
+                continue;
+            };
+            let Some(time) = s.attr_duration(TIME_ATTR) else {
+                warnings.push(warn(
+                    s,
+                    format!("@{}: time = {value} is not a time, left out", s.label),
+                ));
+                continue;
+            };
+            let tag = time_tag(time);
+            if languages.is_empty() {
+                lrc.push_str(&format!("{tag}{}\n", one_line(&s.text())));
+            }
+            for lang in languages {
+                let text = if *lang == self.language {
+                    Some(s.text())
+                } else {
+                    s.translations
+                        .iter()
+                        .enumerate()
+                        .find(|(i, (k, _))| self.translation_language(*i, k) == *lang)
+                        .map(|(_, (_, t))| t.content.clone())
+                };
+                match text {
+                    Some(t) => lrc.push_str(&format!("{tag}{}\n", one_line(&t))),
+                    None => warnings.push(warn(
+                        s,
+                        format!("@{} has no translation to {lang}", s.label),
+                    )),
+                }
+            }
+        }
+        (lrc, warnings)
+    }
+}
+
+/// Text with its whitespace collapsed, LRC having one line for each time
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case(0.0, "[00:00.00]")]
+    #[case(62.5, "[01:02.50]")]
+    #[case(6005.129, "[100:05.12]")]
+    fn time_tag_test(#[case] secs: f64, #[case] expected: &str) {
+        assert_eq!(time_tag(Duration::from_secs_f64(secs)), expected);
+    }
+
+    const SONG: &str = "title = Lied\nlanguage = german\ntranlations = english\n\n@ 1\nGuten << Morgen = morning >>\ntime = 0:12.5\n---\nGood morning\n\n@ 2\nkein Takt\n---\nno beat\n\n@ 3\nGute Nacht\ntime = 1:03\n";
+
+    #[rstest]
+    #[case(&[], "[00:12.50]Guten Morgen\n[01:03.00]Gute Nacht\n", 1)]
+    #[case(
+        &["german", "english"],
+        "[00:12.50]Guten Morgen\n[00:12.50]Good morning\n[01:03.00]Gute Nacht\n",
+        2
+    )]
+    fn lrc_test(#[case] languages: &[&str], #[case] lines: &str, #[case] warnings: usize) {
+        let chap = Chapter::from_str(SONG).unwrap();
+        let languages: Vec<String> = languages.iter().map(|l| l.to_string()).collect();
+        let (lrc, w) = chap.lrc(&languages);
+        assert_eq!(lrc, format!("[ti:Lied]\n[re:transdoc]\n{lines}"));
+        assert_eq!(w.len(), warnings);
+        assert_eq!(w[0].line, 11);
+    }
+}
@@ -1,26 +1,3375 @@
-mod components;
-mod errors;
-mod parser;
-mod syntax;
-mod tokenizer;
-use std::str::FromStr;
-
-fn main() {
-    let args: Vec<String> = std::env::args().collect();
-    if let Some(f) = args.get(1) {
-        let contents = std::fs::read_to_string(f).unwrap();
-        match syntax::Chapter::from_str(&contents) {
-            Ok(mut chap) => {
-                if let Some(o) = args.get(2) {
-                    chap.process();
-                    chap.to_html(o).unwrap();
+use clap::{ArgAction, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use indexmap::IndexMap;
+use std::fs::File;
+use std::io::{IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::OnceLock;
+use tracing::{Level, debug, error, info, warn};
+use transdoc::attrs::{AttrEdit, AttrTarget};
+use transdoc::errors::{Diagnostic, ParseError, Severity};
+use transdoc::filter::FilterScript;
+use transdoc::graph::{GraphFormat, GraphKind};
+use transdoc::history::{self, History};
+use transdoc::hooks::{self, Hooks};
+use transdoc::html::{self, Edition, GlossFormat, GlossMode, HtmlStyle, RepeatGloss};
+#[cfg(feature = "import")]
+use transdoc::import;
+use transdoc::label::{Label, LabelRange};
+use transdoc::lints::{self, LintLevel, Lints};
+use transdoc::plugin::{CommandPlugin, Plugins};
+use transdoc::reorder::Position;
+#[cfg(feature = "tui")]
+use transdoc::review::Review;
+#[cfg(feature = "serve")]
+use transdoc::serve;
+use transdoc::snippet::SnippetFormat;
+use transdoc::stamp::Stamp;
+use transdoc::term::{TermGloss, TermOptions};
+use transdoc::tokenizer::DEFAULT_COMMENT;
+use transdoc::validate::{self, ValidateOptions};
+use transdoc::widget::{Bundle, WIDGET_JS, WIDGET_JS_FILE};
+use transdoc::{
+    agree, appendix, audio, checksum, diff, graph, merge, notes, output, rename, scaffold, schema,
+    site, stats, syntax, text, undo, words,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Generate translation documents from chapter files")]
+struct Cli {
+    /// How to print errors and warnings, gcc by default for check and human otherwise
+    #[arg(long, global = true, value_enum)]
+    error_format: Option<ErrorFormat>,
+    /// Only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Print more about what is being done, twice for even more
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+    /// Overwrite output files that weren't generated by transdoc
+    #[arg(long, global = true)]
+    force: bool,
+    /// Keep the files edited in place as they were in the undo journal,
+    /// for `undo` to restore them
+    #[arg(long, global = true)]
+    backup: bool,
+    /// Print a diff of the changes to the files that are edited in place
+    /// instead of writing them
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Memory map chapter files instead of reading them, for very large files
+    #[arg(long, global = true)]
+    mmap: bool,
+    /// Normalize chapters to Unicode NFC before parsing them
+    #[arg(long, global = true)]
+    nfc: bool,
+    /// Encoding of the input files: utf-8, auto to guess it, or a name like shift_jis, gb18030 or latin1
+    #[arg(long, global = true, default_value = "utf-8")]
+    encoding: text::Encoding,
+    /// Token starting comments in chapter files, at the start of a line or
+    /// after whitespace when followed by whitespace
+    #[arg(long, global = true, default_value = "#", value_parser = comment_token)]
+    comment: String,
+    /// Report the problems of a lint rule as errors, `warnings` for all of
+    /// them; overrides the `[lints]` of transdoc.toml
+    #[arg(long, global = true, value_name = "LINT")]
+    deny: Vec<String>,
+    /// Report the problems of a lint rule as warnings
+    #[arg(long, global = true, value_name = "LINT")]
+    warn: Vec<String>,
+    /// Leave out the problems of a lint rule
+    #[arg(long, global = true, value_name = "LINT")]
+    allow: Vec<String>,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+enum ErrorFormat {
+    /// Multi line messages showing the source line
+    #[default]
+    Human,
+    /// One `file:line:col: severity: message` line each
+    Gcc,
+}
+
+static ERROR_FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+static FORCE: OnceLock<bool> = OnceLock::new();
+static DRY_RUN: OnceLock<bool> = OnceLock::new();
+static UNDO: OnceLock<undo::Run> = OnceLock::new();
+static MMAP: OnceLock<bool> = OnceLock::new();
+static NFC: OnceLock<bool> = OnceLock::new();
+static COMMENT: OnceLock<String> = OnceLock::new();
+static LINTS: OnceLock<Lints> = OnceLock::new();
+
+/// The problems at the levels the project and the options set
+fn lint(diags: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    match LINTS.get() {
+        Some(l) => l.apply(diags),
+        None => diags,
+    }
+}
+
+/// Levels of the lint rules from transdoc.toml and the options
+fn load_lints(cli: &Cli) -> Result<Lints, String> {
+    let mut lints = Lints::load(Path::new(lints::CONFIG_FILE))?;
+    let levels = [
+        (&cli.allow, LintLevel::Allow),
+        (&cli.warn, LintLevel::Warn),
+        (&cli.deny, LintLevel::Deny),
+    ];
+    for (rules, level) in levels {
+        for rule in rules {
+            lints.set(rule, level)?;
+        }
+    }
+    Ok(lints)
+}
+
+fn parse(src: &str) -> Result<syntax::Chapter, ParseError> {
+    let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+    syntax::Chapter::parse_with(src, comment)
+}
+
+/// `parse` with the files the attrs name relative to the chapter file
+fn parse_file(src: &str, file: &Path) -> Result<syntax::Chapter, ParseError> {
+    let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+    syntax::Chapter::parse_in(src, comment, chapter_dir(file))
+}
+
+/// Directory of the chapter file, which the files its attrs name are
+/// relative to
+fn chapter_dir(file: &Path) -> &Path {
+    match file.parent() {
+        Some(dir) if !is_stdio(file) => dir,
+        _ => Path::new(""),
+    }
+}
+
+/// Prints the diagnostic to stderr, warnings are left out with `--quiet`
+fn report(diag: &Diagnostic) {
+    if diag.severity == Severity::Warning && !tracing::enabled!(Level::WARN) {
+        return;
+    }
+    match ERROR_FORMAT.get().copied().unwrap_or_default() {
+        ErrorFormat::Human => eprintln!("{}", diag.human()),
+        ErrorFormat::Gcc => eprintln!("{}", diag.gcc()),
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Render a chapter file to HTML
+    Build(BuildArgs),
+    /// Serve a live preview of a chapter with the editor sync protocol
+    #[cfg(feature = "serve")]
+    Serve {
+        /// Chapter file
+        input: PathBuf,
+        /// Address to listen at
+        #[arg(short, long, default_value = "127.0.0.1:8000")]
+        addr: String,
+    },
+    /// Validate chapter files, printing one `file:line:col: error: message` line per problem
+    Check {
+        /// Chapter files, glob patterns or directories
+        files: Vec<PathBuf>,
+        /// Also read file names from stdin, one per line
+        #[arg(long)]
+        stdin: bool,
+        /// Mark the translations of changed originals stale and store the
+        /// hash of new ones, rewriting only those sentences in the files
+        #[arg(long)]
+        mark_stale: bool,
+        /// Also warn about sentence pairs an aligner likely got wrong: empty
+        /// sides, unusual length ratios and repeated translations
+        #[arg(long)]
+        alignment: bool,
+    },
+    /// Check a project for anything that keeps it from building as
+    /// intended, like unreadable chapters, dictionaries or missing files,
+    /// and summarize its health
+    Doctor {
+        /// Book manifest whose chapters to check along with the files
+        #[arg(short, long)]
+        project: Option<PathBuf>,
+        /// Chapter files, glob patterns or directories, the current
+        /// directory if neither they nor a project are given
+        files: Vec<PathBuf>,
+    },
+    /// Format chapter files: line ends, and with the options whitespace,
+    /// translation labels and sentence order
+    Fmt {
+        /// Chapter files, glob patterns or directories
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        /// Line end to use: lf or crlf
+        #[arg(long, default_value = "lf")]
+        line_ends: text::LineEnd,
+        /// Only list the files that would change, failing if there are any
+        #[arg(long)]
+        check: bool,
+        /// Also remove trailing whitespace and invisible characters, and
+        /// make non-breaking spaces plain ones
+        #[arg(long)]
+        fix: bool,
+        /// Write the language of translations only labelled by their position
+        /// in the translations attr after their separators
+        #[arg(long)]
+        label_translations: bool,
+        /// Put the sentences in the order of their labels, comparing their
+        /// numbers so `2` comes before `10` and `3.9` before `3.12`
+        #[arg(long)]
+        sort_labels: bool,
+    },
+    /// Convert material from other tools into chapters and dictionaries
+    #[cfg(feature = "import")]
+    #[command(subcommand)]
+    Import(ImportSource),
+    /// Start a new file from a skeleton documenting the syntax
+    #[command(subcommand)]
+    New(NewFile),
+    /// Move a sentence, with the comments right above it, to another place
+    /// in the file
+    Move {
+        /// Label of the sentence, like `@5` or `5`
+        label: String,
+        #[command(flatten)]
+        to: PositionArgs,
+        /// Chapter file
+        file: PathBuf,
+    },
+    /// Add a sentence to a chapter file, at the end by default
+    Insert {
+        #[command(flatten)]
+        to: PositionArgs,
+        /// Label of the new sentence, the one after the largest number by default
+        #[arg(long)]
+        label: Option<String>,
+        /// Original of the sentence, and its translations after `---` lines
+        #[arg(long)]
+        text: String,
+        /// Chapter file
+        file: PathBuf,
+    },
+    /// Print one sentence for pasting into chats, forums or issues
+    Show {
+        /// Chapter file
+        input: PathBuf,
+        /// Label of the sentence, like `@42` or `42`
+        label: String,
+        /// md, html or json
+        #[arg(long, default_value = "md")]
+        format: SnippetFormat,
+        /// Only the translation to this language, may be given more than once
+        #[arg(long)]
+        lang: Vec<String>,
+    },
+    /// Print a chapter for reading in the terminal, in color when it is one
+    Read {
+        /// Chapter file
+        input: PathBuf,
+        /// Where to print the meanings of glossed words: below the
+        /// original, inline or none
+        #[arg(long, default_value = "below")]
+        gloss: TermGloss,
+        /// Only the translations to this language, may be given more than once
+        #[arg(long)]
+        lang: Vec<String>,
+        /// Leave the translations out
+        #[arg(long)]
+        no_translations: bool,
+    },
+    /// Step through the sentences in the terminal, editing the translations
+    /// to a language and their status
+    Review {
+        /// Chapter file
+        file: PathBuf,
+        /// Language of the translations to review
+        #[arg(long)]
+        lang: String,
+    },
+    /// Set, remove or rename attrs of sentences or translations in place
+    #[command(subcommand)]
+    Attr(AttrCommand),
+    /// Compare two people's translations of a chapter sentence by sentence
+    Agree {
+        /// Chapter file with the first translations
+        first: PathBuf,
+        /// Chapter file with the second translations
+        second: PathBuf,
+        /// Language of the translations to compare
+        #[arg(long)]
+        lang: String,
+        /// Write the first chapter with the second translations added as
+        /// alternatives to this file
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Score translations against a reference one with chrF and BLEU
+    Eval {
+        /// Chapter file
+        input: PathBuf,
+        /// Language of the translations to score
+        #[arg(long)]
+        lang: String,
+        /// Reference translation, one line for each sentence in order
+        #[arg(short, long)]
+        reference: PathBuf,
+    },
+    /// Bookmark or annotate a sentence in the notes file next to the chapter
+    Note {
+        /// Chapter file
+        input: PathBuf,
+        /// Label of the sentence
+        label: String,
+        /// Text to add to the sentence's note
+        text: Option<String>,
+        /// Bookmark the sentence
+        #[arg(short, long)]
+        bookmark: bool,
+    },
+    /// Collect the translator's notes of the chapters into one Markdown
+    /// document: note attrs, comments and reader's notes by sentence
+    Notes {
+        /// Book manifest whose chapters to collect from, in its order
+        #[arg(short, long, value_name = "MANIFEST")]
+        project: Option<PathBuf>,
+        /// Attrs holding notes, `note` and `comment` by default
+        #[arg(short, long, value_delimiter = ',')]
+        key: Vec<String>,
+        /// Leave out the comments of the source
+        #[arg(long)]
+        no_comments: bool,
+        /// Title of the document, the book's by default
+        #[arg(long)]
+        title: Option<String>,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Chapter files, glob patterns or directories
+        #[arg(required_unless_present = "project")]
+        files: Vec<PathBuf>,
+    },
+    /// Keep earlier translations in the history file next to the chapter
+    /// and show what changed between them
+    #[command(subcommand)]
+    History(HistoryCommand),
+    /// List the words of the originals that are not glossed, in the dictionary or known
+    UnknownWords {
+        /// Chapter file
+        input: PathBuf,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Score chapters by how many of their words are frequent ones
+    Difficulty {
+        /// Frequency list, one word per line with the most frequent first
+        #[arg(short, long)]
+        frequency: PathBuf,
+        /// Size of the frequency band counted as easy words
+        #[arg(short, long, default_value_t = 2000)]
+        top: usize,
+        /// List the chapters from the easiest to the hardest
+        #[arg(short, long)]
+        sort: bool,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Chapter files, glob patterns or directories
+        files: Vec<PathBuf>,
+    },
+    /// Show how much of the text the reader knows at each level of the
+    /// known words, when they are a directory of word lists by level, or
+    /// how much of it is translated to a language
+    Coverage {
+        /// Show the sentences translated to this language, not counting
+        /// the ones skipped on purpose
+        #[arg(long)]
+        lang: Option<String>,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Chapter files, glob patterns or directories
+        files: Vec<PathBuf>,
+    },
+    /// Collect the recordings given by `audio` attrs into a playlist of
+    /// each chapter and lists ffmpeg joins into one file per language
+    Audio {
+        /// Only the recordings of these languages
+        #[arg(short, long, value_delimiter = ',')]
+        lang: Vec<String>,
+        /// Title of the joined files, the name of the output directory by default
+        #[arg(long)]
+        title: Option<String>,
+        /// Directory to write the playlists and lists to
+        #[arg(short, long)]
+        output: PathBuf,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Chapter files, glob patterns or directories
+        files: Vec<PathBuf>,
+    },
+    /// Write a graph of the words of the chapters, joined to the chapters
+    /// using them or to the words used in the same sentences
+    Graph {
+        /// chapters or cooccurrence
+        #[arg(short, long, default_value = "chapters")]
+        kind: GraphKind,
+        /// dot or graphml
+        #[arg(long, default_value = "dot")]
+        format: GraphFormat,
+        /// Leave out the words used fewer times
+        #[arg(short, long, default_value_t = 2)]
+        min_count: usize,
+        /// Frequency list, one word per line with the most frequent first
+        #[arg(short, long, requires = "skip_top")]
+        frequency: Option<PathBuf>,
+        /// Leave out the words within this many of the most frequent ones
+        #[arg(long, default_value_t = 0)]
+        skip_top: usize,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Chapter files, glob patterns or directories
+        files: Vec<PathBuf>,
+    },
+    /// Pick a few sentences with examples of each unknown word into a new chapter
+    Sample {
+        /// Chapter file
+        input: PathBuf,
+        /// Number of example sentences for each word
+        #[arg(short, long, default_value_t = 1)]
+        per_unknown_word: usize,
+        /// Word list to find examples of instead of the unknown words
+        #[arg(short, long)]
+        words: Option<PathBuf>,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Collect the sentences with any of the tags into a new chapter
+    Extract {
+        /// Tag to look for, may be given more than once
+        #[arg(short, long, required_unless_present_any = ["range", "from", "to", "labels"])]
+        tag: Vec<String>,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Chapter files, glob patterns or directories
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Transform a chapter with the operations of a filter script
+    Filter {
+        /// Filter script, one operation on each line
+        script: PathBuf,
+        /// Chapter file
+        input: PathBuf,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print a chapter as JSON in the versioned schema
+    Json {
+        /// Chapter file
+        input: PathBuf,
+        /// Apply the dictionary and glosses first, like building does
+        #[arg(short, long)]
+        processed: bool,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Write timed lyrics (LRC) of the sentences with a `time` attr, for
+    /// music players to show along with a song
+    Lrc {
+        /// Chapter file
+        input: PathBuf,
+        /// Languages to show, the original's by its language; only the
+        /// originals by default
+        #[arg(short, long, value_delimiter = ',')]
+        lang: Vec<String>,
+        #[command(flatten)]
+        select: SelectArgs,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print the checksum of each sentence, or record them next to the
+    /// chapter to list the sentences changed since
+    Checksum {
+        /// Chapter file
+        input: PathBuf,
+        /// Record them in `chapter.checksums` next to the chapter
+        #[arg(short, long)]
+        write: bool,
+        /// Only list the sentences new or changed since they were recorded
+        #[arg(short, long, conflicts_with = "write")]
+        changed: bool,
+    },
+    /// Print the JSON schema of the chapter JSON
+    Schema,
+    /// List the lint rules with their levels, as set by transdoc.toml and
+    /// the options
+    Lints,
+    /// Print a citation of a sentence, like `ch3:@42 "Hola mundo" (Title)`
+    Cite {
+        /// Chapter file
+        input: PathBuf,
+        /// Label of the sentence
+        label: String,
+        /// Only the reference, like `ch3:@42`
+        #[arg(short, long)]
+        short: bool,
+    },
+    /// Export a chapter as JSON with a script rendering it into other pages
+    Embed {
+        /// Chapter file
+        input: PathBuf,
+        /// Directory to write the JSON and the script to
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Name of the JSON without its extension, the name of the chapter
+        /// file by default; needed when the chapter is read from stdin
+        #[arg(long)]
+        name: Option<String>,
+        #[command(flatten)]
+        select: SelectArgs,
+    },
+    /// Three way merge of chapter files, for use as a git merge driver
+    MergeDriver {
+        /// Common ancestor version (%O)
+        base: PathBuf,
+        /// Our version (%A)
+        ours: PathBuf,
+        /// Their version (%B)
+        theirs: PathBuf,
+        /// Output file, usually the same as ours
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Build the chapters of a book manifest and a contents page
+    Book {
+        /// JSON manifest with the `chapters` in reading order, an optional
+        /// `title`, `inherit_glosses`, the `url` to write a feed for, the
+        /// `outputs` to build editions into and the `difficulty` grading
+        manifest: PathBuf,
+        /// Output directory
+        #[arg(short, long)]
+        output: PathBuf,
+        #[command(flatten)]
+        render: RenderArgs,
+    },
+    /// Rename a dictionary headword in the dictionaries and chapters of a
+    /// book, reporting the occurrences that aren't renamed
+    RenameTerm {
+        /// Headword to rename
+        old: String,
+        /// New headword
+        new: String,
+        /// Book manifest listing the chapters
+        #[arg(long, value_name = "MANIFEST")]
+        project: PathBuf,
+    },
+    /// Restore the files edited in place by the last command run with
+    /// `--backup`
+    Undo,
+    /// Print a shell completion script
+    Completions { shell: clap_complete::Shell },
+    /// Print the man page
+    Man {
+        /// Write pages for transdoc and each subcommand into this directory instead
+        #[arg(long, value_name = "DIR")]
+        dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+struct BuildArgs {
+    /// Chapter file, `-` for stdin, or a directory of `.chapter` and `.td` files
+    input: PathBuf,
+    /// Output HTML file, printed if not given or `-`, or the output directory
+    #[arg(value_name = "OUTPUT")]
+    output_file: Option<PathBuf>,
+    /// Output file or directory, same as the positional argument
+    #[arg(short, long, conflicts_with = "output_file")]
+    output: Option<PathBuf>,
+    #[command(flatten)]
+    render: RenderArgs,
+    /// Add data-src="line:col" attributes with the source position to sentences
+    #[arg(long)]
+    data_src: bool,
+    /// Show the sentence labels before the originals, clicking one copies
+    /// its citation; the chapter's `numbered` attr takes precedence
+    #[arg(long)]
+    numbered: bool,
+    /// Write the source positions of the sentences as JSON to this file
+    #[arg(long, value_name = "FILE")]
+    source_map: Option<PathBuf>,
+    /// Fail unless every sentence has a translation to this language and
+    /// all its words are found in the dictionary
+    #[arg(long, value_name = "LANGUAGE")]
+    require_complete: Option<String>,
+    /// Fail when the pages have images without alt text, links without
+    /// text or no language
+    #[arg(long)]
+    a11y_check: bool,
+    /// Fail on the problems `check` warns about, like denying `warnings`,
+    /// and when the dictionary or known words file can't be read or has
+    /// problems, or attrs have bad values
+    #[arg(long, alias = "strict-dict")]
+    strict: bool,
+    /// Indent the HTML with block elements on their own lines
+    #[arg(long, conflicts_with = "minify")]
+    pretty: bool,
+    /// Remove the whitespace between elements and in the CSS
+    #[arg(long)]
+    minify: bool,
+    /// How words glossed before on the page are shown: full, marker or
+    /// none; the chapter's `repeat_gloss` attr takes precedence
+    #[arg(long, default_value = "full")]
+    repeat_gloss: RepeatGloss,
+    /// What the pages have: full, or reader for only the originals with
+    /// their glosses and the dictionary, without the translations
+    #[arg(long, default_value = "full")]
+    edition: Edition,
+    /// Encrypt the translations with the passphrase in this file, `-` to
+    /// read it from stdin; the page asks for it to show them
+    #[cfg(feature = "encrypt")]
+    #[arg(long, value_name = "PASSPHRASE_FILE")]
+    encrypt: Option<PathBuf>,
+    /// Identifier of the project, like a repository or group name, to
+    /// stamp the pages with along with the hashes of their sources
+    #[arg(long, value_name = "ID")]
+    project_id: Option<String>,
+    /// Leave the build time out of the stamp, so the same sources give
+    /// the same pages
+    #[arg(long)]
+    reproducible: bool,
+    #[command(flatten)]
+    select: SelectArgs,
+}
+
+/// How chapters are turned into pages, for `build` and `book`
+#[derive(Args)]
+struct RenderArgs {
+    /// Render `{{name:...}}` fragments with an external command, as name=command
+    #[arg(long, value_name = "NAME=COMMAND")]
+    plugin: Vec<String>,
+    /// Command transforming the chapter JSON before the dictionary is applied
+    #[arg(long, value_name = "COMMAND")]
+    pre_filter: Vec<String>,
+    /// Command transforming the chapter JSON after the dictionary is applied
+    #[arg(long, value_name = "COMMAND")]
+    post_filter: Vec<String>,
+    /// How meanings of glossed words are shown: tooltip, small, bracket or
+    /// hidden; the chapter's `gloss` attr takes precedence
+    #[arg(long, default_value = "tooltip")]
+    gloss: GlossMode,
+    /// What glosses show and in what order, with the fields word, meanings,
+    /// meaning and source in braces like `{meaning} ({source})`; the
+    /// chapter's `gloss_format` attr takes precedence
+    #[arg(long, value_name = "FORMAT")]
+    gloss_format: Option<GlossFormat>,
+}
+
+/// Part of the chapters to work on, all of it when none is given
+#[derive(Args)]
+struct SelectArgs {
+    /// Labels to take like `@10..@25`, `@10..` or `@12`, compared by their
+    /// numbers; may be given more than once
+    #[arg(short, long)]
+    range: Vec<LabelRange>,
+    /// Only the sentences from this label on
+    #[arg(long, value_name = "LABEL")]
+    from: Option<Label>,
+    /// Only the sentences up to this label
+    #[arg(long, value_name = "LABEL")]
+    to: Option<Label>,
+    /// Only the sentences with these labels, separated by commas
+    #[arg(long, value_name = "LABELS", value_delimiter = ',')]
+    labels: Vec<Label>,
+}
+
+impl SelectArgs {
+    fn ranges(&self) -> Vec<LabelRange> {
+        let mut ranges = self.range.clone();
+        if self.from.is_some() || self.to.is_some() {
+            ranges.push(LabelRange {
+                start: self.from.clone(),
+                end: self.to.clone(),
+            });
+        }
+        ranges.extend(self.labels.iter().map(|l| LabelRange {
+            start: Some(l.clone()),
+            end: Some(l.clone()),
+        }));
+        ranges
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ranges().is_empty()
+    }
+
+    /// Keeps the selected sentences of the chapter, warning when there
+    /// are none
+    fn apply(&self, chap: &mut syntax::Chapter, file: &Path) {
+        let ranges = self.ranges();
+        chap.select(&ranges);
+        if !ranges.is_empty() && chap.sentences.is_empty() {
+            warn!(
+                "{}: no sentences with the selected labels",
+                display_name(file)
+            );
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum NewFile {
+    /// Chapter with its attrs and an example sentence
+    Chapter {
+        #[arg(long, default_value = "Unnamed Chapter")]
+        title: String,
+        /// Language of the originals
+        #[arg(long, default_value = "english")]
+        lang: String,
+        /// Languages of the translations
+        #[arg(long, value_delimiter = ',')]
+        tl: Vec<String>,
+        /// Directory with a chapter.chapter template to use instead of the
+        /// builtin one
+        #[arg(long, value_name = "DIR")]
+        templates: Option<PathBuf>,
+        /// Output file, printed if not given
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum AttrCommand {
+    /// Set an attr, adding it where it is missing
+    Set {
+        #[command(flatten)]
+        target: AttrTargetArgs,
+        #[arg(long)]
+        key: String,
+        #[arg(long)]
+        value: String,
+    },
+    /// Remove an attr
+    Rm {
+        #[command(flatten)]
+        target: AttrTargetArgs,
+        #[arg(long)]
+        key: String,
+    },
+    /// Rename an attr, keeping its value and place
+    Rename {
+        #[command(flatten)]
+        target: AttrTargetArgs,
+        #[arg(long)]
+        key: String,
+        /// New name of the attr
+        #[arg(long)]
+        to: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum HistoryCommand {
+    /// Add the translations that changed since they were last recorded
+    Record {
+        /// Chapter files, glob patterns or directories
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print word diffs between the revisions of the translations, in
+    /// color when printing to a terminal
+    Show {
+        /// Chapter file
+        input: PathBuf,
+        /// Only the sentence with this label
+        #[arg(long)]
+        label: Option<String>,
+        /// Only the translations to this language
+        #[arg(long)]
+        lang: Option<String>,
+    },
+}
+
+#[derive(Args)]
+#[group(multiple = false)]
+struct PositionArgs {
+    /// Put it after the sentence with this label
+    #[arg(long, value_name = "LABEL")]
+    after: Option<String>,
+    /// Put it before the sentence with this label
+    #[arg(long, value_name = "LABEL")]
+    before: Option<String>,
+}
+
+impl PositionArgs {
+    fn position(self) -> Position {
+        let label = |l: String| l.trim_start_matches('@').trim().to_string();
+        match (self.after, self.before) {
+            (Some(l), _) => Position::After(label(l)),
+            (_, Some(l)) => Position::Before(label(l)),
+            _ => Position::End,
+        }
+    }
+}
+
+#[derive(Args)]
+struct AttrTargetArgs {
+    /// Edit the attrs of the translations to this language instead of the
+    /// sentences'
+    #[arg(long)]
+    lang: Option<String>,
+    /// Only the sentence with this label, may be given more than once
+    #[arg(long)]
+    label: Vec<String>,
+    /// Only the sentences with this tag, may be given more than once
+    #[arg(long)]
+    tag: Vec<String>,
+    /// Chapter files, glob patterns or directories
+    #[arg(required = true)]
+    files: Vec<PathBuf>,
+}
+
+#[cfg(feature = "import")]
+#[derive(Subcommand)]
+enum ImportSource {
+    /// LingQ lesson (.json) or vocabulary export (.csv)
+    Lingq {
+        /// Exported file
+        file: PathBuf,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// LWT text (.txt) or term export (.tsv, .csv)
+    Lwt {
+        /// Exported file
+        file: PathBuf,
+        /// Title of the chapter, the file name by default
+        #[arg(short, long)]
+        title: Option<String>,
+        /// Language of the text
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Kindle vocabulary builder vocab.db or its CSV export, into a known words list
+    Kindle {
+        /// vocab.db or exported .csv file
+        file: PathBuf,
+        /// Only words of this language, as Kindle names it (e.g. en, ja)
+        #[arg(short, long)]
+        language: Option<String>,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Tatoeba sentences.tsv and links.tsv exports
+    Tatoeba {
+        /// Languages as ISO 639-3 codes, e.g. jpn-eng
+        #[arg(short, long)]
+        pair: String,
+        /// sentences.tsv file
+        sentences: PathBuf,
+        /// links.tsv file
+        links: PathBuf,
+        /// Title of the chapter
+        #[arg(short, long)]
+        title: Option<String>,
+        /// Maximum number of sentence pairs
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// OPUS corpus in Moses format, one sentence per line in each file
+    Opus {
+        /// Languages of the files, e.g. ja-en
+        #[arg(short, long)]
+        pair: String,
+        /// File in the original language
+        original: PathBuf,
+        /// Line aligned file in the translation language
+        translation: PathBuf,
+        /// Title of the chapter, the file name by default
+        #[arg(short, long)]
+        title: Option<String>,
+        /// Maximum number of sentence pairs
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+        /// Output file, printed if not given
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Logs go to stderr so the documents printed to stdout can be piped
+fn init_logging(quiet: bool, verbose: u8) {
+    let level = match (quiet, verbose) {
+        (true, _) => Level::ERROR,
+        (false, 0) => Level::INFO,
+        (false, 1) => Level::DEBUG,
+        (false, _) => Level::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .with_max_level(level)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// Comment token of `--comment`, which has to be something to match
+fn comment_token(s: &str) -> Result<String, String> {
+    if s.trim().is_empty() {
+        Err("the comment token can't be empty or whitespace".to_string())
+    } else if s.contains(char::is_whitespace) {
+        Err(format!("the comment token {s:?} can't contain whitespace"))
+    } else {
+        Ok(s.to_string())
+    }
+}
+
+/// `-` in place of a file name is stdin or stdout
+fn is_stdio(file: &Path) -> bool {
+    file.as_os_str() == "-"
+}
+
+/// Writes the file unless that would replace one transdoc didn't generate
+fn write_file(file: &Path, contents: &str) -> Result<(), String> {
+    let fname = file.to_string_lossy();
+    let force = FORCE.get().copied().unwrap_or_default();
+    if !force && file.exists() && !output::is_generated(file) {
+        return Err(format!(
+            "{fname}: not generated by transdoc, use --force to overwrite it"
+        ));
+    }
+    output::write_atomic(file, contents.as_bytes()).map_err(|e| format!("{fname}: {e}"))
+}
+
+/// Writes the edited source over the file in the encoding it was read in,
+/// keeping the old one in the undo journal with `--backup`, or prints the
+/// diff of the edit with `--dry-run`, giving whether the file was written
+fn rewrite(file: &Path, src: &str, edited: &str) -> std::io::Result<bool> {
+    if !DRY_RUN.get().copied().unwrap_or_default() {
+        let original = if text::encoding() == text::Encoding::Utf8 {
+            Vec::new()
+        } else {
+            std::fs::read(file)?
+        };
+        let bytes = text::encoding()
+            .encode(edited, &original)
+            .unwrap_or_else(|e| {
+                warn!("{}: {e}, writing it as UTF-8", display_name(file));
+                edited.as_bytes().into()
+            });
+        if let Some(run) = UNDO.get() {
+            run.backup(file, &bytes)?;
+        }
+        return output::write_atomic(file, &bytes).map(|_| true);
+    }
+    let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+    print!("{}", diff::unified(src, edited, &display_name(file), color));
+    Ok(false)
+}
+
+fn write_output(output: Option<PathBuf>, contents: &str) -> ExitCode {
+    match output.filter(|o| !is_stdio(o)) {
+        Some(o) => match write_file(&o, contents) {
+            Ok(_) => {
+                info!("Wrote {}", o.to_string_lossy());
+                ExitCode::SUCCESS
+            }
+            Err(e) => {
+                error!("{e}");
+                ExitCode::FAILURE
+            }
+        },
+        None => {
+            print!("{contents}");
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn display_name(file: &Path) -> String {
+    if is_stdio(file) {
+        "<stdin>".into()
+    } else {
+        file.to_string_lossy().to_string()
+    }
+}
+
+/// Reads and parses a chapter file
+fn parse_chapter(file: &Path) -> Result<syntax::Chapter, Diagnostic> {
+    let fname = display_name(file);
+    with_input(file, |src| parse_file(src, file))
+        .map_err(|e| Diagnostic::file(Severity::Error, &fname, e))?
+        .map_err(|e| e.diagnostic(Some(&fname)))
+}
+
+/// Reads and parses a chapter file, reporting the warnings from loading it
+fn read_chapter(file: &Path) -> Result<syntax::Chapter, Diagnostic> {
+    let mut chap = parse_chapter(file)?;
+    chap.diagnostics = lint(std::mem::take(&mut chap.diagnostics));
+    for d in &mut chap.diagnostics {
+        d.file.get_or_insert_with(|| display_name(file));
+        report(d);
+    }
+    let sidecar = notes::sidecar(file);
+    if !is_stdio(file) && sidecar.is_file() {
+        let fname = display_name(&sidecar);
+        match read_file(&sidecar).and_then(|t| notes::parse(&t)) {
+            Ok(n) => chap.notes = n,
+            Err(e) => report(&Diagnostic::file(Severity::Warning, &fname, e)),
+        }
+        for label in chap.orphan_notes() {
+            warn!("{fname}: note of sentence @{label} that isn't in the chapter");
+        }
+    }
+    Ok(chap)
+}
+
+/// Hooks and the renderer settings of the filter, plugin and gloss options
+fn render_options(args: &RenderArgs) -> Result<(Hooks, syntax::HtmlOptions), String> {
+    let mut hooks = Hooks::default();
+    args.pre_filter
+        .iter()
+        .filter_map(|c| hooks::filter_command(c))
+        .for_each(|h| hooks.pre(h));
+    args.post_filter
+        .iter()
+        .filter_map(|c| hooks::filter_command(c))
+        .for_each(|h| hooks.post(h));
+    let mut opts = syntax::HtmlOptions {
+        gloss: args.gloss,
+        gloss_format: args.gloss_format.clone().unwrap_or_default(),
+        ..Default::default()
+    };
+    for p in &args.plugin {
+        match p
+            .split_once('=')
+            .and_then(|(n, c)| Some((n, CommandPlugin::new(c)?)))
+        {
+            Some((name, cmd)) => {
+                debug!("Plugin {:?} runs {cmd:?}", name.trim());
+                opts.plugins.register(name.trim(), cmd)
+            }
+            None => return Err(format!("Plugin should be given as name=command, got {p:?}")),
+        }
+    }
+    Ok((hooks, opts))
+}
+
+fn build_options(args: &BuildArgs) -> Result<(Hooks, syntax::HtmlOptions), String> {
+    let (hooks, mut opts) = render_options(&args.render)?;
+    opts.data_src = args.data_src;
+    opts.numbered = args.numbered;
+    opts.style = if args.pretty {
+        HtmlStyle::Pretty
+    } else if args.minify {
+        HtmlStyle::Minify
+    } else {
+        HtmlStyle::Raw
+    };
+    opts.repeat_gloss = args.repeat_gloss;
+    opts.edition = args.edition;
+    #[cfg(feature = "encrypt")]
+    if let Some(file) = &args.encrypt {
+        let pass = read_file(file)?;
+        // the line end of the file isn't part of it
+        let pass = pass.trim_end_matches(['\r', '\n']);
+        opts.seal = Some(transdoc::encrypt::Sealer::new(pass)?);
+    }
+    Ok((hooks, opts))
+}
+
+fn render(
+    mut chap: syntax::Chapter,
+    hooks: &Hooks,
+    opts: &syntax::HtmlOptions,
+    require_complete: Option<&str>,
+    a11y_check: bool,
+) -> Result<String, String> {
+    chap.process_with(hooks)?;
+    if let Some(lang) = require_complete {
+        let incomplete = chap.incomplete(lang);
+        if !incomplete.is_empty() {
+            return Err(format!(
+                "Not complete in {lang}, missing translations or dictionary words in sentences {}",
+                incomplete.join(", ")
+            ));
+        }
+    }
+    let mut html = Vec::new();
+    chap.write_html(&mut html, opts)
+        .expect("writing to memory doesn't fail");
+    let html = String::from_utf8_lossy(&html).to_string();
+    if a11y_check {
+        let problems = html::a11y_check(&html);
+        if !problems.is_empty() {
+            return Err(format!(
+                "Accessibility check failed: {}",
+                problems.join("; ")
+            ));
+        }
+    }
+    Ok(html)
+}
+
+/// Stamps the page with the version of transdoc, the time unless
+/// `--reproducible` and the hashes of the files it was built from
+fn stamp_html(args: &BuildArgs, html: &str, inputs: &[PathBuf]) -> Result<String, String> {
+    let mut stamp = Stamp::new(args.project_id.clone(), args.reproducible);
+    for file in inputs.iter().filter(|f| !is_stdio(f) && f.is_file()) {
+        stamp.add_source(file)?;
+    }
+    let marker = output::GENERATED_MARKER;
+    Ok(html.replacen(marker, &format!("{marker}\n{}", stamp.comment()), 1))
+}
+
+fn build(mut args: BuildArgs) -> ExitCode {
+    args.output = args.output.take().or(args.output_file.take());
+    let (hooks, opts) = match build_options(&args) {
+        Ok(o) => o,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if args.input.is_dir() {
+        return build_dir(&args, &hooks, &opts);
+    }
+    match read_chapter(&args.input) {
+        Ok(mut chap) => {
+            args.select.apply(&mut chap, &args.input);
+            if let Err(e) = strict_checks(&args, &args.input, &chap) {
+                error!("{e}");
+                return ExitCode::FAILURE;
+            }
+            if let Some(m) = &args.source_map {
+                let map = syntax::SourceMap {
+                    sentences: chap.source_map(),
+                };
+                let json = schema::versioned_json(&map, true);
+                if let Err(e) = write_file(m, &json) {
+                    error!("{e}");
+                    return ExitCode::FAILURE;
+                }
+                info!("Wrote source map {}", m.to_string_lossy());
+            }
+            let mut inputs = vec![args.input.clone()];
+            inputs.extend(site::chapter_inputs(&chap, chapter_dir(&args.input)));
+            match render(
+                chap,
+                &hooks,
+                &opts,
+                args.require_complete.as_deref(),
+                args.a11y_check,
+            )
+            .and_then(|html| stamp_html(&args, &html, &inputs))
+            {
+                Ok(html) => write_output(args.output, &html),
+                Err(e) => {
+                    error!("{e}");
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Err(e) => {
+            report(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Checks a chapter must pass before it is built: fails when the project
+/// denies lints the chapter has problems of, and with `--strict` when it has
+/// lint warnings too or loading its dictionary or known words had problems,
+/// which were reported as warnings
+fn strict_checks(args: &BuildArgs, file: &Path, chap: &syntax::Chapter) -> Result<(), String> {
+    if args.strict || LINTS.get().is_some_and(Lints::denies) {
+        let errors: Vec<Diagnostic> = lint(chap.validate(&ValidateOptions::default()))
+            .into_iter()
+            .filter(|d| args.strict || d.severity == Severity::Error)
+            .collect();
+        for d in errors.iter().filter(|d| !chap.diagnostics.contains(d)) {
+            report(&Diagnostic {
+                file: Some(display_name(file)),
+                ..d.clone()
+            });
+        }
+        if !errors.is_empty() {
+            let reason = match args.strict {
+                true => "lint problems, not building with --strict",
+                false => "problems the lint levels deny, not building",
+            };
+            return Err(format!("{}: {reason}", display_name(file)));
+        }
+    }
+    if !args.strict || chap.diagnostics.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "{}: problems with the dictionary, known words or attrs, not building with --strict",
+        display_name(file)
+    ))
+}
+
+/// Hash of the transdoc version and the options the pages depend on, so
+/// pages built by another version or with other options are built again
+fn options_hash(args: &BuildArgs) -> String {
+    let options = format!(
+        "{} {:?} {:?} {:?} {} {} {} {} {} {:?} {:?} {:?} {:?} {:?} {} {:?} {:?} {:?}",
+        env!("CARGO_PKG_VERSION"),
+        args.render.plugin,
+        args.render.pre_filter,
+        args.render.post_filter,
+        args.data_src,
+        args.numbered,
+        args.strict,
+        args.pretty,
+        args.minify,
+        args.render.gloss,
+        args.render.gloss_format,
+        args.repeat_gloss,
+        args.edition,
+        args.project_id,
+        args.reproducible,
+        COMMENT.get(),
+        NFC.get(),
+        text::encoding(),
+    );
+    checksum::options_hash(&options)
+}
+
+/// Builds every chapter under the input directory into the same place
+/// under the output directory, with the files the pages link to
+fn build_dir(args: &BuildArgs, hooks: &Hooks, opts: &syntax::HtmlOptions) -> ExitCode {
+    let Some(out_dir) = args.output.as_ref().filter(|o| !is_stdio(o)) else {
+        error!("Building a directory needs an output directory");
+        return ExitCode::FAILURE;
+    };
+    if args.source_map.is_some() {
+        error!("Source maps can only be written when building a single file");
+        return ExitCode::FAILURE;
+    }
+    let files = match site::expand_files(std::slice::from_ref(&args.input)) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    // chapters copied into an output directory inside the input aren't
+    // built again into a directory inside that one
+    let files = match out_dir.canonicalize() {
+        Ok(out) => files
+            .into_iter()
+            .filter(|f| f.canonicalize().is_ok_and(|f| !f.starts_with(&out)))
+            .collect(),
+        Err(_) => files,
+    };
+    // a page built before can't be told apart from one that was encrypted,
+    // and showing the translations of a draft is worse than a rebuild
+    #[cfg(feature = "encrypt")]
+    let encrypt = args.encrypt.is_some();
+    #[cfg(not(feature = "encrypt"))]
+    let encrypt = false;
+    let options = options_hash(args);
+    let mut status = ExitCode::SUCCESS;
+    for input in files {
+        let rel = input.strip_prefix(&args.input).unwrap_or(&input);
+        let output = out_dir.join(rel).with_extension("html");
+        let mut chap = match read_chapter(&input) {
+            Ok(c) => c,
+            Err(e) => {
+                report(&e);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        args.select.apply(&mut chap, &input);
+        if let Err(e) = strict_checks(args, &input, &chap) {
+            error!("{e}");
+            status = ExitCode::FAILURE;
+            continue;
+        }
+        let mut inputs = site::chapter_inputs(&chap, chapter_dir(&input));
+        inputs.push(input.clone());
+        let sidecar = notes::sidecar(&input);
+        if sidecar.is_file() {
+            inputs.push(sidecar);
+        }
+        // pages built before without the checks may not pass them, or
+        // have other sentences
+        let sum = chap.checksum();
+        let page = std::fs::read_to_string(&output).unwrap_or_default();
+        if args.require_complete.is_none()
+            && !args.a11y_check
+            && args.select.is_empty()
+            && !encrypt
+            && checksum::page_options(&page) == Some(&options)
+        {
+            if site::is_up_to_date(&output, &inputs) {
+                debug!("{} is up to date", output.to_string_lossy());
+                continue;
+            }
+            // a chapter saved without changes, or with only its comments
+            // and spacing changed, still has the same page
+            let others: Vec<PathBuf> = inputs.iter().filter(|i| **i != input).cloned().collect();
+            if site::is_up_to_date(&output, &others) && checksum::page_checksum(&page) == Some(&sum)
+            {
+                debug!("{} is unchanged", input.to_string_lossy());
+                continue;
+            }
+        }
+        let res = render(
+            chap,
+            hooks,
+            opts,
+            args.require_complete.as_deref(),
+            args.a11y_check,
+        )
+        .and_then(|html| stamp_html(args, &html, &inputs))
+        .map(|html| checksum::with_page_checksum(&html, &sum, &options))
+        .map_err(|e| format!("{}: {e}", input.to_string_lossy()))
+        .and_then(|html| {
+            if let Some(parent) = output.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|e| format!("{}: {e}", parent.to_string_lossy()))?;
+            }
+            write_file(&output, &html)?;
+            info!("Wrote {}", output.to_string_lossy());
+            copy_assets(&html, &input, &output)
+        });
+        if let Err(e) = res {
+            error!("{e}");
+            status = ExitCode::FAILURE;
+        }
+    }
+    status
+}
+
+/// Copies the files the page links to from next to the chapter to next to the page
+fn copy_assets(html: &str, input: &Path, output: &Path) -> Result<(), String> {
+    let from = input.parent().unwrap_or(Path::new("."));
+    let to = output.parent().unwrap_or(Path::new("."));
+    for link in site::asset_links(html) {
+        let src = from.join(&link);
+        let dst = to.join(&link);
+        if !src.is_file() || site::is_up_to_date(&dst, std::slice::from_ref(&src)) {
+            continue;
+        }
+        if let Some(parent) = dst.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| format!("{}: {e}", parent.to_string_lossy()))?;
+        }
+        std::fs::copy(&src, &dst).map_err(|e| format!("{}: {e}", src.to_string_lossy()))?;
+        debug!("Copied {}", dst.to_string_lossy());
+    }
+    Ok(())
+}
+
+fn check(mut files: Vec<PathBuf>, stdin: bool, mark_stale: bool, alignment: bool) -> ExitCode {
+    let opts = ValidateOptions {
+        alignment,
+        ..Default::default()
+    };
+    if stdin {
+        files.extend(
+            std::io::stdin()
+                .lines()
+                .map_while(Result::ok)
+                .filter(|l| !l.trim().is_empty())
+                .map(PathBuf::from),
+        );
+    }
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut errors = 0;
+    for file in files {
+        let fname = display_name(&file);
+        let diags = if mark_stale {
+            mark_stale_translations(&file).map(|src| check_source(&src, &file, &opts))
+        } else {
+            with_input(&file, |src| check_source(src, &file, &opts))
+                .map_err(|e| Diagnostic::file(Severity::Error, &fname, e))
+        };
+        for mut d in lint(diags.unwrap_or_else(|e| vec![e])) {
+            d.file.get_or_insert_with(|| display_name(&file));
+            if d.severity == Severity::Error {
+                errors += 1;
+            }
+            report(&d);
+        }
+    }
+    if errors > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+/// Problems with the chapter source, from the parser, validation and the
+/// linter
+fn check_source(src: &str, file: &Path, opts: &ValidateOptions) -> Vec<Diagnostic> {
+    let mut diags = match parse_file(src, file) {
+        Ok(chap) => chap.validate(opts),
+        Err(e) => vec![e.diagnostic(Some(&display_name(file)))],
+    };
+    diags.extend(validate::lint(text::strip_bom(src)));
+    diags
+}
+
+fn doctor(project: Option<PathBuf>, mut files: Vec<PathBuf>) -> ExitCode {
+    println!(
+        "transdoc {} with {}",
+        env!("CARGO_PKG_VERSION"),
+        transdoc::doctor::features().join(", ")
+    );
+    let mut health = transdoc::doctor::Health::default();
+    let mut problems = Vec::new();
+    if let Some(project) = &project {
+        let fname = display_name(project);
+        match transdoc::book::Book::load(project) {
+            Ok(book) => {
+                for c in book.chapters {
+                    if c.is_file() {
+                        files.push(c);
+                    } else {
+                        problems.push(Diagnostic::file(
+                            Severity::Error,
+                            &fname,
+                            format!(
+                                "Chapter {} doesn't exist, fix its path in the manifest",
+                                c.to_string_lossy()
+                            ),
+                        ));
+                    }
+                }
+            }
+            Err(e) => problems.push(Diagnostic::file(
+                Severity::Error,
+                &fname,
+                format!("Can't read the book manifest: {e}"),
+            )),
+        }
+    } else if files.is_empty() {
+        files.push(PathBuf::from("."));
+    }
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            problems.push(Diagnostic::file(Severity::Error, ".", e));
+            Vec::new()
+        }
+    };
+    if files.is_empty() && problems.is_empty() {
+        problems.push(Diagnostic::file(
+            Severity::Warning,
+            ".",
+            "No chapter files found, give the chapters or the directories with them".into(),
+        ));
+    }
+    let problems = lint(problems);
+    for d in &problems {
+        report(d);
+    }
+    health.project(&problems);
+    for file in files {
+        let fname = display_name(&file);
+        let mut diags = with_input(&file, |src| {
+            let mut diags = check_source(src, &file, &ValidateOptions::default());
+            if let Ok(chap) = parse_file(src, &file) {
+                diags.extend(chap.missing_assets(chapter_dir(&file)));
+                let notes = notes::sidecar(&file);
+                if notes.is_file() {
+                    match read_file(&notes).and_then(|t| notes::parse(&t)) {
+                        Ok(n) => {
+                            let mut chap = chap;
+                            chap.notes = n;
+                            for label in chap.orphan_notes() {
+                                diags.push(Diagnostic::file(
+                                    Severity::Warning,
+                                    &display_name(&notes),
+                                    format!("Note of sentence @{label} that isn't in the chapter, move or remove it"),
+                                ));
+                            }
+                        }
+                        Err(e) => diags.push(Diagnostic::file(
+                            Severity::Warning,
+                            &display_name(&notes),
+                            e,
+                        )),
+                    }
+                }
+            }
+            let sums = transdoc::checksum::sidecar(&file);
+            if sums.is_file()
+                && let Err(e) = read_file(&sums).and_then(|t| transdoc::checksum::parse(&t))
+            {
+                diags.push(Diagnostic::file(
+                    Severity::Warning,
+                    &display_name(&sums),
+                    format!("{e}, record them again with checksum --write"),
+                ));
+            }
+            diags
+        })
+        .unwrap_or_else(|e| vec![Diagnostic::file(Severity::Error, &fname, e)]);
+        diags = lint(diags);
+        for d in &mut diags {
+            d.file.get_or_insert_with(|| fname.clone());
+            report(d);
+        }
+        health.chapter(&diags);
+    }
+    println!("{health}");
+    if health.is_ok() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(feature = "tui")]
+fn review(file: PathBuf, lang: String) -> ExitCode {
+    let fname = display_name(&file);
+    let res = read_file(&file).and_then(|src| {
+        let src = text::strip_bom(&src);
+        let chap = parse_file(src, &file).map_err(|e| {
+            report(&e.diagnostic(Some(&fname)));
+            "Not a valid chapter".to_string()
+        })?;
+        let before = chap.clone();
+        let mut review = Review::new(chap, &lang);
+        review.comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str).into();
+        match transdoc::review::run(review, src).map_err(|e| e.to_string())? {
+            Some(edited) => {
+                if !rewrite(&file, src, &edited).map_err(|e| e.to_string())? {
+                    return Ok(());
+                }
+                info!("Updated {fname}");
+                // keep what the reviewer changed
+                record_history(&file, &before)?;
+                if let Ok(after) = parse_file(&edited, &file) {
+                    record_history(&file, &after)?;
+                }
+            }
+            None => info!("Nothing written to {fname}"),
+        }
+        Ok(())
+    });
+    match res {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(e) => {
+            error!("{fname}: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(not(feature = "tui"))]
+fn review(_file: PathBuf, _lang: String) -> ExitCode {
+    error!("Reviewing needs the tui feature");
+    ExitCode::FAILURE
+}
+
+/// Rewrites the chapter file with the source the edit gives, if it parses
+fn restructure<F>(file: &Path, edit: F) -> ExitCode
+where
+    F: FnOnce(&syntax::Chapter, &str) -> Result<String, String>,
+{
+    let fname = display_name(file);
+    let res = read_input(file)
+        .map_err(|e| Diagnostic::file(Severity::Error, &fname, e))
+        .and_then(|src| {
+            let src = text::strip_bom(&src);
+            let chap = parse_file(src, file).map_err(|e| e.diagnostic(Some(&fname)))?;
+            let edited =
+                edit(&chap, src).map_err(|e| Diagnostic::file(Severity::Error, &fname, e))?;
+            // the inserted text may not be a valid sentence
+            parse(&edited).map_err(|e| e.diagnostic(Some(&fname)))?;
+            let written = if is_stdio(file) {
+                std::io::Write::write_all(&mut std::io::stdout(), edited.as_bytes()).map(|_| true)
+            } else {
+                rewrite(file, src, &edited)
+            };
+            written.map_err(|e| Diagnostic::file(Severity::Error, &fname, e.to_string()))
+        });
+    match res {
+        Ok(written) => {
+            if written {
+                info!("Updated {fname}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            report(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn attr(cmd: AttrCommand) -> ExitCode {
+    let (args, edit) = match cmd {
+        AttrCommand::Set { target, key, value } => (target, AttrEdit::Set(key, value)),
+        AttrCommand::Rm { target, key } => (target, AttrEdit::Remove(key)),
+        AttrCommand::Rename { target, key, to } => (target, AttrEdit::Rename(key, to)),
+    };
+    if let Err(e) = edit.check() {
+        error!("{e}");
+        return ExitCode::FAILURE;
+    }
+    let target = AttrTarget {
+        labels: args.label,
+        tags: args.tag,
+        lang: args.lang,
+    };
+    let files = match site::expand_files(&args.files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        let fname = display_name(&file);
+        let res = read_input(&file)
+            .map_err(|e| Diagnostic::file(Severity::Error, &fname, e))
+            .and_then(|src| {
+                let src = text::strip_bom(&src);
+                let mut chap = parse_file(src, &file).map_err(|e| e.diagnostic(Some(&fname)))?;
+                let modified = chap.edit_attrs(&target, &edit);
+                let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+                let edited = chap.splice_sentences_with(src, comment, &modified);
+                let written = if is_stdio(&file) {
+                    std::io::Write::write_all(&mut std::io::stdout(), edited.as_bytes())
+                        .map(|_| true)
+                } else if modified.is_empty() {
+                    Ok(true)
                 } else {
-                    println!("{chap:?}")
+                    rewrite(&file, src, &edited)
+                };
+                let written = written
+                    .map_err(|e| Diagnostic::file(Severity::Error, &fname, e.to_string()))?;
+                Ok(written.then_some(modified.len()))
+            });
+        match res {
+            Ok(Some(n)) => info!("Updated {n} sentences of {fname}"),
+            Ok(None) => (),
+            Err(e) => {
+                report(&e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+/// Marks the translations of changed originals in the file stale, giving
+/// the source as written back
+fn mark_stale_translations(file: &Path) -> Result<String, Diagnostic> {
+    let fname = display_name(file);
+    let src = read_input(file).map_err(|e| Diagnostic::file(Severity::Error, &fname, e))?;
+    let src = text::strip_bom(&src);
+    let mut chap = parse_file(src, file).map_err(|e| e.diagnostic(Some(&fname)))?;
+    let modified = chap.mark_stale();
+    if modified.is_empty() {
+        return Ok(src.to_string());
+    }
+    let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+    let marked = chap.splice_sentences_with(src, comment, &modified);
+    let written = if is_stdio(file) {
+        std::io::Write::write_all(&mut std::io::stdout(), marked.as_bytes()).map(|_| true)
+    } else {
+        rewrite(file, src, &marked)
+    };
+    if written.map_err(|e| Diagnostic::file(Severity::Error, &fname, e.to_string()))? {
+        info!("Updated {} sentences of {fname}", modified.len());
+    }
+    Ok(marked)
+}
+
+fn fmt(
+    files: Vec<PathBuf>,
+    line_ends: text::LineEnd,
+    check: bool,
+    fix: bool,
+    label_translations: bool,
+    sort_labels: bool,
+) -> ExitCode {
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        let res = read_input(&file).and_then(|src| {
+            let mut formatted = line_ends.normalize(&src).into_owned();
+            if fix {
+                formatted = text::clean_invisible(&formatted).into_owned();
+            }
+            if label_translations {
+                let mut chap = parse(&formatted).map_err(|e| e.to_string())?;
+                let modified = chap.label_translations();
+                let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+                formatted = chap.splice_sentences_with(&formatted, comment, &modified);
+            }
+            if sort_labels {
+                let chap = parse(&formatted).map_err(|e| e.to_string())?;
+                formatted = chap.sort_sentences(&formatted);
+            }
+            if check {
+                if formatted != src {
+                    println!("{}", file.to_string_lossy());
+                    status = ExitCode::FAILURE;
                 }
+                return Ok(());
+            }
+            // a filter prints its input even when there is nothing to change
+            if is_stdio(&file) {
+                print!("{formatted}");
+                return Ok(());
+            }
+            if formatted == src {
+                return Ok(());
+            }
+            if rewrite(&file, &src, &formatted).map_err(|e| e.to_string())? {
+                info!("Formatted {}", file.to_string_lossy());
             }
-            Err(e) => println!("{}", e.user_msg(Some(f))),
+            Ok(())
+        });
+        if let Err(e) = res {
+            error!("{}: {e}", file.to_string_lossy());
+            status = ExitCode::FAILURE;
         }
+    }
+    status
+}
+
+fn read_input(file: &Path) -> Result<String, String> {
+    let bytes = if is_stdio(file) {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|e| e.to_string())?;
+        bytes
     } else {
-        println!("Provide a chapter file");
+        std::fs::read(file).map_err(|e| e.to_string())?
+    };
+    text::encoding().decode(&bytes).map(|t| t.to_string())
+}
+
+/// Runs `f` on the contents of the file, memory mapped with `--mmap` so
+/// large files aren't copied into memory first
+fn with_input<T>(file: &Path, f: impl FnOnce(&str) -> T) -> Result<T, String> {
+    let nfc = NFC.get().copied().unwrap_or_default();
+    let f = |text: &str| if nfc { f(&text::nfc(text)) } else { f(text) };
+    if is_stdio(file) || !MMAP.get().copied().unwrap_or_default() {
+        return read_input(file).map(|s| f(&s));
+    }
+    let file = File::open(file).map_err(|e| e.to_string())?;
+    // SAFETY: the map is only read inside this function. Another process
+    // truncating the file meanwhile would crash the read, which is the
+    // accepted risk of memory mapping files the user points us to
+    let map = unsafe { memmap2::Mmap::map(&file).map_err(|e| e.to_string())? };
+    text::encoding().decode(&map).map(|t| f(&t))
+}
+
+fn read_file(file: &Path) -> Result<String, String> {
+    read_input(file).map_err(|e| format!("{}: {e}", file.to_string_lossy()))
+}
+
+#[cfg(feature = "import")]
+fn open_file(file: &Path) -> Result<Box<dyn std::io::BufRead>, String> {
+    if text::encoding() != text::Encoding::Utf8 {
+        // transcoded as a whole, the readers want UTF-8 lines
+        let text = read_file(file)?;
+        return Ok(Box::new(std::io::Cursor::new(text)));
+    }
+    if is_stdio(file) {
+        return Ok(Box::new(std::io::stdin().lock()));
+    }
+    File::open(file)
+        .map(|f| Box::new(std::io::BufReader::new(f)) as Box<dyn std::io::BufRead>)
+        .map_err(|e| format!("{}: {e}", file.to_string_lossy()))
+}
+
+fn file_stem(file: &Path) -> String {
+    file.file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "import")]
+fn extension(file: &Path) -> String {
+    file.extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(feature = "import")]
+fn language_pair(pair: &str) -> Result<(&str, &str), String> {
+    pair.split_once('-')
+        .ok_or_else(|| format!("Language pair should be like jpn-eng, got {pair:?}"))
+}
+
+#[cfg(feature = "kindle")]
+fn kindle_db(file: &Path, language: Option<&str>) -> Result<Vec<String>, String> {
+    import::kindle_db(file, language).map_err(|e| format!("{}: {e}", file.to_string_lossy()))
+}
+
+#[cfg(all(feature = "import", not(feature = "kindle")))]
+fn kindle_db(file: &Path, _language: Option<&str>) -> Result<Vec<String>, String> {
+    Err(format!(
+        "{}: reading vocab.db needs the kindle feature, use the CSV export instead",
+        file.to_string_lossy()
+    ))
+}
+
+fn new_file(file: NewFile) -> ExitCode {
+    let NewFile::Chapter {
+        title,
+        lang,
+        tl,
+        templates,
+        output,
+    } = file;
+    let template = match templates {
+        Some(dir) => match read_file(&dir.join(scaffold::CHAPTER_TEMPLATE_FILE)) {
+            Ok(t) => t,
+            Err(e) => {
+                error!(
+                    "{}: {e}",
+                    dir.join(scaffold::CHAPTER_TEMPLATE_FILE).to_string_lossy()
+                );
+                return ExitCode::FAILURE;
+            }
+        },
+        None => scaffold::CHAPTER_TEMPLATE.to_string(),
+    };
+    write_output(
+        output,
+        &scaffold::new_chapter(&template, &title, &lang, &tl),
+    )
+}
+
+#[cfg(feature = "import")]
+fn import(source: ImportSource) -> ExitCode {
+    let (converted, output) = match source {
+        ImportSource::Lingq { file, output } => {
+            let conv = read_file(&file).and_then(|c| {
+                if extension(&file) == "json" {
+                    import::lingq_lesson(&c).map(|c| c.source())
+                } else {
+                    import::term_list(&c, b',', &[';']).map(|d| syntax::dictionary_source(&d))
+                }
+            });
+            (conv, output)
+        }
+        ImportSource::Lwt {
+            file,
+            title,
+            language,
+            output,
+        } => {
+            let ext = extension(&file);
+            let conv = read_file(&file).and_then(|c| match ext.as_str() {
+                "tsv" | "csv" => {
+                    let delim = if ext == "tsv" { b'\t' } else { b',' };
+                    import::term_list(&c, delim, &['/', ';', '|'])
+                        .map(|d| syntax::dictionary_source(&d))
+                }
+                _ => {
+                    let title = title.unwrap_or_else(|| file_stem(&file));
+                    Ok(import::text_chapter(&title, language.as_deref(), &c).source())
+                }
+            });
+            (conv, output)
+        }
+        ImportSource::Kindle {
+            file,
+            language,
+            output,
+        } => {
+            let words = if extension(&file) == "csv" {
+                read_file(&file).and_then(|c| import::kindle_csv(&c, language.as_deref()))
+            } else {
+                kindle_db(&file, language.as_deref())
+            };
+            (words.map(|w| words::word_list_source(&w)), output)
+        }
+        ImportSource::Tatoeba {
+            pair,
+            sentences,
+            links,
+            title,
+            limit,
+            output,
+        } => {
+            let conv = language_pair(&pair).and_then(|(org, tl)| {
+                let pairs =
+                    import::tatoeba(open_file(&sentences)?, open_file(&links)?, org, tl, limit)?;
+                let title = title.unwrap_or_else(|| format!("Tatoeba {pair}"));
+                Ok(import::pair_chapter(&title, org, tl, pairs).source())
+            });
+            (conv, output)
+        }
+        ImportSource::Opus {
+            pair,
+            original,
+            translation,
+            title,
+            limit,
+            output,
+        } => {
+            let conv = language_pair(&pair).and_then(|(org, tl)| {
+                let pairs =
+                    import::moses(open_file(&original)?, open_file(&translation)?, tl, limit)?;
+                let title = title.unwrap_or_else(|| file_stem(&original));
+                Ok(import::pair_chapter(&title, org, tl, pairs).source())
+            });
+            (conv, output)
+        }
+    };
+    match converted {
+        Ok(c) => write_output(output, &c),
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn agree(first: PathBuf, second: PathBuf, lang: String, output: Option<PathBuf>) -> ExitCode {
+    let (a, b) = match (read_chapter(&first), read_chapter(&second)) {
+        (Ok(a), Ok(b)) => (a, b),
+        (a, b) => {
+            for e in [a.err(), b.err()].into_iter().flatten() {
+                report(&e);
+            }
+            return ExitCode::FAILURE;
+        }
+    };
+    let divergence = agree::agreement(&a, &b, &lang);
+    println!("label\tdistance\tsimilarity\tlength ratio");
+    for d in &divergence {
+        println!(
+            "{}\t{}\t{:.2}\t{:.2}",
+            d.label, d.distance, d.similarity, d.length_ratio
+        );
+    }
+    if !divergence.is_empty() {
+        let mean = divergence.iter().map(|d| d.similarity).sum::<f64>() / divergence.len() as f64;
+        println!("mean\t\t{mean:.2}\t");
+    }
+    let name = |f: &Path| {
+        f.file_stem()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string()
+    };
+    match output {
+        Some(o) => {
+            let merged = agree::merge_alternatives(&a, &b, &lang, (&name(&first), &name(&second)));
+            write_output(Some(o), &merged.source())
+        }
+        None => ExitCode::SUCCESS,
+    }
+}
+
+fn note(input: PathBuf, label: String, text: Option<String>, bookmark: bool) -> ExitCode {
+    let chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    if !chap.sentences.iter().any(|s| s.label == label) {
+        error!("No sentence @{label} in {}", display_name(&input));
+        return ExitCode::FAILURE;
+    }
+    if text.is_none() && !bookmark {
+        error!("Give the text of the note or --bookmark");
+        return ExitCode::FAILURE;
+    }
+    let mut all = chap.notes;
+    let note = all.entry(label).or_default();
+    note.bookmark |= bookmark;
+    if let Some(t) = text {
+        if !note.text.is_empty() {
+            note.text.push('\n');
+        }
+        note.text.push_str(t.trim());
+    }
+    let sidecar = notes::sidecar(&input);
+    match output::write_atomic(&sidecar, notes::source(&all).as_bytes()) {
+        Ok(()) => {
+            info!("Wrote {}", sidecar.to_string_lossy());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{}: {e}", sidecar.to_string_lossy());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// History of the chapter's translations, empty without a history file
+fn read_history(file: &Path) -> Result<History, String> {
+    let sidecar = history::sidecar(file);
+    if !sidecar.is_file() {
+        return Ok(History::default());
+    }
+    read_file(&sidecar)
+        .and_then(|t| History::parse(&t))
+        .map_err(|e| format!("{}: {e}", sidecar.to_string_lossy()))
+}
+
+/// Records the chapter's translations that changed in its history file
+fn record_history(file: &Path, chap: &syntax::Chapter) -> Result<usize, String> {
+    let mut history = read_history(file)?;
+    let added = chap.record_revisions(&mut history);
+    if added > 0 {
+        let sidecar = history::sidecar(file);
+        output::write_atomic(&sidecar, history.source().as_bytes())
+            .map_err(|e| format!("{}: {e}", sidecar.to_string_lossy()))?;
+    }
+    Ok(added)
+}
+
+fn history(cmd: HistoryCommand) -> ExitCode {
+    match cmd {
+        HistoryCommand::Record { files } => {
+            let files = match site::expand_files(&files) {
+                Ok(f) => f,
+                Err(e) => {
+                    error!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            let mut status = ExitCode::SUCCESS;
+            for file in files {
+                let res = read_chapter(&file)
+                    .map_err(|e| report(&e))
+                    .and_then(|chap| record_history(&file, &chap).map_err(|e| error!("{e}")));
+                match res {
+                    Ok(n) => info!(
+                        "{}: {n} {} recorded",
+                        display_name(&file),
+                        if n == 1 { "revision" } else { "revisions" }
+                    ),
+                    Err(()) => status = ExitCode::FAILURE,
+                }
+            }
+            status
+        }
+        HistoryCommand::Show { input, label, lang } => {
+            let chap = match read_chapter(&input) {
+                Ok(c) => c,
+                Err(e) => {
+                    report(&e);
+                    return ExitCode::FAILURE;
+                }
+            };
+            let mut history = match read_history(&input) {
+                Ok(h) => h,
+                Err(e) => {
+                    error!("{e}");
+                    return ExitCode::FAILURE;
+                }
+            };
+            // changes not recorded yet are shown too
+            chap.record_revisions(&mut history);
+            let label = label.map(|l| l.trim_start_matches('@').trim().to_string());
+            let lang = lang.map(|l| chap.canonical_language(&l));
+            let color = std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none();
+            for ((l, g), revisions) in &history.0 {
+                if label.as_ref().is_some_and(|x| x != l)
+                    || lang.as_ref().is_some_and(|x| x != g)
+                    || revisions.len() < 2
+                {
+                    continue;
+                }
+                println!("@ {l} {g}");
+                for pair in revisions.windows(2) {
+                    println!(
+                        "  {}",
+                        history::diff_text(&history::word_diff(&pair[0], &pair[1]), color)
+                    );
+                }
+            }
+            ExitCode::SUCCESS
+        }
+    }
+}
+
+fn eval(input: PathBuf, lang: String, reference: PathBuf) -> ExitCode {
+    let res = read_chapter(&input)
+        .map_err(|e| report(&e))
+        .and_then(|mut chap| {
+            let text = read_file(&reference).map_err(|e| error!("{e}"))?;
+            let mut refs: Vec<String> = text.lines().map(|l| l.trim().to_string()).collect();
+            while refs.last().is_some_and(|l| l.is_empty()) {
+                refs.pop();
+            }
+            chap.for_languages(std::slice::from_ref(&lang));
+            chap.process();
+            chap.eval(&lang, &refs).map_err(|e| error!("{e}"))
+        });
+    let Ok(report) = res else {
+        return ExitCode::FAILURE;
+    };
+    println!("label\tchrF\tBLEU");
+    for s in &report.sentences {
+        println!("{}\t{:.1}\t{:.1}", s.label, s.stats.chrf(), s.stats.bleu());
+    }
+    println!(
+        "total\t{:.1}\t{:.1}",
+        report.total.chrf(),
+        report.total.bleu()
+    );
+    if !report.missing.is_empty() {
+        warn!(
+            "Not translated to {lang}, not scored: {}",
+            report.missing.join(", ")
+        );
+    }
+    ExitCode::SUCCESS
+}
+
+fn collect_notes(
+    project: Option<PathBuf>,
+    keys: Vec<String>,
+    no_comments: bool,
+    title: Option<String>,
+    output: Option<PathBuf>,
+    files: Vec<PathBuf>,
+) -> ExitCode {
+    let mut book_title = None;
+    let mut chapters = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(project) = project {
+        match transdoc::book::Book::load(&project) {
+            Ok(b) => {
+                book_title = Some(b.title).filter(|t| !t.is_empty());
+                chapters.splice(0..0, b.chapters);
+            }
+            Err(e) => {
+                error!("{}: {e}", project.to_string_lossy());
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let keys: Vec<&str> = match keys.is_empty() {
+        true => appendix::NOTE_ATTRS.to_vec(),
+        false => keys.iter().map(String::as_str).collect(),
+    };
+    let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+    let mut notes = Vec::new();
+    for file in &chapters {
+        let chap = match read_chapter(file) {
+            Ok(c) => c,
+            Err(e) => {
+                report(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        let src = match no_comments || is_stdio(file) {
+            true => None,
+            false => read_file(file).ok(),
+        };
+        let src = src.as_deref().map(text::strip_bom);
+        notes.push((chap.title.clone(), chap.annotations(src, comment, &keys)));
+    }
+    let title = title
+        .or(book_title)
+        .unwrap_or_else(|| "Translator's notes".into());
+    write_output(output, &appendix::markdown(&title, &notes))
+}
+
+fn unknown_words(input: PathBuf, select: SelectArgs) -> ExitCode {
+    match read_chapter(&input) {
+        Ok(mut chap) => {
+            select.apply(&mut chap, &input);
+            chap.process();
+            for (w, n) in chap.unknown_words() {
+                println!("{n}\t{w}");
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            report(&e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn difficulty(
+    frequency: PathBuf,
+    top: usize,
+    sort: bool,
+    select: SelectArgs,
+    files: Vec<PathBuf>,
+) -> ExitCode {
+    let freq = match read_file(&frequency) {
+        Ok(f) => stats::FrequencyList::parse(&f),
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut status = ExitCode::SUCCESS;
+    let mut scores = Vec::new();
+    for file in files {
+        match read_chapter(&file) {
+            Ok(mut chap) => {
+                select.apply(&mut chap, &file);
+                scores.push((file, chap.title.clone(), chap.difficulty(&freq, top)))
+            }
+            Err(e) => {
+                report(&e);
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    if sort {
+        scores.sort_by(|a, b| b.2.coverage().total_cmp(&a.2.coverage()));
+    }
+    println!(
+        "{:>9} {:>9} {:>6}  File",
+        format!("top {top}"),
+        "unlisted",
+        "words"
+    );
+    for (file, title, d) in scores {
+        println!(
+            "{:>8.1}% {:>9} {:>6}  {} ({title})",
+            d.coverage(),
+            d.out_of_list,
+            d.tokens,
+            file.to_string_lossy()
+        );
+    }
+    status
+}
+
+fn vocab_graph(
+    kind: GraphKind,
+    format: GraphFormat,
+    mut filter: graph::WordFilter,
+    frequency: Option<PathBuf>,
+    select: SelectArgs,
+    output: Option<PathBuf>,
+    files: Vec<PathBuf>,
+) -> ExitCode {
+    if let Some(f) = frequency {
+        match read_file(&f) {
+            Ok(f) => filter.frequency = Some(stats::FrequencyList::parse(&f)),
+            Err(e) => {
+                error!("{e}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chapters = Vec::new();
+    for file in &files {
+        match read_chapter(file) {
+            Ok(mut chap) => {
+                select.apply(&mut chap, file);
+                chapters.push(chap);
+            }
+            Err(e) => {
+                report(&e);
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    let chapters: Vec<&syntax::Chapter> = chapters.iter().collect();
+    let graph = graph::VocabGraph::new(kind, &chapters, &filter);
+    write_output(output, &graph.write(format))
+}
+
+fn coverage(lang: Option<String>, select: SelectArgs, files: Vec<PathBuf>) -> ExitCode {
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut status = ExitCode::SUCCESS;
+    for file in files {
+        let mut chap = match read_chapter(&file) {
+            Ok(c) => c,
+            Err(e) => {
+                report(&e);
+                status = ExitCode::FAILURE;
+                continue;
+            }
+        };
+        select.apply(&mut chap, &file);
+        if let Some(lang) = &lang {
+            println!(
+                "{} ({}): {} to {lang}",
+                file.to_string_lossy(),
+                chap.title,
+                chap.progress(lang)
+            );
+            continue;
+        }
+        if chap.known_levels.is_empty() {
+            warn!(
+                "{}: The known words are not a directory of levels",
+                display_name(&file)
+            );
+            continue;
+        }
+        let levels: Vec<String> = chap
+            .level_coverage()
+            .iter()
+            .map(|(l, c)| format!("{l} {c:.1}%"))
+            .collect();
+        println!(
+            "{} ({}): {}",
+            file.to_string_lossy(),
+            chap.title,
+            levels.join(", ")
+        );
+    }
+    status
+}
+
+fn audio(
+    langs: Vec<String>,
+    title: Option<String>,
+    output: PathBuf,
+    select: SelectArgs,
+    files: Vec<PathBuf>,
+) -> ExitCode {
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Err(e) = std::fs::create_dir_all(&output) {
+        error!("{}: {e}", output.to_string_lossy());
+        return ExitCode::FAILURE;
+    }
+    let title = title.unwrap_or_else(|| {
+        std::path::absolute(&output)
+            .ok()
+            .and_then(|o| o.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_default()
+    });
+    let mut status = ExitCode::SUCCESS;
+    let mut write = |file: PathBuf, contents: &str| match write_file(&file, contents) {
+        Ok(_) => info!("Wrote {}", file.to_string_lossy()),
+        Err(e) => {
+            error!("{e}");
+            status = ExitCode::FAILURE;
+        }
+    };
+    // chapters of each language in order, for the joined files
+    let mut books: IndexMap<String, Vec<(String, Vec<audio::Clip>)>> = IndexMap::new();
+    for file in &files {
+        let mut chap = match read_chapter(file) {
+            Ok(c) => c,
+            Err(e) => {
+                report(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        select.apply(&mut chap, file);
+        let (clips, problems) = chap.audio_clips(chapter_dir(file));
+        for mut d in problems {
+            d.file = Some(display_name(file));
+            report(&d);
+        }
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        for (lang, mut clips) in clips {
+            if !langs.is_empty() && !langs.contains(&lang) {
+                continue;
+            }
+            // the playlists are in another directory than the recordings
+            for c in &mut clips {
+                if let Ok(f) = std::path::absolute(&c.file) {
+                    c.file = f;
+                }
+                if !c.file.is_file() {
+                    warn!(
+                        "{}: Recording of @{} in {lang} not found: {}",
+                        display_name(file),
+                        c.label,
+                        c.file.to_string_lossy()
+                    );
+                }
+            }
+            write(
+                output.join(format!("{stem}.{lang}.m3u")),
+                &audio::playlist(&chap.title, &clips),
+            );
+            books
+                .entry(lang)
+                .or_default()
+                .push((chap.title.clone(), clips));
+        }
+    }
+    if books.is_empty() {
+        warn!("No recordings given by audio attrs");
+    }
+    for (lang, chapters) in &books {
+        let clips = chapters.iter().flat_map(|(_, c)| c);
+        write(
+            output.join(format!("{lang}.ffconcat")),
+            &audio::concat_list(clips),
+        );
+        match audio::chapter_markers(&title, chapters) {
+            Some(meta) => write(output.join(format!("{lang}.ffmeta")), &meta),
+            None => warn!("Recordings in {lang} without a duration, no chapter markers written"),
+        }
+    }
+    status
+}
+
+fn sample(
+    input: PathBuf,
+    per_word: usize,
+    word_list: Option<PathBuf>,
+    output: Option<PathBuf>,
+) -> ExitCode {
+    let chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let targets: Vec<String> = match word_list {
+        Some(w) => match read_file(&w) {
+            Ok(l) => words::parse_word_list(&l).into_iter().collect(),
+            Err(e) => {
+                error!("{e}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => {
+            let mut processed = chap.clone();
+            processed.process();
+            processed.unknown_words().into_keys().collect()
+        }
+    };
+    let picked = chap.sample(&targets, per_word);
+    let sampled = syntax::Chapter::new(
+        chap.attrs.clone(),
+        picked
+            .into_iter()
+            .map(|i| chap.sentences[i].clone())
+            .collect(),
+    );
+    write_output(output, &sampled.source())
+}
+
+fn filter(script: PathBuf, input: PathBuf, output: Option<PathBuf>) -> ExitCode {
+    let script: FilterScript = match read_file(&script).and_then(|t| {
+        t.parse()
+            .map_err(|e| format!("{}: {e}", script.to_string_lossy()))
+    }) {
+        Ok(s) => s,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    match chap.filter(&script) {
+        Ok(()) => write_output(output, &chap.source()),
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn extract(
+    tags: Vec<String>,
+    select: SelectArgs,
+    output: Option<PathBuf>,
+    files: Vec<PathBuf>,
+) -> ExitCode {
+    let ranges = select.ranges();
+    let files = match site::expand_files(&files) {
+        Ok(f) => f,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut attrs = None;
+    let mut sentences = Vec::new();
+    for file in &files {
+        let mut chap = match read_chapter(file) {
+            Ok(c) => c,
+            Err(e) => {
+                report(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        // labels only need to be unique within a chapter
+        let prefix = match files.len() {
+            1 => String::new(),
+            _ => format!(
+                "{}-",
+                file.file_stem().unwrap_or_default().to_string_lossy()
+            ),
+        };
+        let tagged = chap.tagged(&tags);
+        let in_ranges = chap.in_ranges(&ranges);
+        chap.retain_sentences(|i, _| {
+            (tags.is_empty() || tagged.contains(&i))
+                && (ranges.is_empty() || in_ranges.contains(&i))
+        });
+        for mut s in std::mem::take(&mut chap.sentences) {
+            s.label = format!("{prefix}{}", s.label);
+            sentences.push(s);
+        }
+        attrs.get_or_insert(chap.attrs);
+    }
+    let mut attrs = attrs.unwrap_or_default();
+    let title = match tags.is_empty() {
+        true => "Extracted sentences".to_string(),
+        false => format!("Sentences tagged {}", tags.join(", ")),
+    };
+    attrs.insert("title".to_string(), title);
+    let extracted = syntax::Chapter::new(attrs, sentences);
+    write_output(output, &extracted.source())
+}
+
+/// Source of the chapter file without a BOM, with the chapter parsed from it
+fn read_source(file: &Path) -> Result<(String, syntax::Chapter), Diagnostic> {
+    let fname = display_name(file);
+    let src = read_input(file).map_err(|e| Diagnostic::file(Severity::Error, &fname, e))?;
+    let src = text::strip_bom(&src).to_string();
+    let chap = parse_file(&src, file).map_err(|e| e.diagnostic(Some(&fname)))?;
+    Ok((src, chap))
+}
+
+fn merge_driver(base: PathBuf, ours: PathBuf, theirs: PathBuf, output: PathBuf) -> ExitCode {
+    let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+    let merged = read_source(&base).and_then(|(bs, b)| {
+        let (os, o) = read_source(&ours)?;
+        let (ts, t) = read_source(&theirs)?;
+        let version = |chap, src| merge::Version { chap, src };
+        merge::merge(
+            version(&b, &bs),
+            version(&o, &os),
+            version(&t, &ts),
+            comment,
+        )
+        .map_err(|e| Diagnostic::file(Severity::Error, &ours.to_string_lossy(), e))
+    });
+    match merged {
+        Ok(m) => {
+            let current = std::fs::read_to_string(&output).unwrap_or_default();
+            if let Err(e) = rewrite(&output, &current, &m.source) {
+                error!("{}: {e}", output.to_string_lossy());
+                return ExitCode::from(2);
+            }
+            if m.conflicts > 0 {
+                warn!(
+                    "{} conflict(s) in {}",
+                    m.conflicts,
+                    output.to_string_lossy()
+                );
+                ExitCode::FAILURE
+            } else {
+                ExitCode::SUCCESS
+            }
+        }
+        Err(e) => {
+            // leave the output untouched so git falls back to a conflict
+            report(&e);
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn book(manifest: PathBuf, out_dir: PathBuf, render: RenderArgs) -> ExitCode {
+    let mut book = match transdoc::book::Book::load(&manifest) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("{}: {e}", manifest.to_string_lossy());
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut badges = IndexMap::new();
+    if let Some(grading) = &book.difficulty {
+        let freq = match read_file(&grading.frequency) {
+            Ok(f) => stats::FrequencyList::parse(&f),
+            Err(e) => {
+                error!("{e}");
+                return ExitCode::FAILURE;
+            }
+        };
+        let mut scores = Vec::new();
+        for input in &book.chapters {
+            match read_chapter(input) {
+                Ok(chap) => scores.push((input.clone(), chap.difficulty(&freq, grading.top))),
+                Err(e) => {
+                    report(&e);
+                    return ExitCode::FAILURE;
+                }
+            }
+        }
+        if grading.sort {
+            scores.sort_by(|a, b| b.1.coverage().total_cmp(&a.1.coverage()));
+            book.chapters = scores.iter().map(|(c, _)| c.clone()).collect();
+        }
+        badges = scores
+            .into_iter()
+            .map(|(c, d)| (c, grading.badge(&d)))
+            .collect();
+    }
+    // pages are named after their chapter files, in the same directory
+    let mut pages: IndexMap<String, &Path> = IndexMap::new();
+    for input in &book.chapters {
+        let href = book_page(input);
+        if let Some(other) = pages.insert(href.clone(), input) {
+            error!(
+                "{} and {} would both be built into {href}, rename one of them",
+                other.to_string_lossy(),
+                input.to_string_lossy()
+            );
+            return ExitCode::FAILURE;
+        }
+    }
+    let (hooks, mut opts) = match render_options(&render) {
+        Ok(o) => o,
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    opts.repeat_gloss = book.repeat_gloss;
+    opts.numbered = book.numbered;
+    for output in book.outputs() {
+        opts.edition = output.edition;
+        let status = book_edition(
+            &book,
+            &out_dir.join(&output.dir),
+            &hooks,
+            &mut opts,
+            &badges,
+        );
+        if status != ExitCode::SUCCESS {
+            return status;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+/// Page of a book chapter, relative to the output directory
+fn book_page(input: &Path) -> String {
+    format!(
+        "{}.html",
+        input.file_stem().unwrap_or_default().to_string_lossy()
+    )
+}
+
+fn book_edition(
+    book: &transdoc::book::Book,
+    out_dir: &Path,
+    hooks: &Hooks,
+    opts: &mut syntax::HtmlOptions,
+    badges: &IndexMap<PathBuf, String>,
+) -> ExitCode {
+    if let Err(e) = std::fs::create_dir_all(out_dir) {
+        error!("{}: {e}", out_dir.to_string_lossy());
+        return ExitCode::FAILURE;
+    }
+    let mut glossary = transdoc::book::Glossary::default();
+    let mut contents = Vec::new();
+    let mut feed = Vec::new();
+    for (i, input) in book.chapters.iter().enumerate() {
+        let mut chap = match read_chapter(input) {
+            Ok(c) => c,
+            Err(e) => {
+                report(&e);
+                return ExitCode::FAILURE;
+            }
+        };
+        if book.numbered {
+            chap.attrs
+                .entry(transdoc::outline::CHAPTER_NUMBER_ATTR.to_string())
+                .or_insert_with(|| (i + 1).to_string());
+        }
+        let title = chap.title.clone();
+        opts.seen = glossary.entries.keys().map(|w| w.to_string()).collect();
+        if book.inherit_glosses {
+            glossary.teach(&mut chap);
+        }
+        glossary.learn(&chap, &title);
+        let href = book_page(input);
+        let output = out_dir.join(&href);
+        if book.url.is_some() {
+            match chap.attrs.get("date") {
+                Some(d) if transdoc::book::atom_date(d).is_some() => {
+                    feed.push(transdoc::book::FeedEntry {
+                        title: title.clone(),
+                        href: href.clone(),
+                        date: d.clone(),
+                        excerpt: chap.description(),
+                    })
+                }
+                Some(d) => warn!(
+                    "{}: date {d:?} is not a YYYY-MM-DD date or RFC 3339 timestamp, not in the feed",
+                    input.to_string_lossy()
+                ),
+                None => warn!("{}: no date attr, not in the feed", input.to_string_lossy()),
+            }
+        }
+        let entry = transdoc::outline::ContentsEntry {
+            difficulty: badges.get(input).cloned(),
+            ..transdoc::outline::ContentsEntry::new(&chap, &href, opts)
+        };
+        let res =
+            render(chap, hooks, opts, None, false).and_then(|html| write_file(&output, &html));
+        if let Err(e) = res {
+            error!("{}: {e}", input.to_string_lossy());
+            return ExitCode::FAILURE;
+        }
+        info!("Wrote {}", output.to_string_lossy());
+        contents.push(entry);
+    }
+    let index = transdoc::book::index_html(
+        &book.title,
+        &contents,
+        (book.inherit_glosses || opts.edition == Edition::Reader).then_some(&glossary),
+        book.url.as_ref().map(|_| "feed.xml"),
+    );
+    if let Some(url) = &book.url {
+        let author = book.author.as_deref().unwrap_or(&book.title);
+        let feed = transdoc::book::atom_feed(&book.title, url, author, &feed);
+        let path = out_dir.join("feed.xml");
+        if let Err(e) = write_file(&path, &feed) {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+        info!("Wrote {}", path.to_string_lossy());
+    }
+    write_output(Some(out_dir.join("index.html")), &index)
+}
+
+fn rename_term(old: String, new: String, project: PathBuf) -> ExitCode {
+    let (old, new) = (old.trim(), new.trim());
+    if old.is_empty() || new.is_empty() || old == new {
+        error!("The new headword should differ from the old one and neither be empty");
+        return ExitCode::FAILURE;
+    }
+    let book = match transdoc::book::Book::load(&project) {
+        Ok(b) => b,
+        Err(e) => {
+            error!("{}: {e}", project.to_string_lossy());
+            return ExitCode::FAILURE;
+        }
+    };
+    let comment = COMMENT.get().map_or(DEFAULT_COMMENT, String::as_str);
+    let mut edits = Vec::new();
+    let mut dictionaries = Vec::new();
+    let mut failed = false;
+    for file in &book.chapters {
+        let src = match read_file(file) {
+            Ok(s) => s,
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+                continue;
+            }
+        };
+        if let Ok(chap) = parse_file(text::strip_bom(&src), file)
+            && let Some(d) = chap.attrs.get("dictionary")
+        {
+            let path = transdoc::parser::resolve_path(chapter_dir(file), d);
+            if !dictionaries.contains(&path) {
+                dictionaries.push(path);
+            }
+        }
+        let renamed = rename::rename_in_chapter(&src, comment, old, new);
+        edits.push((file.clone(), src, renamed));
+    }
+    for file in dictionaries {
+        match read_file(&file) {
+            Ok(src) => {
+                let renamed = rename::rename_in_dictionary(&src, old, new);
+                edits.push((file, src, renamed));
+            }
+            Err(e) => {
+                error!("{e}");
+                failed = true;
+            }
+        }
+    }
+    if failed {
+        // renaming only some of the files would leave the book inconsistent
+        return ExitCode::FAILURE;
+    }
+    let times = |n: usize| match n {
+        1 => "1 occurrence".to_string(),
+        n => format!("{n} occurrences"),
+    };
+    let mut count = 0;
+    for (file, src, renamed) in edits {
+        let fname = display_name(&file);
+        for d in &renamed.skipped {
+            report(&Diagnostic {
+                file: Some(fname.clone()),
+                ..d.clone()
+            });
+        }
+        if !renamed.is_changed() {
+            continue;
+        }
+        match rewrite(&file, &src, &renamed.source) {
+            Ok(true) => info!("{fname}: renamed {}", times(renamed.count)),
+            Ok(false) => (),
+            Err(e) => {
+                error!("{fname}: {e}");
+                return ExitCode::FAILURE;
+            }
+        }
+        count += renamed.count;
+    }
+    if !DRY_RUN.get().copied().unwrap_or_default() {
+        info!("Renamed {} of {old:?} to {new:?}", times(count));
+    }
+    ExitCode::SUCCESS
+}
+
+fn undo() -> ExitCode {
+    let journal = Path::new(undo::UNDO_DIR);
+    let Some(run) = undo::Run::last(journal) else {
+        error!("Nothing to undo, run commands with --backup to keep what they change");
+        return ExitCode::FAILURE;
+    };
+    let changed = match run.changed() {
+        Ok(c) => c,
+        Err(e) => {
+            error!("{}: {e}", run.dir.to_string_lossy());
+            return ExitCode::FAILURE;
+        }
+    };
+    if !changed.is_empty() && !FORCE.get().copied().unwrap_or_default() {
+        for file in changed {
+            error!(
+                "{}: changed since it was rewritten, use --force to undo anyway",
+                file.to_string_lossy()
+            );
+        }
+        return ExitCode::FAILURE;
+    }
+    match run.restore() {
+        Ok(files) => {
+            for file in files {
+                info!("Restored {}", file.to_string_lossy());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn json(input: PathBuf, processed: bool, select: SelectArgs, output: Option<PathBuf>) -> ExitCode {
+    let mut chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    select.apply(&mut chap, &input);
+    if processed {
+        chap.process();
+    }
+    write_output(output, &format!("{}\n", chap.to_json(true)))
+}
+
+fn lrc(
+    input: PathBuf,
+    langs: Vec<String>,
+    select: SelectArgs,
+    output: Option<PathBuf>,
+) -> ExitCode {
+    let mut chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    select.apply(&mut chap, &input);
+    let (lyrics, warnings) = chap.lrc(&langs);
+    for mut d in warnings {
+        d.file = Some(display_name(&input));
+        report(&d);
+    }
+    write_output(output, &lyrics)
+}
+
+fn checksum(input: PathBuf, write: bool, changed: bool) -> ExitCode {
+    let chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let sidecar = checksum::sidecar(&input);
+    if changed {
+        let recorded = if sidecar.is_file() {
+            match read_file(&sidecar).and_then(|t| checksum::parse(&t)) {
+                Ok(r) => r,
+                Err(e) => {
+                    error!("{}: {e}", sidecar.to_string_lossy());
+                    return ExitCode::FAILURE;
+                }
+            }
+        } else {
+            IndexMap::new()
+        };
+        for label in chap.changed_since(&recorded) {
+            println!("@{label}");
+        }
+        return ExitCode::SUCCESS;
+    }
+    let sums = checksum::source(&chap.checksums());
+    if !write {
+        print!("{sums}");
+        return ExitCode::SUCCESS;
+    }
+    match output::write_atomic(&sidecar, sums.as_bytes()) {
+        Ok(_) => {
+            info!("Wrote {}", sidecar.to_string_lossy());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{}: {e}", sidecar.to_string_lossy());
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn cite(input: PathBuf, label: String, short: bool) -> ExitCode {
+    let chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let label = label.trim_start_matches('@').trim();
+    match chap.cite(label) {
+        Some(_) if short => println!("{}", chap.citation(label)),
+        Some(c) => println!("{c}"),
+        None => {
+            error!("{}: no sentence @{label}", display_name(&input));
+            return ExitCode::FAILURE;
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn embed(input: PathBuf, out_dir: PathBuf, name: Option<String>, select: SelectArgs) -> ExitCode {
+    let name = match name {
+        Some(n) => format!("{n}.json"),
+        None if is_stdio(&input) => {
+            error!("A chapter read from stdin needs a --name for its JSON");
+            return ExitCode::FAILURE;
+        }
+        None => format!("{}.json", file_stem(&input)),
+    };
+    let mut chap = match read_chapter(&input) {
+        Ok(c) => c,
+        Err(e) => {
+            report(&e);
+            return ExitCode::FAILURE;
+        }
+    };
+    select.apply(&mut chap, &input);
+    chap.process();
+    let bundle = Bundle::new(&chap, &Plugins::default());
+    let json = schema::versioned_json(&bundle, false);
+    let res = std::fs::create_dir_all(&out_dir)
+        .map_err(|e| format!("{}: {e}", out_dir.to_string_lossy()))
+        .and_then(|_| write_file(&out_dir.join(&name), &json))
+        .and_then(|_| write_file(&out_dir.join(WIDGET_JS_FILE), WIDGET_JS));
+    match res {
+        Ok(_) => {
+            info!(
+                "Wrote {name} and {WIDGET_JS_FILE} to {}",
+                out_dir.to_string_lossy()
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn man(dir: Option<PathBuf>) -> ExitCode {
+    let res = match &dir {
+        Some(d) => {
+            std::fs::create_dir_all(d).and_then(|_| clap_mangen::generate_to(Cli::command(), d))
+        }
+        None => clap_mangen::Man::new(Cli::command()).render(&mut std::io::stdout()),
+    };
+    match res {
+        Ok(_) => {
+            if let Some(d) = dir {
+                info!("Wrote man pages to {}", d.to_string_lossy());
+            }
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            error!("{e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(cli.quiet, cli.verbose);
+    let format = match (cli.error_format, &cli.command) {
+        (Some(f), _) => f,
+        (None, Command::Check { .. }) => ErrorFormat::Gcc,
+        (None, _) => ErrorFormat::Human,
+    };
+    ERROR_FORMAT.set(format).ok();
+    FORCE.set(cli.force).ok();
+    DRY_RUN.set(cli.dry_run).ok();
+    if cli.backup {
+        let journal = Path::new(undo::UNDO_DIR);
+        if let Err(e) = undo::prune(journal, undo::UNDO_RUNS - 1) {
+            warn!("{}: {e}", journal.to_string_lossy());
+        }
+        UNDO.set(undo::Run::start(journal)).ok();
+    }
+    MMAP.set(cli.mmap).ok();
+    NFC.set(cli.nfc).ok();
+    text::set_encoding(cli.encoding);
+    match load_lints(&cli) {
+        Ok(l) => LINTS.set(l).ok(),
+        Err(e) => {
+            error!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    COMMENT.set(cli.comment).ok();
+    match cli.command {
+        Command::Build(args) => build(args),
+        #[cfg(feature = "serve")]
+        Command::Serve { input, addr } => match serve::serve(&input, &addr) {
+            Ok(_) => ExitCode::SUCCESS,
+            Err(e) => {
+                error!("{addr}: {e}");
+                ExitCode::FAILURE
+            }
+        },
+        Command::Check {
+            files,
+            stdin,
+            mark_stale,
+            alignment,
+        } => check(files, stdin, mark_stale, alignment),
+        Command::Doctor { project, files } => doctor(project, files),
+        Command::Fmt {
+            files,
+            line_ends,
+            check,
+            fix,
+            label_translations,
+            sort_labels,
+        } => fmt(
+            files,
+            line_ends,
+            check,
+            fix,
+            label_translations,
+            sort_labels,
+        ),
+        #[cfg(feature = "import")]
+        Command::Import(source) => import(source),
+        Command::New(file) => new_file(file),
+        Command::Move { label, to, file } => {
+            let label = label.trim_start_matches('@').trim().to_string();
+            let to = to.position();
+            restructure(&file, |chap, src| chap.move_sentence(src, &label, &to))
+        }
+        Command::Insert {
+            to,
+            label,
+            text,
+            file,
+        } => {
+            let to = to.position();
+            restructure(&file, |chap, src| {
+                let label = label.clone().unwrap_or_else(|| chap.next_label());
+                chap.insert_sentence(src, &label, &text, &to)
+            })
+        }
+        Command::Read {
+            input,
+            gloss,
+            lang,
+            no_translations,
+        } => {
+            let opts = TermOptions {
+                gloss,
+                languages: lang,
+                hide_translations: no_translations,
+                color: std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+                ..Default::default()
+            };
+            match read_chapter(&input) {
+                Ok(mut chap) => {
+                    chap.for_languages(&opts.languages);
+                    chap.process();
+                    print!("{}", chap.term_text(&opts));
+                    ExitCode::SUCCESS
+                }
+                Err(e) => {
+                    report(&e);
+                    ExitCode::FAILURE
+                }
+            }
+        }
+        Command::Show {
+            input,
+            label,
+            format,
+            lang,
+        } => {
+            let label = label.trim_start_matches('@').trim();
+            let res = read_chapter(&input)
+                .map_err(|e| report(&e))
+                .and_then(|mut chap| {
+                    chap.for_languages(&lang);
+                    chap.process();
+                    chap.snippet(label, format, &lang)
+                        .map_err(|e| error!("{e}"))
+                });
+            match res {
+                Ok(s) => {
+                    println!("{}", s.trim_end());
+                    ExitCode::SUCCESS
+                }
+                Err(_) => ExitCode::FAILURE,
+            }
+        }
+        Command::Review { file, lang } => review(file, lang),
+        Command::Attr(cmd) => attr(cmd),
+        Command::History(cmd) => history(cmd),
+        Command::Agree {
+            first,
+            second,
+            lang,
+            output,
+        } => agree(first, second, lang, output),
+        Command::Eval {
+            input,
+            lang,
+            reference,
+        } => eval(input, lang, reference),
+        Command::Note {
+            input,
+            label,
+            text,
+            bookmark,
+        } => note(input, label, text, bookmark),
+        Command::Notes {
+            project,
+            key,
+            no_comments,
+            title,
+            output,
+            files,
+        } => collect_notes(project, key, no_comments, title, output, files),
+        Command::UnknownWords { input, select } => unknown_words(input, select),
+        Command::Difficulty {
+            frequency,
+            top,
+            sort,
+            select,
+            files,
+        } => difficulty(frequency, top, sort, select, files),
+        Command::Coverage {
+            lang,
+            select,
+            files,
+        } => coverage(lang, select, files),
+        Command::Graph {
+            kind,
+            format,
+            min_count,
+            frequency,
+            skip_top,
+            select,
+            output,
+            files,
+        } => {
+            let filter = graph::WordFilter {
+                min_count,
+                skip_top,
+                frequency: None,
+            };
+            vocab_graph(kind, format, filter, frequency, select, output, files)
+        }
+        Command::Audio {
+            lang,
+            title,
+            output,
+            select,
+            files,
+        } => audio(lang, title, output, select, files),
+        Command::Sample {
+            input,
+            per_unknown_word,
+            words,
+            output,
+        } => sample(input, per_unknown_word, words, output),
+        Command::Extract {
+            tag,
+            select,
+            output,
+            files,
+        } => extract(tag, select, output, files),
+        Command::Filter {
+            script,
+            input,
+            output,
+        } => filter(script, input, output),
+        Command::MergeDriver {
+            base,
+            ours,
+            theirs,
+            output,
+        } => merge_driver(base, ours, theirs, output),
+        Command::Book {
+            manifest,
+            output,
+            render,
+        } => book(manifest, output, render),
+        Command::RenameTerm { old, new, project } => rename_term(old, new, project),
+        Command::Undo => undo(),
+        Command::Json {
+            input,
+            processed,
+            select,
+            output,
+        } => json(input, processed, select, output),
+        Command::Lrc {
+            input,
+            lang,
+            select,
+            output,
+        } => lrc(input, lang, select, output),
+        Command::Checksum {
+            input,
+            write,
+            changed,
+        } => checksum(input, write, changed),
+        Command::Schema => {
+            print!("{}", schema::CHAPTER_SCHEMA);
+            ExitCode::SUCCESS
+        }
+        Command::Lints => {
+            let lints = LINTS.get().cloned().unwrap_or_default();
+            for (rule, _, about) in lints::RULES {
+                let level = lints.level(rule).unwrap_or(LintLevel::Warn);
+                println!("{rule:<26} {level:<5}  {about}");
+            }
+            ExitCode::SUCCESS
+        }
+        Command::Cite {
+            input,
+            label,
+            short,
+        } => cite(input, label, short),
+        Command::Embed {
+            input,
+            output,
+            name,
+            select,
+        } => embed(input, output, name, select),
+        Command::Completions { shell } => {
+            clap_complete::generate(
+                shell,
+                &mut Cli::command(),
+                "transdoc",
+                &mut std::io::stdout(),
+            );
+            ExitCode::SUCCESS
+        }
+        Command::Man { dir } => man(dir),
     }
 }
@@ -0,0 +1,410 @@
+use crate::reorder::comments_above;
+use crate::splice::{splice_attrs, splice_sentence};
+use crate::syntax::{Chapter, Sentence};
+use crate::tokenizer::{get_tokens_with, TokenType};
+use indexmap::IndexMap;
+use std::hash::Hash;
+
+/// Result of a three way merge of chapters
+#[derive(Debug, Clone, PartialEq)]
+pub struct Merged {
+    /// Source text of the merged chapter, with conflict markers if any
+    pub source: String,
+    pub conflicts: usize,
+}
+
+/// Three way merge of a single value, `Err` when both sides changed it differently
+fn merge3<T: PartialEq + Clone>(
+    base: Option<&T>,
+    ours: Option<&T>,
+    theirs: Option<&T>,
+) -> Result<Option<T>, ()> {
+    if ours == theirs || base == theirs {
+        Ok(ours.cloned())
+    } else if base == ours {
+        Ok(theirs.cloned())
+    } else {
+        Err(())
+    }
+}
+
+/// Keys in our order, with keys only they have placed after their predecessor
+fn merged_keys<K: Hash + Eq + Clone>(base: &[K], ours: &[K], theirs: &[K]) -> Vec<K> {
+    let mut keys: Vec<K> = ours.to_vec();
+    for (i, k) in theirs.iter().enumerate() {
+        if keys.contains(k) || base.contains(k) {
+            continue;
+        }
+        let pos = theirs[..i]
+            .iter()
+            .rev()
+            .find_map(|p| keys.iter().position(|q| q == p))
+            .map(|p| p + 1)
+            .unwrap_or(0);
+        keys.insert(pos, k.clone());
+    }
+    // keys deleted by us but present in theirs are kept so merge3 can decide
+    for k in theirs {
+        if !keys.contains(k) && base.contains(k) {
+            let pos = base
+                .iter()
+                .take_while(|b| *b != k)
+                .filter_map(|p| keys.iter().position(|q| q == p))
+                .last()
+                .map(|p| p + 1)
+                .unwrap_or(0);
+            keys.insert(pos, k.clone());
+        }
+    }
+    keys
+}
+
+fn merge_maps<V: PartialEq + Clone>(
+    base: &IndexMap<String, V>,
+    ours: &IndexMap<String, V>,
+    theirs: &IndexMap<String, V>,
+) -> Result<IndexMap<String, V>, ()> {
+    let keys = merged_keys(
+        &base.keys().cloned().collect::<Vec<_>>(),
+        &ours.keys().cloned().collect::<Vec<_>>(),
+        &theirs.keys().cloned().collect::<Vec<_>>(),
+    );
+    let mut merged = IndexMap::new();
+    for k in keys {
+        if let Some(v) = merge3(base.get(&k), ours.get(&k), theirs.get(&k))? {
+            merged.insert(k, v);
+        }
+    }
+    Ok(merged)
+}
+
+fn merge_sentence(base: Option<&Sentence>, ours: &Sentence, theirs: &Sentence) -> Option<Sentence> {
+    let original = merge3(
+        base.map(|b| &b.original),
+        Some(&ours.original),
+        Some(&theirs.original),
+    )
+    .ok()??;
+    let orgattrs = merge_maps(
+        &base.map(|b| b.orgattrs.clone()).unwrap_or_default(),
+        &ours.orgattrs,
+        &theirs.orgattrs,
+    )
+    .ok()?;
+    let translations = merge_maps(
+        &base.map(|b| b.translations.clone()).unwrap_or_default(),
+        &ours.translations,
+        &theirs.translations,
+    )
+    .ok()?;
+    Some(Sentence {
+        label: ours.label.clone(),
+        original,
+        orgattrs,
+        translations,
+        span: ours.span,
+    })
+}
+
+fn conflict(ours: &str, theirs: &str) -> String {
+    format!("<<<<<<< ours\n{ours}=======\n{theirs}>>>>>>> theirs\n")
+}
+
+/// Chapter as parsed, with the source it was parsed from so what is kept
+/// of it keeps its comments and spacing
+#[derive(Debug, Clone, Copy)]
+pub struct Version<'a> {
+    pub chap: &'a Chapter,
+    pub src: &'a str,
+}
+
+/// Source of a sentence with the comment lines right above it and the
+/// text after it, up to the next one
+struct Part<'a> {
+    sentence: &'a Sentence,
+    above: &'a str,
+    text: &'a str,
+    after: &'a str,
+}
+
+impl Part<'_> {
+    fn source(&self) -> String {
+        format!("{}{}{}", self.above, self.text, self.after)
+    }
+
+    /// The sentence and the comments above it, for a side of a conflict
+    fn side(&self, eol: &str) -> String {
+        format!("{}{}{eol}", self.above, self.text)
+    }
+}
+
+impl<'a> Version<'a> {
+    fn starts(&self) -> Vec<usize> {
+        self.chap
+            .sentences
+            .iter()
+            .map(|s| comments_above(self.src, s.span.start))
+            .collect()
+    }
+
+    /// Attrs and comments before the first sentence
+    fn header(&self) -> &'a str {
+        &self.src[..self.starts().first().copied().unwrap_or(self.src.len())]
+    }
+
+    /// Where the text after the last sentence starts, after its line end
+    fn footer_start(&self) -> usize {
+        let Some(last) = self.chap.sentences.last() else {
+            return self.header().len();
+        };
+        let end = last.span.line_end(self.src);
+        let eol = ["\r\n", "\n", "\r"]
+            .into_iter()
+            .find(|e| self.src[end..].starts_with(e));
+        end + eol.map_or(0, str::len)
+    }
+
+    /// Comments and blank lines after the last sentence
+    fn footer(&self) -> &'a str {
+        &self.src[self.footer_start()..]
+    }
+
+    fn sentences(&self) -> Result<IndexMap<String, Part<'a>>, String> {
+        let starts = self.starts();
+        let mut map = IndexMap::new();
+        for (i, s) in self.chap.sentences.iter().enumerate() {
+            let end = s.span.line_end(self.src);
+            let next = starts.get(i + 1).copied().unwrap_or(self.footer_start());
+            let part = Part {
+                sentence: s,
+                above: &self.src[starts[i]..s.span.start],
+                text: &self.src[s.span.start..end],
+                after: &self.src[end..next],
+            };
+            if map.insert(s.label.clone(), part).is_some() {
+                return Err(format!("Duplicate sentence label {:?}", s.label));
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Comments in the source text
+fn comments<'a>(text: &'a str, comment: &str) -> Vec<&'a str> {
+    get_tokens_with(text, comment)
+        .into_iter()
+        .filter(|t| t.ty == TokenType::Comment)
+        .map(|t| t.content.trim_end())
+        .collect()
+}
+
+/// Whether the text has the comments the sides added to the sentence
+fn keeps_comments(text: &str, base: Option<&Part>, sides: &[&Part], comment: &str) -> bool {
+    let kept = comments(text, comment);
+    let old = base.map(|b| comments(b.text, comment)).unwrap_or_default();
+    sides
+        .iter()
+        .flat_map(|p| comments(p.text, comment))
+        .all(|c| old.contains(&c) || kept.contains(&c))
+}
+
+/// Whether the text ends with a blank line
+fn ends_blank(text: &str) -> bool {
+    let text = text.trim_end_matches([' ', '\t']);
+    ["\n\n", "\r\n\r\n", "\r\r"]
+        .iter()
+        .any(|e| text.ends_with(e))
+}
+
+/// Merges the changes made in `ours` and `theirs` since `base`.
+///
+/// Chapter attributes are merged by key, sentences by label and the
+/// translations of a sentence by their language label, so concurrent edits
+/// only conflict when they touch the same attribute, original or translation.
+/// What only one side changed is taken from its source as written, and
+/// sentences both changed are written into the source of a side that
+/// keeps the comments both added; they conflict when neither does.
+pub fn merge(
+    base: Version,
+    ours: Version,
+    theirs: Version,
+    comment: &str,
+) -> Result<Merged, String> {
+    let eol = if ours.src.contains("\r\n") {
+        "\r\n"
+    } else {
+        "\n"
+    };
+    let mut source = String::new();
+    let mut conflicts = 0;
+
+    let (b, o, t) = (&base.chap.attrs, &ours.chap.attrs, &theirs.chap.attrs);
+    match merge_maps(b, o, t) {
+        Ok(a) if a == *o => source.push_str(ours.header()),
+        Ok(a) if a == *t => source.push_str(theirs.header()),
+        Ok(a) => source.push_str(&splice_attrs(ours.header(), comment, o, &a, eol)),
+        Err(_) => {
+            conflicts += 1;
+            source.push_str(&conflict(ours.header(), theirs.header()));
+        }
+    }
+
+    let (bs, os, ts) = (base.sentences()?, ours.sentences()?, theirs.sentences()?);
+    let labels = merged_keys(
+        &bs.keys().cloned().collect::<Vec<_>>(),
+        &os.keys().cloned().collect::<Vec<_>>(),
+        &ts.keys().cloned().collect::<Vec<_>>(),
+    );
+    let mut parts = Vec::new();
+    for l in labels {
+        let (bp, op, tp) = (bs.get(&l), os.get(&l), ts.get(&l));
+        let (b, o, t) = (
+            bp.map(|p| p.sentence),
+            op.map(|p| p.sentence),
+            tp.map(|p| p.sentence),
+        );
+        let merged = match (o, t) {
+            (Some(o), Some(t)) => merge_sentence(b, o, t).map(Some),
+            _ => merge3(b, o, t).ok(),
+        };
+        let sides: Vec<&Part> = [op, tp].into_iter().flatten().collect();
+        let part = match merged {
+            // a sentence as one side has it is kept as written there
+            Some(Some(s)) => match sides.iter().find(|p| *p.sentence == s) {
+                Some(p) => Some(p.source()),
+                // or spliced into the side it keeps the added comments of,
+                // both changed it otherwise
+                None => sides.iter().find_map(|p| {
+                    let text = splice_sentence(p.text, comment, &s, eol);
+                    keeps_comments(&text, bp, &sides, comment)
+                        .then(|| format!("{}{text}{}", p.above, p.after))
+                }),
+            },
+            Some(None) => continue,
+            None => None,
+        };
+        match part {
+            Some(p) => parts.push(p),
+            None => {
+                conflicts += 1;
+                let side = |p: Option<&Part>| p.map(|p| p.side(eol)).unwrap_or_default();
+                parts.push(format!("{}{eol}", conflict(&side(op), &side(tp))));
+            }
+        }
+    }
+    let last = parts.len().saturating_sub(1);
+    for (i, part) in parts.into_iter().enumerate() {
+        source.push_str(&part);
+        if !part.ends_with(['\n', '\r']) {
+            source.push_str(eol);
+        }
+        // a part that was last in its file may be followed by others now
+        if i < last && !ends_blank(&part) {
+            source.push_str(eol);
+        }
+    }
+    // the end of the file as the side that changed it has it
+    if ours.footer() == base.footer() {
+        source.push_str(theirs.footer());
+    } else {
+        source.push_str(ours.footer());
+    }
+    Ok(Merged { source, conflicts })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const BASE: &str = "title = T\n\n@ a\nfirst\n--- en\none\n\n@ b\nsecond\n--- en\ntwo\n";
+
+    fn merge_sources(base: &str, ours: &str, theirs: &str) -> Merged {
+        let [b, o, t] = [base, ours, theirs].map(|s| Chapter::from_str(s).unwrap());
+        let version = |chap, src| Version { chap, src };
+        merge(
+            version(&b, base),
+            version(&o, ours),
+            version(&t, theirs),
+            "#",
+        )
+        .unwrap()
+    }
+
+    #[rstest]
+    #[case(
+        "title = T\n\n@ a\nfirst\n--- en\nOne\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = T\n\n@ a\nfirst\n--- en\none\n--- de\neins\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = T\n\n@ a\nfirst\n--- en\nOne\n--- de\neins\n\n@ b\nsecond\n--- en\ntwo\n"
+    )]
+    #[case(
+        "title = T\n\n@ a\nfirst\n--- en\none\n\n@ b\nSecond\n--- en\ntwo\n",
+        "title = U\n\n@ a\nfirst\n--- en\none\n\n@ b\nsecond\n--- en\ntwo\n\n@ c\nthird\n",
+        "title = U\n\n@ a\nfirst\n--- en\none\n\n@ b\nSecond\n--- en\ntwo\n\n@ c\nthird\n"
+    )]
+    #[case(
+        "title = T\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = T\n\n@ a\nfirst\n--- en\none\n\n@ z\nnew\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = T\n\n@ z\nnew\n\n@ b\nsecond\n--- en\ntwo\n"
+    )]
+    fn merge_clean_test(#[case] ours: &str, #[case] theirs: &str, #[case] expected: &str) {
+        let m = merge_sources(BASE, ours, theirs);
+        assert_eq!(m.conflicts, 0);
+        assert_eq!(m.source, expected);
+    }
+
+    #[rstest]
+    #[case(
+        "title = T\n\n@ a\nfirst\n--- en\nOne\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = T\n\n@ a\nfirst\n--- en\nuno\n\n@ b\nsecond\n--- en\ntwo\n"
+    )]
+    #[case(
+        "title = X\n\n@ a\nfirst\n--- en\none\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = Y\n\n@ a\nfirst\n--- en\none\n\n@ b\nsecond\n--- en\ntwo\n"
+    )]
+    #[case(
+        "title = T\n\n@ b\nsecond\n--- en\ntwo\n",
+        "title = T\n\n@ a\nFirst\n--- en\none\n\n@ b\nsecond\n--- en\ntwo\n"
+    )]
+    fn merge_conflict_test(#[case] ours: &str, #[case] theirs: &str) {
+        let m = merge_sources(BASE, ours, theirs);
+        assert_eq!(m.conflicts, 1);
+        assert!(m.source.contains("<<<<<<< ours\n"));
+    }
+
+    #[test]
+    fn merge_keeps_comments_test() {
+        let base = "# header note\ntitle = T\n\n# about a\n@ a\nfirst  # trailing\n# inner\n--- en\none\n\n@ b\nsecond\n--- en\ntwo\n# end\n";
+        // we translate a, they add an attr to the header and a sentence
+        let ours = base.replace("--- en\none", "--- en\none\n--- de\neins");
+        let theirs = base
+            .replace("title = T", "title = T\nauthor = A")
+            .replace("two\n", "two\n\n@ c\nthird # new\n");
+        let m = merge_sources(base, &ours, &theirs);
+        assert_eq!(m.conflicts, 0);
+        assert_eq!(
+            m.source,
+            "# header note\ntitle = T\nauthor = A\n\n# about a\n@ a\nfirst  # trailing\n# inner\n--- en\none\n--- de\neins\n\n@ b\nsecond\n--- en\ntwo\n\n@ c\nthird # new\n# end\n"
+        );
+
+        // both change the same sentence
+        let ours = base.replace("--- en\ntwo", "--- en\nTwo");
+        let theirs = base.replace("second\n", "second\nlevel = A1 # easy\n");
+        let m = merge_sources(base, &ours, &theirs);
+        assert_eq!(m.conflicts, 0);
+        assert!(
+            m.source
+                .contains("@ b\nsecond\nlevel = A1 # easy\n--- en\nTwo\n# end\n")
+        );
+        assert!(m.source.starts_with(
+            "# header note\ntitle = T\n\n# about a\n@ a\nfirst  # trailing\n# inner\n"
+        ));
+
+        // both add comments the merged sentence can't keep on either side
+        let ours = base.replace("--- en\ntwo", "--- en\nTwo # capital");
+        let m = merge_sources(base, &ours, &theirs);
+        assert_eq!(m.conflicts, 1);
+        assert!(m.source.contains("<<<<<<< ours\n"));
+    }
+}
@@ -0,0 +1,168 @@
+use crate::syntax::{Chapter, Sentence};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Extension of the file next to a chapter with the reader's notes
+pub const NOTES_EXTENSION: &str = "notes";
+
+/// Reader's bookmark and annotation of a sentence
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Note {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bookmark: bool,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub text: String,
+}
+
+/// Notes file of the chapter file, `chapter.notes` next to it
+pub fn sidecar(chapter: &Path) -> PathBuf {
+    chapter.with_extension(NOTES_EXTENSION)
+}
+
+/// Notes by sentence label from a notes file, where `@ label` lines (ending
+/// with ` *` for a bookmark) start the note of a sentence and `#` lines are
+/// comments
+pub fn parse(src: &str) -> Result<IndexMap<String, Note>, String> {
+    let mut notes: IndexMap<String, Note> = IndexMap::new();
+    let mut current: Option<String> = None;
+    for (i, line) in src.lines().enumerate() {
+        if line.starts_with('#') {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('@') {
+            let header = header.trim();
+            let (label, bookmark) = match header.strip_suffix('*') {
+                Some(l) => (l.trim(), true),
+                None => (header, false),
+            };
+            if label.is_empty() {
+                return Err(format!("Line {} has no sentence label after @", i + 1));
+            }
+            let note = notes.entry(label.to_string()).or_default();
+            note.bookmark |= bookmark;
+            current = Some(label.to_string());
+            continue;
+        }
+        match &current {
+            Some(label) => {
+                let note = notes.get_mut(label).expect("label added with its header");
+                note.text.push_str(line);
+                note.text.push('\n');
+            }
+            None if line.trim().is_empty() => (),
+            None => {
+                return Err(format!(
+                    "Line {} isn't after a `@ label` line naming its sentence",
+                    i + 1
+                ));
+            }
+        }
+    }
+    for note in notes.values_mut() {
+        note.text = note.text.trim().to_string();
+    }
+    Ok(notes)
+}
+
+/// Text of a notes file with the notes
+pub fn source(notes: &IndexMap<String, Note>) -> String {
+    let mut src = String::new();
+    for (label, note) in notes {
+        if !src.is_empty() {
+            src.push('\n');
+        }
+        src.push_str(&format!("@ {label}"));
+        if note.bookmark {
+            src.push_str(" *");
+        }
+        src.push('\n');
+        if !note.text.is_empty() {
+            src.push_str(&note.text);
+            src.push('\n');
+        }
+    }
+    src
+}
+
+impl Chapter {
+    /// Whether the sentence has a bookmark, in the notes or with a
+    /// `bookmark = yes` attr
+    pub fn is_bookmarked(&self, sentence: &Sentence) -> bool {
+        self.notes.get(&sentence.label).is_some_and(|n| n.bookmark)
+            || sentence.attr_bool("bookmark").unwrap_or(false)
+    }
+
+    /// Labels of the notes whose sentence isn't in the chapter anymore
+    pub fn orphan_notes(&self) -> Vec<&str> {
+        self.notes
+            .keys()
+            .filter(|l| !self.sentences.iter().any(|s| &s.label == *l))
+            .map(String::as_str)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const NOTES: &str =
+        "# my notes\n@ 1 *\n@ 2\nLook up this idiom.\n\nAsk in class.\n@ 1\nAgain\n";
+
+    #[test]
+    fn parse_test() {
+        let notes = parse(NOTES).unwrap();
+        let note = |bookmark: bool, text: &str| Note {
+            bookmark,
+            text: text.to_string(),
+        };
+        assert_eq!(
+            notes.into_iter().collect::<Vec<_>>(),
+            vec![
+                ("1".to_string(), note(true, "Again")),
+                (
+                    "2".to_string(),
+                    note(false, "Look up this idiom.\n\nAsk in class.")
+                ),
+            ]
+        );
+    }
+
+    #[rstest]
+    #[case("text first\n@ 1\n")]
+    #[case("@ *\n")]
+    #[case("@\n")]
+    fn bad_notes_test(#[case] src: &str) {
+        assert!(parse(src).is_err());
+    }
+
+    #[test]
+    fn source_test() {
+        let notes = parse(NOTES).unwrap();
+        assert_eq!(parse(&source(&notes)).unwrap(), notes);
+    }
+
+    #[test]
+    fn bookmarks_test() {
+        let mut chap = Chapter::from_str("@ 1\na\n\n@ 2\nb\nbookmark = yes\n\n@ 3\nc\n").unwrap();
+        chap.notes = parse("@ 1 *\n@ 3\nplain note\n@ 9\ngone\n").unwrap();
+        let marked: Vec<bool> = chap
+            .sentences
+            .iter()
+            .map(|s| chap.is_bookmarked(s))
+            .collect();
+        assert_eq!(marked, vec![true, true, false]);
+        assert_eq!(chap.orphan_notes(), vec!["9"]);
+        #[cfg(feature = "html")]
+        {
+            let html = chap.sentence_html(&chap.sentences[2], &Default::default());
+            assert!(html.contains("<aside class=\"note\">plain note</aside>"));
+            assert!(!html.contains("data-bookmark"));
+            let html = chap.sentence_html(&chap.sentences[0], &Default::default());
+            assert!(html.contains(" data-bookmark>"));
+        }
+    }
+}
@@ -0,0 +1,470 @@
+use crate::intern;
+use crate::syntax::{Chapter, OrgFragment};
+use regex::Regex;
+
+/// Zeros of the digit scripts numbers can be written in, each followed by
+/// the other nine digits
+const ZEROS: [char; 10] = [
+    '0', '\u{660}', '\u{6F0}', '\u{966}', '\u{9E6}', '\u{A66}', '\u{AE6}', '\u{BE6}', '\u{E50}',
+    '\u{FF10}',
+];
+
+/// Number written in the formats, 1234567.89
+const NUMBER_SAMPLE: [u8; 9] = [1, 2, 3, 4, 5, 6, 7, 8, 9];
+
+/// Format of numbers like the default, `english.number_format = 1,234,567.89`
+pub const DEFAULT_NUMBER_FORMAT: &str = "1,234,567.89";
+
+/// Format of dates like the default, `german.date_format = 31.12.2024`
+pub const DEFAULT_DATE_FORMAT: &str = "2024-12-31";
+
+/// Chapter attrs of a language about numbers, not meant for its byline
+pub const LOCALE_ATTRS: [&str; 2] = ["number_format", "date_format"];
+
+/// Value of the digit and the zero of its script
+pub(crate) fn digit(c: char) -> Option<(u8, char)> {
+    ZEROS.iter().find_map(|&z| {
+        let d = (c as u32).checked_sub(z as u32)?;
+        (d < 10).then_some((d as u8, z))
+    })
+}
+
+/// Runs of digits of the text and the separators between them
+fn runs(text: &str) -> Option<(Vec<Vec<u8>>, Vec<char>, char)> {
+    let mut runs: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut seps = Vec::new();
+    let mut zero = None;
+    for c in text.trim().chars() {
+        match digit(c) {
+            Some((d, z)) => {
+                zero.get_or_insert(z);
+                runs.last_mut()?.push(d);
+            }
+            None if runs.last()?.is_empty() => return None,
+            None => {
+                seps.push(c);
+                runs.push(Vec::new());
+            }
+        }
+    }
+    Some((runs, seps, zero?))
+}
+
+/// How the digits of the integer part are grouped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Grouping {
+    None,
+    /// In threes, 1,234,567
+    Thousands,
+    /// The last three then in twos, 12,34,567
+    Indian,
+}
+
+/// Way a language writes numbers, from a sample of 1234567.89
+#[derive(Debug, Clone, PartialEq)]
+pub struct NumberFormat {
+    pub zero: char,
+    pub grouping: Grouping,
+    pub group: Option<char>,
+    pub decimal: char,
+}
+
+impl std::str::FromStr for NumberFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Number format {s:?} isn't 1234567.89 written like 1,234,567.89");
+        let (runs, seps, zero) = runs(s).ok_or_else(err)?;
+        if runs.concat() != NUMBER_SAMPLE || runs.last().map(Vec::len) != Some(2) {
+            return Err(err());
+        }
+        let decimal = *seps.last().ok_or_else(err)?;
+        let int = &runs[..runs.len() - 1];
+        let group = seps.first().filter(|_| int.len() > 1).copied();
+        let sizes: Vec<usize> = int.iter().skip(1).map(Vec::len).collect();
+        let grouping = match sizes.as_slice() {
+            [] => Grouping::None,
+            [3, 3] => Grouping::Thousands,
+            [2, 3] => Grouping::Indian,
+            _ => return Err(err()),
+        };
+        if seps[..seps.len() - 1].iter().any(|c| Some(*c) != group) {
+            return Err(err());
+        }
+        Ok(Self {
+            zero,
+            grouping,
+            group,
+            decimal,
+        })
+    }
+}
+
+impl NumberFormat {
+    fn digits(&self, digits: &[u8]) -> String {
+        digits
+            .iter()
+            .filter_map(|d| char::from_u32(self.zero as u32 + *d as u32))
+            .collect()
+    }
+
+    /// The number in this format
+    pub fn render(&self, int: &[u8], frac: &[u8]) -> String {
+        let mut groups: Vec<&[u8]> = Vec::new();
+        let mut rest = int;
+        let mut size = 3;
+        while self.grouping != Grouping::None && rest.len() > size {
+            let (head, tail) = rest.split_at(rest.len() - size);
+            groups.push(tail);
+            rest = head;
+            if self.grouping == Grouping::Indian {
+                size = 2;
+            }
+        }
+        groups.push(rest);
+        let sep = self.group.map(String::from).unwrap_or_default();
+        let mut out = groups
+            .iter()
+            .rev()
+            .map(|g| self.digits(g))
+            .collect::<Vec<_>>()
+            .join(&sep);
+        if !frac.is_empty() {
+            out.push(self.decimal);
+            out.push_str(&self.digits(frac));
+        }
+        out
+    }
+
+    /// Regex of numbers in this format, in any script
+    fn regex(&self) -> String {
+        let group = self
+            .group
+            .map(|g| format!("(?:{}\\d{{2,3}})*", regex::escape(&g.to_string())))
+            .unwrap_or_default();
+        format!(
+            "\\d+{group}(?:{}\\d+)?",
+            regex::escape(&self.decimal.to_string())
+        )
+    }
+
+    /// Integer and fraction digits of a number in this format
+    fn parse(&self, text: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+        let (int, frac) = match text.rsplit_once(self.decimal) {
+            Some((i, f)) if Some(self.decimal) != self.group => (i, f),
+            _ => (text, ""),
+        };
+        let values = |t: &str| -> Option<Vec<u8>> {
+            t.chars()
+                .filter(|c| Some(*c) != self.group)
+                .map(|c| digit(c).map(|(d, _)| d))
+                .collect()
+        };
+        Some((values(int)?, values(frac)?))
+    }
+}
+
+/// Part of a date
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DatePart {
+    Day,
+    Month,
+    Year,
+}
+
+/// Way a language writes dates, from a sample of 31 December 2024
+#[derive(Debug, Clone, PartialEq)]
+pub struct DateFormat {
+    zero: char,
+    parts: [DatePart; 3],
+    seps: [char; 2],
+}
+
+impl std::str::FromStr for DateFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || format!("Date format {s:?} isn't 31 December 2024 written like 31.12.2024");
+        let (runs, seps, zero) = runs(s).ok_or_else(err)?;
+        let parts: Vec<DatePart> = runs
+            .iter()
+            .map(|r| match r.as_slice() {
+                [3, 1] => Some(DatePart::Day),
+                [1, 2] => Some(DatePart::Month),
+                [2, 0, 2, 4] => Some(DatePart::Year),
+                _ => None,
+            })
+            .collect::<Option<_>>()
+            .ok_or_else(err)?;
+        let parts: [DatePart; 3] = parts.try_into().map_err(|_| err())?;
+        if !parts.contains(&DatePart::Day) || !parts.contains(&DatePart::Month) {
+            return Err(err());
+        }
+        Ok(Self {
+            zero,
+            parts,
+            seps: [seps[0], seps[1]],
+        })
+    }
+}
+
+impl DateFormat {
+    /// Regex of dates in this format, in any script
+    fn regex(&self) -> String {
+        let part = |p: DatePart| match p {
+            DatePart::Year => "\\d{4}",
+            _ => "\\d{1,2}",
+        };
+        format!(
+            "{}{}{}{}{}",
+            part(self.parts[0]),
+            regex::escape(&self.seps[0].to_string()),
+            part(self.parts[1]),
+            regex::escape(&self.seps[1].to_string()),
+            part(self.parts[2])
+        )
+    }
+
+    /// Day, month and year digits of a date in this format, none when
+    /// the day or month is out of range
+    fn parse(&self, text: &str) -> Option<[Vec<u8>; 3]> {
+        let (runs, _, _) = runs(text)?;
+        let mut dmy: [Vec<u8>; 3] = Default::default();
+        for (p, r) in self.parts.iter().zip(runs) {
+            dmy[*p as usize] = r;
+        }
+        let value = |d: &[u8]| d.iter().fold(0, |v, d| v * 10 + u32::from(*d));
+        let day = value(&dmy[DatePart::Day as usize]);
+        let month = value(&dmy[DatePart::Month as usize]);
+        ((1..=31).contains(&day) && (1..=12).contains(&month)).then_some(dmy)
+    }
+
+    /// The date in this format, days and months with two digits
+    pub fn render(&self, dmy: &[Vec<u8>; 3]) -> String {
+        let mut out = String::new();
+        for (i, p) in self.parts.iter().enumerate() {
+            let mut digits = dmy[*p as usize].clone();
+            if *p != DatePart::Year && digits.len() < 2 {
+                digits.insert(0, 0);
+            }
+            out.extend(
+                digits
+                    .iter()
+                    .filter_map(|d| char::from_u32(self.zero as u32 + *d as u32)),
+            );
+            if let Some(s) = self.seps.get(i) {
+                out.push(*s);
+            }
+        }
+        out
+    }
+}
+
+impl Chapter {
+    /// Number and date formats of the language from its `number_format`
+    /// and `date_format` attrs
+    pub fn locale_formats(&self, lang: &str) -> (NumberFormat, DateFormat) {
+        let format = |key: &str, default: &str| {
+            let attr = format!("{lang}.{key}");
+            let value = self.attrs.get(&attr).map(|v| v.trim()).unwrap_or(default);
+            value.to_string()
+        };
+        let number = format("number_format", DEFAULT_NUMBER_FORMAT)
+            .parse()
+            .unwrap_or_else(|e| {
+                tracing::warn!("{e}, using the default");
+                DEFAULT_NUMBER_FORMAT.parse().expect("valid default")
+            });
+        let date = format("date_format", DEFAULT_DATE_FORMAT)
+            .parse()
+            .unwrap_or_else(|e| {
+                tracing::warn!("{e}, using the default");
+                DEFAULT_DATE_FORMAT.parse().expect("valid default")
+            });
+        (number, date)
+    }
+
+    /// Languages the numbers of the original are localized for, from the
+    /// `localize_numbers` attr
+    pub fn localized_languages(&self) -> Vec<String> {
+        self.attrs
+            .get("localize_numbers")
+            .map(|v| {
+                v.split(',')
+                    .map(str::trim)
+                    .filter(|l| !l.is_empty())
+                    .map(|l| self.canonical_language(l))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Glosses the numbers and dates in the text of the originals with how
+    /// the `localize_numbers` languages write them
+    pub fn localize_numbers(&mut self) {
+        let langs = self.localized_languages();
+        if langs.is_empty() {
+            return;
+        }
+        let (number, date) = self.locale_formats(&self.language);
+        let targets: Vec<(String, NumberFormat, DateFormat)> = langs
+            .into_iter()
+            .map(|l| {
+                let (n, d) = self.locale_formats(&l);
+                (l, n, d)
+            })
+            .collect();
+        let re = Regex::new(&format!("({})|({})", date.regex(), number.regex()))
+            .expect("escaped separators");
+        for s in &mut self.sentences {
+            let mut original = Vec::with_capacity(s.original.len());
+            for o in std::mem::take(&mut s.original) {
+                let OrgFragment::Simple(text) = o else {
+                    original.push(o);
+                    continue;
+                };
+                let mut last = 0;
+                for m in re.captures_iter(&text) {
+                    let whole = m.get(0).expect("match");
+                    let rendered: Option<Vec<String>> = if m.get(1).is_some() {
+                        date.parse(whole.as_str())
+                            .map(|dmy| targets.iter().map(|(_, _, d)| d.render(&dmy)).collect())
+                    } else {
+                        number
+                            .parse(whole.as_str())
+                            // four ungrouped digits are most likely a year
+                            .filter(|(i, f)| {
+                                !(i.len() == 4
+                                    && f.is_empty()
+                                    && whole.as_str().chars().count() == 4)
+                            })
+                            .map(|(i, f)| {
+                                targets.iter().map(|(_, n, _)| n.render(&i, &f)).collect()
+                            })
+                    };
+                    let Some(rendered) = rendered else {
+                        continue;
+                    };
+                    if rendered.iter().all(|r| r == whole.as_str()) {
+                        continue;
+                    }
+                    let meanings: Vec<String> = targets
+                        .iter()
+                        .zip(rendered)
+                        .map(|((l, _, _), r)| format!("{l}: {r}"))
+                        .collect();
+                    if whole.start() > last {
+                        original.push(OrgFragment::Simple(text[last..whole.start()].to_string()));
+                    }
+                    original.push(OrgFragment::Meaning(
+                        whole.as_str().to_string(),
+                        intern::meanings(&meanings),
+                    ));
+                    last = whole.end();
+                }
+                if last < text.len() {
+                    original.push(OrgFragment::Simple(text[last..].to_string()));
+                }
+            }
+            s.original = original;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("1,234,567.89", "1234567", "5", "1,234,567.5")]
+    #[case("12,34,567.89", "1234567", "", "12,34,567")]
+    #[case("१२,३४,५६७.८९", "1234567", "25", "१२,३४,५६७.२५")]
+    #[case("1.234.567,89", "123", "", "123")]
+    #[case("1234567.89", "1234", "", "1234")]
+    fn number_format_test(
+        #[case] format: &str,
+        #[case] int: &str,
+        #[case] frac: &str,
+        #[case] expected: &str,
+    ) {
+        let format = NumberFormat::from_str(format).unwrap();
+        let digits = |t: &str| t.bytes().map(|b| b - b'0').collect::<Vec<u8>>();
+        assert_eq!(format.render(&digits(int), &digits(frac)), expected);
+    }
+
+    #[rstest]
+    #[case("1,234,567")]
+    #[case("1,23,4567.89")]
+    #[case("31.12.2024")]
+    fn bad_number_format_test(#[case] format: &str) {
+        assert!(NumberFormat::from_str(format).is_err());
+    }
+
+    #[rstest]
+    #[case("2024-12-31", true)]
+    #[case("2024-1-5", true)]
+    #[case("2024-13-01", false)]
+    #[case("2024-12-32", false)]
+    #[case("2024-00-10", false)]
+    fn date_range_test(#[case] date: &str, #[case] valid: bool) {
+        let format = DateFormat::from_str(DEFAULT_DATE_FORMAT).unwrap();
+        assert_eq!(format.parse(date).is_some(), valid);
+    }
+
+    #[rstest]
+    #[case("in 1999 and 2,000", vec!["2,000"])]
+    #[case("12345 and 1999.5", vec!["12345", "1999.5"])]
+    #[case("१९९९", vec![])]
+    fn year_test(#[case] text: &str, #[case] glossed: Vec<&str>) {
+        let mut chap = Chapter::from_str(&format!(
+            "localize_numbers = german\ngerman.number_format = 1.234.567,89\n\n@ 1\n{text}\n"
+        ))
+        .unwrap();
+        chap.process();
+        let words: Vec<&str> = chap.sentences[0]
+            .original
+            .iter()
+            .filter_map(|o| match o {
+                OrgFragment::Meaning(w, _) => Some(w.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(words, glossed);
+    }
+
+    #[test]
+    fn localize_numbers_test() {
+        let mut chap = Chapter::from_str(
+            "language = nepali\nnepali.number_format = १२,३४,५६७.८९\nnepali.date_format = २०२४-१२-३१\nlocalize_numbers = english, german\ngerman.number_format = 1.234.567,89\ngerman.date_format = 31.12.2024\n\n@ 1\nवि.सं. २०८१-०१-०५ मा १,२५,००० रुपैयाँ << a >> 3\n",
+        )
+        .unwrap();
+        chap.process();
+        let glosses: Vec<(String, String)> = chap.sentences[0]
+            .original
+            .iter()
+            .filter_map(|o| match o {
+                OrgFragment::Meaning(w, _) => Some((w.clone(), o.gloss()?)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            glosses,
+            vec![
+                (
+                    "२०८१-०१-०५".to_string(),
+                    "english: 2081-01-05; german: 05.01.2081".to_string()
+                ),
+                (
+                    "१,२५,०००".to_string(),
+                    "english: 125,000; german: 125.000".to_string()
+                ),
+            ]
+        );
+        assert_eq!(
+            chap.sentences[0].text(),
+            "वि.सं. २०८१-०१-०५ मा १,२५,००० रुपैयाँ a 3"
+        );
+    }
+}
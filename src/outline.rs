@@ -0,0 +1,321 @@
+#[cfg(feature = "html")]
+use crate::syntax::HtmlOptions;
+use crate::syntax::{Chapter, Sentence};
+use crate::text::escape_attr;
+
+/// Sentence attr starting a section of the chapter at the sentence, with
+/// its heading, like `section = The crossing`
+pub const SECTION_ATTR: &str = "section";
+
+/// Chapter attr with the number of the chapter in its book, put before the
+/// numbers of its sections and sentences; numbered books set it
+pub const CHAPTER_NUMBER_ATTR: &str = "chapter_number";
+
+/// Section of a chapter, from the sentence starting it to the next one
+#[derive(Debug, Clone, PartialEq)]
+pub struct Section {
+    /// Number in the outline, `2` or `3.2` in chapter 3
+    pub number: String,
+    pub title: String,
+    /// Index of the sentence it starts at
+    pub start: usize,
+}
+
+impl Section {
+    /// Id of its heading, `section-3.2`
+    pub fn id(&self) -> String {
+        format!("section-{}", escape_attr(&self.number))
+    }
+
+    /// Heading with its number when numbered
+    #[cfg(feature = "html")]
+    fn heading(&self, numbered: bool) -> String {
+        match numbered {
+            true => format!("{} {}", escape_attr(&self.number), self.title),
+            false => self.title.clone(),
+        }
+    }
+}
+
+impl Sentence {
+    /// Heading of the section the sentence starts, from its `section` attr
+    pub fn section(&self) -> Option<&str> {
+        self.orgattrs
+            .get(SECTION_ATTR)
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+    }
+}
+
+impl Chapter {
+    pub fn chapter_number(&self) -> Option<&str> {
+        self.attrs
+            .get(CHAPTER_NUMBER_ATTR)
+            .map(|n| n.trim())
+            .filter(|n| !n.is_empty())
+    }
+
+    fn number_prefix(&self) -> String {
+        self.chapter_number()
+            .map(|n| format!("{n}."))
+            .unwrap_or_default()
+    }
+
+    /// Sections in order, numbered from 1 after the chapter number
+    pub fn sections(&self) -> Vec<Section> {
+        let prefix = self.number_prefix();
+        self.sentences
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| Some((i, s.section()?)))
+            .enumerate()
+            .map(|(k, (start, title))| Section {
+                number: format!("{prefix}{}", k + 1),
+                title: title.to_string(),
+                start,
+            })
+            .collect()
+    }
+
+    /// Number of each sentence in the outline, counted from 1 in its
+    /// section after the section's number, like `3.2` for the second
+    /// sentence of section 3; sentences before the first section are
+    /// counted in the chapter
+    pub fn outline_numbers(&self) -> Vec<String> {
+        let prefix = self.number_prefix();
+        let mut section = None;
+        let mut n = 0;
+        self.sentences
+            .iter()
+            .map(|s| {
+                if s.section().is_some() {
+                    section = Some(section.unwrap_or(0) + 1);
+                    n = 0;
+                }
+                n += 1;
+                match section {
+                    Some(k) => format!("{prefix}{k}.{n}"),
+                    None => format!("{prefix}{n}"),
+                }
+            })
+            .collect()
+    }
+
+    /// Whether the numbered chapter shows outline numbers instead of the
+    /// labels, as it has sections or a chapter number
+    pub fn is_outlined(&self) -> bool {
+        self.chapter_number().is_some() || self.sentences.iter().any(|s| s.section().is_some())
+    }
+
+    /// Outline number shown for the sentence, if the chapter shows them
+    #[cfg(feature = "html")]
+    pub fn outline_number(&self, sentence: &Sentence, opts: &HtmlOptions) -> Option<String> {
+        if !(self.numbered(opts) && self.is_outlined()) {
+            return None;
+        }
+        let i = self
+            .sentences
+            .iter()
+            .position(|s| s.label == sentence.label)?;
+        self.outline_numbers().into_iter().nth(i)
+    }
+
+    /// Title of the page, after the chapter number when numbered
+    #[cfg(feature = "html")]
+    pub(crate) fn title_html(&self, opts: &HtmlOptions) -> String {
+        match self.chapter_number().filter(|_| self.numbered(opts)) {
+            Some(n) => format!("{} {}", escape_attr(n), self.title),
+            None => self.title.clone(),
+        }
+    }
+
+    /// Heading of the section, linking to itself
+    #[cfg(feature = "html")]
+    pub(crate) fn section_html(&self, section: &Section, opts: &HtmlOptions) -> String {
+        format!(
+            "<h3 class=\"section\" id=\"{id}\"><a href=\"#{id}\">{}</a></h3>",
+            section.heading(self.numbered(opts)),
+            id = section.id()
+        )
+    }
+
+    /// Navigation to the section headings, if there are sections
+    #[cfg(feature = "html")]
+    pub(crate) fn contents_html(&self, opts: &HtmlOptions) -> String {
+        let items: String = self
+            .sections()
+            .iter()
+            .map(|s| {
+                format!(
+                    "<li><a href=\"#{}\">{}</a></li>",
+                    s.id(),
+                    s.heading(self.numbered(opts))
+                )
+            })
+            .collect();
+        match items.is_empty() {
+            true => String::new(),
+            false => format!(
+                "<nav class=\"sections\" role=\"doc-toc\" aria-label=\"Sections\"><ol>{items}</ol></nav>"
+            ),
+        }
+    }
+}
+
+/// Chapter as listed in the table of contents of a book
+#[cfg(feature = "html")]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ContentsEntry {
+    pub title: String,
+    pub href: String,
+    /// Chapter number shown before the title, when numbered
+    pub number: Option<String>,
+    /// Difficulty badge shown after the title, like `92% in top 2000`
+    pub difficulty: Option<String>,
+    pub sections: Vec<Section>,
+}
+
+#[cfg(feature = "html")]
+impl ContentsEntry {
+    pub fn new(chap: &Chapter, href: &str, opts: &HtmlOptions) -> Self {
+        let numbered = chap.numbered(opts);
+        Self {
+            title: chap.title.clone(),
+            href: href.to_string(),
+            number: chap.chapter_number().filter(|_| numbered).map(String::from),
+            difficulty: None,
+            sections: chap
+                .sections()
+                .into_iter()
+                .map(|s| Section {
+                    title: s.heading(numbered),
+                    ..s
+                })
+                .collect(),
+        }
+    }
+
+    pub(crate) fn html(&self) -> String {
+        let title = match &self.number {
+            Some(n) => format!("{} {}", escape_attr(n), self.title),
+            None => self.title.clone(),
+        };
+        let sections: String = self
+            .sections
+            .iter()
+            .map(|s| {
+                format!(
+                    "<li><a href=\"{}#{}\">{}</a></li>",
+                    self.href,
+                    s.id(),
+                    s.title
+                )
+            })
+            .collect();
+        let sections = match sections.is_empty() {
+            true => String::new(),
+            false => format!("<ol class=\"sections\">{sections}</ol>"),
+        };
+        let difficulty = match &self.difficulty {
+            Some(d) => format!(" <small class=\"difficulty\">{}</small>", escape_attr(d)),
+            None => String::new(),
+        };
+        format!(
+            "<li><a href=\"{}\">{title}</a>{difficulty}{sections}</li>",
+            self.href
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str = "title = Viaje\n\n@ 1\na\n\n@ 2\nb\nsection = Salida\n\n@ 3\nc\n\n@ 4\nd\nsection = Llegada\n";
+
+    #[rstest]
+    #[case("", &["1", "1.1", "1.2", "2.1"], &["1", "2"])]
+    #[case("chapter_number = 3\n", &["3.1", "3.1.1", "3.1.2", "3.2.1"], &["3.1", "3.2"])]
+    fn outline_test(#[case] attrs: &str, #[case] sentences: &[&str], #[case] sections: &[&str]) {
+        let chap = Chapter::from_str(&format!("{attrs}{CHAP}")).unwrap();
+        assert_eq!(chap.outline_numbers(), sentences);
+        let numbers: Vec<String> = chap.sections().into_iter().map(|s| s.number).collect();
+        assert_eq!(numbers, sections);
+        assert_eq!(chap.sections()[1].start, 3);
+    }
+
+    #[cfg(feature = "html")]
+    #[rstest]
+    #[case(
+        false,
+        None,
+        "<h3 class=\"section\" id=\"section-1\"><a href=\"#section-1\">Salida</a></h3>"
+    )]
+    #[case(
+        true,
+        Some("1.1"),
+        "<h3 class=\"section\" id=\"section-1\"><a href=\"#section-1\">1 Salida</a></h3>"
+    )]
+    fn html_test(#[case] numbered: bool, #[case] shown: Option<&str>, #[case] heading: &str) {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        let opts = HtmlOptions {
+            numbered,
+            ..Default::default()
+        };
+        assert_eq!(
+            chap.outline_number(&chap.sentences[1], &opts).as_deref(),
+            shown
+        );
+        let mut html = Vec::new();
+        chap.write_html(&mut html, &opts).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(heading), "{html}");
+        assert_eq!(html.matches("class=\"section\"").count(), 2);
+        assert!(html.contains("role=\"doc-toc\""));
+        if let Some(n) = shown {
+            assert!(html.contains(&format!(">{n}</a>")), "{html}");
+        }
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn unsectioned_test() {
+        let chap = Chapter::from_str("@ 1\na\n\n@ 2\nb\n").unwrap();
+        let opts = HtmlOptions {
+            numbered: true,
+            ..Default::default()
+        };
+        assert!(!chap.is_outlined());
+        assert_eq!(chap.outline_number(&chap.sentences[0], &opts), None);
+        assert_eq!(chap.contents_html(&opts), "");
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn contents_entry_test() {
+        let chap = Chapter::from_str(&format!("chapter_number = 2\n{CHAP}")).unwrap();
+        let opts = HtmlOptions {
+            numbered: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            ContentsEntry::new(&chap, "viaje.html", &opts).html(),
+            "<li><a href=\"viaje.html\">2 Viaje</a><ol class=\"sections\"><li><a href=\"viaje.html#section-2.1\">2.1 Salida</a></li><li><a href=\"viaje.html#section-2.2\">2.2 Llegada</a></li></ol></li>"
+        );
+        assert_eq!(
+            ContentsEntry::new(&chap, "viaje.html", &Default::default()).html(),
+            "<li><a href=\"viaje.html\">Viaje</a><ol class=\"sections\"><li><a href=\"viaje.html#section-2.1\">Salida</a></li><li><a href=\"viaje.html#section-2.2\">Llegada</a></li></ol></li>"
+        );
+        let entry = ContentsEntry {
+            difficulty: Some("92% in top 2000".to_string()),
+            sections: Vec::new(),
+            ..ContentsEntry::new(&chap, "viaje.html", &Default::default())
+        };
+        assert_eq!(
+            entry.html(),
+            "<li><a href=\"viaje.html\">Viaje</a> <small class=\"difficulty\">92% in top 2000</small></li>"
+        );
+    }
+}
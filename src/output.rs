@@ -0,0 +1,87 @@
+use crate::lrc::CREATOR_TAG;
+use crate::schema;
+use crate::syntax::{Chapter, SourceMap};
+use crate::widget::Bundle;
+use std::io::Read;
+use std::path::Path;
+
+/// Comment at the start of generated HTML, so rebuilding can replace it
+pub const GENERATED_MARKER: &str = "<!-- Generated by transdoc -->";
+
+/// Whether the file was written by transdoc: HTML with the marker
+/// comment near the start, timed lyrics naming it as their creator, or a
+/// source map, widget bundle or chapter JSON with its schema version
+pub fn is_generated(file: &Path) -> bool {
+    let Ok(f) = std::fs::File::open(file) else {
+        return false;
+    };
+    let mut head = Vec::new();
+    if f.take(1024).read_to_end(&mut head).is_err() {
+        return false;
+    }
+    let head = String::from_utf8_lossy(&head);
+    if head.contains(GENERATED_MARKER) || head.lines().any(|l| l == CREATOR_TAG) {
+        return true;
+    }
+    std::fs::read_to_string(file).is_ok_and(|s| {
+        schema::is_versioned(&s)
+            && (serde_json::from_str::<SourceMap>(&s).is_ok()
+                || serde_json::from_str::<Bundle>(&s).is_ok()
+                || Chapter::from_json(&s).is_ok())
+    })
+}
+
+/// Writes to a temporary file next to the target and renames it over the
+/// target, so it is never left half written
+pub fn write_atomic(file: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let dir = match file.parent() {
+        Some(d) if !d.as_os_str().is_empty() => d,
+        _ => Path::new("."),
+    };
+    let name = file
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let tmp = dir.join(format!(".{name}.{}.tmp", std::process::id()));
+    let res = std::fs::write(&tmp, contents).and_then(|_| std::fs::rename(&tmp, file));
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_atomic_test() {
+        let dir = std::env::temp_dir().join(format!("transdoc-output-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let html = dir.join("out.html");
+        write_atomic(&html, b"<p>x</p>").unwrap();
+        assert_eq!(std::fs::read_to_string(&html).unwrap(), "<p>x</p>");
+        assert!(!is_generated(&html));
+
+        write_atomic(&html, format!("{GENERATED_MARKER}\n<p>y</p>").as_bytes()).unwrap();
+        assert!(is_generated(&html));
+        assert!(!is_generated(&dir.join("missing.html")));
+
+        let map = dir.join("out.json");
+        // JSON of the user without the schema version is kept
+        write_atomic(&map, b"[]").unwrap();
+        assert!(!is_generated(&map));
+        write_atomic(&map, b"{\"sentences\": []}").unwrap();
+        assert!(!is_generated(&map));
+        let source_map = SourceMap { sentences: vec![] };
+        write_atomic(&map, schema::versioned_json(&source_map, true).as_bytes()).unwrap();
+        assert!(is_generated(&map));
+        let chap: Chapter = "@ 1\na\n".parse().unwrap();
+        write_atomic(&map, chap.to_json(true).as_bytes()).unwrap();
+        assert!(is_generated(&map));
+
+        // only the target is left behind
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+#[cfg(feature = "html")]
+use crate::html::escape_attr;
+use crate::syntax::{Chapter, Sentence};
+
+/// Sentence attr with the page of the printed original the sentence is on,
+/// like `page = 37` or `page = xii`; the sentences after it are on the same
+/// page until the next one
+pub const PAGE_ATTR: &str = "page";
+
+impl Sentence {
+    /// Page given by the sentence's own `page` attr
+    pub fn page(&self) -> Option<&str> {
+        self.orgattrs
+            .get(PAGE_ATTR)
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+    }
+}
+
+impl Chapter {
+    /// Page of each sentence, carried over from the last one giving it
+    pub fn pages(&self) -> Vec<Option<&str>> {
+        let mut page = None;
+        self.sentences
+            .iter()
+            .map(|s| {
+                page = s.page().or(page);
+                page
+            })
+            .collect()
+    }
+
+    /// Page each sentence starts, if it is the first one on it
+    pub fn page_breaks(&self) -> Vec<Option<&str>> {
+        let pages = self.pages();
+        pages
+            .iter()
+            .enumerate()
+            .map(|(i, p)| match i {
+                0 => *p,
+                _ => p.filter(|_| pages[i - 1] != *p),
+            })
+            .collect()
+    }
+
+    /// Keeps the sentences for which `keep` is true, giving the kept ones a
+    /// `page` attr where they would otherwise lose the page of a dropped one
+    pub fn retain_sentences(&mut self, mut keep: impl FnMut(usize, &Sentence) -> bool) {
+        let pages: Vec<Option<String>> = self
+            .pages()
+            .into_iter()
+            .map(|p| p.map(String::from))
+            .collect();
+        let mut kept_page = None;
+        let mut i = 0;
+        self.sentences.retain_mut(|s| {
+            let page = &pages[i];
+            let kept = keep(i, s);
+            i += 1;
+            if !kept {
+                return false;
+            }
+            if let Some(p) = page
+                && s.page().is_none()
+                && kept_page.as_ref() != Some(p)
+            {
+                s.orgattrs.insert(PAGE_ATTR.to_string(), p.clone());
+            }
+            kept_page = page.clone();
+            true
+        });
+    }
+
+    /// Navigation to the page markers, like the page list of an EPUB
+    #[cfg(feature = "html")]
+    pub(crate) fn page_list_html(&self) -> String {
+        let links: Vec<String> = self
+            .page_breaks()
+            .into_iter()
+            .flatten()
+            .map(|p| format!("<a href=\"#{}\">{}</a>", page_id(p), escape_attr(p)))
+            .collect();
+        match links.is_empty() {
+            true => String::new(),
+            false => format!(
+                "<nav class=\"pages\" role=\"doc-pagelist\" aria-label=\"Pages of the original\">Pages: {}</nav>",
+                links.join(" ")
+            ),
+        }
+    }
+}
+
+/// Id of the marker of the page, `page-37`
+#[cfg(feature = "html")]
+fn page_id(page: &str) -> String {
+    let page: String = page
+        .chars()
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .collect();
+    format!("page-{}", escape_attr(&page))
+}
+
+/// Marker in the margin where a page of the original starts
+#[cfg(feature = "html")]
+pub(crate) fn page_marker(page: Option<&str>) -> String {
+    match page {
+        Some(p) => format!(
+            "<span class=\"page\" id=\"{}\" role=\"doc-pagebreak\" aria-label=\"Page {p}\">{p}</span>",
+            page_id(p),
+            p = escape_attr(p)
+        ),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str =
+        "@ 1\na\npage = 36\n\n@ 2\nb\n\n@ 3\nc\npage = 37\n\n@ 4\nd\n\n@ 5\ne\npage = 37\n";
+
+    #[test]
+    fn pages_test() {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        assert_eq!(
+            chap.pages(),
+            [Some("36"), Some("36"), Some("37"), Some("37"), Some("37")]
+        );
+        assert_eq!(
+            chap.page_breaks(),
+            [Some("36"), None, Some("37"), None, None]
+        );
+        let chap = Chapter::from_str("@ 1\na\n\n@ 2\nb\npage = xii\n").unwrap();
+        assert_eq!(chap.page_breaks(), [None, Some("xii")]);
+    }
+
+    #[rstest]
+    #[case(&["2", "4"], &[Some("36"), Some("37")])]
+    #[case(&["1", "2", "4"], &[Some("36"), None, Some("37")])]
+    #[case(&["4", "5"], &[Some("37"), Some("37")])]
+    fn retain_test(#[case] labels: &[&str], #[case] expected: &[Option<&str>]) {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.retain_sentences(|_, s| labels.contains(&s.label.as_str()));
+        let pages: Vec<Option<&str>> = chap.sentences.iter().map(Sentence::page).collect();
+        assert_eq!(pages, expected);
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn html_test() {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        assert_eq!(
+            chap.page_list_html(),
+            "<nav class=\"pages\" role=\"doc-pagelist\" aria-label=\"Pages of the original\">Pages: <a href=\"#page-36\">36</a> <a href=\"#page-37\">37</a></nav>"
+        );
+        assert_eq!(
+            page_marker(Some("x ii")),
+            "<span class=\"page\" id=\"page-x-ii\" role=\"doc-pagebreak\" aria-label=\"Page x ii\">x ii</span>"
+        );
+        let mut html = Vec::new();
+        chap.write_html(&mut html, &Default::default()).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert_eq!(html.matches("role=\"doc-pagebreak\"").count(), 2);
+    }
+}
@@ -1,18 +1,27 @@
 use crate::{
     components::*,
-    errors::{MatchRes, ParseError},
+    conditional::{split_conditional, CONDITIONAL_START},
+    errors::{Diagnostic, MatchRes, ParseError, Severity},
+    intern,
+    known::{self, KnownLevels},
     syntax::*,
-    tokenizer::TokenList,
+    text,
+    tokenizer::{TokenList, TokenType},
+    typed,
+    words::{parse_word_list, DictPattern, PATTERN_PREFIX},
 };
+use indexmap::IndexMap;
 use nom::{
     branch::alt,
-    combinator::{map, opt},
-    multi::{many0, separated_list1},
+    combinator::{map, map_opt, opt},
+    multi::{many0, many1, separated_list1},
     sequence::{delimited, pair, preceded, separated_pair},
     Finish, Parser,
 };
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::Arc;
 
 pub fn linetag(inp: TokenList<'_>) -> MatchRes<'_, String> {
     delimited(at, maybe_space(string_val), many1_newlines).parse(inp)
@@ -22,33 +31,105 @@ pub fn str_trimmed(inp: TokenList<'_>) -> MatchRes<'_, String> {
     map(string_val, |s| s.trim().to_string()).parse(inp)
 }
 
+pub fn dict_entry(inp: TokenList<'_>) -> MatchRes<'_, (String, Vec<String>)> {
+    separated_pair(
+        str_trimmed,
+        maybe_space(equal),
+        maybe_space(separated_list1(
+            maybe_space(semicolon),
+            maybe_space(str_trimmed),
+        )),
+    )
+    .parse(inp)
+}
+
 pub fn dict_meaning(inp: TokenList<'_>) -> MatchRes<'_, OrgFragment> {
-    map(
+    map(dict_entry, |(v, m)| {
+        OrgFragment::Meaning(v, intern::meanings(&m))
+    })
+    .parse(inp)
+}
+
+/// `word = @2` picking a sense of the word's dictionary entry
+pub fn dict_sense(inp: TokenList<'_>) -> MatchRes<'_, OrgFragment> {
+    map_opt(
         separated_pair(
             str_trimmed,
             maybe_space(equal),
-            maybe_space(separated_list1(
-                maybe_space(semicolon),
-                maybe_space(str_trimmed),
-            )),
+            preceded(maybe_space(at), str_trimmed),
         ),
-        |(v, m)| OrgFragment::Meaning(v, m),
+        |(w, n)| {
+            Some(OrgFragment::Sense(
+                w,
+                sense_number(&n)?,
+                Meanings::default(),
+            ))
+        },
     )
     .parse(inp)
 }
 
+/// Sense of a dictionary entry, counted from 1
+fn sense_number(s: &str) -> Option<usize> {
+    s.trim().parse().ok().filter(|n| *n > 0)
+}
+
+/// Lookup of one sense like `bank#2`
+fn sense_lookup(s: &str) -> Option<OrgFragment> {
+    let (word, n) = s.rsplit_once('#')?;
+    let n = sense_number(n)?;
+    let word = word.trim();
+    (!word.is_empty()).then(|| OrgFragment::Sense(word.to_string(), n, Meanings::default()))
+}
+
+/// Entries of a dictionary file, `word = meaning; meaning` per line
+pub fn dict_entries(inp: TokenList<'_>) -> MatchRes<'_, Vec<(String, Vec<String>)>> {
+    many0(newline_terminated(maybe_newline(dict_entry))).parse(inp)
+}
+
+/// Dictionary entry with the byte offset it starts at
+type LocatedEntry = ((String, Vec<String>), usize);
+
+fn dict_entry_located(inp: TokenList<'_>) -> MatchRes<'_, LocatedEntry> {
+    let offset = inp.first().map(|t| t.offset).unwrap_or_default();
+    map(dict_entry, |e| (e, offset)).parse(inp)
+}
+
+/// `dict_entries` with the byte offset each entry starts at
+fn dict_entries_located(inp: TokenList<'_>) -> MatchRes<'_, Vec<LocatedEntry>> {
+    many0(newline_terminated(maybe_newline(dict_entry_located))).parse(inp)
+}
+
 pub fn org_frag_dict(inp: TokenList<'_>) -> MatchRes<'_, OrgFragment> {
     delimited(
         angle_start,
         maybe_space(alt((
+            dict_sense,
             dict_meaning,
-            map(str_trimmed, OrgFragment::DictLookup),
+            map(str_trimmed, |s| match s.strip_prefix('?') {
+                Some(c) => {
+                    let (lang, text) = split_conditional(c);
+                    OrgFragment::Conditional(lang, text)
+                }
+                None => sense_lookup(&s).unwrap_or(OrgFragment::DictLookup(s)),
+            }),
         ))),
         maybe_space(angle_end),
     )
     .parse(inp)
 }
 
+pub fn org_frag_plugin(inp: TokenList<'_>) -> MatchRes<'_, OrgFragment> {
+    map_opt(delimited(brace_start, inline_text, brace_end), |s| {
+        let (name, content) = s.split_once(':')?;
+        Some(OrgFragment::Plugin(
+            name.trim().to_string(),
+            content.trim().to_string(),
+        ))
+    })
+    .parse(inp)
+}
+
 pub fn lines_separator(inp: TokenList<'_>) -> MatchRes<'_, Option<String>> {
     newline_terminated(preceded(dash, maybe_space(opt(str_trimmed)))).parse(inp)
 }
@@ -60,31 +141,60 @@ pub fn key_val(inp: TokenList<'_>) -> MatchRes<'_, (String, String)> {
 pub fn original_sentence(inp: TokenList<'_>) -> MatchRes<'_, Vec<OrgFragment>> {
     newline_terminated(many0(alt((
         org_frag_dict,
+        org_frag_plugin,
         map(string_val, OrgFragment::Simple),
     ))))
     .parse(inp)
 }
 
-pub fn attrs(inp: TokenList<'_>) -> MatchRes<'_, HashMap<String, String>> {
+pub fn attrs(inp: TokenList<'_>) -> MatchRes<'_, IndexMap<String, String>> {
     map(many0(newline_terminated(maybe_newline(key_val))), |vals| {
         vals.into_iter().collect()
     })
     .parse(inp)
 }
 
-pub fn tl_sentence(inp: TokenList<'_>) -> MatchRes<'_, Translation> {
+/// `<<?language text>>` clause of a translation, kept as text
+pub fn tl_conditional(inp: TokenList<'_>) -> MatchRes<'_, String> {
+    map_opt(delimited(angle_start, string_val, angle_end), |s| {
+        let c = s.trim().strip_prefix('?')?;
+        Some(format!("{CONDITIONAL_START}{}>>", c.trim()))
+    })
+    .parse(inp)
+}
+
+/// Text of a translation with its conditional clauses; fragments aren't
+/// rendered in translations, so their braces are text
+pub fn tl_text(inp: TokenList<'_>) -> MatchRes<'_, String> {
     map(
-        pair(newline_terminated(string_val), maybe_newline(attrs)),
-        |(s, a)| Translation {
-            content: s,
-            attrs: a,
+        many1(alt((
+            string_val,
+            tl_conditional,
+            map(alt((brace_start, brace_end)), |t| t.content.to_string()),
+        ))),
+        |parts| parts.concat(),
+    )
+    .parse(inp)
+}
+
+/// Translation with its attrs; it can be empty if it has attrs, like one
+/// left out with `status = skipped`
+pub fn tl_sentence(inp: TokenList<'_>) -> MatchRes<'_, Translation> {
+    map_opt(
+        pair(opt(newline_terminated(tl_text)), maybe_newline(attrs)),
+        |(s, a)| match (s, a) {
+            (None, a) if a.is_empty() => None,
+            (s, a) => Some(Translation {
+                content: s.unwrap_or_default(),
+                attrs: a,
+            }),
         },
     )
     .parse(inp)
 }
 
 pub fn sentence(inp: TokenList<'_>) -> MatchRes<'_, Sentence> {
-    map(
+    let (rest, mut sent) = map(
         (
             linetag,
             maybe_newline(original_sentence),
@@ -103,44 +213,206 @@ pub fn sentence(inp: TokenList<'_>) -> MatchRes<'_, Sentence> {
                 .enumerate()
                 .map(|(i, (l, c))| (l.unwrap_or_else(|| i.to_string()), c))
                 .collect(),
+            span: Span::default(),
         },
     )
-    .parse(inp)
+    .parse(inp.clone())?;
+    let used = &inp[..inp.len() - rest.len()];
+    // the blank and comment lines after the sentence aren't part of it
+    let text = used.iter().rev().find(|t| {
+        !matches!(
+            t.ty,
+            TokenType::WhiteSpace | TokenType::NewLine | TokenType::Comment
+        )
+    });
+    if let (Some(first), Some(last)) = (used.first(), text) {
+        sent.span.start = first.offset;
+        sent.span.end = last.end();
+    }
+    Ok((rest, sent))
 }
 
 pub fn chapter(inp: TokenList<'_>) -> MatchRes<'_, Chapter> {
-    map(pair(attrs, many0(maybe_newline(sentence))), |(a, s)| {
+    map(chapter_parts, |(a, s)| Chapter::new(a, s)).parse(inp)
+}
+
+type ChapterParts = (IndexMap<String, String>, Vec<Sentence>);
+
+fn chapter_parts(inp: TokenList<'_>) -> MatchRes<'_, ChapterParts> {
+    pair(attrs, many0(maybe_newline(sentence))).parse(inp)
+}
+
+/// Path of a file named in a chapter attr: relative to the chapter's
+/// directory, or to the working directory when it is only found there
+pub fn resolve_path(dir: &Path, file: &str) -> PathBuf {
+    let path = dir.join(file);
+    if !path.exists() && Path::new(file).exists() {
+        PathBuf::from(file)
+    } else {
+        path
+    }
+}
+
+/// Warning about a chapter attr, at its line when the source is known
+fn attr_diagnostic(
+    src: Option<&str>,
+    key: &str,
+    rule: &'static str,
+    message: String,
+) -> Diagnostic {
+    let line = src.and_then(|s| {
+        s.lines()
+            .enumerate()
+            .find(|(_, l)| l.split_once('=').is_some_and(|(k, _)| k.trim() == key))
+    });
+    Diagnostic {
+        severity: Severity::Warning,
+        line: line.map_or(0, |(i, _)| i + 1),
+        col: line.map_or(0, |_| 1),
+        message,
+        linestr: line.map(|(_, l)| l.to_string()),
+        rule: Some(rule),
+        ..Default::default()
+    }
+}
+
+impl Chapter {
+    /// Chapter from its attrs and sentences, loading the dictionary file if any
+    pub fn new(attrs: IndexMap<String, String>, sentences: Vec<Sentence>) -> Self {
+        Self::load(attrs, sentences, Path::new(""), None)
+    }
+
+    /// Chapter loading the files its attrs name from the directory, with
+    /// the source to locate the attrs in
+    fn load(
+        attrs: IndexMap<String, String>,
+        sentences: Vec<Sentence>,
+        dir: &Path,
+        src: Option<&str>,
+    ) -> Self {
+        let mut diagnostics = Vec::new();
+        let dict = match attrs.get("dictionary") {
+            Some(d) => {
+                let path = resolve_path(dir, d);
+                load_dictionary(&path).unwrap_or_else(|e| {
+                    diagnostics.push(attr_diagnostic(
+                        src,
+                        "dictionary",
+                        "dictionary",
+                        format!(
+                            "Can't read the dictionary {}: {e}, none of its words are glossed",
+                            path.to_string_lossy()
+                        ),
+                    ));
+                    Dictionary::default()
+                })
+            }
+            None => Dictionary::default(),
+        };
+        diagnostics.extend(dict.diagnostics);
+        for (key, e) in typed::check_attrs(&attrs, typed::CHAPTER_ATTRS) {
+            diagnostics.push(attr_diagnostic(
+                src,
+                &key,
+                "attr-type",
+                format!("{e}, using the default"),
+            ));
+        }
+        let mut known_levels = KnownLevels::new();
+        let known_words = match attrs.get("known_words") {
+            Some(f) if resolve_path(dir, f).is_dir() => {
+                let path = resolve_path(dir, f);
+                let order: Vec<&str> = attrs
+                    .get("known_levels")
+                    .map(|l| l.split(',').map(str::trim).collect())
+                    .unwrap_or_default();
+                match known::load_levels(&path, &order) {
+                    Ok(levels) => known_levels = levels,
+                    Err(e) => diagnostics.push(attr_diagnostic(
+                        src,
+                        "known_words",
+                        "known-words",
+                        format!("Can't read the known words {}: {e}", path.to_string_lossy()),
+                    )),
+                }
+                known_levels.values().flatten().cloned().collect()
+            }
+            Some(f) => {
+                let path = resolve_path(dir, f);
+                match text::read_text(&path) {
+                    Ok(s) => parse_word_list(&s),
+                    Err(e) => {
+                        diagnostics.push(attr_diagnostic(
+                            src,
+                            "known_words",
+                            "known-words",
+                            format!("Can't read the known words {}: {e}", path.to_string_lossy()),
+                        ));
+                        HashSet::new()
+                    }
+                }
+            }
+            None => HashSet::new(),
+        };
         Chapter {
-            title: a
+            title: attrs
                 .get("title")
                 .map(String::from)
                 .unwrap_or("Unnamed Chapter".into()),
-            language: a
+            language: attrs
                 .get("language")
                 .map(String::from)
                 .unwrap_or("english".into()),
-            tl_languages: a
-                .get("tranlations")
+            tl_languages: attrs
+                .get(TRANSLATIONS_ATTR)
+                .or_else(|| attrs.get(TRANSLATIONS_ALIAS))
                 .map(|v| v.split(",").map(|l| l.to_string()).collect())
                 .unwrap_or_default(),
-            dictionary: a
-                .get("dictionary")
-                .map(|d| load_dictionary(d))
-                .unwrap_or_default(),
-            sentences: s,
-            attrs: a,
+            dictionary: dict.entries,
+            patterns: dict.patterns,
+            provenance: dict.provenance,
+            diagnostics,
+            notes: IndexMap::new(),
+            known_words,
+            known_levels,
+            sentences,
+            attrs,
+            dir: dir.to_path_buf(),
         }
-    })
-    .parse(inp)
+    }
 }
 
 impl FromStr for Chapter {
     type Err = ParseError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let tokens = crate::tokenizer::get_tokens(s);
-        match chapter(TokenList::new(&tokens)).finish() {
-            Ok((rest, chapter)) => {
+        Self::parse_with(s, crate::tokenizer::DEFAULT_COMMENT)
+    }
+}
+
+impl Chapter {
+    /// Parses the chapter with comments started by the given token
+    /// instead of `#`
+    pub fn parse_with(s: &str, comment: &str) -> Result<Self, ParseError> {
+        Self::parse_in(s, comment, Path::new(""))
+    }
+
+    /// `parse_with` for a chapter in the directory, which the files named
+    /// by its attrs are relative to
+    pub fn parse_in(s: &str, comment: &str, dir: &Path) -> Result<Self, ParseError> {
+        let s = text::strip_bom(s);
+        let tokens = crate::tokenizer::get_tokens_with(s, comment);
+        match chapter_parts(TokenList::new(&tokens)).finish() {
+            Ok((rest, (attrs, sentences))) => {
                 if rest.is_empty() {
+                    let mut chapter = Chapter::load(attrs, sentences, dir, Some(s));
+                    let mut prev = Span {
+                        line: 1,
+                        ..Default::default()
+                    };
+                    for sent in &mut chapter.sentences {
+                        sent.span.locate_after(s, &prev);
+                        prev = sent.span;
+                    }
                     Ok(chapter)
                 } else {
                     let err = maybe_newline(sentence)
@@ -163,35 +435,102 @@ impl FromStr for Chapter {
     }
 }
 
-fn load_dictionary(file: &str) -> HashMap<String, Vec<String>> {
-    let mut dict = HashMap::new();
-    if let Ok(s) = std::fs::read_to_string(file) {
+/// Contents of a dictionary file
+#[derive(Default)]
+struct Dictionary {
+    entries: HashMap<Arc<str>, Meanings>,
+    patterns: Vec<DictPattern>,
+    provenance: HashMap<Arc<str>, Provenance>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// Entries of the dictionary file, an error when it can't be read
+fn load_dictionary(path: &Path) -> Result<Dictionary, String> {
+    let mut dict = Dictionary::default();
+    let file = &*path.to_string_lossy();
+    let bytes = std::fs::read(path).map_err(|e| e.to_string())?;
+    let contents = text::encoding()
+        .decode(&bytes)
+        .map(|t| t.into_owned())
+        .map_err(|e| {
+            dict.diagnostics
+                .push(Diagnostic::file(Severity::Warning, file, e))
+        })
+        .ok();
+    if let Some(s) = contents {
         let tokens = crate::tokenizer::get_tokens(&s);
 
-        match trailing_newlines(attrs)
+        match trailing_newlines(dict_entries_located)
             .parse(TokenList::new(&tokens))
             .finish()
         {
-            Ok((rest, attrs)) => {
-                dict.extend(attrs.into_iter().map(|(k, v)| (k, vec![v])));
+            Ok((rest, entries)) => {
+                // entries come in file order, each located from the one before
+                let mut prev = Span {
+                    line: 1,
+                    ..Default::default()
+                };
+                for ((w, m), start) in entries {
+                    let mut span = Span {
+                        start,
+                        ..Default::default()
+                    };
+                    span.locate_after(&s, &prev);
+                    prev = span;
+                    let provenance = Provenance::File {
+                        file: file.to_string(),
+                        line: span.line,
+                    };
+                    let meanings = intern::meanings(&m);
+                    if let Some(pattern) = w.strip_prefix(PATTERN_PREFIX) {
+                        match DictPattern::new(pattern, meanings) {
+                            Ok(p) => {
+                                // a repeated pattern keeps its place
+                                match dict.patterns.iter_mut().find(|q| q.key() == w) {
+                                    Some(q) => *q = p,
+                                    None => dict.patterns.push(p),
+                                }
+                                dict.provenance.insert(intern::intern(&w), provenance);
+                            }
+                            Err(e) => dict.diagnostics.push(Diagnostic {
+                                line: span.line,
+                                col: span.col,
+                                ..Diagnostic::file(Severity::Warning, file, e)
+                            }),
+                        }
+                        continue;
+                    }
+                    let w = intern::intern(&w);
+                    dict.provenance.insert(w.clone(), provenance);
+                    dict.entries.insert(w, meanings);
+                }
                 if !rest.is_empty() {
-                    let err = key_val(rest)
+                    let err = dict_entry(rest)
                         .finish()
                         .expect_err("Rest should be empty if network parse is complete");
-                    eprintln!(
-                        "{}",
-                        ParseError::new(TokenList::new(&tokens), err.internal.input, err.ty,)
-                            .user_msg(Some(file))
-                    )
+                    dict.diagnostics.push(
+                        ParseError::new(TokenList::new(&tokens), err.internal.input, err.ty)
+                            .diagnostic(Some(file)),
+                    );
                 }
             }
-            Err(e) => eprintln!(
-                "{}",
-                ParseError::new(TokenList::new(&tokens), e.internal.input, e.ty,)
+            Err(e) => dict.diagnostics.push(
+                ParseError::new(TokenList::new(&tokens), e.internal.input, e.ty)
+                    .diagnostic(Some(file)),
             ),
         }
     }
-    dict
+    tracing::debug!(
+        "{} dictionary entries and {} patterns loaded from {file}",
+        dict.entries.len(),
+        dict.patterns.len()
+    );
+    // a broken dictionary still gives the entries before the problem
+    dict.diagnostics.iter_mut().for_each(|d| {
+        d.severity = Severity::Warning;
+        d.rule = Some("dictionary");
+    });
+    Ok(dict)
 }
 
 #[cfg(test)]
@@ -206,12 +545,26 @@ mod tests {
     #[case("@what")]
     #[case("何か?")]
     #[case("यो काम गर्छ र")]
+    #[case("a {{x}} b")]
+    #[case("a {{ruby:x}} }} {{")]
     fn sentence_valid_test(#[case] txt: &str) {
         let tk = get_tokens(txt);
         let (rest, _) = original_sentence(TokenList::new(&tk)).unwrap();
         assert_eq!(rest, TokenList::new(&[]));
     }
 
+    #[rstest]
+    #[case("@ 1\na {{x}}\n---\nb {{ c }}\n", "a {{x}}", "b {{ c }}")]
+    #[case("@ 1\n}} a {{\n---\n}}\n", "}} a {{", "}}")]
+    #[case("@ 1\na\n---\n{{ruby:x}}\n", "a", "{{ruby:x}}")]
+    #[case("@ 1\n{{ruby:漢字|かんじ}} {{img:a.png|cat}}\n---\nb\n", "漢字 cat", "b")]
+    fn braces_as_text_test(#[case] txt: &str, #[case] original: &str, #[case] translation: &str) {
+        let chap = Chapter::from_str(txt).unwrap();
+        let s = &chap.sentences[0];
+        assert_eq!(s.text(), original);
+        assert_eq!(s.translations["0"].content, translation);
+    }
+
     #[rstest]
     #[case("<<abb>>")]
     #[case("<< ab b>>")]
@@ -227,6 +580,20 @@ mod tests {
         assert_eq!(rest, TokenList::new(&[]));
     }
 
+    #[rstest]
+    #[case("{{ruby:漢字|かんじ}}")]
+    #[case("{{ verse : 3 }}")]
+    #[case("{{chem:H2O = water; <<x>>}}")]
+    #[should_panic]
+    #[case("{{no name}}")]
+    #[should_panic]
+    #[case("{{ruby:x")]
+    fn org_frag_plugin_test(#[case] txt: &str) {
+        let tk = get_tokens(txt);
+        let (rest, _) = org_frag_plugin(TokenList::new(&tk)).unwrap();
+        assert_eq!(rest, TokenList::new(&[]));
+    }
+
     #[rstest]
     #[case("a = b")]
     #[case("a = b")]
@@ -238,4 +605,125 @@ mod tests {
         let (rest, _) = dict_meaning(TokenList::new(&tk)).unwrap();
         assert_eq!(rest, TokenList::new(&[]));
     }
+
+    #[rstest]
+    #[case("@ a\nx\n", 0, 1, 1)]
+    #[case("title = t\n\n@ a\nx\n", 11, 3, 1)]
+    #[case("# c\n  @ a\nx\n", 6, 2, 3)]
+    fn sentence_span_test(
+        #[case] txt: &str,
+        #[case] start: usize,
+        #[case] line: usize,
+        #[case] col: usize,
+    ) {
+        let chap = Chapter::from_str(txt).unwrap();
+        let span = chap.sentences[0].span;
+        assert_eq!((span.start, span.line, span.col), (start, line, col));
+        assert_eq!(&txt[span.start..span.start + 1], "@");
+    }
+
+    #[rstest]
+    #[case("\r\n")]
+    #[case("\r")]
+    fn line_ends_test(#[case] end: &str) {
+        let lf = include_str!("../sample.chapter");
+        let converted = lf.replace('\n', end);
+        // every other line with the other line end; a lone `\r` before an
+        // empty `\n` line would be a windows line end, so only mix those
+        let mixed: String = lf
+            .split_inclusive('\n')
+            .enumerate()
+            .map(|(i, l)| match i % 2 {
+                0 => l.replace('\n', end),
+                _ => l.to_string(),
+            })
+            .collect();
+        let expected = Chapter::from_str(lf).unwrap();
+        let lines = |c: &Chapter| -> Vec<(usize, usize)> {
+            c.sentences
+                .iter()
+                .map(|s| (s.span.line, s.span.col))
+                .collect()
+        };
+        let texts = match end {
+            "\r\n" => vec![converted, mixed],
+            _ => vec![converted],
+        };
+        for txt in texts {
+            let chap = Chapter::from_str(&txt).unwrap();
+            assert_eq!(chap, expected);
+            assert_eq!(lines(&chap), lines(&expected));
+        }
+    }
+
+    #[rstest]
+    #[case("translations")]
+    #[case("tranlations")]
+    fn translations_attr_test(#[case] key: &str) {
+        let chap = Chapter::from_str(&format!("{key} = english,german\n\n@ 1\na\n")).unwrap();
+        assert_eq!(chap.tl_languages, vec!["english", "german"]);
+        assert_eq!(chap.translations_key(), key);
+        assert!(chap.diagnostics.is_empty());
+    }
+
+    #[rstest]
+    #[case("@ a\nx\n\n@ b\n<< y\n")]
+    #[case("@ a\r\nx\r\n\r\n@ b\r\n<< y\r\n")]
+    #[case("@ a\r\nx\n\r\n@ b\n<< y\r\n")]
+    fn error_line_test(#[case] txt: &str) {
+        let err = Chapter::from_str(txt).unwrap_err().diagnostic(None);
+        assert_eq!(err.line, 5);
+    }
+
+    #[test]
+    fn dictionary_provenance_test() {
+        let file =
+            std::env::temp_dir().join(format!("transdoc-provenance-{}.dict", std::process::id()));
+        std::fs::write(
+            &file,
+            "# words\na = x\n\nb = y; z\na = w\nre:.*ment = noun\nre:( = bad\n",
+        )
+        .unwrap();
+        let name = file.to_string_lossy().to_string();
+        let dict = load_dictionary(&file).unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(dict.entries["a"].join("; "), "w");
+        assert_eq!(dict.provenance["a"].to_string(), format!("{name}:5"));
+        assert_eq!(dict.provenance["b"].to_string(), format!("{name}:4"));
+        assert_eq!(dict.patterns.len(), 1);
+        assert_eq!(dict.patterns[0].key(), "re:.*ment");
+        assert_eq!(
+            dict.provenance["re:.*ment"].to_string(),
+            format!("{name}:6")
+        );
+        assert_eq!(dict.diagnostics.len(), 1);
+        assert_eq!(dict.diagnostics[0].line, 7);
+    }
+
+    #[rstest]
+    #[case("title = x\ndictionary = missing.dict\n\n@ 1\na\n", 2, "missing.dict")]
+    #[case("known_words  = missing.txt\n\n@ 1\na\n", 1, "missing.txt")]
+    fn missing_file_test(#[case] src: &str, #[case] line: usize, #[case] file: &str) {
+        let chap = Chapter::from_str(src).unwrap();
+        assert_eq!(chap.diagnostics.len(), 1);
+        let d = &chap.diagnostics[0];
+        assert_eq!((d.severity, d.line, d.col), (Severity::Warning, line, 1));
+        assert!(d.message.contains(file), "{}", d.message);
+    }
+
+    #[test]
+    fn dictionary_relative_to_chapter_test() {
+        let dir = std::env::temp_dir().join(format!("transdoc-dict-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("words.dict"), "a = x\n").unwrap();
+        let chap = Chapter::parse_in(
+            "dictionary = words.dict\n\n@ 1\n<< a >>\n",
+            crate::tokenizer::DEFAULT_COMMENT,
+            &dir,
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+        let chap = chap.unwrap();
+        assert!(chap.diagnostics.is_empty());
+        assert_eq!(chap.dictionary["a"].join("; "), "x");
+    }
 }
@@ -0,0 +1,214 @@
+use crate::text::escape_attr;
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+use std::sync::LazyLock;
+
+/// Renders the content of `{{name:content}}` fragments of one name
+pub trait FragmentPlugin: Send + Sync {
+    /// HTML for the fragment content
+    fn html(&self, content: &str) -> String;
+
+    /// Text of the fragment as read in the original, the content by default
+    fn text(&self, content: &str) -> String {
+        content.to_string()
+    }
+}
+
+/// Ruby annotation, `{{ruby:漢字|かんじ}}`
+pub struct Ruby;
+
+impl FragmentPlugin for Ruby {
+    fn html(&self, content: &str) -> String {
+        match content.split_once('|') {
+            Some((base, ruby)) => format!(
+                "<ruby>{}<rt>{}</rt></ruby>",
+                escape_attr(base),
+                escape_attr(ruby)
+            ),
+            None => escape_attr(content),
+        }
+    }
+
+    fn text(&self, content: &str) -> String {
+        content.split('|').next().unwrap_or_default().to_string()
+    }
+}
+
+/// Image with its alt text, `{{img:cat.png|A cat on a mat}}`
+pub struct Image;
+
+impl FragmentPlugin for Image {
+    fn html(&self, content: &str) -> String {
+        match content.split_once('|') {
+            Some((src, alt)) => format!(
+                "<img src=\"{}\" alt=\"{}\">",
+                escape_attr(src.trim()),
+                escape_attr(alt.trim())
+            ),
+            None => format!("<img src=\"{}\">", escape_attr(content.trim())),
+        }
+    }
+
+    fn text(&self, content: &str) -> String {
+        content
+            .split_once('|')
+            .map(|(_, alt)| alt.trim().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// External program given the content on stdin, printing the HTML to stdout
+#[derive(Debug)]
+pub struct CommandPlugin {
+    program: String,
+    args: Vec<String>,
+}
+
+impl CommandPlugin {
+    /// Command line split at whitespace, there is no shell involved
+    pub fn new(command: &str) -> Option<Self> {
+        let mut parts = command.split_whitespace().map(String::from);
+        Some(Self {
+            program: parts.next()?,
+            args: parts.collect(),
+        })
+    }
+
+    fn run(&self, content: &str) -> std::io::Result<String> {
+        tracing::trace!("Running {:?} on {content:?}", self.program);
+        let out = run_piped(
+            Command::new(&self.program).args(&self.args),
+            content.as_bytes(),
+        )?;
+        if !out.status.success() {
+            return Err(std::io::Error::other(format!("exited with {}", out.status)));
+        }
+        Ok(String::from_utf8_lossy(&out.stdout).trim_end().to_string())
+    }
+}
+
+/// Runs the command with the input written to its stdin from another
+/// thread while its stdout is read, so neither side waits on a full pipe
+pub(crate) fn run_piped(command: &mut Command, input: &[u8]) -> std::io::Result<Output> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let stdin = child.stdin.take();
+    std::thread::scope(|s| {
+        let writer = s.spawn(move || match stdin {
+            // dropped after writing, closing the pipe
+            Some(mut w) => w.write_all(input),
+            None => Ok(()),
+        });
+        let out = child.wait_with_output()?;
+        match writer.join().expect("writing stdin doesn't panic") {
+            // a command may exit without reading all of it
+            Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(e),
+            _ => Ok(out),
+        }
+    })
+}
+
+impl FragmentPlugin for CommandPlugin {
+    fn html(&self, content: &str) -> String {
+        self.run(content).unwrap_or_else(|e| {
+            tracing::warn!("Plugin {:?} failed: {e}", self.program);
+            escape_attr(content)
+        })
+    }
+}
+
+/// The plugins every chapter has, for the plain text of fragments
+static BUILTIN: LazyLock<Plugins> = LazyLock::new(Plugins::default);
+
+/// Fragment plugins by name
+pub struct Plugins {
+    plugins: HashMap<String, Box<dyn FragmentPlugin>>,
+}
+
+impl Default for Plugins {
+    fn default() -> Self {
+        let mut plugins = Self {
+            plugins: HashMap::new(),
+        };
+        plugins.register("ruby", Ruby);
+        plugins.register("img", Image);
+        plugins
+    }
+}
+
+impl std::fmt::Debug for Plugins {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_set().entries(self.plugins.keys()).finish()
+    }
+}
+
+impl Plugins {
+    /// The default plugins, shared
+    pub fn builtin() -> &'static Self {
+        &BUILTIN
+    }
+
+    pub fn register<P: FragmentPlugin + 'static>(&mut self, name: &str, plugin: P) {
+        self.plugins.insert(name.to_string(), Box::new(plugin));
+    }
+
+    /// HTML of a fragment, unknown plugins keep their escaped content in
+    /// a span
+    pub fn html(&self, name: &str, content: &str) -> String {
+        match self.plugins.get(name) {
+            Some(p) => p.html(content),
+            None => format!(
+                "<span class=\"plugin-{}\">{}</span>",
+                escape_attr(name),
+                escape_attr(content)
+            ),
+        }
+    }
+
+    /// Plain text of a fragment as read in the original
+    pub fn text(&self, name: &str, content: &str) -> String {
+        match self.plugins.get(name) {
+            Some(p) => p.text(content),
+            None => content.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("ruby", "漢字|かんじ", "<ruby>漢字<rt>かんじ</rt></ruby>")]
+    #[case("ruby", "kana", "kana")]
+    #[case("ruby", "a<b|c", "<ruby>a&lt;b<rt>c</rt></ruby>")]
+    #[case("img", "a.png | A cat", r#"<img src="a.png" alt="A cat">"#)]
+    #[case("img", "a.png", r#"<img src="a.png">"#)]
+    #[case(
+        "img",
+        "a.png?x=1&y=2 | A \"big\" <cat>",
+        r#"<img src="a.png?x=1&amp;y=2" alt="A &quot;big&quot; &lt;cat&gt;">"#
+    )]
+    #[case("verse", "3", "<span class=\"plugin-verse\">3</span>")]
+    #[case("verse", "<script>", "<span class=\"plugin-verse\">&lt;script&gt;</span>")]
+    fn plugin_html_test(#[case] name: &str, #[case] content: &str, #[case] expected: &str) {
+        assert_eq!(Plugins::default().html(name, content), expected);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn command_plugin_test() {
+        let mut plugins = Plugins::default();
+        plugins.register("up", CommandPlugin::new("tr a-z A-Z").unwrap());
+        assert_eq!(plugins.html("up", "abc"), "ABC");
+        assert_eq!(plugins.text("up", "abc"), "abc");
+        // more than a pipe holds
+        let long = "a".repeat(1 << 20);
+        plugins.register("cat", CommandPlugin::new("cat").unwrap());
+        assert_eq!(plugins.html("cat", &long), long);
+    }
+}
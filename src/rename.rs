@@ -0,0 +1,260 @@
+use crate::errors::{Diagnostic, Severity};
+use crate::intern;
+use crate::syntax::Span;
+use crate::tokenizer::{get_tokens, get_tokens_with, Token, TokenType};
+use crate::words::{DictPattern, Normalizer, PATTERN_PREFIX};
+use std::ops::Range;
+
+/// Source with a headword renamed, how many times it was and the
+/// occurrences that were left alone as renaming them could change what
+/// they mean
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Renamed {
+    pub source: String,
+    pub count: usize,
+    pub skipped: Vec<Diagnostic>,
+}
+
+impl Renamed {
+    pub fn is_changed(&self) -> bool {
+        self.count > 0
+    }
+}
+
+/// Word as lookups match it, ignoring case and the surrounding punctuation
+fn fold(word: &str) -> String {
+    Normalizer::default().apply(word).to_lowercase()
+}
+
+/// Rewrites the headwords in the ranges of the source equal to `old`,
+/// skipping the ones that only look like it
+struct Renamer<'a> {
+    src: &'a str,
+    old: &'a str,
+    new: &'a str,
+    edits: Vec<Range<usize>>,
+    skipped: Vec<Diagnostic>,
+}
+
+impl<'a> Renamer<'a> {
+    fn new(src: &'a str, old: &'a str, new: &'a str) -> Self {
+        Self {
+            src,
+            old,
+            new,
+            edits: Vec::new(),
+            skipped: Vec::new(),
+        }
+    }
+
+    fn skip(&mut self, at: usize, message: String) {
+        let mut span = Span {
+            start: at,
+            ..Default::default()
+        };
+        span.locate(self.src);
+        let linestr = self.src[at..].lines().next().unwrap_or_default();
+        let start = self.src[..at].rfind('\n').map_or(0, |i| i + 1);
+        self.skipped.push(Diagnostic {
+            severity: Severity::Warning,
+            file: None,
+            line: span.line,
+            col: span.col,
+            message,
+            linestr: Some(format!("{}{linestr}", &self.src[start..at])),
+            rule: None,
+        });
+    }
+
+    /// Headword in the range of the source, surrounding whitespace included
+    fn headword(&mut self, range: Range<usize>) {
+        let text = &self.src[range.clone()];
+        let head = text.trim();
+        if head.is_empty() {
+            return;
+        }
+        let start = range.start + (text.len() - text.trim_start().len());
+        if head == self.old {
+            self.edits.push(start..start + head.len());
+        } else if fold(head) == fold(self.old) {
+            self.skip(
+                start,
+                format!(
+                    "{head:?} differs from {:?} in case or punctuation",
+                    self.old
+                ),
+            );
+        } else if head.split_whitespace().any(|w| fold(w) == fold(self.old)) {
+            self.skip(
+                start,
+                format!("{:?} is part of the longer headword {head:?}", self.old),
+            );
+        }
+    }
+
+    fn finish(self) -> Renamed {
+        let mut source = String::with_capacity(self.src.len());
+        let mut last = 0;
+        for r in &self.edits {
+            source.push_str(&self.src[last..r.start]);
+            source.push_str(self.new);
+            last = r.end;
+        }
+        source.push_str(&self.src[last..]);
+        Renamed {
+            source,
+            count: self.edits.len(),
+            skipped: self.skipped,
+        }
+    }
+}
+
+/// Renames the headword in the `<< old >>`, `<< old = ... >>` and
+/// `<< old#2 >>` fragments of a chapter, whose comments start with the
+/// given token
+pub fn rename_in_chapter(src: &str, comment: &str, old: &str, new: &str) -> Renamed {
+    let tokens = get_tokens_with(src, comment);
+    let mut renamer = Renamer::new(src, old, new);
+    let mut i = 0;
+    while i < tokens.len() {
+        if tokens[i].ty != TokenType::AngleStart {
+            i += 1;
+            continue;
+        }
+        let inner = &tokens[i + 1..];
+        let Some(end) = inner
+            .iter()
+            .position(|t| matches!(t.ty, TokenType::AngleEnd | TokenType::NewLine))
+            .filter(|e| inner[*e].ty == TokenType::AngleEnd)
+        else {
+            i += 1;
+            continue;
+        };
+        let inner = &inner[..end];
+        i += end + 2;
+        let Some(first) = inner.first() else {
+            continue;
+        };
+        let stop = inner
+            .iter()
+            .find(|t| t.ty == TokenType::Equal)
+            .map_or(tokens[i - 1].offset, |t| t.offset);
+        let text = &src[first.offset..stop];
+        if text.trim_start().starts_with('?') {
+            // conditional text, not a word
+            continue;
+        }
+        // a sense lookup's number stays after the renamed word
+        let stop = match text.trim_end().rsplit_once('#') {
+            Some((w, n)) if !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()) => {
+                first.offset + w.len()
+            }
+            _ => stop,
+        };
+        renamer.headword(first.offset..stop);
+    }
+    renamer.finish()
+}
+
+/// Renames the entry of a dictionary file, unless the new headword has an
+/// entry already; patterns matching only the old headword are reported
+pub fn rename_in_dictionary(src: &str, old: &str, new: &str) -> Renamed {
+    let tokens = get_tokens(src);
+    let mut renamer = Renamer::new(src, old, new);
+    let keys: Vec<Range<usize>> = tokens
+        .split(|t| t.ty == TokenType::NewLine)
+        .filter_map(entry_key)
+        .collect();
+    let has_new = keys.iter().any(|k| src[k.clone()].trim() == new);
+    for key in keys {
+        let head = src[key.clone()].trim();
+        if let Some(pattern) = head.strip_prefix(PATTERN_PREFIX) {
+            let Ok(p) = DictPattern::new(pattern, intern::meanings::<&str>(&[])) else {
+                continue;
+            };
+            if p.regex.is_match(old) && !p.regex.is_match(new) {
+                renamer.skip(
+                    key.start,
+                    format!("Pattern {head:?} matches {old:?} but not {new:?}"),
+                );
+            }
+        } else if head == old && has_new {
+            renamer.skip(key.start, format!("{new:?} has an entry already"));
+        } else {
+            renamer.headword(key);
+        }
+    }
+    renamer.finish()
+}
+
+/// Range of the headword of a dictionary line, before its `=`
+fn entry_key(line: &[Token]) -> Option<Range<usize>> {
+    let first = line.iter().find(|t| t.ty != TokenType::WhiteSpace)?;
+    if first.ty == TokenType::Comment {
+        return None;
+    }
+    let equal = line.iter().find(|t| t.ty == TokenType::Equal)?;
+    Some(first.offset..equal.offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(
+        "<< kaji >> a << kaji = fire >>",
+        "<< kazi >> a << kazi = fire >>",
+        2,
+        0
+    )]
+    #[case("<< kaji#2 >> << kaji = @1 >>", "<< kazi#2 >> << kazi = @1 >>", 2, 0)]
+    #[case(
+        "kaji << ?en kaji >> # << kaji >>",
+        "kaji << ?en kaji >> # << kaji >>",
+        0,
+        0
+    )]
+    #[case(
+        "<< Kaji >> << kaji ghar = home >>",
+        "<< Kaji >> << kaji ghar = home >>",
+        0,
+        2
+    )]
+    #[case("<<kaji>>", "<<kazi>>", 1, 0)]
+    fn chapter_test(
+        #[case] src: &str,
+        #[case] expected: &str,
+        #[case] count: usize,
+        #[case] skipped: usize,
+    ) {
+        let renamed = rename_in_chapter(src, "#", "kaji", "kazi");
+        assert_eq!(renamed.source, expected);
+        assert_eq!(renamed.count, count);
+        assert_eq!(renamed.skipped.len(), skipped);
+    }
+
+    #[rstest]
+    #[case(
+        "kaji = fire\n# kaji = x\nghar = home\n",
+        "kazi = fire\n# kaji = x\nghar = home\n",
+        0
+    )]
+    #[case("kaji = fire\nkazi = flame\n", "kaji = fire\nkazi = flame\n", 1)]
+    #[case("re:ka.i = fire\n", "re:ka.i = fire\n", 0)]
+    #[case("re:kaj. = fire\n", "re:kaj. = fire\n", 1)]
+    fn dictionary_test(#[case] src: &str, #[case] expected: &str, #[case] skipped: usize) {
+        let renamed = rename_in_dictionary(src, "kaji", "kazi");
+        assert_eq!(renamed.source, expected);
+        assert_eq!(renamed.skipped.len(), skipped);
+    }
+
+    #[test]
+    fn skipped_location_test() {
+        let renamed = rename_in_chapter("@ 1\nto << Kaji >>\n", "#", "kaji", "kazi");
+        let d = &renamed.skipped[0];
+        assert_eq!((d.line, d.col), (2, 7));
+        assert_eq!(d.linestr.as_deref(), Some("to << Kaji >>"));
+    }
+}
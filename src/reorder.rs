@@ -0,0 +1,240 @@
+use crate::label::Label;
+use crate::syntax::Chapter;
+use std::ops::Range;
+
+/// Where a sentence goes, relative to the sentence with the label
+#[derive(Debug, Clone, PartialEq)]
+pub enum Position {
+    After(String),
+    Before(String),
+    End,
+}
+
+/// Source of a chapter split into its sentences, so they can be reordered
+/// without touching the text between them
+struct Blocks<'a> {
+    src: &'a str,
+    header: &'a str,
+    /// Sentence text with the comment lines right above it
+    blocks: Vec<&'a str>,
+    /// Text after each block, blank lines and comments
+    gaps: Vec<&'a str>,
+}
+
+impl<'a> Blocks<'a> {
+    fn new(chap: &Chapter, src: &'a str) -> Self {
+        let ranges: Vec<Range<usize>> = chap
+            .sentences
+            .iter()
+            .map(|s| comments_above(src, s.span.start)..s.span.line_end(src))
+            .collect();
+        let header_end = ranges.first().map_or(src.len(), |r| r.start);
+        let gaps = ranges
+            .iter()
+            .enumerate()
+            .map(|(i, r)| &src[r.end..ranges.get(i + 1).map_or(src.len(), |n| n.start)])
+            .collect();
+        Self {
+            src,
+            header: &src[..header_end],
+            blocks: ranges.iter().map(|r| &src[r.clone()]).collect(),
+            gaps,
+        }
+    }
+
+    fn source(&self) -> String {
+        let mut out = String::with_capacity(self.src.len());
+        out.push_str(self.header);
+        for (b, g) in self.blocks.iter().zip(&self.gaps) {
+            out.push_str(b);
+            out.push_str(g);
+        }
+        out
+    }
+}
+
+/// Start of the comment lines right above the byte offset
+pub(crate) fn comments_above(src: &str, start: usize) -> usize {
+    let mut start = start;
+    while start > 0 {
+        let before = src[..start].trim_end_matches(['\n', '\r']);
+        // a blank line separates the comment from the sentence
+        if !matches!(&src[before.len()..start], "\n" | "\r\n" | "\r") {
+            break;
+        }
+        let line_start = before.rfind(['\n', '\r']).map_or(0, |i| i + 1);
+        if !before[line_start..].trim_start().starts_with('#') {
+            break;
+        }
+        start = line_start;
+    }
+    start
+}
+
+impl Chapter {
+    fn sentence_index(&self, label: &str) -> Result<usize, String> {
+        self.sentences
+            .iter()
+            .position(|s| s.label == label)
+            .ok_or_else(|| format!("No sentence labelled {label:?}"))
+    }
+
+    /// Index a sentence at the position would have in the chapter as is
+    fn insert_index(&self, to: &Position) -> Result<usize, String> {
+        match to {
+            Position::After(l) => Ok(self.sentence_index(l)? + 1),
+            Position::Before(l) => self.sentence_index(l),
+            Position::End => Ok(self.sentences.len()),
+        }
+    }
+
+    /// Source the chapter was parsed from with the sentence, and the
+    /// comments right above it, moved to the position
+    pub fn move_sentence(&self, src: &str, label: &str, to: &Position) -> Result<String, String> {
+        let from = self.sentence_index(label)?;
+        let mut at = self.insert_index(to)?;
+        let mut b = Blocks::new(self, src);
+        let block = b.blocks.remove(from);
+        if at > from {
+            at -= 1;
+        }
+        b.blocks.insert(at, block);
+        Ok(b.source())
+    }
+
+    /// Source the chapter was parsed from with a new sentence at the
+    /// position, its source being `@ label` and the text on the next lines
+    pub fn insert_sentence(
+        &self,
+        src: &str,
+        label: &str,
+        text: &str,
+        to: &Position,
+    ) -> Result<String, String> {
+        if self.sentences.iter().any(|s| s.label == label) {
+            return Err(format!("Label {label:?} is already used"));
+        }
+        let at = self.insert_index(to)?;
+        let eol = if src.contains("\r\n") { "\r\n" } else { "\n" };
+        let block = format!("@ {label}{eol}{}", text.trim_end().replace('\n', eol));
+        let gap = format!("{eol}{eol}");
+        if self.sentences.is_empty() {
+            return Ok(format!("{}{gap}{block}{eol}", src.trim_end()));
+        }
+        let mut b = Blocks::new(self, src);
+        b.blocks.insert(at, &block);
+        b.gaps.insert(at.saturating_sub(1), &gap);
+        Ok(b.source())
+    }
+
+    /// Source the chapter was parsed from with the sentences, and the
+    /// comments right above them, in the order of their labels
+    pub fn sort_sentences(&self, src: &str) -> String {
+        let mut b = Blocks::new(self, src);
+        let mut order: Vec<usize> = (0..b.blocks.len()).collect();
+        order.sort_by_cached_key(|i| Label::from(self.sentences[*i].label.as_str()));
+        b.blocks = order.iter().map(|i| b.blocks[*i]).collect();
+        b.source()
+    }
+
+    /// Label after the largest numeric one, for new sentences
+    pub fn next_label(&self) -> String {
+        let last = self
+            .sentences
+            .iter()
+            .filter_map(|s| s.label.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0);
+        (last + 1).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str = "title = T\n\n# one\n@ 1\na\n---\nA\n\n# free\n\n@ 2\nb\n\n@ 3\nc\n# end\n";
+
+    fn labels(src: &str) -> Vec<String> {
+        Chapter::from_str(src)
+            .unwrap()
+            .sentences
+            .into_iter()
+            .map(|s| s.label)
+            .collect()
+    }
+
+    #[rstest]
+    #[case(
+        "1",
+        Position::After("3".into()),
+        "title = T\n\n@ 2\nb\n\n# free\n\n@ 3\nc\n\n# one\n@ 1\na\n---\nA\n# end\n"
+    )]
+    #[case(
+        "3",
+        Position::Before("1".into()),
+        "title = T\n\n@ 3\nc\n\n# free\n\n# one\n@ 1\na\n---\nA\n\n@ 2\nb\n# end\n"
+    )]
+    #[case("2", Position::After("2".into()), CHAP)]
+    #[case("1", Position::Before("2".into()), CHAP)]
+    fn move_sentence_test(#[case] label: &str, #[case] to: Position, #[case] expected: &str) {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        assert_eq!(chap.move_sentence(CHAP, label, &to).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case(Position::After("1".into()), vec!["1", "4", "2", "3"])]
+    #[case(Position::Before("1".into()), vec!["4", "1", "2", "3"])]
+    #[case(Position::After("3".into()), vec!["1", "2", "3", "4"])]
+    #[case(Position::End, vec!["1", "2", "3", "4"])]
+    fn insert_sentence_test(#[case] to: Position, #[case] expected: Vec<&str>) {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        let label = chap.next_label();
+        let inserted = chap
+            .insert_sentence(CHAP, &label, "new\n---\nNEW\n", &to)
+            .unwrap();
+        assert_eq!(labels(&inserted), expected);
+        assert!(inserted.contains("# one\n@ 1\n") && inserted.contains("# free\n"));
+        let chap = Chapter::from_str(&inserted).unwrap();
+        let new = chap.sentences.iter().find(|s| s.label == "4").unwrap();
+        assert_eq!(new.text(), "new");
+        assert_eq!(new.translations.len(), 1);
+    }
+
+    #[test]
+    fn insert_into_empty_test() {
+        let chap = Chapter::from_str("title = T\n").unwrap();
+        let src = chap
+            .insert_sentence("title = T\n", "1", "a", &Position::End)
+            .unwrap();
+        assert_eq!(src, "title = T\n\n@ 1\na\n");
+    }
+
+    #[test]
+    fn unknown_label_test() {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        assert!(chap
+            .move_sentence(CHAP, "9", &Position::After("1".into()))
+            .is_err());
+        assert!(chap
+            .move_sentence(CHAP, "1", &Position::After("9".into()))
+            .is_err());
+        assert!(chap
+            .insert_sentence(CHAP, "2", "x", &Position::After("1".into()))
+            .is_err());
+    }
+
+    #[test]
+    fn sort_sentences_test() {
+        let src = "title = T\n\n@ 10\nc\n\n# two\n@ 2\nb\n---\nB\n\n@ 1.5\na\n";
+        let chap = Chapter::from_str(src).unwrap();
+        let sorted = chap.sort_sentences(src);
+        assert_eq!(
+            sorted,
+            "title = T\n\n@ 1.5\na\n\n# two\n@ 2\nb\n---\nB\n\n@ 10\nc\n"
+        );
+        assert_eq!(labels(&sorted), vec!["1.5", "2", "10"]);
+    }
+}
@@ -0,0 +1,448 @@
+use crate::syntax::{Chapter, OrgFragment, Translation};
+use crate::tokenizer::DEFAULT_COMMENT;
+use indexmap::IndexMap;
+use std::collections::BTreeSet;
+
+/// Translation attr the review sets
+pub const STATUS_ATTR: &str = "status";
+
+/// Statuses the review steps through, no attr coming after the last
+pub const STATUSES: [&str; 2] = ["draft", "reviewed"];
+
+/// Translation pass over a chapter in one language, keeping the parsed
+/// chapter to write back and a processed copy to show the glosses of
+pub struct Review {
+    pub chap: Chapter,
+    shown: Chapter,
+    pub lang: String,
+    pub current: usize,
+    /// Token starting comments in the chapter's source
+    pub comment: String,
+    modified: BTreeSet<usize>,
+}
+
+impl Review {
+    pub fn new(chap: Chapter, lang: &str) -> Self {
+        let mut shown = chap.clone();
+        shown.for_languages(&[lang.to_string()]);
+        shown.process();
+        Self {
+            chap,
+            shown,
+            lang: lang.to_string(),
+            current: 0,
+            comment: DEFAULT_COMMENT.to_string(),
+            modified: BTreeSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chap.sentences.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chap.sentences.is_empty()
+    }
+
+    pub fn next(&mut self) {
+        if self.current + 1 < self.len() {
+            self.current += 1;
+        }
+    }
+
+    pub fn prev(&mut self) {
+        self.current = self.current.saturating_sub(1);
+    }
+
+    /// Original of the current sentence with the dictionary applied
+    pub fn original(&self) -> &[OrgFragment] {
+        &self.shown.sentences[self.current].original
+    }
+
+    /// Key of the current sentence's translation to the language
+    fn key(&self) -> Option<String> {
+        self.chap.sentences[self.current]
+            .translations
+            .keys()
+            .enumerate()
+            .find(|(i, k)| self.chap.translation_language(*i, k) == self.lang)
+            .map(|(_, k)| k.clone())
+    }
+
+    /// Current sentence's translation to the language
+    pub fn translation(&self) -> Option<&Translation> {
+        let key = self.key()?;
+        self.chap.sentences[self.current].translations.get(&key)
+    }
+
+    /// The current sentence's translations to the other languages
+    pub fn others(&self) -> Vec<(String, &str)> {
+        self.chap.sentences[self.current]
+            .translations
+            .iter()
+            .enumerate()
+            .map(|(i, (k, t))| (self.chap.translation_language(i, k), t.content.as_str()))
+            .filter(|(l, _)| *l != self.lang)
+            .collect()
+    }
+
+    pub fn status(&self) -> Option<&str> {
+        self.translation()?
+            .attrs
+            .get(STATUS_ATTR)
+            .map(String::as_str)
+    }
+
+    fn translation_mut(&mut self) -> &mut Translation {
+        let key = self.key().unwrap_or_else(|| {
+            let s = &self.chap.sentences[self.current];
+            // positional when it is the next one in the `translations` attr
+            let n = s.translations.len();
+            if self.chap.tl_languages.get(n).map(|l| l.trim()) == Some(self.lang.as_str())
+                && s.translations
+                    .keys()
+                    .enumerate()
+                    .all(|(i, k)| *k == i.to_string())
+            {
+                n.to_string()
+            } else {
+                self.lang.clone()
+            }
+        });
+        self.modified.insert(self.current);
+        self.chap.sentences[self.current]
+            .translations
+            .entry(key)
+            .or_insert_with(|| Translation {
+                content: String::new(),
+                attrs: IndexMap::new(),
+            })
+    }
+
+    /// Replaces the current sentence's translation, adding it if missing
+    pub fn set_translation(&mut self, text: &str) {
+        let text = text.trim();
+        if self.translation().is_some_and(|t| t.content == text) {
+            return;
+        }
+        self.translation_mut().content = text.to_string();
+    }
+
+    /// Moves the translation's status to the next of `STATUSES`, removing
+    /// it after the last
+    pub fn cycle_status(&mut self) {
+        let next = match self.status() {
+            None => Some(STATUSES[0]),
+            Some(s) => STATUSES
+                .iter()
+                .position(|st| *st == s)
+                .and_then(|i| STATUSES.get(i + 1))
+                .copied(),
+        };
+        let attrs = &mut self.translation_mut().attrs;
+        match next {
+            Some(s) => attrs.insert(STATUS_ATTR.to_string(), s.to_string()),
+            None => attrs.shift_remove(STATUS_ATTR),
+        };
+    }
+
+    /// Whether there are changes to write
+    pub fn is_modified(&self) -> bool {
+        !self.modified.is_empty()
+    }
+
+    /// Source the chapter was parsed from with the reviewed sentences
+    /// written again, failing if it wouldn't parse back to the same chapter
+    pub fn source(&self, src: &str) -> Result<String, String> {
+        let modified: Vec<usize> = self.modified.iter().copied().collect();
+        let edited = self
+            .chap
+            .splice_sentences_with(src, &self.comment, &modified);
+        match Chapter::parse_with(&edited, &self.comment) {
+            Ok(c) if c.sentences == self.chap.sentences => Ok(edited),
+            Ok(_) => Err("The edited translations don't read back the same".to_string()),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+}
+
+#[cfg(feature = "tui")]
+pub use tui::run;
+
+#[cfg(feature = "tui")]
+mod tui {
+    use super::Review;
+    use crate::syntax::OrgFragment;
+    use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+    use ratatui::layout::{Constraint, Layout, Position};
+    use ratatui::style::{Modifier, Style, Stylize};
+    use ratatui::text::{Line, Span};
+    use ratatui::widgets::{Block, Paragraph, Wrap};
+    use ratatui::{DefaultTerminal, Frame};
+
+    const HELP: &str = "←/→ move  e edit  s status  g glosses  w write  q write and quit  Q quit";
+
+    /// Translation being typed, with the cursor as a char index
+    struct Input {
+        text: Vec<char>,
+        cursor: usize,
+    }
+
+    struct App<'a> {
+        review: Review,
+        src: &'a str,
+        input: Option<Input>,
+        glosses: bool,
+        message: String,
+    }
+
+    /// Steps through the sentences letting the translations be edited,
+    /// giving the source to write if there were changes
+    pub fn run(review: Review, src: &str) -> std::io::Result<Option<String>> {
+        let mut terminal = ratatui::try_init()?;
+        let mut app = App {
+            review,
+            src,
+            input: None,
+            glosses: true,
+            message: String::new(),
+        };
+        let res = app.event_loop(&mut terminal);
+        ratatui::restore();
+        res
+    }
+
+    impl App<'_> {
+        fn event_loop(
+            &mut self,
+            terminal: &mut DefaultTerminal,
+        ) -> std::io::Result<Option<String>> {
+            let mut written = None;
+            loop {
+                terminal.draw(|f| self.draw(f))?;
+                let Event::Key(key) = event::read()? else {
+                    continue;
+                };
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+                if let Some(input) = &mut self.input {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let text: String = input.text.iter().collect();
+                            self.review.set_translation(&text);
+                            self.input = None;
+                        }
+                        KeyCode::Esc => self.input = None,
+                        KeyCode::Left => input.cursor = input.cursor.saturating_sub(1),
+                        KeyCode::Right => input.cursor = (input.cursor + 1).min(input.text.len()),
+                        KeyCode::Home => input.cursor = 0,
+                        KeyCode::End => input.cursor = input.text.len(),
+                        KeyCode::Backspace if input.cursor > 0 => {
+                            input.cursor -= 1;
+                            input.text.remove(input.cursor);
+                        }
+                        KeyCode::Delete if input.cursor < input.text.len() => {
+                            input.text.remove(input.cursor);
+                        }
+                        KeyCode::Char(c) => {
+                            input.text.insert(input.cursor, c);
+                            input.cursor += 1;
+                        }
+                        _ => (),
+                    }
+                    continue;
+                }
+                self.message.clear();
+                match key.code {
+                    KeyCode::Right | KeyCode::Char('n') | KeyCode::Char('j') => self.review.next(),
+                    KeyCode::Left | KeyCode::Char('p') | KeyCode::Char('k') => self.review.prev(),
+                    KeyCode::Char('e') | KeyCode::Enter => {
+                        let text: Vec<char> = self
+                            .review
+                            .translation()
+                            .map(|t| t.content.chars().collect())
+                            .unwrap_or_default();
+                        self.input = Some(Input {
+                            cursor: text.len(),
+                            text,
+                        });
+                    }
+                    KeyCode::Char('s') => self.review.cycle_status(),
+                    KeyCode::Char('g') => self.glosses = !self.glosses,
+                    KeyCode::Char('w') => match self.review.source(self.src) {
+                        Ok(src) => {
+                            self.message = "Changes will be written on quitting".to_string();
+                            written = Some(src);
+                        }
+                        Err(e) => self.message = e,
+                    },
+                    KeyCode::Char('q') => {
+                        if !self.review.is_modified() {
+                            return Ok(written);
+                        }
+                        match self.review.source(self.src) {
+                            Ok(src) => return Ok(Some(src)),
+                            Err(e) => self.message = e,
+                        }
+                    }
+                    KeyCode::Char('Q') => return Ok(None),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(None);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        fn draw(&self, frame: &mut Frame) {
+            let [org_area, tl_area, others_area, help_area] = Layout::vertical([
+                Constraint::Percentage(40),
+                Constraint::Min(3),
+                Constraint::Percentage(30),
+                Constraint::Length(1),
+            ])
+            .areas(frame.area());
+            let r = &self.review;
+            if r.is_empty() {
+                frame.render_widget(Paragraph::new("The chapter has no sentences"), org_area);
+                return;
+            }
+            let s = &r.chap.sentences[r.current];
+            let title = format!(
+                " {} {}/{} @{} ",
+                r.chap.title,
+                r.current + 1,
+                r.len(),
+                s.label
+            );
+            let original: Vec<Span> = r
+                .original()
+                .iter()
+                .flat_map(|o| match o {
+                    OrgFragment::Sense(w, _, m) if m.is_empty() => {
+                        vec![Span::styled(w.clone(), Style::new().red())]
+                    }
+                    OrgFragment::Meaning(w, _)
+                    | OrgFragment::Compound(w, _)
+                    | OrgFragment::Sense(w, ..)
+                        if self.glosses =>
+                    {
+                        vec![
+                            Span::styled(w.clone(), Style::new().underlined()),
+                            Span::styled(
+                                format!(" [{}]", o.gloss().unwrap_or_default()),
+                                Style::new().dim(),
+                            ),
+                        ]
+                    }
+                    OrgFragment::Meaning(w, _)
+                    | OrgFragment::Compound(w, _)
+                    | OrgFragment::Sense(w, ..) => {
+                        vec![Span::styled(w.clone(), Style::new().underlined())]
+                    }
+                    OrgFragment::DictLookup(w) => vec![Span::styled(w.clone(), Style::new().red())],
+                    o => vec![Span::raw(o.text().to_string())],
+                })
+                .collect();
+            frame.render_widget(
+                Paragraph::new(Line::from(original))
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(title)),
+                org_area,
+            );
+
+            let status = r.status().map(|s| format!("({s}) ")).unwrap_or_default();
+            let tl_title = format!(" {} {status}", r.lang);
+            let tl_block = Block::bordered().title(tl_title);
+            match &self.input {
+                Some(input) => {
+                    let text: String = input.text.iter().collect();
+                    let inner = tl_block.inner(tl_area);
+                    frame.render_widget(
+                        Paragraph::new(text)
+                            .wrap(Wrap { trim: false })
+                            .block(tl_block.border_style(Style::new().yellow())),
+                        tl_area,
+                    );
+                    // wrapped lines are ignored, the cursor only follows the first one
+                    let col = input.cursor.min(inner.width.saturating_sub(1) as usize) as u16;
+                    frame.set_cursor_position(Position::new(inner.x + col, inner.y));
+                }
+                None => {
+                    let text = r
+                        .translation()
+                        .map(|t| t.content.clone())
+                        .unwrap_or_default();
+                    frame.render_widget(
+                        Paragraph::new(text)
+                            .wrap(Wrap { trim: false })
+                            .block(tl_block),
+                        tl_area,
+                    );
+                }
+            }
+
+            let others: Vec<Line> = r
+                .others()
+                .into_iter()
+                .map(|(l, t)| Line::from(format!("{l}: {t}")).add_modifier(Modifier::DIM))
+                .collect();
+            frame.render_widget(
+                Paragraph::new(others)
+                    .wrap(Wrap { trim: false })
+                    .block(Block::bordered().title(" other translations ")),
+                others_area,
+            );
+            let help = if self.message.is_empty() {
+                HELP
+            } else {
+                &self.message
+            };
+            frame.render_widget(Line::from(help).dim(), help_area);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const CHAP: &str = "tranlations = english, german\n\n# keep\n@ 1\n<< a >>\n---\nA\n\n@ 2\nb\n--- german\nB\n\n@ 3\nc\n";
+
+    #[test]
+    fn review_test() {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.dictionary
+            .insert("a".into(), crate::intern::meanings(&["x"]));
+        let mut r = Review::new(chap, "english");
+        assert!(matches!(r.original()[0], OrgFragment::Meaning(..)));
+        assert_eq!(r.translation().unwrap().content, "A");
+        r.set_translation("A");
+        assert!(!r.is_modified());
+        r.cycle_status();
+        assert_eq!(r.status(), Some("draft"));
+        r.next();
+        assert!(r.translation().is_none());
+        assert_eq!(r.others(), vec![("german".to_string(), "B")]);
+        r.set_translation(" Bee ");
+        r.next();
+        r.set_translation("C");
+        r.next();
+        assert_eq!(r.current, 2);
+
+        let src = r.source(CHAP).unwrap();
+        assert!(src.starts_with("tranlations = english, german\n\n# keep\n@ 1\n<< a >>\n"));
+        let chap = Chapter::from_str(&src).unwrap();
+        assert_eq!(chap.sentences[0].translations["0"].attrs["status"], "draft");
+        assert_eq!(chap.sentences[1].translations["english"].content, "Bee");
+        assert_eq!(chap.sentences[2].translations["0"].content, "C");
+
+        let mut r = Review::new(chap, "english");
+        r.cycle_status();
+        assert_eq!(r.status(), Some("reviewed"));
+        r.cycle_status();
+        assert_eq!(r.status(), None);
+    }
+}
@@ -0,0 +1,59 @@
+use crate::template::render;
+
+/// Skeleton of a new chapter, documenting the syntax in comments
+pub const CHAPTER_TEMPLATE: &str =
+    "# Chapter attrs come first, one `key = value` per line. Others are
+# `dictionary = words.dict` for a file of `word = meaning; meaning` lines
+# and `known_words = known.txt` for words that needn't be glossed.
+title = {{title}}
+language = {{language}}
+translations = {{translations}}
+
+# Each sentence starts with `@ label` and its original on the next lines.
+# << word = meaning >> glosses a word, << word >> looks it up in the
+# dictionary. Sentence attrs like `tags = idiom` or `level = A2` follow the
+# original, then each translation comes after a `---` line, in the order of
+# the translations attr. Lines starting with # are comments.
+@ 1
+An << example = sample; instance >> sentence.
+{{example_translations}}";
+
+/// Name of the template file for new chapters in a template directory
+pub const CHAPTER_TEMPLATE_FILE: &str = "chapter.chapter";
+
+/// New chapter from the template, its `{{title}}`, `{{language}}`,
+/// `{{translations}}` and `{{example_translations}}` placeholders filled
+pub fn new_chapter(template: &str, title: &str, language: &str, translations: &[String]) -> String {
+    let examples: String = translations
+        .iter()
+        .map(|l| format!("---\nThe translation to {l}.\n"))
+        .collect();
+    render(
+        template,
+        &[
+            ("title", title),
+            ("language", language),
+            ("translations", &translations.join(",")),
+            ("example_translations", &examples),
+        ],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::syntax::Chapter;
+    use std::str::FromStr;
+
+    #[test]
+    fn new_chapter_test() {
+        let tls = vec!["english".to_string(), "german".to_string()];
+        let src = new_chapter(CHAPTER_TEMPLATE, "Chapter 3", "japanese", &tls);
+        let chap = Chapter::from_str(&src).unwrap();
+        assert_eq!(chap.title, "Chapter 3");
+        assert_eq!(chap.language, "japanese");
+        assert_eq!(chap.tl_languages, vec!["english", "german"]);
+        assert_eq!(chap.sentences.len(), 1);
+        assert_eq!(chap.sentences[0].translations.len(), 2);
+    }
+}
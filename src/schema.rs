@@ -0,0 +1,135 @@
+use crate::syntax::Chapter;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Version of the chapter JSON. Adding optional properties keeps it, it
+/// goes up when a property is renamed, removed or changes meaning.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// JSON schema of the chapter JSON
+pub const CHAPTER_SCHEMA: &str = include_str!("chapter.schema.json");
+
+/// Data with the version of the schema it follows
+#[derive(Serialize, Deserialize)]
+struct Versioned<T> {
+    /// Taken as the current one when missing, for hand written JSON
+    #[serde(default = "current_version")]
+    schema_version: u32,
+    #[serde(flatten)]
+    data: T,
+}
+
+fn current_version() -> u32 {
+    SCHEMA_VERSION
+}
+
+/// Struct as JSON with the schema version as its first property, which
+/// also marks the file as written by transdoc
+pub fn versioned_json<T: Serialize>(data: &T, pretty: bool) -> String {
+    let doc = Versioned {
+        schema_version: SCHEMA_VERSION,
+        data,
+    };
+    if pretty {
+        serde_json::to_string_pretty(&doc)
+    } else {
+        serde_json::to_string(&doc)
+    }
+    .expect("versioned data is plain data")
+}
+
+/// Whether the JSON is an object with a schema version, as written by
+/// [`versioned_json`]
+pub fn is_versioned(json: &str) -> bool {
+    serde_json::from_str::<Value>(json).is_ok_and(|v| v.get("schema_version").is_some())
+}
+
+impl Chapter {
+    /// Chapter as JSON in the versioned schema, used by the filter
+    /// commands, the preview server and JSON exports
+    pub fn to_json(&self, pretty: bool) -> String {
+        versioned_json(self, pretty)
+    }
+
+    /// Chapter from JSON of the schema version or an older one
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let doc: Versioned<Chapter> = serde_json::from_str(json).map_err(|e| e.to_string())?;
+        match doc.schema_version {
+            1..=SCHEMA_VERSION => Ok(doc.data),
+            v => Err(format!(
+                "Chapter JSON has schema version {v}, this transdoc reads versions 1 to {SCHEMA_VERSION}"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str = "tranlations = english\ncompounds = yes\n\n@ 1\nhaustür << haus = house >> << tür = door >> {{p:x}} <<?english y>> <<zz>>\n---\nt\nk = v\n";
+
+    #[test]
+    fn roundtrip_test() {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.process();
+        let json = chap.to_json(false);
+        assert!(json.starts_with("{\"schema_version\":1,"));
+        assert_eq!(Chapter::from_json(&json).unwrap(), chap);
+    }
+
+    #[rstest]
+    #[case(r#"{"schema_version": 2, "title": "", "language": "", "tl_languages": [], "dictionary": {}, "known_words": [], "sentences": [], "attrs": {}}"#, false)]
+    #[case(r#"{"schema_version": 0, "title": "", "language": "", "tl_languages": [], "dictionary": {}, "known_words": [], "sentences": [], "attrs": {}}"#, false)]
+    #[case(r#"{"title": "", "language": "", "tl_languages": [], "dictionary": {}, "known_words": [], "sentences": [], "attrs": {}, "added_later": 1}"#, true)]
+    fn version_test(#[case] json: &str, #[case] ok: bool) {
+        assert_eq!(Chapter::from_json(json).is_ok(), ok);
+    }
+
+    /// Property names of the object the schema describes
+    fn schema_properties<'a>(schema: &'a Value, def: &str) -> Vec<&'a str> {
+        let obj = match def {
+            "" => schema,
+            d => &schema["$defs"][d],
+        };
+        obj["properties"]
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect()
+    }
+
+    #[test]
+    fn schema_covers_json_test() {
+        let schema: Value = serde_json::from_str(CHAPTER_SCHEMA).unwrap();
+        assert_eq!(
+            schema["properties"]["schema_version"]["const"],
+            SCHEMA_VERSION
+        );
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.process();
+        chap.notes.insert("1".into(), Default::default());
+        let json: Value = serde_json::from_str(&chap.to_json(false)).unwrap();
+        let keys = |v: &Value| -> Vec<String> { v.as_object().unwrap().keys().cloned().collect() };
+        let chapter_props = schema_properties(&schema, "");
+        for k in keys(&json) {
+            assert!(chapter_props.contains(&k.as_str()), "{k}");
+        }
+        let sentence_props = schema_properties(&schema, "sentence");
+        for k in keys(&json["sentences"][0]) {
+            assert!(sentence_props.contains(&k.as_str()), "{k}");
+        }
+        let variants: Vec<&str> = schema["$defs"]["fragment"]["oneOf"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v["required"][0].as_str().unwrap())
+            .collect();
+        for f in json["sentences"][0]["original"].as_array().unwrap() {
+            assert!(variants.contains(&keys(f)[0].as_str()), "{f}");
+        }
+    }
+}
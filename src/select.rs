@@ -0,0 +1,99 @@
+use crate::syntax::Chapter;
+use crate::words::words;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+
+impl Chapter {
+    /// Indices of a small set of sentences with `per_word` examples of each
+    /// of the words, in the order they appear in the chapter.
+    ///
+    /// Sentences are picked greedily by how many still needed examples they
+    /// give, words with fewer examples in the chapter than asked for get as
+    /// many as there are.
+    pub fn sample(&self, targets: &[String], per_word: usize) -> Vec<usize> {
+        let mut needed: HashMap<&str, usize> =
+            targets.iter().map(|t| (t.as_str(), per_word)).collect();
+        // the targets in each sentence, from one pass over its words
+        let sentence_words: Vec<Vec<&str>> = self
+            .sentences
+            .iter()
+            .map(|s| {
+                let text = s.text();
+                let mut ws: Vec<&str> = words(&text)
+                    .filter_map(|w| needed.get_key_value(w).map(|(t, _)| *t))
+                    .collect();
+                ws.sort_unstable();
+                ws.dedup();
+                ws
+            })
+            .collect();
+        // gains only go down as words get their examples, so a sentence
+        // whose gain is still the one it was queued with is the best one;
+        // earlier sentences win ties
+        let mut queue: BinaryHeap<(usize, Reverse<usize>)> = sentence_words
+            .iter()
+            .enumerate()
+            .filter(|(_, ws)| !ws.is_empty())
+            .map(|(i, ws)| (ws.len(), Reverse(i)))
+            .collect();
+        let mut picked = vec![false; self.sentences.len()];
+        while let Some((gain, Reverse(i))) = queue.pop() {
+            let now = sentence_words[i].iter().filter(|w| needed[*w] > 0).count();
+            if now < gain {
+                if now > 0 {
+                    queue.push((now, Reverse(i)));
+                }
+                continue;
+            }
+            picked[i] = true;
+            for w in &sentence_words[i] {
+                let n = needed.get_mut(w).expect("only targets are matched");
+                *n = n.saturating_sub(1);
+            }
+        }
+        (0..picked.len()).filter(|i| picked[*i]).collect()
+    }
+
+    /// Indices of the sentences with any of the tags
+    pub fn tagged(&self, tags: &[String]) -> Vec<usize> {
+        self.sentences
+            .iter()
+            .enumerate()
+            .filter(|(_, s)| s.tags().iter().any(|t| tags.iter().any(|w| w == t)))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str = "@ 1\ncat dog\n\n@ 2\ncat\n\n@ 3\ndog bird\n\n@ 4\nbird cat\n";
+
+    #[rstest]
+    #[case(vec!["cat", "dog", "bird"], 1, vec![0, 2])]
+    #[case(vec!["cat"], 2, vec![0, 1])]
+    #[case(vec!["bird", "fish"], 5, vec![2, 3])]
+    #[case(vec![], 1, vec![])]
+    fn sample_test(#[case] targets: Vec<&str>, #[case] per: usize, #[case] expected: Vec<usize>) {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        let targets: Vec<String> = targets.into_iter().map(String::from).collect();
+        assert_eq!(chap.sample(&targets, per), expected);
+    }
+
+    #[rstest]
+    #[case(vec!["idiom"], vec![0, 2])]
+    #[case(vec!["grammar", "idiom"], vec![0, 1, 2])]
+    #[case(vec!["other"], vec![])]
+    fn tagged_test(#[case] tags: Vec<&str>, #[case] expected: Vec<usize>) {
+        let chap = Chapter::from_str(
+            "@ 1\na\ntags = grammar, idiom\n\n@ 2\nb\ntags = grammar\n\n@ 3\nc\ntags = idiom\n\n@ 4\nd\n",
+        )
+        .unwrap();
+        let tags: Vec<String> = tags.into_iter().map(String::from).collect();
+        assert_eq!(chap.tagged(&tags), expected);
+    }
+}
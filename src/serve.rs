@@ -0,0 +1,199 @@
+use crate::syntax::{Chapter, HtmlOptions, SourceMapEntry};
+use crate::tokenizer::DEFAULT_COMMENT;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::Path;
+use tracing::{debug, error, info};
+
+/// Scrolls to the sentence of `?line=N` and reports clicks on sentences
+/// to the page's opener or parent frame, for editor previews
+const SYNC_SCRIPT: &str = r#"<script>
+(function () {
+    const line = new URLSearchParams(location.search).get("line");
+    if (line) {
+        fetch("/sync?line=" + line).then(r => r.json()).then(s => {
+            const el = document.getElementById(s.anchor);
+            if (el) el.scrollIntoView();
+        });
+    }
+    document.addEventListener("click", e => {
+        const el = e.target.closest("[data-src]");
+        if (!el) return;
+        const [line, col] = el.dataset.src.split(":").map(Number);
+        const msg = { type: "transdoc-source", anchor: el.id, line, col };
+        (window.opener || window.parent).postMessage(msg, "*");
+    });
+})();
+</script>
+"#;
+
+/// Value of a query parameter, percent decoded
+pub fn query_param(query: &str, key: &str) -> Option<String> {
+    query.split('&').find_map(|kv| {
+        let (k, v) = kv.split_once('=')?;
+        (k == key).then(|| percent_decode(v))
+    })
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(b)) => {
+                out.push(b);
+                i += 2;
+            }
+            (b'+', _) => out.push(b' '),
+            (b, _) => out.push(b),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+fn entry_json(entry: Option<SourceMapEntry>) -> (&'static str, String) {
+    match entry {
+        Some(e) => (
+            "200 OK",
+            serde_json::to_string(&e).expect("source map is plain data"),
+        ),
+        None => ("404 Not Found", "null".to_string()),
+    }
+}
+
+/// Page with the sync script inserted once, before the last closing
+/// body tag or at the end without one
+fn with_sync_script(html: &str) -> String {
+    let at = html.rfind("</body>").unwrap_or(html.len());
+    format!("{}{SYNC_SCRIPT}{}", &html[..at], &html[at..])
+}
+
+/// Response to a request for the path: status, content type and body.
+///
+/// `/` is the rendered chapter, `/chapter.json` the parsed chapter as
+/// versioned JSON, `/sync?line=N` gives the sentence shown for a source
+/// line and `/sync?anchor=ID` the source position of a sentence element.
+pub fn respond(file: &Path, target: &str) -> (&'static str, &'static str, String) {
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let chap = match std::fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| {
+            let dir = file.parent().unwrap_or(Path::new(""));
+            Chapter::parse_in(&s, DEFAULT_COMMENT, dir).map_err(|e| e.user_msg(None))
+        }) {
+        Ok(c) => c,
+        Err(e) => return ("500 Internal Server Error", "text/plain", e),
+    };
+    match path {
+        "/" => {
+            let mut chap = chap;
+            chap.process();
+            let opts = HtmlOptions {
+                data_src: true,
+                ..Default::default()
+            };
+            let mut html = Vec::new();
+            if let Err(e) = chap.write_html(&mut html, &opts) {
+                return ("500 Internal Server Error", "text/plain", e.to_string());
+            }
+            let html = with_sync_script(&String::from_utf8_lossy(&html));
+            ("200 OK", "text/html; charset=utf-8", html)
+        }
+        "/chapter.json" => ("200 OK", "application/json", chap.to_json(false)),
+        "/sync" => {
+            let entry = if let Some(l) = query_param(query, "line") {
+                l.parse().ok().and_then(|l| chap.sentence_at_line(l))
+            } else if let Some(a) = query_param(query, "anchor") {
+                chap.sentence_at_anchor(&a)
+            } else {
+                None
+            };
+            let (status, body) = entry_json(entry);
+            (status, "application/json", body)
+        }
+        _ => ("404 Not Found", "text/plain", "Not Found".to_string()),
+    }
+}
+
+fn handle(file: &Path, mut stream: TcpStream) -> std::io::Result<()> {
+    let mut request = String::new();
+    BufReader::new(&stream).read_line(&mut request)?;
+    let target = request.split_whitespace().nth(1).unwrap_or("/");
+    let (status, ty, body) = respond(file, target);
+    debug!("{target} {status}");
+    write!(
+        stream,
+        "HTTP/1.1 {status}\r\nContent-Type: {ty}\r\nContent-Length: {}\r\nAccess-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+}
+
+/// Serves the chapter at `addr`, rendering it again on every request
+pub fn serve(file: &Path, addr: &str) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    info!("Serving {} at http://{addr}/", file.to_string_lossy());
+    for stream in listener.incoming() {
+        match stream {
+            Ok(s) => {
+                if let Err(e) = handle(file, s) {
+                    error!("{e}");
+                }
+            }
+            Err(e) => error!("{e}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("line=12", "line", Some("12"))]
+    #[case("a=1&anchor=line-%E0%A4%95", "anchor", Some("line-क"))]
+    #[case("anchor=a+b%2", "anchor", Some("a b%2"))]
+    #[case("line=1", "anchor", None)]
+    fn query_param_test(#[case] query: &str, #[case] key: &str, #[case] expected: Option<&str>) {
+        assert_eq!(query_param(query, key).as_deref(), expected);
+    }
+
+    #[rstest]
+    #[case("<body>a</body>", "<body>a", "</body>")]
+    #[case("<body><pre></body></pre></body>", "<body><pre></body></pre>", "</body>")]
+    #[case("<p>a</p>", "<p>a</p>", "")]
+    fn sync_script_test(#[case] html: &str, #[case] before: &str, #[case] after: &str) {
+        assert_eq!(with_sync_script(html), format!("{before}{SYNC_SCRIPT}{after}"));
+    }
+
+    #[rstest]
+    #[case(1, None)]
+    #[case(4, Some("line-first"))]
+    #[case(8, Some("line-first"))]
+    #[case(9, Some("line-second"))]
+    #[case(100, Some("line-second"))]
+    fn sync_line_test(#[case] line: usize, #[case] anchor: Option<&str>) {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("sample.chapter");
+        let (_, _, body) = respond(&file, &format!("/sync?line={line}"));
+        let entry: Option<SourceMapEntry> = serde_json::from_str(&body).unwrap();
+        assert_eq!(entry.map(|e| e.anchor).as_deref(), anchor);
+    }
+
+    #[test]
+    fn sync_anchor_test() {
+        let file = Path::new(env!("CARGO_MANIFEST_DIR")).join("sample.chapter");
+        let (status, _, body) = respond(&file, "/sync?anchor=line-second");
+        assert_eq!(status, "200 OK");
+        let entry: SourceMapEntry = serde_json::from_str(&body).unwrap();
+        assert_eq!((entry.line, entry.col), (9, 1));
+        let (status, _, _) = respond(&file, "/sync?anchor=line-none");
+        assert_eq!(status, "404 Not Found");
+    }
+}
@@ -0,0 +1,242 @@
+use crate::parser::resolve_path;
+use crate::syntax::Chapter;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Extensions of the chapter files in a directory build
+pub const CHAPTER_EXTENSIONS: [&str; 2] = ["chapter", "td"];
+
+/// File listing the files batch commands leave out, in gitignore syntax
+pub const IGNORE_FILE: &str = ".transdocignore";
+
+/// Patterns of the ignore file of a directory, matched against paths
+/// relative to it
+pub struct IgnoreList(Gitignore);
+
+impl IgnoreList {
+    /// Patterns of the directory's ignore file, none if there isn't one
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let mut builder = GitignoreBuilder::new(dir);
+        let file = dir.join(IGNORE_FILE);
+        if file.is_file()
+            && let Some(e) = builder.add(&file)
+        {
+            return Err(format!("{}: {e}", file.to_string_lossy()));
+        }
+        builder
+            .build()
+            .map(Self)
+            .map_err(|e| format!("{}: {e}", file.to_string_lossy()))
+    }
+
+    pub fn is_ignored(&self, path: &Path) -> bool {
+        let path = path.strip_prefix("./").unwrap_or(path);
+        if path.has_root() || path.components().any(|c| c == Component::ParentDir) {
+            // outside of the directory
+            return false;
+        }
+        self.0.matched_path_or_any_parents(path, false).is_ignore()
+    }
+}
+
+/// Files given on the command line: glob patterns are expanded, directories
+/// searched for chapter files and files matching the ignore files of the
+/// current directory or the directories in between left out
+pub fn expand_files(args: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for arg in args {
+        let pattern = arg.to_string_lossy();
+        let paths: Vec<PathBuf> = if pattern.contains(['*', '?', '[']) {
+            glob::glob(&pattern)
+                .map_err(|e| format!("{pattern}: {e}"))?
+                .filter_map(Result::ok)
+                .collect()
+        } else {
+            vec![arg.clone()]
+        };
+        if paths.is_empty() {
+            return Err(format!("{pattern}: no files match"));
+        }
+        for path in paths {
+            if path.is_dir() {
+                let found = chapter_files(&path).map_err(|e| format!("{pattern}: {e}"))?;
+                files.extend(found.into_iter().map(|f| path.join(f)));
+            } else {
+                files.push(path);
+            }
+        }
+    }
+    let cwd = std::env::current_dir().map_err(|e| e.to_string())?;
+    let mut ignores: HashMap<PathBuf, IgnoreList> = HashMap::new();
+    let mut kept = Vec::with_capacity(files.len());
+    for file in files {
+        let mut ignored = false;
+        for dir in file.ancestors().skip(1) {
+            // directories above the current one aren't searched
+            let inside = if dir.is_absolute() {
+                dir.starts_with(&cwd)
+            } else {
+                !dir.components().any(|c| c == Component::ParentDir)
+            };
+            if !inside {
+                continue;
+            }
+            if !ignores.contains_key(dir) {
+                ignores.insert(dir.to_path_buf(), IgnoreList::load(dir)?);
+            }
+            let rel = file.strip_prefix(dir).unwrap_or(&file);
+            if ignores[dir].is_ignored(rel) {
+                ignored = true;
+                break;
+            }
+        }
+        if !ignored {
+            kept.push(file);
+        }
+    }
+    Ok(kept)
+}
+
+/// Chapter files under the directory, relative to it and sorted; hidden
+/// entries are left out
+pub fn chapter_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![PathBuf::new()];
+    while let Some(rel) = dirs.pop() {
+        for entry in std::fs::read_dir(dir.join(&rel))? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name.to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = rel.join(&name);
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|e| CHAPTER_EXTENSIONS.iter().any(|c| e == *c))
+            {
+                files.push(path);
+            }
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Files the chapter in the directory is built from besides itself: its
+/// dictionary, known words and templates
+pub fn chapter_inputs(chap: &Chapter, dir: &Path) -> Vec<PathBuf> {
+    chap.attrs
+        .iter()
+        .filter_map(|(k, v)| match k.as_str() {
+            "dictionary" | "known_words" => Some(resolve_path(dir, v)),
+            _ if k.starts_with("template.") => Some(resolve_path(dir, v)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Relative paths linked from the page with `src` and `href` attributes,
+/// without their query and fragment; links leaving the directory are left out
+pub fn asset_links(html: &str) -> Vec<String> {
+    let mut links = Vec::new();
+    for attr in ["src=\"", "href=\""] {
+        let mut rest = html;
+        while let Some(start) = rest.find(attr) {
+            // `data-src` is the source position, not a link
+            let is_attr = rest[..start].chars().last().is_none_or(char::is_whitespace);
+            rest = &rest[start + attr.len()..];
+            let Some(end) = rest.find('"') else {
+                break;
+            };
+            let link = rest[..end].split(['?', '#']).next().unwrap_or_default();
+            rest = &rest[end..];
+            let relative = !link.is_empty()
+                && !link.contains(':')
+                && Path::new(link)
+                    .components()
+                    .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+            if is_attr && relative && !links.iter().any(|l| l == link) {
+                links.push(link.to_string());
+            }
+        }
+    }
+    links
+}
+
+/// Whether the output exists and is newer than all the inputs
+pub fn is_up_to_date(output: &Path, inputs: &[PathBuf]) -> bool {
+    let modified = |p: &Path| p.metadata().and_then(|m| m.modified()).ok();
+    let Some(out) = modified(output) else {
+        return false;
+    };
+    inputs.iter().all(|i| modified(i).is_some_and(|m| m <= out))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(r#"<img src="img/a.png">"#, vec!["img/a.png"])]
+    #[case(r#"<a href="b.html#x">b</a> <a href="b.html?q">"#, vec!["b.html"])]
+    #[case(r#"<a href="https://example.com/x.png">"#, vec![])]
+    #[case(r#"<img src="/abs.png"><img src="../up.png">"#, vec![])]
+    #[case(r##"<p data-src="1:1"><a href="#top">"##, vec![])]
+    fn asset_links_test(#[case] html: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(asset_links(html), expected);
+    }
+
+    #[test]
+    fn ignore_list_test() {
+        let dir = std::env::temp_dir().join(format!("transdoc-ignore-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("drafts")).unwrap();
+        std::fs::write(
+            dir.join(IGNORE_FILE),
+            "drafts/\n*.scratch.chapter\n!keep.scratch.chapter\n",
+        )
+        .unwrap();
+        let ignore = IgnoreList::load(&dir).unwrap();
+        assert!(ignore.is_ignored(Path::new("drafts/a.chapter")));
+        assert!(ignore.is_ignored(Path::new("./b.scratch.chapter")));
+        assert!(!ignore.is_ignored(Path::new("keep.scratch.chapter")));
+        assert!(!ignore.is_ignored(Path::new("a.chapter")));
+        assert!(!ignore.is_ignored(Path::new("../drafts/a.chapter")));
+        assert!(!IgnoreList::load(&dir.join("drafts"))
+            .unwrap()
+            .is_ignored(Path::new("a.chapter")));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn chapter_files_test() {
+        let dir = std::env::temp_dir().join(format!("transdoc-site-{}", std::process::id()));
+        for f in ["a.chapter", "sub/b.td", "sub/c.dict", ".hidden/d.chapter"] {
+            let path = dir.join(f);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let files = chapter_files(&dir).unwrap();
+        assert_eq!(
+            files,
+            vec![PathBuf::from("a.chapter"), PathBuf::from("sub/b.td")]
+        );
+        assert_eq!(
+            expand_files(&[dir.join("sub/*.td")]).unwrap(),
+            vec![dir.join("sub/b.td")]
+        );
+        assert!(expand_files(&[dir.join("*.missing")]).is_err());
+        assert!(is_up_to_date(
+            &dir.join("sub/c.dict"),
+            &[dir.join("a.chapter")]
+        ));
+        assert!(!is_up_to_date(
+            &dir.join("missing"),
+            &[dir.join("a.chapter")]
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
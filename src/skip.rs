@@ -0,0 +1,145 @@
+use crate::review::STATUS_ATTR;
+use crate::syntax::{Chapter, Sentence, Translation};
+
+/// Sentence attr marking it as left untranslated on purpose, like wordplay
+/// with no equivalent that a note explains
+pub const SKIP_ATTR: &str = "skip";
+
+/// Status of a translation left out on purpose in its language, given to
+/// an empty one: `--- german` followed by `status = skipped`
+pub const SKIPPED: &str = "skipped";
+
+impl Translation {
+    pub fn is_skipped(&self) -> bool {
+        self.content.trim().is_empty()
+            && self.attrs.get(STATUS_ATTR).map(|s| s.trim()) == Some(SKIPPED)
+    }
+}
+
+impl Sentence {
+    /// Whether the sentence has a `skip = yes` attr
+    pub fn is_skipped(&self) -> bool {
+        self.attr_bool(SKIP_ATTR).unwrap_or(false)
+    }
+}
+
+/// Where a sentence is in translating it to a language
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TranslationState {
+    Translated,
+    /// Left untranslated on purpose
+    Skipped,
+    Missing,
+}
+
+/// Sentences of a chapter translated to a language, and the ones left out
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Progress {
+    pub sentences: usize,
+    pub translated: usize,
+    pub skipped: usize,
+}
+
+impl Progress {
+    /// Percentage of the sentences to translate that are, the skipped ones
+    /// not counting
+    pub fn coverage(&self) -> f64 {
+        match self.sentences - self.skipped {
+            0 => 100.0,
+            n => self.translated as f64 * 100.0 / n as f64,
+        }
+    }
+}
+
+impl std::fmt::Display for Progress {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} sentences translated ({:.1}%)",
+            self.translated,
+            self.sentences - self.skipped,
+            self.coverage()
+        )?;
+        if self.skipped > 0 {
+            write!(f, ", {} skipped", self.skipped)?;
+        }
+        Ok(())
+    }
+}
+
+impl Chapter {
+    /// Whether the sentence is translated to the language; an empty
+    /// translation isn't unless it is skipped
+    pub fn translation_state(&self, sentence: &Sentence, lang: &str) -> TranslationState {
+        if sentence.is_skipped() {
+            return TranslationState::Skipped;
+        }
+        let tl = sentence
+            .translations
+            .iter()
+            .enumerate()
+            .find(|(i, (k, _))| self.translation_language(*i, k) == lang)
+            .map(|(_, (_, t))| t);
+        match tl {
+            Some(t) if t.is_skipped() => TranslationState::Skipped,
+            Some(t) if !t.content.trim().is_empty() => TranslationState::Translated,
+            _ => TranslationState::Missing,
+        }
+    }
+
+    pub fn progress(&self, lang: &str) -> Progress {
+        let mut progress = Progress::default();
+        for s in &self.sentences {
+            progress.sentences += 1;
+            match self.translation_state(s, lang) {
+                TranslationState::Translated => progress.translated += 1,
+                TranslationState::Skipped => progress.skipped += 1,
+                TranslationState::Missing => (),
+            }
+        }
+        progress
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const CHAP: &str = "@ 1\nhola\n--- german\nhallo\n\n@ 2\nun juego de palabras\nskip = yes\n\n@ 3\notro\n--- german\nstatus = skipped\n--- french\nautre\n\n@ 4\nadios\n";
+
+    #[rstest]
+    #[case("german", 1, 2, "1 of 2 sentences translated (50.0%), 2 skipped")]
+    #[case("french", 1, 1, "1 of 3 sentences translated (33.3%), 1 skipped")]
+    fn progress_test(
+        #[case] lang: &str,
+        #[case] translated: usize,
+        #[case] skipped: usize,
+        #[case] expected: &str,
+    ) {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        let progress = chap.progress(lang);
+        assert_eq!(
+            progress,
+            Progress {
+                sentences: 4,
+                translated,
+                skipped
+            }
+        );
+        assert_eq!(progress.to_string(), expected);
+    }
+
+    #[test]
+    fn skipped_source_test() {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        assert!(chap.sentences[2].translations["german"].is_skipped());
+        assert_eq!(
+            chap.sentences[2].source(),
+            "@ 3\notro\n--- german\nstatus = skipped\n--- french\nautre\n"
+        );
+        assert_eq!(Chapter::from_str(&chap.source()).unwrap(), chap);
+        assert!(Chapter::from_str("@ 1\nhola\n--- german\n\n@ 2\nadios\n").is_err());
+    }
+}
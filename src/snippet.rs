@@ -0,0 +1,151 @@
+use crate::plugin::Plugins;
+#[cfg(feature = "html")]
+use crate::syntax::HtmlOptions;
+use crate::syntax::{Chapter, OrgFragment, Sentence};
+use crate::widget::BundleSentence;
+use std::str::FromStr;
+
+/// Format of a sentence printed on its own
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SnippetFormat {
+    #[default]
+    Markdown,
+    #[cfg(feature = "html")]
+    Html,
+    Json,
+}
+
+impl FromStr for SnippetFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "md" | "markdown" => Ok(Self::Markdown),
+            #[cfg(feature = "html")]
+            "html" => Ok(Self::Html),
+            #[cfg(not(feature = "html"))]
+            "html" => Err("The html format needs the html feature".to_string()),
+            "json" => Ok(Self::Json),
+            _ => Err(format!("Unknown format {s:?}, use md, html or json")),
+        }
+    }
+}
+
+impl Chapter {
+    /// One sentence of a processed chapter for pasting elsewhere, with the
+    /// translations to the languages, or all of them when none are given
+    pub fn snippet(
+        &self,
+        label: &str,
+        format: SnippetFormat,
+        languages: &[String],
+    ) -> Result<String, String> {
+        let mut s: Sentence = self
+            .sentences
+            .iter()
+            .find(|s| s.label == label)
+            .ok_or_else(|| format!("No sentence labelled {label:?}"))?
+            .clone();
+        // named after their languages, positional keys would be wrong once
+        // some are left out
+        s.translations = s
+            .translations
+            .iter()
+            .enumerate()
+            .map(|(i, (k, t))| (self.translation_language(i, k), t.clone()))
+            .filter(|(l, _)| languages.is_empty() || languages.contains(l))
+            .collect();
+        let plugins = Plugins::default();
+        Ok(match format {
+            SnippetFormat::Markdown => markdown(&s, &plugins),
+            #[cfg(feature = "html")]
+            SnippetFormat::Html => self.sentence_html(&s, &HtmlOptions::default()),
+            SnippetFormat::Json => {
+                serde_json::to_string_pretty(&BundleSentence::new(self, &s, &plugins))
+                    .expect("sentence is plain data")
+            }
+        })
+    }
+}
+
+fn markdown(s: &Sentence, plugins: &Plugins) -> String {
+    let mut glosses = Vec::new();
+    let original: String = s
+        .original
+        .iter()
+        .map(|o| match o {
+            OrgFragment::Meaning(w, _)
+            | OrgFragment::Compound(w, _)
+            | OrgFragment::Sense(w, ..)
+                if o.gloss().is_some() =>
+            {
+                glosses.push(format!("- **{w}**: {}", o.gloss().unwrap_or_default()));
+                format!("**{w}**")
+            }
+            OrgFragment::Plugin(n, c) => plugins.text(n, c),
+            o => o.text().to_string(),
+        })
+        .collect();
+    let mut md = format!("> {}\n", original.trim());
+    if !glosses.is_empty() {
+        md.push('\n');
+        md.push_str(&glosses.join("\n"));
+        md.push('\n');
+    }
+    for (lang, t) in &s.translations {
+        md.push_str(&format!("\n*{lang}*: {}\n", t.content));
+    }
+    md
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const CHAP: &str = "tranlations = english, german\n\n@ 1\na\n\n@ 42\nhola << mundo = world; earth >>\n---\nhello world\n---\nhallo Welt\n";
+
+    #[rstest]
+    #[case(
+        SnippetFormat::Markdown,
+        vec![],
+        "> hola **mundo**\n\n- **mundo**: world; earth\n\n*english*: hello world\n\n*german*: hallo Welt\n"
+    )]
+    #[case(
+        SnippetFormat::Markdown,
+        vec!["german"],
+        "> hola **mundo**\n\n- **mundo**: world; earth\n\n*german*: hallo Welt\n"
+    )]
+    fn snippet_test(
+        #[case] format: SnippetFormat,
+        #[case] languages: Vec<&str>,
+        #[case] expected: &str,
+    ) {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        let languages: Vec<String> = languages.into_iter().map(String::from).collect();
+        assert_eq!(chap.snippet("42", format, &languages).unwrap(), expected);
+    }
+
+    #[cfg(feature = "html")]
+    #[test]
+    fn snippet_html_test() {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        assert_eq!(
+            chap.snippet("42", SnippetFormat::Html, &["german".to_string()])
+                .unwrap(),
+            r#"<p id="line-42"><div class="org">hola <span title="world; earth">mundo<span class="sr-only"> (world; earth)</span></span></div><div class="tl">hallo Welt</div></p>"#
+        );
+    }
+
+    #[test]
+    fn snippet_json_test() {
+        let chap = Chapter::from_str(CHAP).unwrap();
+        let json = chap
+            .snippet("42", SnippetFormat::Json, &["german".to_string()])
+            .unwrap();
+        let s: BundleSentence = serde_json::from_str(&json).unwrap();
+        assert_eq!(s.label, "42");
+        assert_eq!(s.translations.keys().collect::<Vec<_>>(), vec!["german"]);
+        assert!(chap.snippet("7", SnippetFormat::Json, &[]).is_err());
+    }
+}
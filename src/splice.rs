@@ -0,0 +1,436 @@
+use crate::parser::sentence;
+use crate::syntax::{escape_comments, Chapter, Sentence, Translation};
+use crate::tokenizer::{get_tokens_with, Token, TokenList, TokenType, DEFAULT_COMMENT};
+use indexmap::IndexMap;
+use std::ops::Range;
+
+/// What a line of a sentence's source holds
+#[derive(Debug, Clone, PartialEq)]
+enum Kind {
+    /// Only spaces or a comment
+    Blank,
+    /// `@ label` or a `---` separator
+    Head,
+    /// Original or translation text
+    Text,
+    /// `key = value` with the key
+    Attr(String),
+}
+
+/// Line of a sentence's source, as byte offsets into it
+struct Line {
+    /// Where the line starts and ends, before its line end
+    start: usize,
+    end: usize,
+    /// Its text, without the spaces and comment around it
+    text: Range<usize>,
+    kind: Kind,
+}
+
+/// Head line of the label or a separator with the lines after it, up to
+/// the next separator
+#[derive(Default)]
+struct Block {
+    head: usize,
+    text: Option<usize>,
+    attrs: Vec<usize>,
+}
+
+fn line(src: &str, tokens: &[Token], start: usize, end: usize) -> Line {
+    let text: Vec<&Token> = tokens
+        .iter()
+        .filter(|t| !matches!(t.ty, TokenType::WhiteSpace | TokenType::Comment))
+        .collect();
+    let (Some(first), Some(last)) = (text.first(), text.last()) else {
+        return Line {
+            start,
+            end,
+            text: start..start,
+            kind: Kind::Blank,
+        };
+    };
+    let mut depth = 0usize;
+    let mut equal = None;
+    for t in &text {
+        match t.ty {
+            TokenType::AngleStart | TokenType::BraceStart => depth += 1,
+            TokenType::AngleEnd | TokenType::BraceEnd => depth = depth.saturating_sub(1),
+            TokenType::Equal if depth == 0 => {
+                equal = Some(t.offset);
+                break;
+            }
+            _ => (),
+        }
+    }
+    let kind = match (first.ty, equal) {
+        (TokenType::At | TokenType::Dash, _) => Kind::Head,
+        (_, Some(eq)) => Kind::Attr(src[first.offset..eq].trim().to_string()),
+        _ => Kind::Text,
+    };
+    Line {
+        start,
+        end,
+        text: first.offset..last.end(),
+        kind,
+    }
+}
+
+/// Lines of the sentence's source
+fn lines(src: &str, tokens: &[Token]) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut from = 0;
+    let mut start = 0;
+    for (i, t) in tokens.iter().enumerate() {
+        if t.ty == TokenType::NewLine {
+            lines.push(line(src, &tokens[from..i], start, t.offset));
+            from = i + 1;
+            start = t.end();
+        }
+    }
+    lines.push(line(src, &tokens[from..], start, src.len()));
+    lines
+}
+
+fn blocks(lines: &[Line]) -> Vec<Block> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for (i, l) in lines.iter().enumerate() {
+        match (&l.kind, blocks.last_mut()) {
+            (Kind::Head, _) => blocks.push(Block {
+                head: i,
+                ..Default::default()
+            }),
+            (Kind::Text, Some(b)) if b.text.is_none() && b.attrs.is_empty() => b.text = Some(i),
+            (Kind::Attr(_), Some(b)) => b.attrs.push(i),
+            _ => (),
+        }
+    }
+    blocks
+}
+
+fn attr_line(key: &str, value: &str) -> String {
+    format!("{key} = {}", escape_comments(value))
+}
+
+/// Changes to the source of a sentence, as ranges and their replacements
+struct Edits<'a> {
+    lines: &'a [Line],
+    eol: &'a str,
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl Edits<'_> {
+    fn replace(&mut self, line: usize, text: String) {
+        self.edits.push((self.lines[line].text.clone(), text));
+    }
+
+    /// Removes the line with the line end before it, or after it for the
+    /// first line
+    fn delete(&mut self, line: usize) {
+        let range = match line {
+            0 => self.lines[0].start..self.lines.get(1).map_or(self.lines[0].end, |l| l.start),
+            _ => self.lines[line - 1].end..self.lines[line].end,
+        };
+        self.edits.push((range, String::new()));
+    }
+
+    /// Adds the text on a line after the given one, or before the first
+    fn insert_after(&mut self, line: Option<usize>, text: &str) {
+        let edit = match line {
+            Some(l) => {
+                let end = self.lines[l].end;
+                (end..end, format!("{}{text}", self.eol))
+            }
+            None => (0..0, format!("{text}{}", self.eol)),
+        };
+        self.edits.push(edit);
+    }
+
+    /// Writes the attr lines of the block that changed, a key only renamed
+    /// staying on its line and new keys going after the last attr
+    fn attrs(
+        &mut self,
+        block: &Block,
+        anchor: Option<usize>,
+        old: &IndexMap<String, String>,
+        new: &IndexMap<String, String>,
+    ) {
+        let mut added: Vec<(&String, &String)> =
+            new.iter().filter(|(k, _)| !old.contains_key(*k)).collect();
+        let mut anchor = anchor;
+        for &i in &block.attrs {
+            let Kind::Attr(key) = &self.lines[i].kind else {
+                continue;
+            };
+            match new.get(key) {
+                Some(v) => {
+                    if old.get(key) != Some(v) {
+                        self.replace(i, attr_line(key, v));
+                    }
+                    anchor = Some(i);
+                }
+                None => {
+                    let renamed = old
+                        .get_index_of(key)
+                        .and_then(|p| new.get_index(p))
+                        .and_then(|(k, _)| added.iter().position(|(a, _)| *a == k));
+                    match renamed {
+                        Some(p) => {
+                            let (k, v) = added.remove(p);
+                            self.replace(i, attr_line(k, v));
+                            anchor = Some(i);
+                        }
+                        None => self.delete(i),
+                    }
+                }
+            }
+        }
+        for (k, v) in added {
+            self.insert_after(anchor, &attr_line(k, v));
+        }
+    }
+
+    fn translation(
+        &mut self,
+        block: &Block,
+        i: usize,
+        old: (&String, &Translation),
+        new: (&String, &Translation),
+    ) {
+        let (old_lang, old) = old;
+        let (lang, new) = new;
+        if old_lang != lang {
+            self.replace(block.head, separator(i, lang));
+        }
+        let mut anchor = block.head;
+        if old.content != new.content {
+            match block.text {
+                Some(t) if new.content.is_empty() => self.delete(t),
+                Some(t) => {
+                    self.replace(t, escape_comments(&new.content));
+                    anchor = t;
+                }
+                None => self.insert_after(Some(block.head), &escape_comments(&new.content)),
+            }
+        } else if let Some(t) = block.text {
+            anchor = t;
+        }
+        self.attrs(block, Some(anchor), &old.attrs, &new.attrs);
+    }
+
+    fn apply(mut self, src: &str) -> String {
+        // an insertion goes before a removal starting at the same place
+        self.edits.sort_by_key(|(r, _)| (r.start, r.end));
+        let mut out = String::with_capacity(src.len());
+        let mut pos = 0;
+        for (r, text) in self.edits {
+            out.push_str(&src[pos..r.start]);
+            out.push_str(&text);
+            pos = r.end;
+        }
+        out.push_str(&src[pos..]);
+        out
+    }
+}
+
+/// Separator line of the translation at the position
+fn separator(i: usize, lang: &str) -> String {
+    // positional labels are what the parser gives to bare separators
+    if lang == i.to_string() {
+        "---".to_string()
+    } else {
+        format!("--- {lang}")
+    }
+}
+
+/// Source of the sentence written from scratch, when its old source
+/// doesn't line up with it
+fn rewritten(s: &Sentence, eol: &str) -> String {
+    s.source().trim_end_matches('\n').replace('\n', eol)
+}
+
+/// Source of the sentence, from `@` to the end of its last line of text,
+/// with the lines that changed written again
+pub(crate) fn splice_sentence(src: &str, comment: &str, new: &Sentence, eol: &str) -> String {
+    let tokens = get_tokens_with(src, comment);
+    let Ok((_, old)) = sentence(TokenList::new(&tokens)) else {
+        return rewritten(new, eol);
+    };
+    let lines = lines(src, &tokens);
+    let blocks = blocks(&lines);
+    if blocks.len() != old.translations.len() + 1 {
+        return rewritten(new, eol);
+    }
+    let mut edits = Edits {
+        lines: &lines,
+        eol,
+        edits: Vec::new(),
+    };
+    let first = &blocks[0];
+    if old.label != new.label {
+        edits.replace(first.head, format!("@ {}", new.label));
+    }
+    let original: String = new.original.iter().map(|o| o.source()).collect();
+    let mut anchor = first.head;
+    match first.text {
+        Some(t) => {
+            if old.original != new.original {
+                edits.replace(t, original);
+            }
+            anchor = t;
+        }
+        None if !original.is_empty() => edits.insert_after(Some(first.head), &original),
+        None => (),
+    }
+    edits.attrs(first, Some(anchor), &old.orgattrs, &new.orgattrs);
+    for (i, block) in blocks[1..].iter().enumerate() {
+        let old = old
+            .translations
+            .get_index(i)
+            .expect("a block per translation");
+        match new.translations.get_index(i) {
+            Some(new) => edits.translation(block, i, old, new),
+            None => {
+                let last = block
+                    .attrs
+                    .last()
+                    .or(block.text.as_ref())
+                    .unwrap_or(&block.head);
+                for l in block.head..=*last {
+                    edits.delete(l);
+                }
+            }
+        }
+    }
+    let mut out = edits.apply(src);
+    for (i, (lang, tl)) in new
+        .translations
+        .iter()
+        .enumerate()
+        .skip(old.translations.len())
+    {
+        out.push_str(eol);
+        out.push_str(&separator(i, lang));
+        out.push_str(eol);
+        out.push_str(&tl.source().trim_end_matches('\n').replace('\n', eol));
+    }
+    out
+}
+
+/// Attr lines, like the ones a chapter starts with, with the attrs that
+/// changed from `old` to `new` written again
+pub(crate) fn splice_attrs(
+    src: &str,
+    comment: &str,
+    old: &IndexMap<String, String>,
+    new: &IndexMap<String, String>,
+    eol: &str,
+) -> String {
+    let tokens = get_tokens_with(src, comment);
+    let lines = lines(src, &tokens);
+    let block = Block {
+        attrs: (0..lines.len())
+            .filter(|i| matches!(lines[*i].kind, Kind::Attr(_)))
+            .collect(),
+        ..Default::default()
+    };
+    let mut edits = Edits {
+        lines: &lines,
+        eol,
+        edits: Vec::new(),
+    };
+    edits.attrs(&block, None, old, new);
+    edits.apply(src)
+}
+
+impl Chapter {
+    /// Source the chapter was parsed from with the given sentences written
+    /// again, so comments and spacing elsewhere are kept
+    pub fn splice_sentences(&self, src: &str, indices: &[usize]) -> String {
+        self.splice_sentences_with(src, DEFAULT_COMMENT, indices)
+    }
+
+    /// `splice_sentences` for a source with comments started by the given
+    /// token; only the lines that changed are written, keeping the
+    /// comments inside the sentences too
+    pub fn splice_sentences_with(&self, src: &str, comment: &str, indices: &[usize]) -> String {
+        let eol = if src.contains("\r\n") { "\r\n" } else { "\n" };
+        let mut out = String::with_capacity(src.len());
+        let mut pos = 0;
+        for &i in indices {
+            let s = &self.sentences[i];
+            let end = s.span.line_end(src);
+            out.push_str(&src[pos..s.span.start]);
+            out.push_str(&splice_sentence(&src[s.span.start..end], comment, s, eol));
+            pos = end;
+        }
+        out.push_str(&src[pos..]);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const CHAP: &str = "@ 1\nhola mundo   # trailing remark\n# inner note about the original\nlevel = A1 # easy\ntags = a\n--- en\nhello world\n# about the translation\nstatus = draft\n\n# next\n@ 2\nb\n";
+
+    #[test]
+    fn keeps_comments_test() {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        assert_eq!(chap.mark_stale(), vec![0, 1]);
+        let hashed = chap.splice_sentences(CHAP, &[0, 1]);
+        let hash = chap.sentences[0].original_hash();
+        assert_eq!(
+            hashed,
+            format!(
+                "@ 1\nhola mundo   # trailing remark\n# inner note about the original\nlevel = A1 # easy\ntags = a\nhash = {hash}\n--- en\nhello world\n# about the translation\nstatus = draft\n\n# next\n@ 2\nb\nhash = {}\n",
+                chap.sentences[1].original_hash()
+            )
+        );
+        assert_eq!(Chapter::from_str(&hashed).unwrap(), chap);
+    }
+
+    #[test]
+    fn changed_lines_test() {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        let s = &mut chap.sentences[0];
+        s.orgattrs.shift_remove("tags");
+        s.orgattrs.insert("level".into(), "B2".into());
+        let tl = s.translations.get_mut("en").unwrap();
+        tl.content = "hi # world".into();
+        tl.attrs.shift_remove("status");
+        tl.attrs.insert("stale".into(), "yes".into());
+        let edited = chap.splice_sentences(CHAP, &[0]);
+        assert_eq!(
+            edited,
+            "@ 1\nhola mundo   # trailing remark\n# inner note about the original\nlevel = B2 # easy\n--- en\nhi \\# world\n# about the translation\nstale = yes\n\n# next\n@ 2\nb\n"
+        );
+        assert_eq!(Chapter::from_str(&edited).unwrap(), chap);
+    }
+
+    #[test]
+    fn other_comment_token_test() {
+        let src = "@ 1\na // note\n---\nA // last\r\n";
+        let mut chap = Chapter::parse_with(src, "//").unwrap();
+        chap.sentences[0]
+            .translations
+            .get_mut("0")
+            .unwrap()
+            .attrs
+            .insert("status".into(), "draft".into());
+        chap.sentences[0].translations.insert(
+            "de".into(),
+            Translation {
+                content: "B".into(),
+                attrs: IndexMap::new(),
+            },
+        );
+        let edited = chap.splice_sentences_with(src, "//", &[0]);
+        assert_eq!(
+            edited,
+            "@ 1\na // note\n---\nA // last\r\nstatus = draft\r\n--- de\r\nB\r\n"
+        );
+        assert_eq!(Chapter::parse_with(&edited, "//").unwrap(), chap);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::checksum::fnv1a;
+use crate::syntax::{Chapter, Sentence};
+
+/// Sentence attr with the hash of the original its translations were made from
+pub const HASH_ATTR: &str = "hash";
+
+/// Translation attr marking it as made from an older original
+pub const STALE_ATTR: &str = "stale";
+
+impl Sentence {
+    /// Hash of the original as written, glosses included
+    pub fn original_hash(&self) -> String {
+        let src: String = self.original.iter().map(|o| o.source()).collect();
+        format!("{:016x}", fnv1a(src.trim()))
+    }
+
+    /// Whether the original changed since its hash was stored
+    pub fn is_changed(&self) -> bool {
+        self.orgattrs
+            .get(HASH_ATTR)
+            .is_some_and(|h| *h != self.original_hash())
+    }
+
+    /// Languages of the translations marked stale
+    pub fn stale_translations(&self) -> Vec<&str> {
+        self.translations
+            .iter()
+            .filter(|(_, t)| t.attrs.get(STALE_ATTR).is_some_and(|v| v == "yes"))
+            .map(|(l, _)| l.as_str())
+            .collect()
+    }
+}
+
+impl Chapter {
+    /// Marks the translations of the sentences whose original changed as
+    /// stale and stores the hash of the originals that have none or an old
+    /// one, giving the indices of the sentences that were modified
+    pub fn mark_stale(&mut self) -> Vec<usize> {
+        let mut modified = Vec::new();
+        for (i, s) in self.sentences.iter_mut().enumerate() {
+            let hash = s.original_hash();
+            if s.orgattrs.get(HASH_ATTR) == Some(&hash) {
+                continue;
+            }
+            if s.orgattrs.contains_key(HASH_ATTR) {
+                for t in s.translations.values_mut() {
+                    t.attrs.insert(STALE_ATTR.to_string(), "yes".to_string());
+                }
+            }
+            s.orgattrs.insert(HASH_ATTR.to_string(), hash);
+            modified.push(i);
+        }
+        modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn mark_stale_test() {
+        let old = "# notes\n@ 1\nhello\n---\nhola\n\n# keep\n@ 2\nbye\n---\nadios\n";
+        let mut chap = Chapter::from_str(old).unwrap();
+        assert_eq!(chap.mark_stale(), vec![0, 1]);
+        let hashed = chap.splice_sentences(old, &[0, 1]);
+        assert!(hashed.contains("# notes\n@ 1\nhello\nhash = "));
+        assert!(hashed.contains("# keep\n@ 2\n"));
+
+        let edited = hashed.replace("bye", "goodbye");
+        let mut chap = Chapter::from_str(&edited).unwrap();
+        assert!(!chap.sentences[0].is_changed());
+        assert!(chap.sentences[1].is_changed());
+        assert_eq!(chap.mark_stale(), vec![1]);
+        let marked = chap.splice_sentences(&edited, &[1]);
+        let chap = Chapter::from_str(&marked).unwrap();
+        assert!(!chap.sentences[1].is_changed());
+        assert_eq!(chap.sentences[1].stale_translations(), vec!["0"]);
+        assert!(chap.sentences[0].stale_translations().is_empty());
+        assert!(marked.starts_with(&hashed[..hashed.find("@ 2").unwrap()]));
+    }
+}
@@ -0,0 +1,120 @@
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Where an export came from, written into it so a copy going around can
+/// be traced back to the sources it was built from
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stamp {
+    /// Version of transdoc
+    pub version: String,
+    /// When it was built, like `2024-03-01T12:00:00Z`, left out of
+    /// reproducible builds
+    pub built: Option<String>,
+    /// Source files and the SHA-256 of their contents
+    pub sources: Vec<(String, String)>,
+    /// Identifier of the project, like a repository or group name
+    pub project: Option<String>,
+}
+
+impl Stamp {
+    /// Stamp of this version of transdoc, built now unless reproducible
+    pub fn new(project: Option<String>, reproducible: bool) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            built: (!reproducible).then(|| {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                timestamp(now.as_secs())
+            }),
+            sources: Vec::new(),
+            project,
+        }
+    }
+
+    /// Adds the file to the sources with the hash of its contents
+    pub fn add_source(&mut self, file: &Path) -> Result<(), String> {
+        let bytes = std::fs::read(file).map_err(|e| format!("{}: {e}", file.to_string_lossy()))?;
+        let name = file.to_string_lossy().to_string();
+        if !self.sources.iter().any(|(n, _)| *n == name) {
+            self.sources.push((name, sha256_hex(&bytes)));
+        }
+        Ok(())
+    }
+
+    /// HTML comment with the stamp, one field per line
+    pub fn comment(&self) -> String {
+        let mut lines = vec![format!("transdoc {}", self.version)];
+        if let Some(b) = &self.built {
+            lines.push(format!("built {b}"));
+        }
+        if let Some(p) = &self.project {
+            lines.push(format!("project {p}"));
+        }
+        for (name, hash) in &self.sources {
+            lines.push(format!("source {name} sha256:{hash}"));
+        }
+        // `--` can't be in a comment
+        format!("<!--\n{}\n-->", lines.join("\n").replace("--", "- -"))
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// UTC date and time of the seconds since the Unix epoch
+pub fn timestamp(secs: u64) -> String {
+    let (days, rest) = ((secs / 86400) as i64, secs % 86400);
+    // civil date from days, after Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(0, "1970-01-01T00:00:00Z")]
+    #[case(951_782_400, "2000-02-29T00:00:00Z")]
+    #[case(1_709_294_461, "2024-03-01T12:01:01Z")]
+    #[case(4_107_542_399, "2100-02-28T23:59:59Z")]
+    fn timestamp_test(#[case] secs: u64, #[case] expected: &str) {
+        assert_eq!(timestamp(secs), expected);
+    }
+
+    #[test]
+    fn comment_test() {
+        let stamp = Stamp {
+            version: "0.1.0".into(),
+            built: None,
+            sources: vec![("a--b.chapter".into(), sha256_hex(b"abc"))],
+            project: Some("group".into()),
+        };
+        assert_eq!(
+            stamp.comment(),
+            "<!--\ntransdoc 0.1.0\nproject group\nsource a- -b.chapter sha256:ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n-->"
+        );
+        assert!(Stamp::new(None, true).built.is_none());
+        assert!(Stamp::new(None, false).built.is_some());
+    }
+}
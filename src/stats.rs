@@ -0,0 +1,105 @@
+use crate::syntax::Chapter;
+use crate::words::words;
+use std::collections::HashMap;
+
+/// Words ranked by frequency, the most frequent first
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrequencyList {
+    ranks: HashMap<String, usize>,
+}
+
+impl FrequencyList {
+    /// Frequency list file with one word per line, most frequent first.
+    ///
+    /// Only the first column is used, so lists with counts after the word
+    /// (`word 1234`) work as they are.
+    pub fn parse(text: &str) -> Self {
+        let mut ranks = HashMap::new();
+        for w in text.lines().filter_map(|l| l.split_whitespace().next()) {
+            let rank = ranks.len() + 1;
+            ranks.entry(w.to_lowercase()).or_insert(rank);
+        }
+        Self { ranks }
+    }
+
+    /// Rank of the word starting at 1, `None` if it's not in the list
+    pub fn rank(&self, word: &str) -> Option<usize> {
+        self.ranks.get(&word.to_lowercase()).copied()
+    }
+}
+
+/// How hard a chapter is to read, from the frequency of its words
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Difficulty {
+    /// Words in the originals
+    pub tokens: usize,
+    /// Words within the top N of the frequency list
+    pub in_band: usize,
+    /// Distinct words not in the frequency list at all
+    pub out_of_list: usize,
+}
+
+impl Difficulty {
+    /// Percentage of words within the frequency band
+    pub fn coverage(&self) -> f64 {
+        if self.tokens == 0 {
+            100.0
+        } else {
+            self.in_band as f64 * 100.0 / self.tokens as f64
+        }
+    }
+}
+
+impl Chapter {
+    /// Difficulty of the originals against the `top` most frequent words
+    pub fn difficulty(&self, freq: &FrequencyList, top: usize) -> Difficulty {
+        let mut diff = Difficulty::default();
+        let mut missing = std::collections::HashSet::new();
+        for s in &self.sentences {
+            for w in words(&s.text()) {
+                diff.tokens += 1;
+                match freq.rank(w) {
+                    Some(r) if r <= top => diff.in_band += 1,
+                    Some(_) => (),
+                    None => {
+                        missing.insert(w.to_lowercase());
+                    }
+                }
+            }
+        }
+        diff.out_of_list = missing.len();
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    const FREQ: &str = "the 100\nis 90\na\ncat 10\n";
+
+    #[rstest]
+    #[case("@ 1\nThe cat is a cat\n", 2, 5, 2, 0)]
+    #[case("@ 1\nThe << dog >> is here\n", 4, 4, 2, 2)]
+    #[case("", 4, 0, 0, 0)]
+    fn difficulty_test(
+        #[case] txt: &str,
+        #[case] top: usize,
+        #[case] tokens: usize,
+        #[case] in_band: usize,
+        #[case] out_of_list: usize,
+    ) {
+        let chap = Chapter::from_str(txt).unwrap();
+        let diff = chap.difficulty(&FrequencyList::parse(FREQ), top);
+        assert_eq!(
+            diff,
+            Difficulty {
+                tokens,
+                in_band,
+                out_of_list
+            }
+        );
+    }
+}
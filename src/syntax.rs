@@ -1,108 +1,1065 @@
-use std::collections::HashMap;
-use std::fs::File;
+#[cfg(feature = "html")]
+use crate::align;
+use crate::errors::Diagnostic;
+#[cfg(feature = "html")]
+use crate::html::{Edition, GlossFormat, GlossMode, HtmlStyle, RepeatGloss, escape_attr};
+pub use crate::intern::Meanings;
+use crate::intern::intern;
+use crate::known::KnownLevels;
+use crate::label::Label;
+use crate::notes::Note;
+#[cfg(feature = "html")]
+use crate::output::{GENERATED_MARKER, write_atomic};
+#[cfg(feature = "html")]
+use crate::page::page_marker;
+use crate::plugin::Plugins;
+#[cfg(feature = "html")]
+use crate::template::{Templates, render};
+use crate::words::{DEFAULT_PATTERN_LIMIT, DictPattern, Normalizer, is_known, words};
+use indexmap::IndexMap;
+#[cfg(feature = "cli")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "html")]
 use std::io::prelude::*;
+#[cfg(feature = "html")]
 use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum OrgFragment {
     Simple(String),
-    Meaning(String, Vec<String>),
+    Meaning(String, Meanings),
     DictLookup(String),
+    /// `{{name:content}}` rendered by the plugin of that name
+    Plugin(String, String),
+    /// Word missing from the dictionary glossed by its parts that aren't
+    Compound(String, Vec<(String, Meanings)>),
+    /// `<<?language text>>` only shown to readers of that language, see
+    /// `Chapter::for_languages`
+    Conditional(String, String),
+    /// `<< word#2 >>` or `<< word = @2 >>`, glossed with one sense of its
+    /// dictionary entry counted from 1; the meanings are the whole entry
+    /// once processed and empty before
+    Sense(String, usize, Meanings),
+}
+
+/// Gloss of a compound, the meanings of each part after it
+pub fn compound_gloss(parts: &[(String, Meanings)]) -> String {
+    parts
+        .iter()
+        .map(|(p, m)| format!("{p} ({})", m.join("; ")))
+        .collect::<Vec<_>>()
+        .join(" + ")
+}
+
+/// Splits the word into a dictionary word and the rest, also in the
+/// dictionary, trying the longest first part first; the rest is also tried
+/// capitalized, for languages capitalizing nouns
+fn split_compound<'a>(
+    word: &str,
+    lookup: impl Fn(&str) -> Option<&'a Meanings>,
+) -> Option<Vec<(String, Meanings)>> {
+    let bounds: Vec<usize> = word.char_indices().map(|(i, _)| i).skip(2).collect();
+    for &i in bounds.iter().rev() {
+        let (first, rest) = word.split_at(i);
+        if rest.chars().count() < 2 {
+            continue;
+        }
+        let Some(m) = lookup(first) else {
+            continue;
+        };
+        let mut chars = rest.chars();
+        let capitalized: String = chars
+            .next()
+            .into_iter()
+            .flat_map(char::to_uppercase)
+            .chain(chars)
+            .collect();
+        for rest in [rest, capitalized.as_str()] {
+            if let Some(r) = lookup(rest) {
+                return Some(vec![
+                    (first.to_string(), m.clone()),
+                    (rest.to_string(), r.clone()),
+                ]);
+            }
+        }
+    }
+    None
 }
 
 impl OrgFragment {
-    fn html(&self) -> String {
+    #[cfg(feature = "html")]
+    fn html(&self, opts: &HtmlOptions, glosses: &mut Glosses) -> String {
         match self {
-            Self::Simple(s) => s.to_string(),
-            Self::Meaning(s, m) => format!("<span title=\"{}\">{s}</span>", m.join("; ")),
+            Self::Simple(s) if glosses.levels.is_empty() => s.to_string(),
+            Self::Simple(s) => crate::known::wrap_known(s, glosses.levels),
+            Self::Meaning(s, m) => glosses.html(s, m),
             Self::DictLookup(s) => format!("<span class=\"unk\">{s}</span>"),
+            Self::Plugin(n, c) => opts.plugins.html(n, c),
+            // glosses made while rendering aren't kept in the interner
+            Self::Compound(s, parts) => format!(
+                "<span class=\"compound\">{}</span>",
+                glosses.html(s, &Meanings::from([compound_gloss(parts).into()]))
+            ),
+            Self::Conditional(..) => String::new(),
+            Self::Sense(s, ..) => match self.gloss() {
+                Some(g) => glosses.html(s, &Meanings::from([g.into()])),
+                None => format!("<span class=\"unk\">{s}</span>"),
+            },
+        }
+    }
+
+    /// Meanings shown for the word, if it is glossed
+    pub fn gloss(&self) -> Option<String> {
+        match self {
+            Self::Meaning(_, m) => Some(m.join("; ")),
+            Self::Compound(_, parts) => Some(compound_gloss(parts)),
+            Self::Sense(_, n, m) => n
+                .checked_sub(1)
+                .and_then(|i| m.get(i))
+                .map(|s| format!("{s} (sense {n} of {})", m.len())),
+            _ => None,
+        }
+    }
+
+    /// Text of the fragment as it appears in the original, plugin
+    /// fragments give the text of the built-in plugin
+    pub fn text(&self) -> Cow<'_, str> {
+        match self {
+            Self::Simple(s)
+            | Self::Meaning(s, _)
+            | Self::DictLookup(s)
+            | Self::Compound(s, _)
+            | Self::Sense(s, ..) => Cow::Borrowed(s),
+            Self::Plugin(n, c) => Cow::Owned(Plugins::builtin().text(n, c)),
+            Self::Conditional(..) => Cow::Borrowed(""),
+        }
+    }
+
+    pub fn source(&self) -> String {
+        match self {
+            Self::Simple(s) => escape_comments(s),
+            Self::Meaning(s, m) => format!(
+                "<< {} = {} >>",
+                escape_comments(s),
+                escape_comments(&m.join("; "))
+            ),
+            Self::DictLookup(s) | Self::Compound(s, _) => format!("<< {} >>", escape_comments(s)),
+            Self::Plugin(n, c) => format!("{{{{{n}:{}}}}}", escape_comments(c)),
+            Self::Conditional(l, t) if t.is_empty() => format!("<< ?{l} >>"),
+            Self::Conditional(l, t) => format!("<< ?{l} {} >>", escape_comments(t)),
+            Self::Sense(s, n, _) => format!("<< {}#{n} >>", escape_comments(s)),
+        }
+    }
+}
+
+/// Gloss settings of a page and the words glossed on it so far
+#[cfg(feature = "html")]
+struct Glosses<'a> {
+    mode: GlossMode,
+    format: GlossFormat,
+    repeat: RepeatGloss,
+    seen: HashSet<String>,
+    /// Known words marked with their level
+    levels: &'a KnownLevels,
+    provenance: &'a HashMap<Arc<str>, Provenance>,
+}
+
+#[cfg(feature = "html")]
+impl Glosses<'_> {
+    fn html(&mut self, word: &str, meanings: &Meanings) -> String {
+        let meanings: Vec<&str> = meanings.iter().map(|m| m.as_ref()).collect();
+        let source = self.provenance.get(word).map(|p| p.to_string());
+        let gloss = self.format.render(word, &meanings, source.as_deref());
+        if self.seen.insert(word.to_string()) {
+            return self.mode.html(word, &gloss);
+        }
+        match self.repeat {
+            RepeatGloss::Full => self.mode.html(word, &gloss),
+            RepeatGloss::Marker => format!("<span class=\"seen\">{word}</span>"),
+            RepeatGloss::None => word.to_string(),
+        }
+    }
+}
+
+/// Chapter attr overriding a renderer option, a bad value is warned about
+fn attr_or<T: std::str::FromStr<Err = String>>(
+    attrs: &IndexMap<String, String>,
+    key: &str,
+    default: T,
+) -> T {
+    match attrs.get(key).map(|v| v.parse()) {
+        Some(Ok(v)) => v,
+        Some(Err(e)) => {
+            tracing::warn!("{e}, using the default");
+            default
+        }
+        None => default,
+    }
+}
+
+/// Text with a backslash before each `#` that could start a comment: at
+/// the start or after whitespace
+pub fn escape_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut prev: Option<char> = None;
+    for c in text.chars() {
+        if c == '#' && prev.is_none_or(char::is_whitespace) {
+            out.push('\\');
         }
+        out.push(c);
+        prev = Some(c);
     }
+    out
+}
+
+/// Writes the attrs back as `key = value` lines
+pub fn attrs_source(attrs: &IndexMap<String, String>) -> String {
+    attrs
+        .iter()
+        .map(|(k, v)| format!("{k} = {}\n", escape_comments(v)))
+        .collect()
+}
+
+/// Writes dictionary entries as `word = meaning; meaning` lines
+pub fn dictionary_source<K: AsRef<str>, V: AsRef<[S]>, S: AsRef<str>>(
+    entries: impl IntoIterator<Item = (K, V)>,
+) -> String {
+    entries
+        .into_iter()
+        .map(|(k, v)| {
+            let meanings: Vec<&str> = v.as_ref().iter().map(AsRef::as_ref).collect();
+            format!("{} = {}\n", k.as_ref(), meanings.join("; "))
+        })
+        .collect()
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Translation {
     pub content: String,
-    pub attrs: HashMap<String, String>,
+    pub attrs: IndexMap<String, String>,
 }
 
 impl Translation {
-    fn html(&self) -> String {
-        format!("<div class=\"tl\">{}</div>", self.content)
+    /// HTML of the translation with the words of the link groups wrapped
+    #[cfg(feature = "html")]
+    fn html(&self, links: &HashMap<usize, Vec<String>>) -> String {
+        if links.is_empty() {
+            return format!("<div class=\"tl\">{}</div>", self.content);
+        }
+        let (content, _) = align::wrap_words(&self.content, 0, links);
+        format!("<div class=\"tl\">{content}</div>")
+    }
+
+    pub fn source(&self) -> String {
+        if self.content.is_empty() {
+            return attrs_source(&self.attrs);
+        }
+        format!(
+            "{}\n{}",
+            escape_comments(&self.content),
+            attrs_source(&self.attrs)
+        )
+    }
+}
+
+/// Where something is in the source file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Span {
+    /// Byte offsets of the start and end; a sentence ends with its last
+    /// text, before the blank and comment lines after it
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub col: usize,
+}
+
+/// Number of line ends in the text, `\r\n`, `\n` and a lone `\r` each
+/// counting as one
+fn line_ends(text: &str) -> usize {
+    text.matches('\n').count() + text.matches('\r').count() - text.matches("\r\n").count()
+}
+
+/// Byte offset of the start of the last line of the text
+fn last_line_start(text: &str) -> usize {
+    text.rfind(['\n', '\r']).map(|i| i + 1).unwrap_or(0)
+}
+
+impl Span {
+    /// Fills line and column (both from 1, column in bytes) from the start
+    pub fn locate(&mut self, src: &str) {
+        let before = &src[..self.start.min(src.len())];
+        self.line = line_ends(before) + 1;
+        self.col = before.len() - last_line_start(before) + 1;
+    }
+
+    /// End of the line the span ends on, before its line end, so a
+    /// comment after the last text is kept with it
+    pub fn line_end(&self, src: &str) -> usize {
+        let end = self.end.min(src.len());
+        end + src[end..].find(['\n', '\r']).unwrap_or(src.len() - end)
+    }
+
+    /// `locate` counting the lines from an earlier located span, so
+    /// locating all the sentences in order doesn't rescan the file each time
+    pub fn locate_after(&mut self, src: &str, prev: &Span) {
+        let start = self.start.min(src.len());
+        let from = prev.start.min(start);
+        self.line = prev.line + line_ends(&src[from..start]);
+        self.col = start - last_line_start(&src[..start]) + 1;
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Sentence {
     pub label: String,
     pub original: Vec<OrgFragment>,
-    pub orgattrs: HashMap<String, String>,
-    pub translations: HashMap<String, Translation>,
+    pub orgattrs: IndexMap<String, String>,
+    pub translations: IndexMap<String, Translation>,
+    #[serde(default)]
+    pub span: Span,
+}
+
+/// Sentences are equal by their contents, wherever they are in the file,
+/// so a merge sees a sentence moved by an edit above it as unchanged
+impl PartialEq for Sentence {
+    fn eq(&self, other: &Self) -> bool {
+        self.label == other.label
+            && self.original == other.original
+            && self.orgattrs == other.orgattrs
+            && self.translations == other.translations
+    }
 }
 
 impl Sentence {
-    fn html(&self) -> String {
-        let org: Vec<String> = self.original.iter().map(OrgFragment::html).collect();
-        let tls: Vec<String> = self.translations.values().map(Translation::html).collect();
-        format!(
-            "<p id=\"line-{}\"><div class=\"org\">{}</div>{}</p>",
-            self.label,
-            org.join(""),
-            tls.join("")
+    #[cfg(feature = "html")]
+    fn html(
+        &self,
+        templates: &Templates,
+        opts: &HtmlOptions,
+        glosses: &mut Glosses,
+        note: Option<&Note>,
+        bookmark: bool,
+        number: &str,
+    ) -> String {
+        let links = self.links();
+        let mut words = 0;
+        let org: Vec<String> = self
+            .original
+            .iter()
+            .map(|o| {
+                let html = o.html(opts, glosses);
+                if links.is_empty() {
+                    return html;
+                }
+                let (html, n) = align::wrap_fragment(o, html, words, &links.original);
+                words += n;
+                html
+            })
+            .collect();
+        let tls: Vec<String> = self
+            .translations
+            .values()
+            .filter(|_| opts.edition == Edition::Full)
+            .zip(&links.translations)
+            .map(|(t, l)| {
+                let html = t.html(l);
+                #[cfg(feature = "encrypt")]
+                if let Some(sealer) = &opts.seal {
+                    return sealer.seal_html(&html);
+                }
+                html
+            })
+            .collect();
+        let data_src = if opts.data_src {
+            format!(" data-src=\"{}:{}\"", self.span.line, self.span.col)
+        } else {
+            String::new()
+        };
+        let tags = self.tags();
+        let tags = if tags.is_empty() {
+            String::new()
+        } else {
+            // tags can have spaces but not commas
+            format!(" data-tags=\"{}\"", escape_attr(&tags.join(",")))
+        };
+        let level = match self.level() {
+            Some(l) => format!(" data-level=\"{}\"", escape_attr(l)),
+            None => String::new(),
+        };
+        let bookmark = if bookmark { " data-bookmark" } else { "" };
+        let notes = match note.filter(|n| !n.text.is_empty()) {
+            Some(n) => format!(
+                "<aside class=\"note\">{}</aside>",
+                escape_attr(&n.text).replace('\n', "<br>")
+            ),
+            None => String::new(),
+        };
+        render(
+            templates.sentence(self.orgattrs.get("template").map(String::as_str)),
+            &[
+                ("label", &Label::from(self.label.as_str()).id()),
+                ("data_src", &data_src),
+                ("tags", &tags),
+                ("level", &level),
+                ("bookmark", bookmark),
+                ("number", number),
+                ("original", &org.join("")),
+                ("translations", &tls.join("")),
+                ("notes", &notes),
+            ],
         )
     }
+
+    /// Tags from the comma separated `tags` attr
+    pub fn tags(&self) -> Vec<&str> {
+        self.attr_list("tags")
+    }
+
+    /// Difficulty level from the `level` attr, like `A2`
+    pub fn level(&self) -> Option<&str> {
+        self.orgattrs
+            .get("level")
+            .map(|l| l.trim())
+            .filter(|l| !l.is_empty())
+    }
+
+    /// Plain text of the original without the glosses
+    pub fn text(&self) -> String {
+        self.original.iter().map(OrgFragment::text).collect()
+    }
+
+    /// Source text of the sentence, parsing it back gives the same sentence
+    pub fn source(&self) -> String {
+        let mut src = format!("@ {}\n", self.label);
+        self.original.iter().for_each(|o| src.push_str(&o.source()));
+        src.push('\n');
+        src.push_str(&attrs_source(&self.orgattrs));
+        for (i, (lang, tl)) in self.translations.iter().enumerate() {
+            // positional labels are what the parser gives to bare separators
+            if *lang == i.to_string() {
+                src.push_str("---\n");
+            } else {
+                src.push_str(&format!("--- {lang}\n"));
+            }
+            src.push_str(&tl.source());
+        }
+        src
+    }
+}
+
+/// Shows only the sentences with the chosen tag
+#[cfg(feature = "html")]
+const TAG_FILTER: &str = r#"<label class="tag-filter">Tag <select onchange="
+    for (const el of document.querySelectorAll('[id^=line-]')) {
+        el.hidden = this.value !== '' && !(el.dataset.tags || '').split(',').includes(this.value);
+    }"><option value="">All</option>{{options}}</select></label>
+"#;
+
+/// Blurs or hides the sentences above the chosen level
+#[cfg(feature = "html")]
+const LEVEL_FILTER: &str = r#"<form class="level-filter" onchange="
+    const levels = [...this.level.options].map(o => o.value);
+    const max = levels.indexOf(this.level.value);
+    for (const el of document.querySelectorAll('[data-level]')) {
+        const above = max > 0 && levels.indexOf(el.dataset.level) > max;
+        el.classList.toggle('level-blur', above && this.mode.value === 'blur');
+        el.classList.toggle('level-hide', above && this.mode.value === 'hide');
+    }">Level <select name="level"><option value="">All</option>{{options}}</select>
+<select name="mode"><option>blur</option><option>hide</option></select></form>
+"#;
+
+/// Highlights the words aligned with the one under the pointer
+#[cfg(feature = "html")]
+const ALIGN_HOVER: &str = r#"<script>
+for (const el of document.querySelectorAll('[data-align]')) {
+    const line = el.closest('[id^=line-]');
+    const linked = () => el.dataset.align.split(' ')
+        .flatMap(g => [...line.querySelectorAll(`[data-align~="${g}"]`)]);
+    el.addEventListener('mouseenter', () => linked().forEach(e => e.classList.add('aligned')));
+    el.addEventListener('mouseleave', () => linked().forEach(e => e.classList.remove('aligned')));
+}
+</script>
+"#;
+
+/// Renderer settings that don't come from the chapter itself
+#[cfg(feature = "html")]
+#[derive(Debug, Default)]
+pub struct HtmlOptions {
+    pub plugins: Plugins,
+    /// Add `data-src="line:col"` of the source to the sentences
+    pub data_src: bool,
+    pub style: HtmlStyle,
+    /// How glosses are shown unless the chapter's `gloss` attr says otherwise
+    pub gloss: GlossMode,
+    /// What glosses show unless the chapter's `gloss_format` attr says
+    /// otherwise
+    pub gloss_format: GlossFormat,
+    /// How words glossed before on the page are shown unless the chapter's
+    /// `repeat_gloss` attr says otherwise
+    pub repeat_gloss: RepeatGloss,
+    /// Show the sentence labels before the originals unless the chapter's
+    /// `numbered` attr says otherwise
+    pub numbered: bool,
+    pub edition: Edition,
+    /// Words glossed on earlier pages, counted as glossed before
+    pub seen: HashSet<String>,
+    /// Encrypt the translations, for the page to decrypt with the passphrase
+    #[cfg(feature = "encrypt")]
+    pub seal: Option<crate::encrypt::Sealer>,
 }
 
-#[derive(Debug, Clone)]
+/// Position of a rendered sentence in the source file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceMapEntry {
+    pub label: String,
+    /// id of the sentence element in the HTML
+    pub anchor: String,
+    pub line: usize,
+    pub col: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Source map written by `build --source-map`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceMap {
+    pub sentences: Vec<SourceMapEntry>,
+}
+
+/// Where a dictionary entry of a chapter came from
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Provenance {
+    /// Line of a dictionary file
+    File { file: String, line: usize },
+    /// Inline gloss of a sentence of the chapter
+    Sentence { label: String },
+    /// Inline gloss of a sentence of an earlier chapter of the book
+    Chapter { chapter: String, label: String },
+}
+
+impl std::fmt::Display for Provenance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File { file, line } => write!(f, "{file}:{line}"),
+            Self::Sentence { label } => write!(f, "learned from sentence @{label}"),
+            Self::Chapter { chapter, label } => {
+                write!(f, "learned from sentence @{label} of {chapter}")
+            }
+        }
+    }
+}
+
+/// Chapter attr listing the languages of the positional translations
+pub const TRANSLATIONS_ATTR: &str = "translations";
+
+/// Misspelling of [`TRANSLATIONS_ATTR`] older chapters use, still read
+pub const TRANSLATIONS_ALIAS: &str = "tranlations";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Chapter {
     pub title: String,
     pub language: String,
     pub tl_languages: Vec<String>,
-    pub dictionary: HashMap<String, Vec<String>>,
+    pub dictionary: HashMap<Arc<str>, Meanings>,
+    /// Dictionary entries glossing the words their regex matches, tried in
+    /// order when a word isn't in the dictionary
+    #[serde(skip)]
+    pub patterns: Vec<DictPattern>,
+    /// Where the dictionary entries came from
+    #[serde(default)]
+    pub provenance: HashMap<Arc<str>, Provenance>,
+    pub known_words: HashSet<String>,
+    /// Known words by proficiency level when they come from a directory,
+    /// easiest first; `known_words` has all of them
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub known_levels: KnownLevels,
     pub sentences: Vec<Sentence>,
-    pub attrs: HashMap<String, String>,
+    pub attrs: IndexMap<String, String>,
+    /// Reader's notes by sentence label, from the chapter's notes file
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub notes: IndexMap<String, Note>,
+    /// Problems found while loading the files the chapter refers to
+    #[serde(skip)]
+    pub diagnostics: Vec<Diagnostic>,
+    /// Directory the files the attrs name are relative to, the chapter's
+    #[serde(skip)]
+    pub dir: PathBuf,
 }
 
 impl Chapter {
+    /// Key of the translations attr, the alias when only it is set
+    pub fn translations_key(&self) -> &'static str {
+        if !self.attrs.contains_key(TRANSLATIONS_ATTR)
+            && self.attrs.contains_key(TRANSLATIONS_ALIAS)
+        {
+            TRANSLATIONS_ALIAS
+        } else {
+            TRANSLATIONS_ATTR
+        }
+    }
+
+    /// Source text of the chapter, comments and spacing are not preserved
+    pub fn source(&self) -> String {
+        let mut src = attrs_source(&self.attrs);
+        for s in &self.sentences {
+            src.push('\n');
+            src.push_str(&s.source());
+        }
+        src
+    }
+
+    /// Language of the translation at the index of a sentence with the key,
+    /// positional ones named after the `translations` attr
+    pub fn translation_language(&self, index: usize, key: &str) -> String {
+        match self.tl_languages.get(index) {
+            Some(l) if key == index.to_string() => l.trim().to_string(),
+            _ => self.canonical_language(key),
+        }
+    }
+
+    /// Names the translations labelled by their position after their
+    /// language in the `translations` attr, so inserting one doesn't shift
+    /// the others, giving the indices of the sentences that changed
+    pub fn label_translations(&mut self) -> Vec<usize> {
+        let mut modified = Vec::new();
+        for (si, s) in self.sentences.iter_mut().enumerate() {
+            let positional = s
+                .translations
+                .keys()
+                .enumerate()
+                .any(|(i, k)| *k == i.to_string() && i < self.tl_languages.len());
+            if !positional {
+                continue;
+            }
+            s.translations = std::mem::take(&mut s.translations)
+                .into_iter()
+                .enumerate()
+                .map(|(i, (k, t))| match self.tl_languages.get(i) {
+                    Some(l) if k == i.to_string() => (l.trim().to_string(), t),
+                    _ => (k, t),
+                })
+                .collect();
+            modified.push(si);
+        }
+        modified
+    }
+
+    /// Language a separator label stands for: the one it is an alias of
+    /// in a `language.aliases = a, b` attr, or the one of the `tranlations`
+    /// attr it matches ignoring case
+    pub fn canonical_language(&self, label: &str) -> String {
+        let label = label.trim();
+        let lower = label.to_lowercase();
+        for (k, v) in &self.attrs {
+            if let Some(lang) = k.strip_suffix(".aliases")
+                && v.split(',').any(|a| a.trim().to_lowercase() == lower)
+            {
+                return lang.to_string();
+            }
+        }
+        self.tl_languages
+            .iter()
+            .map(|l| l.trim())
+            .find(|l| l.to_lowercase() == lower)
+            .unwrap_or(label)
+            .to_string()
+    }
+
+    /// Languages of the translations, from the `translations` attr and the
+    /// separators naming them
+    pub fn translation_languages(&self) -> Vec<String> {
+        let mut langs: Vec<String> = self
+            .tl_languages
+            .iter()
+            .map(|l| l.trim().to_string())
+            .collect();
+        for s in &self.sentences {
+            for (i, k) in s.translations.keys().enumerate() {
+                let lang = self.translation_language(i, k);
+                if !langs.contains(&lang) {
+                    langs.push(lang);
+                }
+            }
+        }
+        langs
+    }
+
+    /// Metadata of each translation language from `language.key = value`
+    /// chapter attrs, like `english.translator = Alice`
+    pub fn language_attrs(&self) -> IndexMap<String, IndexMap<String, String>> {
+        let langs = self.translation_languages();
+        let mut meta: IndexMap<String, IndexMap<String, String>> = IndexMap::new();
+        for (k, v) in &self.attrs {
+            if let Some((lang, key)) = k.split_once('.')
+                && langs.iter().any(|l| l == lang)
+            {
+                meta.entry(lang.to_string())
+                    .or_default()
+                    .insert(key.to_string(), v.trim().to_string());
+            }
+        }
+        meta
+    }
+
+    /// Who translated to which language and the other language attrs
+    #[cfg(feature = "html")]
+    fn byline_html(&self) -> String {
+        let lines: Vec<String> = self
+            .language_attrs()
+            .iter()
+            .map(|(lang, attrs)| {
+                let mut line = match attrs.get("translator") {
+                    Some(t) => format!("{lang} translation by {t}"),
+                    None => format!("{lang} translation"),
+                };
+                let rest: Vec<String> = attrs
+                    .iter()
+                    .filter(|(k, _)| {
+                        *k != "translator"
+                            && *k != "aliases"
+                            && !crate::numbers::LOCALE_ATTRS.contains(&k.as_str())
+                    })
+                    .map(|(k, v)| format!("{k}: {v}"))
+                    .collect();
+                if !rest.is_empty() {
+                    line.push_str(&format!(" ({})", rest.join(", ")));
+                }
+                format!("<li>{line}</li>")
+            })
+            .collect();
+        if lines.is_empty() {
+            String::new()
+        } else {
+            format!("<ul class=\"byline\">{}</ul>\n", lines.concat())
+        }
+    }
+
+    /// Levels of the sentences from the easiest, ordered like `A1 < A2 < B1`
+    /// or `2 < 10`
+    pub fn levels(&self) -> Vec<&str> {
+        let mut levels: Vec<&str> = self.sentences.iter().filter_map(|s| s.level()).collect();
+        levels.sort_unstable_by_key(|l| (l.len(), *l));
+        levels.dedup();
+        levels
+    }
+
+    /// Summary for the page metadata, the `description` attr or the text
+    /// of the first sentence
+    pub fn description(&self) -> String {
+        let text = match self.attrs.get("description") {
+            Some(d) => d.clone(),
+            None => self
+                .sentences
+                .first()
+                .map(Sentence::text)
+                .unwrap_or_default(),
+        };
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    /// Page head from the template, with the title, description, author and
+    /// language as meta and Open Graph tags
+    #[cfg(feature = "html")]
+    fn head_html(&self, templates: &Templates) -> String {
+        let title = escape_attr(&self.title);
+        let description = escape_attr(&self.description());
+        let author = escape_attr(self.attrs.get("author").map_or("", |a| a.trim()));
+        let lang = escape_attr(self.attrs.get("lang").map_or("", |l| l.trim()));
+        let mut meta = vec![
+            format!(r#"<meta name="description" content="{description}">"#),
+            r#"<meta property="og:type" content="article">"#.to_string(),
+            format!(r#"<meta property="og:title" content="{title}">"#),
+            format!(r#"<meta property="og:description" content="{description}">"#),
+        ];
+        if !author.is_empty() {
+            meta.push(format!(r#"<meta name="author" content="{author}">"#));
+            meta.push(format!(
+                r#"<meta property="article:author" content="{author}">"#
+            ));
+        }
+        if !lang.is_empty() {
+            meta.push(format!(r#"<meta property="og:locale" content="{lang}">"#));
+        }
+        render(
+            templates.head(),
+            &[
+                ("title", &title),
+                ("description", &description),
+                ("author", &author),
+                ("lang", &lang),
+                ("meta", &meta.join("\n    ")),
+            ],
+        )
+    }
+
+    /// Source positions of the sentences, for previews to sync with editors
+    pub fn source_map(&self) -> Vec<SourceMapEntry> {
+        self.sentences
+            .iter()
+            .map(|s| SourceMapEntry {
+                label: s.label.clone(),
+                anchor: Label::from(s.label.as_str()).anchor(),
+                line: s.span.line,
+                col: s.span.col,
+                start: s.span.start,
+                end: s.span.end,
+            })
+            .collect()
+    }
+
+    /// Sentence at a source line, the last one starting at or before it
+    pub fn sentence_at_line(&self, line: usize) -> Option<SourceMapEntry> {
+        self.source_map()
+            .into_iter()
+            .take_while(|e| e.line <= line)
+            .last()
+    }
+
+    /// Source position of the sentence with the given element id
+    pub fn sentence_at_anchor(&self, anchor: &str) -> Option<SourceMapEntry> {
+        self.source_map().into_iter().find(|e| e.anchor == anchor)
+    }
+
+    /// Times patterns may gloss words of one sentence, from the
+    /// `pattern_limit` attr
+    pub fn pattern_limit(&self) -> usize {
+        self.attr_integer("pattern_limit")
+            .and_then(|n| usize::try_from(n).ok())
+            .unwrap_or(DEFAULT_PATTERN_LIMIT)
+    }
+
+    /// Whether words missing from the dictionary are split into parts in
+    /// it, from the `compounds` attr
+    pub fn compounds(&self) -> bool {
+        self.attr_bool("compounds").unwrap_or(false)
+    }
+
+    /// Adds the inline glosses to the dictionary and resolves the lookups.
+    ///
+    /// Lookups only use the inline glosses that come before them, like
+    /// reading the chapter in order would, and sentences are resolved in
+    /// parallel. Words missing from the dictionary that the reader doesn't
+    /// know are split into compounds when `compounds` is on, otherwise get
+    /// the meanings of the first pattern matching them, up to
+    /// `pattern_limit` times per sentence. Amounts of units and then numbers
+    /// and dates are glossed last, see `convert_units` and
+    /// `localize_numbers`.
     pub fn process(&mut self) {
-        for s in &mut self.sentences {
-            for w in &mut s.original {
-                match w {
-                    OrgFragment::Simple(_) => (),
-                    OrgFragment::Meaning(s, m) => {
-                        if let std::collections::hash_map::Entry::Vacant(e) =
-                            self.dictionary.entry(s.to_string())
-                        {
-                            e.insert(m.clone());
-                        }
+        self.expand_variables();
+        self.for_languages(&[]);
+        let normalizer = attr_or(&self.attrs, "normalize", Normalizer::default());
+        let limit = self.pattern_limit();
+        let compounds = self.compounds();
+        // position of the first inline gloss of words not in the dictionary
+        let mut inline: HashMap<String, (usize, usize)> = HashMap::new();
+        for (i, s) in self.sentences.iter().enumerate() {
+            for (j, w) in s.original.iter().enumerate() {
+                if let OrgFragment::Meaning(s, m) = w
+                    && !self.dictionary.contains_key(s.as_str())
+                {
+                    let key = normalizer.apply(s);
+                    if !inline.contains_key(key.as_ref()) {
+                        inline.insert(key.into_owned(), (i, j));
+                        self.dictionary.insert(intern(s), m.clone());
+                        let label = self.sentences[i].label.clone();
+                        self.provenance
+                            .insert(intern(s), Provenance::Sentence { label });
+                    }
+                }
+            }
+        }
+        let normalized: HashMap<String, &Meanings> = if normalizer.is_empty() {
+            HashMap::new()
+        } else {
+            let mut n = HashMap::new();
+            for (k, v) in &self.dictionary {
+                n.entry(normalizer.apply(k).into_owned()).or_insert(v);
+            }
+            n
+        };
+        let dictionary = &self.dictionary;
+        let known_words = &self.known_words;
+        let patterns = &self.patterns;
+        let lookup = |w: &str| {
+            dictionary
+                .get(w)
+                .or_else(|| normalized.get(normalizer.apply(w).as_ref()).copied())
+        };
+        #[cfg(feature = "cli")]
+        let sentences = self.sentences.par_iter_mut();
+        #[cfg(not(feature = "cli"))]
+        let sentences = self.sentences.iter_mut();
+        sentences.enumerate().for_each(|(i, s)| {
+            let mut matched = 0;
+            for (j, w) in s.original.iter_mut().enumerate() {
+                if let OrgFragment::Sense(s, n, m) = w {
+                    let key = normalizer.apply(s);
+                    let glossed_later = inline.get(key.as_ref()).is_some_and(|pos| *pos > (i, j));
+                    if let Some(entry) = lookup(s).filter(|e| !glossed_later && *n <= e.len()) {
+                        *m = entry.clone();
+                    }
+                    continue;
+                }
+                let OrgFragment::DictLookup(s) = w else {
+                    continue;
+                };
+                let key = normalizer.apply(s);
+                let glossed_later = inline.get(key.as_ref()).is_some_and(|pos| *pos > (i, j));
+                let meanings = dictionary
+                    .get(s.as_str())
+                    .or_else(|| normalized.get(key.as_ref()).copied());
+                let compound = || {
+                    (compounds && !glossed_later)
+                        .then(|| split_compound(&key, lookup))
+                        .flatten()
+                };
+                match meanings {
+                    Some(m) if !glossed_later => {
+                        *w = OrgFragment::Meaning(s.to_string(), m.clone())
+                    }
+                    _ if is_known(known_words, s) => {
+                        // the reader knows it, no need to mark it unknown
+                        *w = OrgFragment::Simple(s.to_string());
                     }
-                    OrgFragment::DictLookup(s) => {
-                        if let Some(m) = self.dictionary.get(s) {
-                            *w = OrgFragment::Meaning(s.to_string(), m.clone());
+                    _ if let Some(parts) = compound() => {
+                        *w = OrgFragment::Compound(s.to_string(), parts);
+                    }
+                    _ if !glossed_later && matched < limit => {
+                        if let Some(p) = patterns.iter().find(|p| p.regex.is_match(&key)) {
+                            matched += 1;
+                            *w = OrgFragment::Meaning(s.to_string(), p.meanings.clone());
                         }
                     }
+                    _ => (),
+                }
+            }
+        });
+        self.convert_units();
+        self.localize_numbers();
+    }
+
+    /// Words of the originals that are neither glossed, in the dictionary
+    /// nor known, with their number of occurrences
+    pub fn unknown_words(&self) -> IndexMap<String, usize> {
+        let glossed: HashSet<&str> = self
+            .sentences
+            .iter()
+            .flat_map(|s| &s.original)
+            .filter_map(|o| match o {
+                OrgFragment::Meaning(w, _) => Some(w.as_str()),
+                OrgFragment::Sense(w, _, m) if !m.is_empty() => Some(w.as_str()),
+                _ => None,
+            })
+            .collect();
+        let mut unknown: IndexMap<String, usize> = IndexMap::new();
+        for frag in self.sentences.iter().flat_map(|s| &s.original) {
+            let text = match frag {
+                OrgFragment::Simple(s) => s.as_str(),
+                OrgFragment::DictLookup(s) => s.as_str(),
+                OrgFragment::Sense(s, _, m) if m.is_empty() => s.as_str(),
+                OrgFragment::Meaning(..)
+                | OrgFragment::Sense(..)
+                | OrgFragment::Compound(..)
+                | OrgFragment::Plugin(..)
+                | OrgFragment::Conditional(..) => {
+                    continue;
+                }
+            };
+            for w in words(text) {
+                if glossed.contains(w)
+                    || self.dictionary.contains_key(w)
+                    || is_known(&self.known_words, w)
+                    || self.patterns.iter().any(|p| p.regex.is_match(w))
+                {
+                    continue;
                 }
+                *unknown.entry(w.to_string()).or_default() += 1;
             }
         }
+        unknown.sort_by(|_, a, _, b| b.cmp(a));
+        unknown
     }
+}
 
+#[cfg(feature = "html")]
+impl Chapter {
     pub fn to_html<P: AsRef<Path>>(&self, file: P) -> std::io::Result<()> {
-        let mut f = File::create(file)?;
+        self.to_html_with(file, &HtmlOptions::default())
+    }
+
+    pub fn to_html_with<P: AsRef<Path>>(&self, file: P, opts: &HtmlOptions) -> std::io::Result<()> {
+        let mut html = Vec::new();
+        self.write_html(&mut html, opts)?;
+        write_atomic(file.as_ref(), &html)
+    }
+
+    fn glosses(&self, opts: &HtmlOptions) -> Glosses<'_> {
+        Glosses {
+            mode: attr_or(&self.attrs, "gloss", opts.gloss),
+            format: attr_or(&self.attrs, "gloss_format", opts.gloss_format.clone()),
+            repeat: attr_or(&self.attrs, "repeat_gloss", opts.repeat_gloss),
+            seen: opts.seen.clone(),
+            levels: &self.known_levels,
+            provenance: &self.provenance,
+        }
+    }
+
+    /// HTML of one sentence as on the page, without the page around it
+    pub fn sentence_html(&self, sentence: &Sentence, opts: &HtmlOptions) -> String {
+        let templates = Templates::from_attrs(&self.attrs, &self.dir);
+        sentence.html(
+            &templates,
+            opts,
+            &mut self.glosses(opts),
+            self.notes.get(&sentence.label),
+            self.is_bookmarked(sentence),
+            &self.number_html(
+                sentence,
+                self.outline_number(sentence, opts).as_deref(),
+                opts,
+            ),
+        )
+    }
+
+    pub fn write_html<W: Write>(&self, f: &mut W, opts: &HtmlOptions) -> std::io::Result<()> {
+        if opts.style == HtmlStyle::Raw {
+            return self.write_raw_html(f, opts);
+        }
+        let mut html = Vec::new();
+        self.write_raw_html(&mut html, opts)?;
+        f.write_all(opts.style.apply(&String::from_utf8_lossy(&html)).as_bytes())
+    }
+
+    fn write_raw_html<W: Write>(&self, f: &mut W, opts: &HtmlOptions) -> std::io::Result<()> {
+        let templates = Templates::from_attrs(&self.attrs, &self.dir);
         write!(
             f,
-            r#"
-<html>
-    <title> {0} </title>
+            r##"{GENERATED_MARKER}
+<html{1}>
+    <head>
+    {2}
+    </head>
     <body>
 	<style>
 	 .tl {{
-	     color: #aabbaa;
+	     color: #4d6b4d;
 	 }}
 	 .alt {{
 	     color: green;
 	 }}
+	 .byline {{
+	     list-style: none;
+	     padding: 0;
+	     font-style: italic;
+	 }}
 	 .unk {{
-	     color: red;
+	     color: #c00;
 	 }}
 	 span {{
 	     color: blue;
@@ -110,25 +1067,561 @@ impl Chapter {
 	 span:hover {{
 	     background-color: pink;
 	 }}
+	 .gloss {{
+	     font-size: smaller;
+	     color: #595959;
+	     margin-left: 0.2em;
+	 }}
+	 .seen {{
+	     color: inherit;
+	     text-decoration: underline dotted;
+	 }}
+	 .compound {{
+	     border-bottom: 1px dashed;
+	 }}
+	 .gloss-toggle {{
+	     cursor: pointer;
+	 }}
+	 .gloss-toggle .gloss {{
+	     display: none;
+	 }}
+	 .gloss-toggle.shown .gloss {{
+	     display: inline;
+	 }}
+	 .quote {{
+	     font-style: italic;
+	 }}
+	 .letter {{
+	     border-left: 2px solid #ddd;
+	     padding-left: 1em;
+	 }}
+	 .sign {{
+	     text-align: center;
+	     font-variant: small-caps;
+	 }}
+	 [data-level^=A] {{
+	     border-left: 3px solid #9c9;
+	 }}
+	 [data-level^=B] {{
+	     border-left: 3px solid #ec8;
+	 }}
+	 [data-level^=C] {{
+	     border-left: 3px solid #e99;
+	 }}
+	 .level-blur {{
+	     filter: blur(4px);
+	 }}
+	 .level-blur:hover {{
+	     filter: none;
+	 }}
+	 .level-hide {{
+	     display: none;
+	 }}
+	 .sr-only {{
+	     position: absolute;
+	     width: 1px;
+	     height: 1px;
+	     overflow: hidden;
+	     clip-path: inset(50%);
+	     white-space: nowrap;
+	 }}
+	 .note {{
+	     float: right;
+	     clear: right;
+	     width: 12em;
+	     margin-left: 1em;
+	     font-size: smaller;
+	     color: #7a5c00;
+	 }}
+	 [data-bookmark] {{
+	     border-right: 3px solid #c90;
+	 }}
+	 .aligned {{
+	     background-color: #fd8;
+	 }}
+	 .sealed::before {{
+	     content: "Encrypted translation";
+	     color: #888;
+	     font-style: italic;
+	 }}
+	 .page {{
+	     float: left;
+	     clear: left;
+	     margin-left: -3.5em;
+	     width: 3em;
+	     text-align: right;
+	     color: #888;
+	     font-size: smaller;
+	 }}
+	 .pages {{
+	     font-size: smaller;
+	     color: #888;
+	 }}
+	 .section a {{
+	     color: inherit;
+	     text-decoration: none;
+	 }}
+	 .sections ol {{
+	     list-style: none;
+	 }}
+	 .num {{
+	     color: #888;
+	     font-size: smaller;
+	     margin-right: 0.5em;
+	     text-decoration: none;
+	 }}
+	 .skip {{
+	     position: absolute;
+	     left: -999em;
+	 }}
+	 .skip:focus {{
+	     left: 0;
+	 }}
 	</style>
+<a class="skip" href="#text">Skip to the text</a>
+<main>
 <h2> {0} </h2>
-"#,
-            self.title
+"##,
+            self.title_html(opts),
+            self.attrs
+                .get("lang")
+                .map(|l| format!(" lang=\"{l}\""))
+                .unwrap_or_default(),
+            self.head_html(&templates)
         )?;
-        for s in &self.sentences {
-            writeln!(f, "{}", s.html())?
+        write!(f, "{}", self.byline_html())?;
+        let mut tags: Vec<&str> = self.sentences.iter().flat_map(|s| s.tags()).collect();
+        tags.sort_unstable();
+        tags.dedup();
+        if !tags.is_empty() {
+            let options: String = tags
+                .iter()
+                .map(|t| format!("<option>{}</option>", escape_attr(t)))
+                .collect();
+            write!(f, "{}", TAG_FILTER.replace("{{options}}", &options))?;
+        }
+        let levels = self.levels();
+        if !levels.is_empty() {
+            let options: String = levels
+                .iter()
+                .map(|l| format!("<option>{}</option>", escape_attr(l)))
+                .collect();
+            write!(f, "{}", LEVEL_FILTER.replace("{{options}}", &options))?;
+        }
+        #[cfg(feature = "encrypt")]
+        if let Some(sealer) = &opts.seal {
+            write!(f, "{}", sealer.form_html())?;
         }
-        if !self.dictionary.is_empty() {
+        let mut glosses = self.glosses(opts);
+        write!(f, "{}", self.contents_html(opts))?;
+        write!(f, "{}", self.page_list_html())?;
+        writeln!(f, "<article id=\"text\">")?;
+        let outline = match self.numbered(opts) && self.is_outlined() {
+            true => self.outline_numbers().into_iter().map(Some).collect(),
+            false => vec![None; self.sentences.len()],
+        };
+        let mut sections = self.sections().into_iter().peekable();
+        for ((s, page), outline) in self.sentences.iter().zip(self.page_breaks()).zip(outline) {
+            if let Some(section) = sections.next_if(|_| s.section().is_some()) {
+                writeln!(f, "{}", self.section_html(&section, opts))?;
+            }
+            let number = format!(
+                "{}{}",
+                page_marker(page),
+                self.number_html(s, outline.as_deref(), opts)
+            );
+            let html = s.html(
+                &templates,
+                opts,
+                &mut glosses,
+                self.notes.get(&s.label),
+                self.is_bookmarked(s),
+                &number,
+            );
+            writeln!(f, "{html}")?
+        }
+        writeln!(f, "</article>")?;
+        if self.sentences.iter().any(|s| !s.alignments().is_empty()) {
+            write!(f, "{ALIGN_HOVER}")?;
+        }
+        if !self.dictionary.is_empty() || !self.patterns.is_empty() {
             write!(
                 f,
-                "<h3>Dictionary</h3><table><tr><th>Word</th> <th>Meanings</th></tr>"
+                "<section aria-labelledby=\"dictionary\"><h3 id=\"dictionary\">Dictionary</h3><table><tr><th>Word</th> <th>Meanings</th> <th>From</th></tr>"
             )?;
-            for (k, v) in &self.dictionary {
-                write!(f, "<tr><td>{k}</td><td>{}</td></tr>", v.join("; "))?;
+            let mut entries: Vec<_> = self.dictionary.iter().collect();
+            crate::collate::sort_by_word(&mut entries, &self.language, |(k, _)| k);
+            for (k, v) in entries {
+                write!(
+                    f,
+                    "<tr><td>{k}</td><td>{}</td><td>{}</td></tr>",
+                    v.join("; "),
+                    self.provenance
+                        .get(k)
+                        .map(|p| p.to_string())
+                        .unwrap_or_default()
+                )?;
             }
-            write!(f, "</table>")?;
+            for p in &self.patterns {
+                let key = p.key();
+                write!(
+                    f,
+                    "<tr><td><code>{}</code></td><td>{}</td><td>{}</td></tr>",
+                    escape_attr(&key),
+                    p.meanings.join("; "),
+                    self.provenance
+                        .get(key.as_str())
+                        .map(|p| p.to_string())
+                        .unwrap_or_default()
+                )?;
+            }
+            write!(f, "</table></section>")?;
         }
-        write!(f, "</body></html>")?;
+        write!(f, "</main></body></html>")?;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    fn meaning(frag: &OrgFragment) -> Option<String> {
+        match frag {
+            OrgFragment::Meaning(_, m) => Some(m.join("; ")),
+            _ => None,
+        }
+    }
+
+    #[rstest]
+    // glossed before the lookup
+    #[case("@ 1\n<< a = x >>\n\n@ 2\n<< a >>\n", vec![Some("x"), Some("x")])]
+    // glossed after the lookup
+    #[case("@ 1\n<< a >>\n\n@ 2\n<< a = x >>\n", vec![None, Some("x")])]
+    // the dictionary wins over later inline glosses
+    #[case("@ 1\n<< b >>\n\n@ 2\n<< b = y >>\n", vec![Some("d"), Some("y")])]
+    // punctuation is ignored for lookups
+    #[case("@ 1\n<< b, >>\n\n@ 2\n<< a = x >>\n\n@ 3\n<< a. >>\n", vec![Some("d"), Some("x"), Some("x")])]
+    #[case("@ 1\n<< a. >>\n\n@ 2\n<< a = x >>\n", vec![None, Some("x")])]
+    #[case("normalize = none\n\n@ 1\n<< b, >>\n", vec![None])]
+    #[case("normalize = punct, case\n\n@ 1\n<< B! >>\n", vec![Some("d")])]
+    fn process_test(#[case] src: &str, #[case] expected: Vec<Option<&str>>) {
+        let mut chap = Chapter::from_str(src).unwrap();
+        chap.dictionary
+            .insert("b".into(), crate::intern::meanings(&["d"]));
+        chap.process();
+        let got: Vec<Option<String>> = chap
+            .sentences
+            .iter()
+            .map(|s| meaning(&s.original[0]))
+            .collect();
+        let expected: Vec<Option<String>> =
+            expected.into_iter().map(|e| e.map(String::from)).collect();
+        assert_eq!(got, expected);
+    }
+
+    #[rstest]
+    // exact entries before patterns, the first matching pattern wins
+    #[case("@ 1\n<< agreement >> << b >> << payment >> << ments >>\n", vec![Some("noun, pay"), Some("d"), Some("noun, pay"), None])]
+    #[case("pattern_limit = 1\n\n@ 1\n<< agreement >> << payment >>\n\n@ 2\n<< payment >>\n", vec![Some("noun, pay"), None, Some("noun, pay")])]
+    // an inline gloss later on wins
+    #[case("@ 1\n<< payment >>\n\n@ 2\n<< payment = p >>\n", vec![None, Some("p")])]
+    fn pattern_test(#[case] src: &str, #[case] expected: Vec<Option<&str>>) {
+        let mut chap = Chapter::from_str(src).unwrap();
+        chap.dictionary
+            .insert("b".into(), crate::intern::meanings(&["d"]));
+        chap.patterns = vec![
+            DictPattern::new(".*ment", crate::intern::meanings(&["noun", "pay"])).unwrap(),
+            DictPattern::new("pay.*", crate::intern::meanings(&["never"])).unwrap(),
+        ];
+        chap.process();
+        let got: Vec<Option<String>> = chap
+            .sentences
+            .iter()
+            .flat_map(|s| &s.original)
+            .filter(|o| !matches!(o, OrgFragment::Simple(_)))
+            .map(|o| meaning(o).map(|m| m.replace("; ", ", ")))
+            .collect();
+        let expected: Vec<Option<String>> =
+            expected.into_iter().map(|e| e.map(String::from)).collect();
+        assert_eq!(got, expected);
+    }
+
+    #[rstest]
+    #[case("@ 1\n<< bank#2 >>\n", Some("money place (sense 2 of 3)"))]
+    #[case("@ 1\n<< bank = @3 >>\n", Some("tilt (sense 3 of 3)"))]
+    #[case("@ 1\n<< bank#4 >>\n", None)]
+    #[case("@ 1\n<< river#2 >>\n\n@ 2\n<< river = x; y >>\n", None)]
+    #[case(
+        "@ 1\n<< river = x; y >>\n\n@ 2\n<< river#2 >>\n",
+        Some("y (sense 2 of 2)")
+    )]
+    fn sense_test(#[case] src: &str, #[case] expected: Option<&str>) {
+        let mut chap = Chapter::from_str(src).unwrap();
+        let source = chap.source();
+        assert_eq!(
+            Chapter::from_str(&source).unwrap().sentences,
+            chap.sentences
+        );
+        chap.dictionary.insert(
+            "bank".into(),
+            crate::intern::meanings(&["river side", "money place", "tilt"]),
+        );
+        chap.process();
+        let s = chap
+            .sentences
+            .iter()
+            .find(|s| matches!(s.original[0], OrgFragment::Sense(..)))
+            .unwrap();
+        assert_eq!(s.original[0].gloss().as_deref(), expected);
+        #[cfg(feature = "html")]
+        {
+            let html = chap.sentence_html(s, &Default::default());
+            match expected {
+                Some(e) => assert!(html.contains(&format!("title=\"{e}\"")), "{html}"),
+                None => assert!(html.contains("class=\"unk\""), "{html}"),
+            }
+        }
+    }
+
+    #[rstest]
+    #[case(
+        "compounds = yes\n\n@ 1\n<< haustür >>\n",
+        Some("haus (house) + Tür (door)")
+    )]
+    // the longest first part that leaves a dictionary word
+    #[case(
+        "compounds = yes\n\n@ 1\n<< hausmeister. >>\n",
+        Some("hausmeis (x) + ter (y)")
+    )]
+    #[case("compounds = yes\n\n@ 1\n<< hausfoo >>\n", None)]
+    #[case("@ 1\n<< haustür >>\n", None)]
+    fn compound_test(#[case] src: &str, #[case] expected: Option<&str>) {
+        let mut chap = Chapter::from_str(src).unwrap();
+        for (w, m) in [
+            ("haus", "house"),
+            ("Tür", "door"),
+            ("hausmeis", "x"),
+            ("ter", "y"),
+            ("meister", "z"),
+        ] {
+            chap.dictionary
+                .insert(w.into(), crate::intern::meanings(&[m]));
+        }
+        chap.process();
+        let frag = &chap.sentences[0].original[0];
+        assert_eq!(frag.gloss().as_deref(), expected);
+        assert_eq!(
+            matches!(frag, OrgFragment::Compound(..)),
+            expected.is_some()
+        );
+    }
+
+    #[rstest]
+    #[case("@ 1\n\\# not a comment #3 # comment\n--- a\n\\# x\n")]
+    #[case("title = \\# x\n\n@ 1\nC# and F#\n")]
+    fn comment_escape_test(#[case] src: &str) {
+        let chap = Chapter::from_str(src).unwrap();
+        assert_eq!(Chapter::from_str(&chap.source()).unwrap(), chap);
+        assert!(chap.source().contains('#'));
+    }
+
+    #[test]
+    fn tags_test() {
+        let chap =
+            Chapter::from_str("@ 1\na\ntags = grammar, idiom, phrasal verb, \"a\"&<b>\n\n@ 2\nb\n")
+                .unwrap();
+        assert_eq!(
+            chap.sentences[0].tags(),
+            vec!["grammar", "idiom", "phrasal verb", "\"a\"&<b>"]
+        );
+        #[cfg(feature = "html")]
+        {
+            let mut html = Vec::new();
+            chap.write_html(&mut html, &HtmlOptions::default()).unwrap();
+            let html = String::from_utf8(html).unwrap();
+            assert!(html.contains(
+                r#"id="line-1" data-tags="grammar,idiom,phrasal verb,&quot;a&quot;&amp;&lt;b&gt;">"#
+            ));
+            assert!(html.contains(r#"id="line-2">"#));
+            assert!(html.contains(
+                "<option>&quot;a&quot;&amp;&lt;b&gt;</option><option>grammar</option><option>idiom</option><option>phrasal verb</option>"
+            ));
+        }
+    }
+
+    #[cfg(feature = "html")]
+    #[rstest]
+    #[case("", GlossMode::Small, "<span>a<small class=\"gloss\">x</small></span>")]
+    #[case("gloss = bracket\n", GlossMode::Small, "<span>a [x]</span>")]
+    #[case("gloss = nope\n", GlossMode::Bracket, "<span>a [x]</span>")]
+    #[case(
+        "gloss = tooltip\n",
+        GlossMode::Small,
+        r#"<span title="x">a<span class="sr-only"> (x)</span></span>"#
+    )]
+    #[case(
+        "gloss_format = {word}: {meanings}\n",
+        GlossMode::Bracket,
+        "<span>a [a: x]</span>"
+    )]
+    #[case("gloss_format = {reading}\n", GlossMode::Bracket, "<span>a [x]</span>")]
+    fn gloss_test(#[case] attrs: &str, #[case] global: GlossMode, #[case] expected: &str) {
+        let chap = Chapter::from_str(&format!("{attrs}\n@ 1\n<< a = x >>\n")).unwrap();
+        let opts = HtmlOptions {
+            gloss: global,
+            ..Default::default()
+        };
+        let mut html = Vec::new();
+        chap.write_html(&mut html, &opts).unwrap();
+        assert!(String::from_utf8(html).unwrap().contains(expected));
+    }
+
+    #[cfg(feature = "html")]
+    #[rstest]
+    #[case(
+        RepeatGloss::Full,
+        r#"<span title="x">a<span class="sr-only"> (x)</span></span> <span title="x">a<span class="sr-only"> (x)</span></span>"#
+    )]
+    #[case(
+        RepeatGloss::Marker,
+        r#"<span title="x">a<span class="sr-only"> (x)</span></span> <span class="seen">a</span>"#
+    )]
+    #[case(
+        RepeatGloss::None,
+        r#"<span title="x">a<span class="sr-only"> (x)</span></span> a"#
+    )]
+    fn repeat_gloss_test(#[case] repeat: RepeatGloss, #[case] expected: &str) {
+        let mut chap = Chapter::from_str("@ 1\n<< a = x >> << a >>\n\n@ 2\n<< b = y >>\n").unwrap();
+        chap.process();
+        let opts = HtmlOptions {
+            repeat_gloss: repeat,
+            seen: HashSet::from(["b".to_string()]),
+            ..Default::default()
+        };
+        let mut html = Vec::new();
+        chap.write_html(&mut html, &opts).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        assert!(html.contains(expected));
+        assert_eq!(
+            html.contains(r#"<span title="y">b<span class="sr-only">"#),
+            repeat == RepeatGloss::Full
+        );
+    }
+
+    #[cfg(feature = "html")]
+    #[rstest]
+    #[case(Edition::Full, true)]
+    #[case(Edition::Reader, false)]
+    fn edition_test(#[case] edition: Edition, #[case] translated: bool) {
+        let mut chap = Chapter::from_str("@ 1\n<< a = x >> b\n---\nx y\n").unwrap();
+        chap.process();
+        let opts = HtmlOptions {
+            edition,
+            ..Default::default()
+        };
+        let html = chap.sentence_html(&chap.sentences[0], &opts);
+        assert!(html.contains(r#"<span title="x">a"#), "{html}");
+        assert_eq!(html.contains("<div class=\"tl\">x y</div>"), translated);
+    }
+
+    #[test]
+    fn label_translations_test() {
+        let src = "tranlations = english, german\n\n@ 1\na\n---\nA\n---\nB\n\n@ 2\nb\n--- german\nC\n\n@ 3\nc\n---\nD\n--- french\nE\n";
+        let mut chap = Chapter::from_str(src).unwrap();
+        let before: Vec<Vec<String>> = (0..3)
+            .map(|i| {
+                let s = &chap.sentences[i];
+                s.translations
+                    .keys()
+                    .enumerate()
+                    .map(|(j, k)| chap.translation_language(j, k))
+                    .collect()
+            })
+            .collect();
+        assert_eq!(chap.label_translations(), vec![0, 2]);
+        let labelled = Chapter::from_str(&chap.splice_sentences(src, &[0, 2])).unwrap();
+        for (s, langs) in labelled.sentences.iter().zip(before) {
+            assert_eq!(s.translations.keys().cloned().collect::<Vec<_>>(), langs);
+        }
+        let mut labelled = labelled;
+        assert!(labelled.label_translations().is_empty());
+    }
+
+    #[test]
+    fn language_attrs_test() {
+        let chap = Chapter::from_str(
+            "tranlations = english\nenglish.translator = Alice\nenglish.status = draft\ngerman.translator = Bob\nfrench.translator = Eve\ntemplate.x = x.html\n\n@ 1\na\n---\nA\n--- german\nB\n",
+        )
+        .unwrap();
+        let meta = chap.language_attrs();
+        assert_eq!(meta.keys().collect::<Vec<_>>(), vec!["english", "german"]);
+        assert_eq!(meta["english"]["status"], "draft");
+        #[cfg(feature = "html")]
+        {
+            let mut html = Vec::new();
+            chap.write_html(&mut html, &HtmlOptions::default()).unwrap();
+            assert!(String::from_utf8(html).unwrap().contains(
+                r#"<ul class="byline"><li>english translation by Alice (status: draft)</li><li>german translation by Bob</li></ul>"#
+            ));
+        }
+    }
+
+    #[cfg(feature = "html")]
+    #[rstest]
+    #[case(
+        "title = A \"B\"\n\n@ 1\n<< Hello = hi >>   world\n",
+        &[
+            r#"<title> A &quot;B&quot; </title>"#,
+            r#"<meta name="description" content="Hello world">"#,
+            r#"<meta property="og:title" content="A &quot;B&quot;">"#,
+        ],
+        &["og:locale", "author"]
+    )]
+    #[case(
+        "title = T\ndescription = About T\nauthor = Me\nlang = ne\n\n@ 1\nx\n",
+        &[
+            r#"<html lang="ne">"#,
+            r#"<meta property="og:description" content="About T">"#,
+            r#"<meta name="author" content="Me">"#,
+            r#"<meta property="og:locale" content="ne">"#,
+        ],
+        &[]
+    )]
+    fn head_test(#[case] src: &str, #[case] present: &[&str], #[case] absent: &[&str]) {
+        let chap = Chapter::from_str(src).unwrap();
+        let mut html = Vec::new();
+        chap.write_html(&mut html, &HtmlOptions::default()).unwrap();
+        let html = String::from_utf8(html).unwrap();
+        for p in present {
+            assert!(html.contains(p), "{p} not in the page");
+        }
+        for a in absent {
+            assert!(!html.contains(a), "{a} in the page");
+        }
+    }
+
+    #[rstest]
+    #[case("@ 1\na\nlevel = B1\n\n@ 2\nb\nlevel = A2\n\n@ 3\nc\nlevel = B1\n", vec!["A2", "B1"])]
+    #[case("@ 1\na\nlevel = 10\n\n@ 2\nb\nlevel = 2\n\n@ 3\nc\n", vec!["2", "10"])]
+    #[case("@ 1\na\nlevel = A<1\n", vec!["A<1"])]
+    #[case("@ 1\na\n", vec![])]
+    fn levels_test(#[case] src: &str, #[case] expected: Vec<&str>) {
+        let chap = Chapter::from_str(src).unwrap();
+        assert_eq!(chap.levels(), expected);
+        #[cfg(feature = "html")]
+        {
+            let mut html = Vec::new();
+            chap.write_html(&mut html, &HtmlOptions::default()).unwrap();
+            let html = String::from_utf8(html).unwrap();
+            assert_eq!(html.contains("level-filter"), !expected.is_empty());
+            for l in expected {
+                let l = escape_attr(l);
+                assert!(html.contains(&format!(r#"data-level="{l}""#)));
+                assert!(html.contains(&format!("<option>{l}</option>")));
+            }
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use crate::parser::resolve_path;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Sentence partial used when none is chosen
+pub const DEFAULT_SENTENCE: &str = r#"<p id="line-{{label}}"{{data_src}}{{tags}}{{level}}{{bookmark}}><div class="org">{{number}}{{original}}</div>{{translations}}{{notes}}</p>"#;
+
+/// Page head used when the chapter has no `template.head` attr
+pub const DEFAULT_HEAD: &str = r#"<meta charset="utf-8">
+    <title> {{title}} </title>
+    {{meta}}"#;
+
+/// Sentence partials that come with transdoc
+const BUILTIN_SENTENCES: [(&str, &str); 3] = [
+    (
+        "quote",
+        r#"<blockquote id="line-{{label}}"{{data_src}}{{tags}}{{level}}{{bookmark}} class="quote"><div class="org">{{number}}{{original}}</div>{{translations}}{{notes}}</blockquote>"#,
+    ),
+    (
+        "letter",
+        r#"<div id="line-{{label}}"{{data_src}}{{tags}}{{level}}{{bookmark}} class="letter"><div class="org">{{number}}{{original}}</div>{{translations}}{{notes}}</div>"#,
+    ),
+    (
+        "sign",
+        r#"<div id="line-{{label}}"{{data_src}}{{tags}}{{level}}{{bookmark}} class="sign"><div class="org">{{number}}{{original}}</div>{{translations}}{{notes}}</div>"#,
+    ),
+];
+
+/// Named sentence partials a sentence can pick with its `template` attr,
+/// and the page head
+#[derive(Debug, Clone, PartialEq)]
+pub struct Templates {
+    sentences: HashMap<String, String>,
+    head: String,
+}
+
+impl Default for Templates {
+    fn default() -> Self {
+        Self {
+            sentences: BUILTIN_SENTENCES
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+            head: DEFAULT_HEAD.to_string(),
+        }
+    }
+}
+
+impl Templates {
+    /// Built in partials plus the ones from `template.<name> = file` chapter
+    /// attrs, relative to the chapter directory like dictionaries,
+    /// `template.head` replacing the page head
+    pub fn from_attrs(attrs: &IndexMap<String, String>, dir: &Path) -> Self {
+        let mut templates = Self::default();
+        for (k, v) in attrs {
+            if let Some(name) = k.strip_prefix("template.") {
+                match std::fs::read_to_string(resolve_path(dir, v)) {
+                    Ok(t) => {
+                        tracing::debug!("Template {name:?} loaded from {v}");
+                        if name == "head" {
+                            templates.head = t.trim_end().to_string();
+                        } else {
+                            templates.insert(name, t.trim_end())
+                        }
+                    }
+                    Err(e) => tracing::warn!("Template {name:?} not loaded: {v}: {e}"),
+                }
+            }
+        }
+        templates
+    }
+
+    pub fn insert(&mut self, name: &str, template: &str) {
+        self.sentences
+            .insert(name.to_string(), template.to_string());
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.sentences.contains_key(name)
+    }
+
+    /// Page head, with `{{title}}`, `{{description}}`, `{{author}}`,
+    /// `{{lang}}` and `{{meta}}` (all of them as meta tags) placeholders
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Sentence partial of the given name, the default one if there is none
+    pub fn sentence(&self, name: Option<&str>) -> &str {
+        name.and_then(|n| self.sentences.get(n))
+            .map(String::as_str)
+            .unwrap_or(DEFAULT_SENTENCE)
+    }
+}
+
+/// Replaces the `{{name}}` placeholders in the template with their values
+pub fn render(template: &str, vars: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        match after.find("}}") {
+            Some(end) => {
+                let name = after[..end].trim();
+                match vars.iter().find(|(k, _)| *k == name) {
+                    Some((_, v)) => out.push_str(v),
+                    None => out.push_str(&rest[start..start + end + 4]),
+                }
+                rest = &after[end + 2..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("<p>{{label}}</p>", "<p>l1</p>")]
+    #[case("{{ label }}:{{original}}", "l1:text")]
+    #[case("{{unknown}} {{label", "{{unknown}} {{label")]
+    #[case("no vars", "no vars")]
+    fn render_test(#[case] template: &str, #[case] expected: &str) {
+        assert_eq!(
+            render(template, &[("label", "l1"), ("original", "text")]),
+            expected
+        );
+    }
+
+    #[test]
+    fn sentence_template_test() {
+        let mut t = Templates::default();
+        t.insert("epigraph", "<em>{{original}}</em>");
+        assert_eq!(t.sentence(Some("epigraph")), "<em>{{original}}</em>");
+        assert!(t.sentence(Some("quote")).starts_with("<blockquote"));
+        assert_eq!(t.sentence(Some("missing")), DEFAULT_SENTENCE);
+        assert_eq!(t.sentence(None), DEFAULT_SENTENCE);
+    }
+
+    #[test]
+    fn from_attrs_test() {
+        let dir = std::env::temp_dir().join(format!("transdoc-template-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("epigraph.html"), "<em>{{original}}</em>\n").unwrap();
+        let attrs =
+            IndexMap::from([("template.epigraph".to_string(), "epigraph.html".to_string())]);
+        let t = Templates::from_attrs(&attrs, &dir);
+        assert_eq!(t.sentence(Some("epigraph")), "<em>{{original}}</em>");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,164 @@
+use crate::plugin::Plugins;
+use crate::syntax::{Chapter, OrgFragment};
+use std::str::FromStr;
+
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const UNDERLINE: &str = "\x1b[4m";
+const CYAN: &str = "\x1b[36m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Where the meanings of glossed words are printed
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum TermGloss {
+    /// On the lines under the original
+    #[default]
+    Below,
+    /// In brackets after the word
+    Inline,
+    /// Not at all, the words are only underlined
+    None,
+}
+
+impl FromStr for TermGloss {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "below" => Ok(Self::Below),
+            "inline" => Ok(Self::Inline),
+            "none" => Ok(Self::None),
+            _ => Err(format!(
+                "Unknown gloss display {s:?}, use below, inline or none"
+            )),
+        }
+    }
+}
+
+/// Settings of the terminal rendering
+#[derive(Debug, Default)]
+pub struct TermOptions {
+    pub plugins: Plugins,
+    pub gloss: TermGloss,
+    /// Languages of the translations to print, all of them when empty
+    pub languages: Vec<String>,
+    pub hide_translations: bool,
+    /// Whether to use ANSI escapes, plain text otherwise
+    pub color: bool,
+}
+
+impl TermOptions {
+    fn style(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{style}{text}{RESET}")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+impl Chapter {
+    /// Text of a processed chapter for reading in a terminal
+    pub fn term_text(&self, opts: &TermOptions) -> String {
+        let mut out = format!("{}\n\n", opts.style(BOLD, &self.title));
+        for s in &self.sentences {
+            let mut below = Vec::new();
+            out.push_str(&opts.style(DIM, &format!("@{} ", s.label)));
+            for o in &s.original {
+                let text = match o {
+                    OrgFragment::Sense(w, _, m) if m.is_empty() => opts.style(RED, w),
+                    OrgFragment::Meaning(w, _)
+                    | OrgFragment::Compound(w, _)
+                    | OrgFragment::Sense(w, ..) => {
+                        let meanings = o.gloss().unwrap_or_default();
+                        match opts.gloss {
+                            TermGloss::Inline => format!(
+                                "{} {}",
+                                opts.style(UNDERLINE, w),
+                                opts.style(DIM, &format!("[{meanings}]"))
+                            ),
+                            TermGloss::Below => {
+                                below.push(format!("{w}: {meanings}"));
+                                opts.style(UNDERLINE, w)
+                            }
+                            TermGloss::None => opts.style(UNDERLINE, w),
+                        }
+                    }
+                    OrgFragment::DictLookup(w) => opts.style(RED, w),
+                    OrgFragment::Plugin(n, c) => opts.plugins.text(n, c),
+                    OrgFragment::Simple(t) => t.clone(),
+                    OrgFragment::Conditional(..) => continue,
+                };
+                out.push_str(&opts.style(CYAN, &text));
+            }
+            out.push('\n');
+            for b in below {
+                out.push_str(&format!("    {}\n", opts.style(DIM, &b)));
+            }
+            if !opts.hide_translations {
+                for (i, (k, t)) in s.translations.iter().enumerate() {
+                    let lang = self.translation_language(i, k);
+                    if opts.languages.is_empty() || opts.languages.contains(&lang) {
+                        out.push_str(&opts.style(DIM, &format!("  {lang}: {}", t.content)));
+                        out.push('\n');
+                    }
+                }
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const CHAP: &str = "title = T\ntranlations = english, german\n\n@ 1\nhola << mundo = world >> << x >>\n---\nhello world\n---\nhallo Welt\n";
+
+    #[rstest]
+    #[case(
+        TermGloss::Below,
+        vec![],
+        false,
+        "T\n\n@1 hola mundo x\n    mundo: world\n  english: hello world\n  german: hallo Welt\n\n"
+    )]
+    #[case(
+        TermGloss::Inline,
+        vec!["german"],
+        false,
+        "T\n\n@1 hola mundo [world] x\n  german: hallo Welt\n\n"
+    )]
+    #[case(TermGloss::None, vec![], true, "T\n\n@1 hola mundo x\n\n")]
+    fn term_text_test(
+        #[case] gloss: TermGloss,
+        #[case] languages: Vec<&str>,
+        #[case] hide_translations: bool,
+        #[case] expected: &str,
+    ) {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.process();
+        let opts = TermOptions {
+            gloss,
+            languages: languages.into_iter().map(String::from).collect(),
+            hide_translations,
+            ..Default::default()
+        };
+        assert_eq!(chap.term_text(&opts), expected);
+    }
+
+    #[test]
+    fn term_color_test() {
+        let mut chap = Chapter::from_str(CHAP).unwrap();
+        chap.process();
+        let opts = TermOptions {
+            color: true,
+            ..Default::default()
+        };
+        let text = chap.term_text(&opts);
+        assert!(text.contains(&format!("{CYAN}{UNDERLINE}mundo{RESET}{RESET}")));
+        assert!(text.contains(&format!("{DIM}  english: hello world{RESET}")));
+    }
+}
@@ -0,0 +1,347 @@
+#[cfg(feature = "cjk")]
+use chardetng::{EncodingDetector, Iso2022JpDetection, Utf8Detection};
+use std::borrow::Cow;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+
+const BOM: char = '\u{feff}';
+
+/// Encoding the input files of the process are read in
+static ENCODING: OnceLock<Encoding> = OnceLock::new();
+
+/// Sets the encoding `read_text` and the files chapters name are read in,
+/// once at the start
+pub fn set_encoding(encoding: Encoding) {
+    ENCODING.set(encoding).ok();
+}
+
+/// Encoding input files are read in, UTF-8 unless set
+pub fn encoding() -> Encoding {
+    ENCODING.get().copied().unwrap_or_default()
+}
+
+/// Text without the byte order mark Windows editors put at the start
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix(BOM).unwrap_or(text)
+}
+
+/// Bytes as UTF-8 text, the error telling where the first invalid byte is
+pub fn decode(bytes: &[u8]) -> Result<&str, String> {
+    std::str::from_utf8(bytes).map_err(|e| {
+        let valid = &bytes[..e.valid_up_to()];
+        let line = valid.iter().filter(|b| **b == b'\n').count() + 1;
+        let col = valid.len() - valid.iter().rposition(|b| *b == b'\n').map_or(0, |i| i + 1) + 1;
+        format!(
+            "not UTF-8 text, invalid byte at line {line} column {col}; give its encoding with --encoding"
+        )
+    })
+}
+
+/// Character encoding of input files
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// Any encoding of the WHATWG encoding standard, like `shift_jis`,
+    /// `gb18030` or `latin1`
+    #[cfg(feature = "cjk")]
+    Other(&'static encoding_rs::Encoding),
+    /// Guessed from the contents
+    #[cfg(feature = "cjk")]
+    Auto,
+}
+
+impl FromStr for Encoding {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(Self::Utf8),
+            #[cfg(feature = "cjk")]
+            "auto" => Ok(Self::Auto),
+            #[cfg(feature = "cjk")]
+            label => encoding_rs::Encoding::for_label(label.as_bytes())
+                .map(Self::Other)
+                .ok_or_else(|| format!("unknown encoding {s:?}")),
+            #[cfg(not(feature = "cjk"))]
+            _ => Err(format!(
+                "encoding {s:?} needs the cjk feature, only UTF-8 is read without it"
+            )),
+        }
+    }
+}
+
+impl Encoding {
+    /// Encoding of the bytes, guessed from them for `Auto`
+    #[cfg(feature = "cjk")]
+    fn detect(&self, bytes: &[u8]) -> &'static encoding_rs::Encoding {
+        match self {
+            Self::Utf8 => encoding_rs::UTF_8,
+            Self::Other(e) => e,
+            Self::Auto => match encoding_rs::Encoding::for_bom(bytes) {
+                Some((e, _)) => e,
+                None if std::str::from_utf8(bytes).is_ok() => encoding_rs::UTF_8,
+                None => {
+                    let mut detector = EncodingDetector::new(Iso2022JpDetection::Allow);
+                    detector.feed(bytes, true);
+                    detector.guess(None, Utf8Detection::Allow)
+                }
+            },
+        }
+    }
+
+    /// Text of the bytes in this encoding, a byte order mark decides over it
+    pub fn decode<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, str>, String> {
+        #[cfg(feature = "cjk")]
+        if *self != Self::Utf8 {
+            return self.transcode(bytes);
+        }
+        decode(bytes).map(|t| Cow::Borrowed(strip_bom(t)))
+    }
+
+    #[cfg(feature = "cjk")]
+    fn transcode<'a>(&self, bytes: &'a [u8]) -> Result<Cow<'a, str>, String> {
+        let (text, used, errors) = self.detect(bytes).decode(bytes);
+        if errors {
+            return Err(format!(
+                "not valid {} text; give its encoding with --encoding",
+                used.name()
+            ));
+        }
+        tracing::debug!("Decoded as {}", used.name());
+        Ok(text)
+    }
+
+    /// Bytes of the edited text in the encoding the original bytes were
+    /// read in, an error if the text can't be written in it
+    #[cfg(feature = "cjk")]
+    pub fn encode<'a>(&self, text: &'a str, original: &[u8]) -> Result<Cow<'a, [u8]>, String> {
+        let enc = self.detect(original);
+        if enc == encoding_rs::UTF_8 {
+            return Ok(Cow::Borrowed(text.as_bytes()));
+        }
+        let (bytes, used, unmappable) = enc.encode(text);
+        if used != enc || unmappable {
+            return Err(format!("can't be written back as {}", enc.name()));
+        }
+        Ok(bytes)
+    }
+
+    /// UTF-8 being the only encoding, the text is written as it is
+    #[cfg(not(feature = "cjk"))]
+    pub fn encode<'a>(&self, text: &'a str, _original: &[u8]) -> Result<Cow<'a, [u8]>, String> {
+        Ok(Cow::Borrowed(text.as_bytes()))
+    }
+}
+
+/// Contents of a text file in the encoding set with `set_encoding`,
+/// without a byte order mark
+pub fn read_text(file: &Path) -> Result<String, String> {
+    let bytes = std::fs::read(file).map_err(|e| e.to_string())?;
+    encoding().decode(&bytes).map(Cow::into_owned)
+}
+
+/// Text in Unicode normalization form C, so the same letter typed as one
+/// character or as a base and combining mark compares the same
+pub fn nfc(text: &str) -> Cow<'_, str> {
+    match is_nfc_quick(text.chars()) {
+        IsNormalized::Yes => Cow::Borrowed(text),
+        _ => Cow::Owned(text.nfc().collect()),
+    }
+}
+
+/// Kind of a character that can't be seen but changes what a word is,
+/// so a dictionary lookup of it misses; none for other characters.
+///
+/// The zero width joiner and non-joiner aren't included as they are part
+/// of the spelling in scripts like Devanagari and Persian.
+pub fn invisible_kind(c: char) -> Option<&'static str> {
+    match c {
+        '\u{a0}' | '\u{2007}' | '\u{202f}' => Some("non-breaking space"),
+        '\u{200b}' | '\u{2060}' | '\u{feff}' | '\u{ad}' => Some("zero width character"),
+        '\u{200e}' | '\u{200f}' | '\u{61c}' | '\u{202a}'..='\u{202e}' | '\u{2066}'..='\u{2069}' => {
+            Some("bidi control character")
+        }
+        _ => None,
+    }
+}
+
+/// Text without trailing whitespace on its lines, with non-breaking spaces
+/// made plain ones and the other invisible characters removed
+pub fn clean_invisible(text: &str) -> Cow<'_, str> {
+    let mut out = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let end = &line[content.len()..];
+        for c in content.trim_end_matches([' ', '\t']).chars() {
+            match invisible_kind(c) {
+                Some("non-breaking space") => out.push(' '),
+                Some(_) => (),
+                None => out.push(c),
+            }
+        }
+        out.push_str(end);
+    }
+    if out == text {
+        Cow::Borrowed(text)
+    } else {
+        Cow::Owned(out)
+    }
+}
+
+/// Text escaped to go in a quoted attribute value of HTML or XML
+pub fn escape_attr(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Line end to write text files with
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum LineEnd {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+impl FromStr for LineEnd {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "lf" | "unix" => Ok(Self::Lf),
+            "crlf" | "windows" => Ok(Self::Crlf),
+            _ => Err(format!("unknown line end {s:?}, expected lf or crlf")),
+        }
+    }
+}
+
+impl LineEnd {
+    /// Text with all its `\r\n`, `\n` and lone `\r` line ends made this one
+    pub fn normalize<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let end = match self {
+            Self::Lf => "\n",
+            Self::Crlf => "\r\n",
+        };
+        let mut out = String::with_capacity(text.len());
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' => {
+                    chars.next_if_eq(&'\n');
+                    out.push_str(end);
+                }
+                '\n' => out.push_str(end),
+                c => out.push(c),
+            }
+        }
+        if out == text {
+            Cow::Borrowed(text)
+        } else {
+            Cow::Owned(out)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case(b"\xef\xbb\xbftitle = x", Ok("title = x"))]
+    #[case(
+        b"a\nb\xffc",
+        Err("not UTF-8 text, invalid byte at line 2 column 2; give its encoding with --encoding")
+    )]
+    fn decode_test(#[case] bytes: &[u8], #[case] expected: Result<&str, &str>) {
+        let got = decode(bytes).map(strip_bom);
+        assert_eq!(got, expected.map_err(String::from));
+    }
+
+    #[cfg(feature = "cjk")]
+    #[rstest]
+    #[case("utf-8", b"caf\xc3\xa9", Ok("café"))]
+    #[case("latin1", b"caf\xe9", Ok("café"))]
+    #[case("shift_jis", b"\x93\xfa\x96\x7b", Ok("日本"))]
+    #[case("auto", b"\xef\xbb\xbfx", Ok("x"))]
+    #[case("auto", b"\xff\xfex\x00", Ok("x"))]
+    #[case("auto", "日本語の文章です。これは日本語です。".as_bytes(), Ok("日本語の文章です。これは日本語です。"))]
+    #[case(
+        "auto",
+        b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x95\xb6\x8f\xcd\x82\xc5\x82\xb7",
+        Ok("日本語の文章です")
+    )]
+    #[case(
+        "utf-8",
+        b"caf\xe9",
+        Err("not UTF-8 text, invalid byte at line 1 column 4; give its encoding with --encoding")
+    )]
+    #[case(
+        "shift_jis",
+        b"\x93",
+        Err("not valid Shift_JIS text; give its encoding with --encoding")
+    )]
+    fn encoding_test(
+        #[case] encoding: &str,
+        #[case] bytes: &[u8],
+        #[case] expected: Result<&str, &str>,
+    ) {
+        let enc = Encoding::from_str(encoding).unwrap();
+        assert_eq!(
+            enc.decode(bytes).as_deref().map_err(String::as_str),
+            expected
+        );
+    }
+
+    #[cfg(feature = "cjk")]
+    #[rstest]
+    #[case("latin1", b"caf\xe9", "café!", Ok(&b"caf\xe9!"[..]))]
+    #[case("auto", b"caf\xc3\xa9", "café!", Ok("café!".as_bytes()))]
+    #[case(
+        "auto",
+        b"\x93\xfa\x96\x7b\x8c\xea\x82\xcc\x95\xb6\x8f\xcd\x82\xc5\x82\xb7",
+        "日本",
+        Ok(&b"\x93\xfa\x96\x7b"[..])
+    )]
+    #[case("shift_jis", b"a", "café", Err("can't be written back as Shift_JIS"))]
+    fn encode_test(
+        #[case] encoding: &str,
+        #[case] original: &[u8],
+        #[case] text: &str,
+        #[case] expected: Result<&[u8], &str>,
+    ) {
+        let enc = Encoding::from_str(encoding).unwrap();
+        assert_eq!(
+            enc.encode(text, original)
+                .as_deref()
+                .map_err(String::as_str),
+            expected
+        );
+    }
+
+    #[rstest]
+    #[case("e\u{301}", "\u{e9}")]
+    #[case("न\u{93c}", "\u{929}")]
+    #[case("plain", "plain")]
+    fn nfc_test(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(nfc(text), expected);
+    }
+
+    #[rstest]
+    #[case("a  \nb\t\r\nc", "a\nb\r\nc")]
+    #[case("a\u{a0}b\u{200b}c\u{200f}", "a bc")]
+    #[case("क्\u{200d}ष\n", "क्\u{200d}ष\n")]
+    fn clean_invisible_test(#[case] text: &str, #[case] expected: &str) {
+        assert_eq!(clean_invisible(text), expected);
+    }
+
+    #[rstest]
+    #[case(LineEnd::Lf, "a\r\nb\nc\rd\n", "a\nb\nc\nd\n")]
+    #[case(LineEnd::Crlf, "a\r\nb\nc\rd\n", "a\r\nb\r\nc\r\nd\r\n")]
+    #[case(LineEnd::Lf, "a\n\r\n", "a\n\n")]
+    fn line_end_test(#[case] end: LineEnd, #[case] text: &str, #[case] expected: &str) {
+        assert_eq!(end.normalize(text), expected);
+    }
+}
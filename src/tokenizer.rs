@@ -1,9 +1,9 @@
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag, take},
+    bytes::complete::{is_not, tag, take, take_till1},
     combinator::{map, recognize},
     multi::{many0, many1},
-    sequence::pair,
+    sequence::{pair, preceded},
     IResult, Needed, Parser,
 };
 
@@ -11,11 +11,22 @@ use nom::{
 pub struct Token<'a> {
     pub ty: TokenType,
     pub content: &'a str,
+    /// Byte offset of the token in the tokenized text
+    pub offset: usize,
 }
 
 impl<'a> Token<'a> {
     fn new(ty: TokenType, content: &'a str) -> Self {
-        Self { ty, content }
+        Self {
+            ty,
+            content,
+            offset: 0,
+        }
+    }
+
+    /// Byte offset just after the token
+    pub fn end(&self) -> usize {
+        self.offset + self.content.len()
     }
 }
 
@@ -113,6 +124,8 @@ pub enum TokenType {
     Comment,
     AngleStart,
     AngleEnd,
+    BraceStart,
+    BraceEnd,
     At,
     Equal,
     Semicolon,
@@ -131,18 +144,31 @@ fn whitespace(i: &str) -> TokenRes<'_> {
 }
 
 fn newline(i: &str) -> TokenRes<'_> {
-    // only unix, mac and windows line end supported for now
-    map(alt((tag("\n\r"), tag("\r\n"), tag("\n"))), |s| {
+    // windows, unix and old mac line ends
+    map(alt((tag("\r\n"), tag("\n"), tag("\r"))), |s| {
         Token::new(TokenType::NewLine, s)
     })
     .parse(i)
 }
 
-fn comment(i: &str) -> TokenRes<'_> {
-    map(recognize(pair(tag("#"), many0(is_not("\n\r")))), |s| {
-        Token::new(TokenType::Comment, s)
-    })
-    .parse(i)
+/// Comment from the token to the end of the line
+fn comment<'a>(token: &str) -> impl Fn(&'a str) -> TokenRes<'a> + '_ {
+    move |i: &'a str| {
+        map(recognize(pair(tag(token), many0(is_not("\n\r")))), |s| {
+            Token::new(TokenType::Comment, s)
+        })
+        .parse(i)
+    }
+}
+
+/// Escaped comment token, kept as text without the backslash
+fn escaped<'a>(token: &str) -> impl Fn(&'a str) -> TokenRes<'a> + '_ {
+    move |i: &'a str| {
+        map(preceded(tag("\\"), tag(token)), |s| {
+            Token::new(TokenType::Char, s)
+        })
+        .parse(i)
+    }
 }
 
 fn symbols(i: &str) -> TokenRes<'_> {
@@ -158,22 +184,109 @@ fn symbols(i: &str) -> TokenRes<'_> {
 }
 
 fn known_token(i: &str) -> TokenRes<'_> {
-    alt((whitespace, newline, comment, symbols)).parse(i)
+    alt((whitespace, newline, symbols)).parse(i)
+}
+
+/// Characters some other token can start with, besides the comment token
+const TOKEN_STARTS: &[char] = &[
+    ' ', '\t', '\n', '\r', '\\', '<', '>', '{', '}', '@', '=', ';', '-',
+];
+
+/// Run of characters that can't start any other token, kept as one token
+/// so plain text doesn't turn into a token per character
+fn char_run<'a>(comment: &str) -> impl Fn(&'a str) -> TokenRes<'a> + '_ {
+    let first = comment.chars().next();
+    move |i: &'a str| {
+        map(
+            take_till1(|c| TOKEN_STARTS.contains(&c) || Some(c) == first),
+            |s| Token::new(TokenType::Char, s),
+        )
+        .parse(i)
+    }
 }
 
 fn character(i: &str) -> TokenRes<'_> {
     map(take(1usize), |s| Token::new(TokenType::Char, s)).parse(i)
 }
 
-fn all_tokens(i: &str) -> VecTokenRes<'_> {
-    many0(alt((known_token, character))).parse(i)
+/// Comment token used unless configured otherwise
+pub const DEFAULT_COMMENT: &str = "#";
+
+/// Whether the `{{` the text starts with opens a `{{name:content}}`
+/// fragment closed on the same line, otherwise the braces are text
+fn opens_fragment(rest: &str) -> bool {
+    let inner = &rest[2..];
+    inner.find("}}").is_some_and(|end| {
+        let inner = &inner[..end];
+        inner.contains(':') && !inner.contains(['\n', '\r'])
+    })
+}
+
+/// Whether a comment can start after the tokens: at the start of a line,
+/// or after whitespace if the comment token is followed by whitespace too,
+/// so `#3` or `C#` in the middle of a line stay text
+fn comment_allowed(tokens: &[Token], rest: &str, comment: &str) -> bool {
+    let mut before = tokens
+        .iter()
+        .rev()
+        .skip_while(|t| t.ty == TokenType::WhiteSpace);
+    match before.next() {
+        None => true,
+        Some(t) if t.ty == TokenType::NewLine => true,
+        _ => {
+            tokens.last().is_some_and(|t| t.ty == TokenType::WhiteSpace)
+                && rest[comment.len()..]
+                    .chars()
+                    .next()
+                    .is_none_or(char::is_whitespace)
+        }
+    }
 }
 
-pub fn get_tokens(txt: &str) -> Vec<Token> {
-    let (res, tokens) = all_tokens(txt).expect("Parser shouldn't error out");
+fn all_tokens<'a>(i: &'a str, comment_token: &str) -> VecTokenRes<'a> {
+    let mut tokens = Vec::new();
+    let mut rest = i;
+    let mut in_fragment = false;
+    while !rest.is_empty() {
+        let res =
+            if rest.starts_with(comment_token) && comment_allowed(&tokens, rest, comment_token) {
+                comment(comment_token)(rest)
+            } else if !in_fragment && rest.starts_with("{{") && opens_fragment(rest) {
+                in_fragment = true;
+                map(tag("{{"), |s| Token::new(TokenType::BraceStart, s)).parse(rest)
+            } else if in_fragment && rest.starts_with("}}") {
+                in_fragment = false;
+                map(tag("}}"), |s| Token::new(TokenType::BraceEnd, s)).parse(rest)
+            } else {
+                alt((
+                    escaped(comment_token),
+                    char_run(comment_token),
+                    known_token,
+                    character,
+                ))
+                .parse(rest)
+            };
+        let (r, t) = res?;
+        tokens.push(t);
+        rest = r;
+    }
+    Ok((rest, tokens))
+}
+
+pub fn get_tokens(txt: &str) -> Vec<Token<'_>> {
+    get_tokens_with(txt, DEFAULT_COMMENT)
+}
+
+/// Tokens of the text with comments started by the given token
+pub fn get_tokens_with<'a>(txt: &'a str, comment: &str) -> Vec<Token<'a>> {
+    let (res, mut tokens) = all_tokens(txt, comment).expect("Parser shouldn't error out");
     if !res.is_empty() {
-        println!("{res:?}");
-        panic!("Logic Error on Parser, there shouldn't be anything left")
+        panic!("Logic Error on Parser, there shouldn't be anything left: {res:?}")
+    }
+    // tokens are slices of txt, so their position is the pointer difference
+    let start = txt.as_ptr() as usize;
+    for t in &mut tokens {
+        t.offset = t.content.as_ptr() as usize - start;
     }
     tokens
 }
@@ -198,7 +311,7 @@ mod tests {
     #[case("# comment\n", TokenType::Comment, "\n")]
     #[case("# comment\n123", TokenType::Comment, "\n123")]
     fn comment_test(#[case] txt: &str, #[case] value: TokenType, #[case] reminder: &str) {
-        let (rest, n) = comment(txt).unwrap();
+        let (rest, n) = comment("#")(txt).unwrap();
         assert_eq!(rest, reminder);
         assert_eq!(n.ty, value);
     }
@@ -208,6 +321,9 @@ mod tests {
     #[should_panic]
     #[case("\\\n", TokenType::NewLine, "")] // escaped newline should be escaped
     #[case("\n   ", TokenType::NewLine, "   ")]
+    #[case("\r\nx", TokenType::NewLine, "x")]
+    #[case("\rx", TokenType::NewLine, "x")]
+    #[case("\n\rx", TokenType::NewLine, "\rx")]
     fn newline_test(#[case] txt: &str, #[case] value: TokenType, #[case] reminder: &str) {
         let (rest, n) = newline(txt).unwrap();
         assert_eq!(rest, reminder);
@@ -219,9 +335,59 @@ mod tests {
     #[case("@what the hell", TokenType::At, "")]
     #[case("何か?", TokenType::Char, "")]
     #[case("यो काम गर्छ र", TokenType::Char, "")]
+    #[case("{{ruby:x}}", TokenType::BraceStart, "")]
+    #[case("{{x}}", TokenType::Char, "")]
+    #[case("}} {{a:b\n}}", TokenType::Char, "")]
     fn maybe_string_test(#[case] txt: &str, #[case] value: TokenType, #[case] reminder: &str) {
-        let (rest, n) = all_tokens(txt).unwrap();
+        let (rest, n) = all_tokens(txt, DEFAULT_COMMENT).unwrap();
         assert_eq!(rest, reminder);
         assert_eq!(n[0].ty, value);
     }
+
+    #[rstest]
+    #[case("ab", vec![0])]
+    #[case("a-b", vec![0, 1, 2])]
+    #[case("@ x\n", vec![0, 1, 2, 3])]
+    #[case("यो <<", vec![0, 6, 7])]
+    fn token_offset_test(#[case] txt: &str, #[case] offsets: Vec<usize>) {
+        let tk = get_tokens(txt);
+        assert_eq!(tk.iter().map(|t| t.offset).collect::<Vec<_>>(), offsets);
+        assert_eq!(tk.last().unwrap().end(), txt.len());
+    }
+
+    #[rstest]
+    #[case("my name is", vec!["my", " ", "name", " ", "is"])]
+    #[case("a<b> c", vec!["a", "<", "b", ">", " ", "c"])]
+    #[case("x--- y;", vec!["x", "---", " ", "y", ";"])]
+    #[case("a {{b}} }}", vec!["a", " ", "{", "{", "b", "}", "}", " ", "}", "}"])]
+    #[case("{{a:b}}}}", vec!["{{", "a:b", "}}", "}", "}"])]
+    fn char_run_test(#[case] txt: &str, #[case] contents: Vec<&str>) {
+        let tk = get_tokens(txt);
+        assert_eq!(tk.iter().map(|t| t.content).collect::<Vec<_>>(), contents);
+    }
+
+    #[rstest]
+    #[case("# note\nx", "#", vec![TokenType::Comment, TokenType::NewLine, TokenType::Char])]
+    #[case("  # note", "#", vec![TokenType::WhiteSpace, TokenType::Comment])]
+    #[case("x # note", "#", vec![TokenType::Char, TokenType::WhiteSpace, TokenType::Comment])]
+    #[case("item #3", "#", vec![TokenType::Char, TokenType::WhiteSpace, TokenType::Char, TokenType::Char])]
+    #[case("C#", "#", vec![TokenType::Char, TokenType::Char])]
+    #[case("\\# x", "#", vec![TokenType::Char, TokenType::WhiteSpace, TokenType::Char])]
+    #[case("#x // note", "//", vec![TokenType::Char, TokenType::WhiteSpace, TokenType::Comment])]
+    #[case("// note", "//", vec![TokenType::Comment])]
+    fn comment_position_test(
+        #[case] txt: &str,
+        #[case] comment: &str,
+        #[case] types: Vec<TokenType>,
+    ) {
+        let tk = get_tokens_with(txt, comment);
+        assert_eq!(tk.iter().map(|t| t.ty).collect::<Vec<_>>(), types);
+    }
+
+    #[test]
+    fn escaped_comment_test() {
+        let tk = get_tokens("\\#3");
+        assert_eq!(tk[0].content, "#");
+        assert_eq!(tk[0].offset, 1);
+    }
 }
@@ -0,0 +1,262 @@
+use crate::syntax::{Chapter, Sentence, TRANSLATIONS_ALIAS, TRANSLATIONS_ATTR};
+use indexmap::IndexMap;
+use std::time::Duration;
+
+/// Type of the values of an attr transdoc reads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttrType {
+    /// `yes`, `true` or `on`, and `no`, `false` or `off`
+    Bool,
+    /// Whole number like `3`
+    Integer,
+    /// Comma separated values like `english, german`
+    List,
+    /// Time like `90s`, `1h30m` or `1:30`
+    Duration,
+}
+
+/// Chapter attrs transdoc reads and the type of their values
+pub const CHAPTER_ATTRS: &[(&str, AttrType)] = &[
+    ("compounds", AttrType::Bool),
+    ("known_levels", AttrType::List),
+    ("numbered", AttrType::Bool),
+    ("pattern_limit", AttrType::Integer),
+    (TRANSLATIONS_ALIAS, AttrType::List),
+    (TRANSLATIONS_ATTR, AttrType::List),
+];
+
+/// Sentence attrs transdoc reads and the type of their values
+pub const SENTENCE_ATTRS: &[(&str, AttrType)] = &[
+    ("tags", AttrType::List),
+    ("bookmark", AttrType::Bool),
+    ("skip", AttrType::Bool),
+    ("audio", AttrType::List),
+    ("time", AttrType::Duration),
+];
+
+impl AttrType {
+    /// Type of the recognized attr
+    pub fn of(key: &str, recognized: &[(&str, AttrType)]) -> Option<Self> {
+        recognized.iter().find(|(k, _)| *k == key).map(|(_, t)| *t)
+    }
+
+    /// Problem with the value of the attr if it isn't of the type
+    pub fn check(self, key: &str, value: &str) -> Result<(), String> {
+        match self {
+            Self::Bool => parse_bool(value).map(|_| ()),
+            Self::Integer => parse_integer(value).map(|_| ()),
+            Self::List => Ok(()),
+            Self::Duration => parse_duration(value).map(|_| ()),
+        }
+        .map_err(|e| format!("Attr {key} = {}: {e}", value.trim()))
+    }
+}
+
+/// Boolean attr value
+pub fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.trim().to_lowercase().as_str() {
+        "yes" | "true" | "on" => Ok(true),
+        "no" | "false" | "off" => Ok(false),
+        _ => Err("not a boolean, use yes or no".into()),
+    }
+}
+
+/// Whole number attr value
+pub fn parse_integer(value: &str) -> Result<u64, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| "not a whole number".into())
+}
+
+/// Values of a comma separated list, without the empty ones
+pub fn parse_list(value: &str) -> Vec<&str> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// Duration in seconds (`90`, `1.5s`), with units (`2m`, `1h30m`, `250ms`)
+/// or as `m:ss` and `h:mm:ss`
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let err = || "not a duration, like 90s, 1h30m or 1:30".to_string();
+    if value.is_empty() {
+        return Err(err());
+    }
+    let seconds = |n: &str| {
+        n.parse::<f64>()
+            .ok()
+            .filter(|n| n.is_finite() && *n >= 0.0)
+            .ok_or_else(err)
+    };
+    if value.contains(':') {
+        let parts: Vec<&str> = value.split(':').collect();
+        if parts.len() > 3 {
+            return Err(err());
+        }
+        let mut total = 0.0;
+        for p in parts {
+            total = total * 60.0 + seconds(p)?;
+        }
+        return Ok(Duration::from_secs_f64(total));
+    }
+    if let Ok(s) = seconds(value) {
+        return Ok(Duration::from_secs_f64(s));
+    }
+    let mut total = 0.0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .ok_or_else(err)?;
+        let unit_end = rest[digits..]
+            .find(|c: char| c.is_ascii_digit())
+            .map_or(rest.len(), |i| digits + i);
+        let scale = match rest[digits..unit_end].trim() {
+            "h" => 3600.0,
+            "m" | "min" => 60.0,
+            "s" => 1.0,
+            "ms" => 0.001,
+            _ => return Err(err()),
+        };
+        total += seconds(&rest[..digits])? * scale;
+        rest = &rest[unit_end..];
+    }
+    Ok(Duration::from_secs_f64(total))
+}
+
+/// Problems with the values of the recognized attrs
+pub fn check_attrs(
+    attrs: &IndexMap<String, String>,
+    recognized: &[(&str, AttrType)],
+) -> Vec<(String, String)> {
+    attrs
+        .iter()
+        .filter_map(|(k, v)| {
+            let err = AttrType::of(k, recognized)?.check(k, v).err()?;
+            Some((k.clone(), err))
+        })
+        .collect()
+}
+
+impl Chapter {
+    /// Chapter attr as a boolean, `None` when missing or not one
+    pub fn attr_bool(&self, key: &str) -> Option<bool> {
+        self.attrs.get(key).and_then(|v| parse_bool(v).ok())
+    }
+
+    /// Chapter attr as a whole number, `None` when missing or not one
+    pub fn attr_integer(&self, key: &str) -> Option<u64> {
+        self.attrs.get(key).and_then(|v| parse_integer(v).ok())
+    }
+
+    /// Chapter attr as a comma separated list, empty when missing
+    pub fn attr_list(&self, key: &str) -> Vec<&str> {
+        self.attrs
+            .get(key)
+            .map(|v| parse_list(v))
+            .unwrap_or_default()
+    }
+
+    /// Chapter attr as a duration, `None` when missing or not one
+    pub fn attr_duration(&self, key: &str) -> Option<Duration> {
+        self.attrs.get(key).and_then(|v| parse_duration(v).ok())
+    }
+}
+
+impl Sentence {
+    /// Sentence attr as a boolean, `None` when missing or not one
+    pub fn attr_bool(&self, key: &str) -> Option<bool> {
+        self.orgattrs.get(key).and_then(|v| parse_bool(v).ok())
+    }
+
+    /// Sentence attr as a comma separated list, empty when missing
+    pub fn attr_list(&self, key: &str) -> Vec<&str> {
+        self.orgattrs
+            .get(key)
+            .map(|v| parse_list(v))
+            .unwrap_or_default()
+    }
+
+    /// Sentence attr as a duration, `None` when missing or not one
+    pub fn attr_duration(&self, key: &str) -> Option<Duration> {
+        self.orgattrs.get(key).and_then(|v| parse_duration(v).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("yes", Some(true))]
+    #[case(" On ", Some(true))]
+    #[case("false", Some(false))]
+    #[case("maybe", None)]
+    fn bool_test(#[case] value: &str, #[case] expected: Option<bool>) {
+        assert_eq!(parse_bool(value).ok(), expected);
+    }
+
+    #[rstest]
+    #[case("90", Some(90.0))]
+    #[case("1.5s", Some(1.5))]
+    #[case("1h30m", Some(5400.0))]
+    #[case("2m 5s", Some(125.0))]
+    #[case("250ms", Some(0.25))]
+    #[case("1:30", Some(90.0))]
+    #[case("1:02:03", Some(3723.0))]
+    #[case("5 days", None)]
+    #[case("m", None)]
+    #[case("1:2:3:4", None)]
+    #[case("", None)]
+    fn duration_test(#[case] value: &str, #[case] expected: Option<f64>) {
+        assert_eq!(
+            parse_duration(value).ok().map(|d| d.as_secs_f64()),
+            expected
+        );
+    }
+
+    #[test]
+    fn accessors_test() {
+        let chap = Chapter::from_str(
+            "compounds = on\npattern_limit = 3\ntranlations = english, , german\nbreak = 1m\n\n@ 1\na\ntags = x,y\nbookmark = no\n",
+        )
+        .unwrap();
+        assert_eq!(chap.attr_bool("compounds"), Some(true));
+        assert_eq!(chap.attr_integer("pattern_limit"), Some(3));
+        assert_eq!(chap.attr_list("tranlations"), vec!["english", "german"]);
+        assert_eq!(chap.attr_duration("break"), Some(Duration::from_secs(60)));
+        assert_eq!(chap.attr_bool("missing"), None);
+        assert_eq!(chap.sentences[0].attr_list("tags"), vec!["x", "y"]);
+        assert_eq!(chap.sentences[0].attr_bool("bookmark"), Some(false));
+    }
+
+    #[test]
+    fn check_test() {
+        let chap = Chapter::from_str("title = 1\ncompounds = sure\npattern_limit = -1\n\n@ 1\na\n")
+            .unwrap();
+        let lines: Vec<(usize, &str)> = chap
+            .diagnostics
+            .iter()
+            .map(|d| (d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                (
+                    2,
+                    "Attr compounds = sure: not a boolean, use yes or no, using the default"
+                ),
+                (
+                    3,
+                    "Attr pattern_limit = -1: not a whole number, using the default"
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+use crate::output::write_atomic;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory the undo journal is kept in, relative to the working directory
+pub const UNDO_DIR: &str = ".transdoc/undo";
+
+/// Rewrites kept in the journal, the older ones are dropped
+pub const UNDO_RUNS: usize = 20;
+
+/// Name of the file listing the rewritten files of a run
+const JOURNAL: &str = "journal";
+
+/// Files rewritten by one transdoc run, kept as they were before and after
+/// in a directory of the journal named by the time the run started
+#[derive(Debug, Clone)]
+pub struct Run {
+    pub dir: PathBuf,
+}
+
+impl Run {
+    /// Run starting now, its directory created with the first backup
+    pub fn start(journal: &Path) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        // sorting the names sorts the runs by time
+        let name = format!("{:020}-{}", now.as_millis(), std::process::id());
+        Self {
+            dir: journal.join(name),
+        }
+    }
+
+    /// Most recent run of the journal
+    pub fn last(journal: &Path) -> Option<Self> {
+        runs(journal).pop().map(|dir| Self { dir })
+    }
+
+    /// Files of the run in the order they were rewritten
+    pub fn files(&self) -> std::io::Result<Vec<PathBuf>> {
+        let list = std::fs::read_to_string(self.dir.join(JOURNAL))?;
+        Ok(list.lines().map(PathBuf::from).collect())
+    }
+
+    /// Keeps the file as it is and as it will be written, before it is
+    /// rewritten with the contents
+    pub fn backup(&self, file: &Path, contents: &[u8]) -> std::io::Result<()> {
+        let before = std::fs::read(file)?;
+        let file = std::path::absolute(file)?;
+        std::fs::create_dir_all(&self.dir)?;
+        let mut list = match std::fs::read_to_string(self.dir.join(JOURNAL)) {
+            Ok(l) => l,
+            Err(e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e),
+        };
+        let n = list.lines().count();
+        std::fs::write(self.dir.join(format!("{n}.before")), before)?;
+        std::fs::write(self.dir.join(format!("{n}.after")), contents)?;
+        list.push_str(&format!("{}\n", file.to_string_lossy()));
+        write_atomic(&self.dir.join(JOURNAL), list.as_bytes())
+    }
+
+    /// Files of the run changed since it rewrote them, which restoring
+    /// would lose the changes of
+    pub fn changed(&self) -> std::io::Result<Vec<PathBuf>> {
+        let files = self.files()?;
+        let mut changed = Vec::new();
+        for (n, file) in files.iter().enumerate() {
+            if files[n + 1..].contains(file) {
+                // rewritten again later in the run
+                continue;
+            }
+            let after = std::fs::read(self.dir.join(format!("{n}.after")))?;
+            if std::fs::read(file).ok().as_ref() != Some(&after) {
+                changed.push(file.clone());
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Puts the files back as they were before the run, last rewrite first
+    /// so a file rewritten twice ends up as it was at the start, and
+    /// removes the run from the journal
+    pub fn restore(self) -> std::io::Result<Vec<PathBuf>> {
+        let files = self.files()?;
+        for (n, file) in files.iter().enumerate().rev() {
+            let before = std::fs::read(self.dir.join(format!("{n}.before")))?;
+            write_atomic(file, &before)?;
+        }
+        std::fs::remove_dir_all(&self.dir)?;
+        let mut restored: Vec<PathBuf> = Vec::new();
+        for file in files {
+            if !restored.contains(&file) {
+                restored.push(file);
+            }
+        }
+        Ok(restored)
+    }
+}
+
+/// Run directories of the journal, oldest first
+fn runs(journal: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(journal) else {
+        return Vec::new();
+    };
+    let mut runs: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.join(JOURNAL).is_file())
+        .collect();
+    runs.sort();
+    runs
+}
+
+/// Drops the oldest runs of the journal beyond the last `keep`
+pub fn prune(journal: &Path, keep: usize) -> std::io::Result<()> {
+    let runs = runs(journal);
+    for dir in &runs[..runs.len().saturating_sub(keep)] {
+        std::fs::remove_dir_all(dir)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn undo_test() {
+        let dir = std::env::temp_dir().join(format!("transdoc-undo-{}", std::process::id()));
+        let journal = dir.join("undo");
+        std::fs::create_dir_all(&dir).unwrap();
+        let (a, b) = (dir.join("a.chapter"), dir.join("b.chapter"));
+        std::fs::write(&a, "a1").unwrap();
+        std::fs::write(&b, "b1").unwrap();
+
+        let run = Run::start(&journal);
+        for (file, text) in [(&a, "a2"), (&b, "b2"), (&a, "a3")] {
+            run.backup(file, text.as_bytes()).unwrap();
+            std::fs::write(file, text).unwrap();
+        }
+        assert_eq!(run.changed().unwrap(), Vec::<PathBuf>::new());
+        std::fs::write(&a, "edited").unwrap();
+        assert_eq!(run.changed().unwrap().len(), 1);
+        std::fs::write(&a, "a3").unwrap();
+
+        let last = Run::last(&journal).unwrap();
+        assert_eq!(last.dir, run.dir);
+        assert_eq!(last.restore().unwrap().len(), 2);
+        assert_eq!(std::fs::read_to_string(&a).unwrap(), "a1");
+        assert_eq!(std::fs::read_to_string(&b).unwrap(), "b1");
+        assert!(Run::last(&journal).is_none());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_test() {
+        let journal = std::env::temp_dir().join(format!("transdoc-prune-{}", std::process::id()));
+        for name in ["1", "2", "3"] {
+            std::fs::create_dir_all(journal.join(name)).unwrap();
+            std::fs::write(journal.join(name).join(JOURNAL), "").unwrap();
+        }
+        prune(&journal, 2).unwrap();
+        assert_eq!(runs(&journal), vec![journal.join("2"), journal.join("3")]);
+        std::fs::remove_dir_all(&journal).unwrap();
+    }
+}
@@ -0,0 +1,519 @@
+use crate::errors::{Diagnostic, Severity};
+use crate::skip::TranslationState;
+use crate::syntax::{Chapter, OrgFragment, Sentence};
+use crate::template::Templates;
+use crate::text;
+use crate::typed;
+use crate::vars;
+use std::collections::HashMap;
+
+/// Checks `Chapter::validate` does besides the ones always done
+#[derive(Debug, Default, Clone)]
+pub struct ValidateOptions {
+    /// Warn about sentences without any translation
+    pub untranslated: bool,
+    /// Warn about pairs an aligner likely got wrong, see `Chapter::alignment`
+    pub alignment: bool,
+}
+
+/// How many times longer or shorter than usual in the chapter a
+/// translation can be before its alignment is suspicious
+const LENGTH_RATIO_LIMIT: f64 = 3.0;
+
+fn at(sentence: &Sentence, rule: &'static str, severity: Severity, message: String) -> Diagnostic {
+    Diagnostic {
+        severity,
+        file: None,
+        line: sentence.span.line,
+        col: sentence.span.col,
+        message,
+        linestr: None,
+        rule: Some(rule),
+    }
+}
+
+impl Chapter {
+    /// Problems with a parsed chapter that the syntax allows: duplicate
+    /// labels, unknown templates, stale translations and the ones found
+    /// loading its dictionary.
+    ///
+    /// Positions are those of the sentences, and the diagnostics have no
+    /// file unless they come from another file than the chapter.
+    pub fn validate(&self, opts: &ValidateOptions) -> Vec<Diagnostic> {
+        let mut diags = self.diagnostics.clone();
+        let builtin = Templates::default();
+        let mut labels: HashMap<&str, &Sentence> = HashMap::new();
+        let variables = self.variables();
+        for s in &self.sentences {
+            if let Some(first) = labels.get(s.label.as_str()) {
+                diags.push(at(
+                    s,
+                    "duplicate-label",
+                    Severity::Error,
+                    format!(
+                        "Duplicate label {:?}, first used at line {}",
+                        s.label, first.span.line
+                    ),
+                ));
+            } else {
+                labels.insert(&s.label, s);
+            }
+            let texts = s
+                .original
+                .iter()
+                .filter_map(|o| match o {
+                    OrgFragment::Simple(t) => Some(t.as_str()),
+                    _ => None,
+                })
+                .chain(s.translations.values().map(|t| t.content.as_str()));
+            for name in texts.flat_map(|t| vars::undefined(t, &variables)) {
+                diags.push(at(
+                    s,
+                    "undefined-variable",
+                    Severity::Warning,
+                    format!(
+                        "Undefined variable {{{name}}} in sentence {:?}, define it with a `def {name} = …` chapter attr",
+                        s.label
+                    ),
+                ));
+            }
+            if let Some(t) = s.orgattrs.get("template")
+                && !builtin.contains(t)
+                && !self.attrs.contains_key(&format!("template.{t}"))
+            {
+                diags.push(at(
+                    s,
+                    "unknown-template",
+                    Severity::Warning,
+                    format!("Unknown template {t:?}, the default one is used"),
+                ));
+            }
+            if s.is_changed() {
+                diags.push(at(
+                    s,
+                    "changed-original",
+                    Severity::Warning,
+                    format!(
+                        "Original of sentence {:?} changed since it was translated, mark the translations stale with --mark-stale",
+                        s.label
+                    ),
+                ));
+            }
+            for lang in s.stale_translations() {
+                diags.push(at(
+                    s,
+                    "stale-translation",
+                    Severity::Warning,
+                    format!("Translation {lang} of sentence {:?} is stale", s.label),
+                ));
+            }
+            if self.tl_languages.len() > 1
+                && s.translations.keys().any(|k| k.parse::<usize>().is_ok())
+            {
+                diags.push(at(
+                    s,
+                    "unlabeled-translation",
+                    Severity::Warning,
+                    format!(
+                        "Sentence {:?} has translations without a language label, label them with fmt --label-translations",
+                        s.label
+                    ),
+                ));
+            }
+            if !self.tl_languages.is_empty() {
+                for (i, k) in s.translations.keys().enumerate() {
+                    let lang = self.translation_language(i, k);
+                    if k.parse::<usize>().is_err()
+                        && !self.tl_languages.iter().any(|l| l.trim() == lang)
+                    {
+                        diags.push(at(
+                            s,
+                            "unknown-translation-label",
+                            Severity::Warning,
+                            format!(
+                                "Translation label {k:?} of sentence {:?} is not in the translations attr, add it or map it with a `language.aliases = {k}` attr",
+                                s.label
+                            ),
+                        ));
+                    }
+                }
+            }
+            for o in &s.original {
+                if let OrgFragment::Sense(w, n, _) = o
+                    && let Some(m) = self.dictionary.get(w.as_str())
+                    && *n > m.len()
+                {
+                    diags.push(at(
+                        s,
+                        "sense-out-of-range",
+                        Severity::Warning,
+                        format!(
+                            "Sense {n} of {w:?} in sentence {:?}, but its dictionary entry has {} meanings",
+                            s.label,
+                            m.len()
+                        ),
+                    ));
+                }
+            }
+            for (t, alignment) in s.alignments() {
+                let words = s.original_words();
+                let tl_words = s.translations[t].content.split_whitespace().count();
+                let problem = match alignment {
+                    Err(e) => Some(e),
+                    Ok(a) => a
+                        .0
+                        .iter()
+                        .find(|(o, tl)| o.iter().any(|w| *w > words) || tl.iter().any(|w| *w > tl_words))
+                        .map(|_| format!("word number past the {words} words of the original or the {tl_words} of the translation")),
+                };
+                if let Some(p) = problem {
+                    diags.push(at(
+                        s,
+                        "invalid-alignment",
+                        Severity::Warning,
+                        format!(
+                            "Alignment of translation {} of sentence {:?}: {p}",
+                            t + 1,
+                            s.label
+                        ),
+                    ));
+                }
+            }
+            for (_, e) in typed::check_attrs(&s.orgattrs, typed::SENTENCE_ATTRS) {
+                diags.push(at(
+                    s,
+                    "attr-type",
+                    Severity::Warning,
+                    format!("{e} in sentence {:?}", s.label),
+                ));
+            }
+            if opts.untranslated && s.translations.is_empty() && !s.is_skipped() {
+                diags.push(at(
+                    s,
+                    "untranslated",
+                    Severity::Warning,
+                    format!("Sentence {:?} has no translation", s.label),
+                ));
+            }
+        }
+        if opts.alignment {
+            diags.extend(self.alignment());
+        }
+        diags
+    }
+
+    /// Suspicious sentence pairs, as made by importers and aligners: empty
+    /// sides, translations much longer or shorter than the others in their
+    /// language compared to their originals, and the same translation given
+    /// to different originals
+    pub fn alignment(&self) -> Vec<Diagnostic> {
+        let mut diags = Vec::new();
+        // length ratios by language, for the usual one
+        let mut ratios: HashMap<String, Vec<f64>> = HashMap::new();
+        let mut seen: HashMap<(String, &str), &Sentence> = HashMap::new();
+        let pairs: Vec<(&Sentence, String, &str)> =
+            self.sentences
+                .iter()
+                .flat_map(|s| {
+                    s.translations.iter().enumerate().map(move |(i, (k, t))| {
+                        (s, self.translation_language(i, k), t.content.trim())
+                    })
+                })
+                .collect();
+        for (s, lang, tl) in &pairs {
+            let org = s.text();
+            let org = org.trim();
+            if org.is_empty() || tl.is_empty() {
+                let side = if org.is_empty() {
+                    "original"
+                } else {
+                    "translation"
+                };
+                diags.push(at(
+                    s,
+                    "empty-pair",
+                    Severity::Warning,
+                    format!("Empty {side} in the {lang} pair of sentence {:?}", s.label),
+                ));
+                continue;
+            }
+            ratios
+                .entry(lang.clone())
+                .or_default()
+                .push(tl.chars().count() as f64 / org.chars().count() as f64);
+            match seen.get(&(lang.clone(), *tl)) {
+                Some(first) if first.text().trim() != org => diags.push(at(
+                    s,
+                    "repeated-translation",
+                    Severity::Warning,
+                    format!(
+                        "Translation {lang} of sentence {:?} is the same as that of sentence {:?}",
+                        s.label, first.label
+                    ),
+                )),
+                Some(_) => (),
+                None => {
+                    seen.insert((lang.clone(), tl), s);
+                }
+            }
+        }
+        let usual: HashMap<String, f64> = ratios
+            .into_iter()
+            .filter(|(_, r)| r.len() >= 3)
+            .map(|(l, mut r)| {
+                r.sort_by(f64::total_cmp);
+                (l, r[r.len() / 2])
+            })
+            .collect();
+        for (s, lang, tl) in &pairs {
+            let org = s.text();
+            let org = org.trim();
+            let Some(median) = usual.get(lang) else {
+                continue;
+            };
+            if org.is_empty() || tl.is_empty() {
+                continue;
+            }
+            let ratio = tl.chars().count() as f64 / org.chars().count() as f64;
+            if ratio > median * LENGTH_RATIO_LIMIT || ratio * LENGTH_RATIO_LIMIT < *median {
+                diags.push(at(
+                    s,
+                    "length-ratio",
+                    Severity::Warning,
+                    format!(
+                        "Translation {lang} of sentence {:?} is {ratio:.1} times as long as the original, {median:.1} is usual",
+                        s.label
+                    ),
+                ));
+            }
+        }
+        diags.sort_by_key(|d| d.line);
+        diags
+    }
+
+    /// Labels of the sentences without a translation to the language, or
+    /// with words left to look up in the dictionary after processing,
+    /// leaving out the ones skipped on purpose.
+    ///
+    /// Translations count as in the language when their separator names it,
+    /// or when they are at its position in the `translations` attr.
+    pub fn incomplete(&self, lang: &str) -> Vec<&str> {
+        self.sentences
+            .iter()
+            .filter(|s| {
+                let translated = match self.translation_state(s, lang) {
+                    TranslationState::Skipped => return false,
+                    state => state == TranslationState::Translated,
+                };
+                let unresolved = s.original.iter().any(|o| match o {
+                    OrgFragment::DictLookup(_) => true,
+                    OrgFragment::Sense(_, _, m) => m.is_empty(),
+                    _ => false,
+                });
+                !translated || unresolved
+            })
+            .map(|s| s.label.as_str())
+            .collect()
+    }
+}
+
+/// Trailing whitespace and invisible characters in the chapter source,
+/// which make words differ from the dictionary's without showing why
+pub fn lint(src: &str) -> Vec<Diagnostic> {
+    let mut diags = Vec::new();
+    let warn = |rule: &'static str, line: usize, col: usize, message: String| Diagnostic {
+        severity: Severity::Warning,
+        file: None,
+        line,
+        col,
+        message,
+        linestr: None,
+        rule: Some(rule),
+    };
+    let src = text::LineEnd::Lf.normalize(src);
+    for (i, line) in src.split_inclusive('\n').enumerate() {
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_end_matches([' ', '\t']);
+        for (col, c) in trimmed.char_indices() {
+            if let Some(kind) = text::invisible_kind(c) {
+                diags.push(warn(
+                    "invisible-character",
+                    i + 1,
+                    col + 1,
+                    format!("Invisible {kind} U+{:04X}", c as u32),
+                ));
+            }
+        }
+        if trimmed.len() < content.len() {
+            diags.push(warn(
+                "trailing-whitespace",
+                i + 1,
+                trimmed.len() + 1,
+                "Trailing whitespace".into(),
+            ));
+        }
+    }
+    diags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("@ a\nx\n---\ny\n", false, vec![])]
+    #[case(
+        "@ a\nx\n---\ny\n\n@ a\nz\n---\nw\n",
+        false,
+        vec![(Severity::Error, 6, "Duplicate label \"a\", first used at line 1")]
+    )]
+    #[case(
+        "@ a\nx\ntemplate = nope\n---\ny\n",
+        false,
+        vec![(Severity::Warning, 1, "Unknown template \"nope\", the default one is used")]
+    )]
+    #[case("template.nope = n.html\n\n@ a\nx\ntemplate = nope\n", false, vec![])]
+    #[case("@ a\nx\ntemplate = quote\n", false, vec![])]
+    #[case(
+        "def hero = Genji\n\n@ a\n{hero} and {villain}\n---\n{hero}\n",
+        false,
+        vec![(Severity::Warning, 3, "Undefined variable {villain} in sentence \"a\", define it with a `def villain = …` chapter attr")]
+    )]
+    #[case("tranlations = german\ngerman.aliases = de\n\n@ a\nx\n---\ny\n--- de\nz\n", false, vec![])]
+    #[case(
+        "tranlations = german, english\n\n@ a\nx\n---\ny\n--- english\nz\n\n@ b\nx\n--- german\ny\n",
+        false,
+        vec![(Severity::Warning, 3, "Sentence \"a\" has translations without a language label, label them with fmt --label-translations")]
+    )]
+    #[case(
+        "tranlations = german\n\n@ a\nx\n--- de\nz\n",
+        false,
+        vec![(Severity::Warning, 3, "Translation label \"de\" of sentence \"a\" is not in the translations attr, add it or map it with a `language.aliases = de` attr")]
+    )]
+    #[case(
+        "@ a\nx\nhash = 0\n---\ny\nstale = yes\n",
+        false,
+        vec![
+            (Severity::Warning, 1, "Original of sentence \"a\" changed since it was translated, mark the translations stale with --mark-stale"),
+            (Severity::Warning, 1, "Translation 0 of sentence \"a\" is stale"),
+        ]
+    )]
+    #[case(
+        "@ a\nx\n",
+        true,
+        vec![(Severity::Warning, 1, "Sentence \"a\" has no translation")]
+    )]
+    #[case(
+        "@ a\nx << y z = w >>\n---\nv w\nalign = 1-1, 3-2\n---\nv\nalign = 1-2\n---\nu\nalign = 1+2\n",
+        false,
+        vec![
+            (Severity::Warning, 1, "Alignment of translation 2 of sentence \"a\": word number past the 3 words of the original or the 1 of the translation"),
+            (Severity::Warning, 1, "Alignment of translation 3 of sentence \"a\": \"1+2\" isn't a pair like 2-3"),
+        ]
+    )]
+    #[case(
+        "@ a\nx\nbookmark = later\n",
+        false,
+        vec![(Severity::Warning, 1, "Attr bookmark = later: not a boolean, use yes or no in sentence \"a\"")]
+    )]
+    #[case("@ a\nx\nskip = yes\n", true, vec![])]
+    fn validate_test(
+        #[case] src: &str,
+        #[case] untranslated: bool,
+        #[case] expected: Vec<(Severity, usize, &str)>,
+    ) {
+        let chap = Chapter::from_str(src).unwrap();
+        let diags = chap.validate(&ValidateOptions {
+            untranslated,
+            ..Default::default()
+        });
+        let got: Vec<(Severity, usize, &str)> = diags
+            .iter()
+            .map(|d| (d.severity, d.line, d.message.as_str()))
+            .collect();
+        assert_eq!(got, expected);
+    }
+
+    #[test]
+    fn sense_test() {
+        let mut chap = Chapter::from_str("@ a\n<< b#3 >> << b#2 >> << c#9 >>\n").unwrap();
+        chap.dictionary
+            .insert("b".into(), crate::intern::meanings(&["x", "y"]));
+        let got: Vec<String> = chap
+            .validate(&Default::default())
+            .into_iter()
+            .map(|d| d.message)
+            .collect();
+        assert_eq!(
+            got,
+            vec!["Sense 3 of \"b\" in sentence \"a\", but its dictionary entry has 2 meanings"]
+        );
+    }
+
+    #[rstest]
+    #[case("@ a\nabc\n---\nxyz\n\n@ b\nabcd\n---\nwxyz\n", vec![])]
+    #[case(
+        "tranlations = en\n\n@ a\nabc\n---\nxyz\n\n@ b\nabc\n---\nxyz\n\n@ c\ndef\n---\nxyz\n",
+        vec![(13, "Translation en of sentence \"c\" is the same as that of sentence \"a\"")]
+    )]
+    #[case(
+        "@ a\nabcd\n---\nwxyz\n\n@ b\nabcd\n---\nwx\n\n@ c\nabcd\n---\nwxyz wxyz wxyz wxyz\n",
+        vec![(11, "Translation 0 of sentence \"c\" is 4.8 times as long as the original, 1.0 is usual")]
+    )]
+    #[case(
+        "@ a\n{{ruby:}}\n--- de\nx\n",
+        vec![(1, "Empty original in the de pair of sentence \"a\"")]
+    )]
+    fn alignment_test(#[case] src: &str, #[case] expected: Vec<(usize, &str)>) {
+        let chap = Chapter::from_str(src).unwrap();
+        let got: Vec<(usize, String)> = chap
+            .alignment()
+            .into_iter()
+            .map(|d| (d.line, d.message))
+            .collect();
+        let expected: Vec<(usize, String)> = expected
+            .into_iter()
+            .map(|(l, m)| (l, m.to_string()))
+            .collect();
+        assert_eq!(got, expected);
+    }
+
+    #[rstest]
+    #[case("@ a\nx\n", vec![])]
+    #[case("@ a \nx\t\r\n", vec![(1, 4, "Trailing whitespace"), (2, 2, "Trailing whitespace")])]
+    #[case(
+        "@ a\nx\u{a0}y\u{200b}\n",
+        vec![
+            (2, 2, "Invisible non-breaking space U+00A0"),
+            (2, 5, "Invisible zero width character U+200B"),
+        ]
+    )]
+    #[case("@ a\rb \r", vec![(2, 2, "Trailing whitespace")])]
+    #[case("@ a\n\u{202e}x\n", vec![(2, 1, "Invisible bidi control character U+202E")])]
+    fn lint_test(#[case] src: &str, #[case] expected: Vec<(usize, usize, &str)>) {
+        let got: Vec<(usize, usize, String)> = lint(src)
+            .into_iter()
+            .map(|d| (d.line, d.col, d.message))
+            .collect();
+        let expected: Vec<(usize, usize, String)> = expected
+            .into_iter()
+            .map(|(l, c, m)| (l, c, m.to_string()))
+            .collect();
+        assert_eq!(got, expected);
+    }
+
+    #[rstest]
+    #[case("@ a\nx\n--- german\ny\n\n@ b\nx\n--- french\ny\n", vec!["b"])]
+    #[case("tranlations = french, german\n\n@ a\nx\n---\ny\n---\nz\n\n@ b\nx\n---\ny\n", vec!["b"])]
+    #[case("@ a\n<< x >>\n--- german\ny\n", vec!["a"])]
+    #[case("tranlations = german\ngerman.aliases = de, deu\n\n@ a\nx\n--- DE\ny\n\n@ b\nx\n--- German\ny\n\n@ c\nx\n--- en\ny\n", vec!["c"])]
+    #[case("@ a\n<< x = y >>\n--- german\ny\n", vec![])]
+    #[case("@ a\n<< x >>\nskip = yes\n\n@ b\nx\n--- german\nstatus = skipped\n\n@ c\nx\n--- german\nstatus = draft\n", vec!["c"])]
+    fn incomplete_test(#[case] src: &str, #[case] expected: Vec<&str>) {
+        let mut chap = Chapter::from_str(src).unwrap();
+        chap.process();
+        assert_eq!(chap.incomplete("german"), expected);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::syntax::{Chapter, OrgFragment};
+use indexmap::IndexMap;
+use regex::{Captures, Regex};
+use std::borrow::Cow;
+use std::sync::LazyLock;
+
+/// Start of the chapter attrs defining variables, `def name = Lady Murasaki`
+pub const DEF_PREFIX: &str = "def ";
+
+/// `{name}` in the text
+static VARIABLE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{([\p{L}_][\p{L}\p{N}_-]*)\}").expect("valid regex"));
+
+/// Text with the `{name}` of the variables replaced by their values, the
+/// undefined ones are left as they are
+pub fn expand<'a>(text: &'a str, vars: &IndexMap<String, String>) -> Cow<'a, str> {
+    VARIABLE.replace_all(text, |c: &Captures| match vars.get(&c[1]) {
+        Some(v) => v.clone(),
+        None => c[0].to_string(),
+    })
+}
+
+/// Names of the `{name}` in the text that aren't variables
+pub fn undefined<'a>(text: &'a str, vars: &IndexMap<String, String>) -> Vec<&'a str> {
+    VARIABLE
+        .captures_iter(text)
+        .filter_map(|c| c.get(1))
+        .map(|m| m.as_str())
+        .filter(|n| !vars.contains_key(*n))
+        .collect()
+}
+
+impl Chapter {
+    /// Variables from the `def name = value` chapter attrs
+    pub fn variables(&self) -> IndexMap<String, String> {
+        self.attrs
+            .iter()
+            .filter_map(|(k, v)| {
+                let name = k.strip_prefix(DEF_PREFIX)?.trim();
+                Some((name.to_string(), v.trim().to_string()))
+            })
+            .collect()
+    }
+
+    /// Replaces the variables in the title, the text of the originals and
+    /// the translations with their values
+    pub fn expand_variables(&mut self) {
+        let vars = self.variables();
+        if vars.is_empty() {
+            return;
+        }
+        if let Cow::Owned(t) = expand(&self.title, &vars) {
+            self.title = t;
+        }
+        for s in &mut self.sentences {
+            for o in &mut s.original {
+                if let OrgFragment::Simple(t) = o
+                    && let Cow::Owned(e) = expand(t, &vars)
+                {
+                    *t = e;
+                }
+            }
+            for t in s.translations.values_mut() {
+                if let Cow::Owned(e) = expand(&t.content, &vars) {
+                    t.content = e;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+    use std::str::FromStr;
+
+    #[rstest]
+    #[case("by {name}.", "by Lady Murasaki.")]
+    #[case("{name}{name}", "Lady MurasakiLady Murasaki")]
+    #[case("{other} and { name } stay", "{other} and { name } stay")]
+    fn expand_test(#[case] text: &str, #[case] expected: &str) {
+        let vars: IndexMap<String, String> =
+            [("name".to_string(), "Lady Murasaki".to_string())].into();
+        assert_eq!(expand(text, &vars), expected);
+    }
+
+    #[test]
+    fn expand_variables_test() {
+        let mut chap = Chapter::from_str(
+            "title = The tale of {hero}\ndef hero = Genji\ndef author = Lady Murasaki\n\n@ 1\n{hero} << a = x >> {author}\n---\n{author} wrote of {hero} and {nobody}\n",
+        )
+        .unwrap();
+        chap.expand_variables();
+        assert_eq!(chap.title, "The tale of Genji");
+        assert_eq!(chap.sentences[0].text(), "Genji a Lady Murasaki");
+        assert_eq!(
+            chap.sentences[0].translations["0"].content,
+            "Lady Murasaki wrote of Genji and {nobody}"
+        );
+    }
+}
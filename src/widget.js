@@ -0,0 +1,122 @@
+// <!-- Generated by transdoc -->
+// Renders transdoc chapter bundles into <div data-transdoc="chapter.json">
+// elements, with gloss popups and a toggle for each translation language.
+(function () {
+  "use strict";
+
+  var STYLE =
+    ".transdoc .td-word { color: blue; cursor: pointer; }" +
+    ".transdoc .td-unk { color: #c00; }" +
+    ".transdoc .td-compound { border-bottom: 1px dotted; }" +
+    ".transdoc .td-gloss { display: none; font-size: smaller; color: #595959; }" +
+    ".transdoc .td-word[aria-expanded=true] .td-gloss { display: inline; }" +
+    ".transdoc .td-tl { color: #4d6b4d; }" +
+    ".transdoc .td-hidden { display: none; }" +
+    ".transdoc .td-byline { list-style: none; padding: 0; font-style: italic; }";
+
+  function el(tag, cls, text) {
+    var e = document.createElement(tag);
+    if (cls) e.className = cls;
+    if (text !== undefined) e.textContent = text;
+    return e;
+  }
+
+  function word(piece) {
+    var w = el("span", piece.compound ? "td-word td-compound" : "td-word", piece.word);
+    w.title = piece.gloss;
+    w.setAttribute("role", "button");
+    w.setAttribute("tabindex", "0");
+    w.setAttribute("aria-expanded", "false");
+    w.appendChild(el("small", "td-gloss", " (" + piece.gloss + ")"));
+    function toggle() {
+      var shown = w.getAttribute("aria-expanded") === "true";
+      w.setAttribute("aria-expanded", String(!shown));
+    }
+    w.addEventListener("click", toggle);
+    w.addEventListener("keydown", function (ev) {
+      if (ev.key === "Enter" || ev.key === " ") {
+        ev.preventDefault();
+        toggle();
+      }
+    });
+    return w;
+  }
+
+  function render(root, bundle) {
+    root.classList.add("transdoc");
+    if (bundle.lang) root.lang = bundle.lang;
+    root.appendChild(el("h2", null, bundle.title));
+    var meta = bundle.language_attrs || {};
+    if (Object.keys(meta).length) {
+      var byline = el("ul", "td-byline");
+      Object.keys(meta).forEach(function (lang) {
+        var by = meta[lang].translator ? " by " + meta[lang].translator : "";
+        byline.appendChild(el("li", null, lang + " translation" + by));
+      });
+      root.appendChild(byline);
+    }
+
+    var toggles = el("div", "td-langs");
+    bundle.languages.forEach(function (lang) {
+      var label = el("label", null, " " + lang);
+      var box = el("input");
+      box.type = "checkbox";
+      box.checked = true;
+      box.addEventListener("change", function () {
+        root.querySelectorAll(".td-tl").forEach(function (t) {
+          if (t.dataset.lang === lang) t.classList.toggle("td-hidden", !box.checked);
+        });
+      });
+      label.insertBefore(box, label.firstChild);
+      toggles.appendChild(label);
+    });
+    if (bundle.languages.length) root.appendChild(toggles);
+
+    bundle.sentences.forEach(function (s) {
+      var p = el("p");
+      p.id = "td-line-" + s.label;
+      var org = el("div", "td-org");
+      s.original.forEach(function (piece) {
+        if (piece.word !== undefined) {
+          org.appendChild(word(piece));
+        } else if (piece.unknown !== undefined) {
+          org.appendChild(el("span", "td-unk", piece.unknown));
+        } else {
+          org.insertAdjacentHTML("beforeend", piece.html);
+        }
+      });
+      p.appendChild(org);
+      Object.keys(s.translations).forEach(function (lang) {
+        var tl = el("div", "td-tl");
+        tl.dataset.lang = lang;
+        tl.innerHTML = s.translations[lang];
+        p.appendChild(tl);
+      });
+      root.appendChild(p);
+    });
+  }
+
+  function load() {
+    var style = el("style", null, STYLE);
+    document.head.appendChild(style);
+    document.querySelectorAll("[data-transdoc]").forEach(function (root) {
+      fetch(root.dataset.transdoc)
+        .then(function (r) {
+          if (!r.ok) throw new Error(r.status + " " + r.statusText);
+          return r.json();
+        })
+        .then(function (bundle) {
+          render(root, bundle);
+        })
+        .catch(function (e) {
+          root.textContent = "Could not load " + root.dataset.transdoc + ": " + e.message;
+        });
+    });
+  }
+
+  if (document.readyState === "loading") {
+    document.addEventListener("DOMContentLoaded", load);
+  } else {
+    load();
+  }
+})();
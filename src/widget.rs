@@ -0,0 +1,157 @@
+use crate::plugin::Plugins;
+use crate::syntax::{Chapter, OrgFragment, Sentence};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// Script rendering bundles into `<div data-transdoc="chapter.json">`
+/// elements of the page it is included in
+pub const WIDGET_JS: &str = include_str!("widget.js");
+
+/// Name the script is written with next to the bundles
+pub const WIDGET_JS_FILE: &str = "transdoc-widget.js";
+
+/// Chapter ready to be rendered by the widget, with the dictionary
+/// applied and the plugins rendered
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bundle {
+    pub title: String,
+    /// Language code of the original from the `lang` attr
+    #[serde(default)]
+    pub lang: Option<String>,
+    /// Languages of the translations in the order the toggles are shown
+    pub languages: Vec<String>,
+    /// Metadata of the languages, like their translator
+    #[serde(default)]
+    pub language_attrs: IndexMap<String, IndexMap<String, String>>,
+    pub sentences: Vec<BundleSentence>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleSentence {
+    pub label: String,
+    pub original: Vec<BundlePiece>,
+    /// Translation HTML by language
+    pub translations: IndexMap<String, String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BundlePiece {
+    Word {
+        word: String,
+        gloss: String,
+        /// Glossed by its parts
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        compound: bool,
+    },
+    /// Word not found in the dictionary
+    Unknown {
+        unknown: String,
+    },
+    Html {
+        html: String,
+    },
+}
+
+impl Bundle {
+    /// Bundle of a processed chapter
+    pub fn new(chap: &Chapter, plugins: &Plugins) -> Self {
+        let mut languages: Vec<String> = Vec::new();
+        let sentences: Vec<BundleSentence> = chap
+            .sentences
+            .iter()
+            .map(|s| BundleSentence::new(chap, s, plugins))
+            .collect();
+        for l in sentences.iter().flat_map(|s| s.translations.keys()) {
+            if !languages.contains(l) {
+                languages.push(l.clone());
+            }
+        }
+        Self {
+            title: chap.title.clone(),
+            lang: chap.attrs.get("lang").map(|l| l.trim().to_string()),
+            languages,
+            language_attrs: chap.language_attrs(),
+            sentences,
+        }
+    }
+}
+
+impl BundleSentence {
+    /// Sentence of a processed chapter
+    pub fn new(chap: &Chapter, s: &Sentence, plugins: &Plugins) -> Self {
+        let original = s
+            .original
+            .iter()
+            .filter(|o| !matches!(o, OrgFragment::Conditional(..)))
+            .map(|o| match o {
+                OrgFragment::Simple(t) => BundlePiece::Html { html: t.clone() },
+                OrgFragment::Sense(w, _, m) if m.is_empty() => {
+                    BundlePiece::Unknown { unknown: w.clone() }
+                }
+                OrgFragment::Meaning(w, _)
+                | OrgFragment::Compound(w, _)
+                | OrgFragment::Sense(w, ..) => BundlePiece::Word {
+                    word: w.clone(),
+                    gloss: o.gloss().unwrap_or_default(),
+                    compound: matches!(o, OrgFragment::Compound(..)),
+                },
+                OrgFragment::DictLookup(w) => BundlePiece::Unknown { unknown: w.clone() },
+                OrgFragment::Plugin(n, c) | OrgFragment::Conditional(n, c) => BundlePiece::Html {
+                    html: plugins.html(n, c),
+                },
+            })
+            .collect();
+        let translations = s
+            .translations
+            .iter()
+            .enumerate()
+            .map(|(i, (k, t))| (chap.translation_language(i, k), t.content.clone()))
+            .collect();
+        Self {
+            label: s.label.clone(),
+            original,
+            translations,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[test]
+    fn bundle_test() {
+        let mut chap = Chapter::from_str(
+            "title = T\ntranlations = english, german\nlang = ne\n\n@ 1\n<< a = x >> {{ruby:漢|かん}} << b >>\n---\nA\n---\nB\n\n@ 2\nc\n--- french\nC\n",
+        )
+        .unwrap();
+        chap.process();
+        let bundle = Bundle::new(&chap, &Plugins::default());
+        assert_eq!(bundle.lang.as_deref(), Some("ne"));
+        assert_eq!(bundle.languages, vec!["english", "german", "french"]);
+        assert_eq!(
+            bundle.sentences[0].original,
+            vec![
+                BundlePiece::Word {
+                    word: "a".into(),
+                    gloss: "x".into(),
+                    compound: false,
+                },
+                BundlePiece::Html { html: " ".into() },
+                BundlePiece::Html {
+                    html: "<ruby>漢<rt>かん</rt></ruby>".into()
+                },
+                BundlePiece::Html { html: " ".into() },
+                BundlePiece::Unknown {
+                    unknown: "b".into()
+                },
+            ]
+        );
+        assert_eq!(bundle.sentences[1].translations["french"], "C");
+        let json = serde_json::to_string(&bundle).unwrap();
+        assert!(json.contains(r#"{"word":"a","gloss":"x"}"#));
+        assert_eq!(serde_json::from_str::<Bundle>(&json).unwrap(), bundle);
+    }
+}
@@ -0,0 +1,190 @@
+use crate::intern::Meanings;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::str::FromStr;
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Words in the text, split at unicode word boundaries
+pub fn words(text: &str) -> impl Iterator<Item = &str> {
+    text.unicode_words()
+}
+
+/// Words of a word list file, one per line, `#` starts a comment. They
+/// are lowercased for [`is_known`] to match them in any case.
+pub fn parse_word_list(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(|l| l.split('#').next().unwrap_or_default().trim())
+        .filter(|l| !l.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Writes a word list file, one word per line
+pub fn word_list_source<'a>(words: impl IntoIterator<Item = &'a String>) -> String {
+    words.into_iter().map(|w| format!("{w}\n")).collect()
+}
+
+/// Whether the word is in the lowercased list, ignoring case
+pub fn is_known(known: &HashSet<String>, word: &str) -> bool {
+    known.contains(word) || known.contains(&word.to_lowercase())
+}
+
+/// Step of the normalization words go through to be looked up
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NormalizeStep {
+    /// Strip punctuation and whitespace at the start and end
+    Punct,
+    /// Fold to lowercase
+    Case,
+    /// Collapse runs of whitespace into one space and trim it
+    Space,
+    /// Unicode compatibility composition, so `ﬁ` matches `fi`
+    Nfkc,
+}
+
+/// Punctuation besides the ASCII one that sticks to words
+const PUNCTUATION: &[char] = &[
+    '。', '、', '，', '．', '！', '？', '：', '；', '（', '）', '「', '」', '『', '』', '“', '”',
+    '‘', '’', '«', '»', '‹', '›', '…', '—', '–', '¡', '¿', '।', '॥', '・', '؟', '،',
+];
+
+fn is_punct(c: char) -> bool {
+    c.is_ascii_punctuation() || PUNCTUATION.contains(&c)
+}
+
+impl NormalizeStep {
+    fn apply(&self, word: &str) -> String {
+        match self {
+            Self::Punct => word
+                .trim_matches(|c: char| is_punct(c) || c.is_whitespace())
+                .to_string(),
+            Self::Case => word.to_lowercase(),
+            Self::Space => word.split_whitespace().collect::<Vec<_>>().join(" "),
+            Self::Nfkc => word.nfkc().collect(),
+        }
+    }
+}
+
+/// Prefix of dictionary keys that are regexes instead of words
+pub const PATTERN_PREFIX: &str = "re:";
+
+/// Times patterns may gloss words of one sentence by default
+pub const DEFAULT_PATTERN_LIMIT: usize = 3;
+
+/// Dictionary entry glossing the words its regex matches in full, like
+/// `re:.*ment = suffix forming nouns`, for productive affixes
+#[derive(Debug, Clone)]
+pub struct DictPattern {
+    pub regex: Regex,
+    pub meanings: Meanings,
+}
+
+impl PartialEq for DictPattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.regex.as_str() == other.regex.as_str() && self.meanings == other.meanings
+    }
+}
+
+impl DictPattern {
+    /// Pattern from the regex of a `re:` key
+    pub fn new(pattern: &str, meanings: Meanings) -> Result<Self, String> {
+        Regex::new(&format!("^(?:{pattern})$"))
+            .map(|regex| Self { regex, meanings })
+            .map_err(|e| format!("Invalid dictionary pattern {pattern:?}: {e}"))
+    }
+
+    /// Key of the entry in the dictionary file
+    pub fn key(&self) -> String {
+        let re = self.regex.as_str();
+        format!("{PATTERN_PREFIX}{}", &re[4..re.len() - 2])
+    }
+}
+
+/// Normalization applied to words and dictionary keys when looking words
+/// up, the text shown is left as it is
+#[derive(Debug, Clone, PartialEq)]
+pub struct Normalizer(pub Vec<NormalizeStep>);
+
+impl Default for Normalizer {
+    fn default() -> Self {
+        Self(vec![NormalizeStep::Punct, NormalizeStep::Space])
+    }
+}
+
+impl FromStr for Normalizer {
+    type Err = String;
+    /// Comma separated steps: `punct`, `case`, `space` and `nfkc`, or `none`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut steps = Vec::new();
+        for step in s.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+            steps.push(match step {
+                "none" => continue,
+                "punct" => NormalizeStep::Punct,
+                "case" => NormalizeStep::Case,
+                "space" => NormalizeStep::Space,
+                "nfkc" => NormalizeStep::Nfkc,
+                _ => {
+                    return Err(format!(
+                        "unknown normalization {step:?}, expected punct, case, space, nfkc or none"
+                    ))
+                }
+            });
+        }
+        Ok(Self(steps))
+    }
+}
+
+impl Normalizer {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn apply<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        let mut word = Cow::Borrowed(word);
+        for step in &self.0 {
+            let next = step.apply(&word);
+            if next != *word {
+                word = Cow::Owned(next);
+            }
+        }
+        word
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[rstest]
+    #[case("my name is", vec!["my", "name", "is"])]
+    #[case("Hello, world!", vec!["Hello", "world"])]
+    #[case("यो काम गर्छ र।", vec!["यो", "काम", "गर्छ", "र"])]
+    fn words_test(#[case] txt: &str, #[case] expected: Vec<&str>) {
+        assert_eq!(words(txt).collect::<Vec<_>>(), expected);
+    }
+
+    #[rstest]
+    #[case("punct, space", "  word, ", "word")]
+    #[case("punct", "«don't»", "don't")]
+    #[case("punct", "काम।", "काम")]
+    #[case("case,space", "New\t York", "new york")]
+    #[case("nfkc", "ﬁne", "fine")]
+    #[case("none", "Word,", "Word,")]
+    fn normalizer_test(#[case] steps: &str, #[case] word: &str, #[case] expected: &str) {
+        let norm = Normalizer::from_str(steps).unwrap();
+        assert_eq!(norm.apply(word), expected);
+    }
+
+    #[test]
+    fn word_list_test() {
+        let known = parse_word_list("# known words\nhund\n  katze  # cat\n\nTokyo\n");
+        assert_eq!(known.len(), 3);
+        assert!(is_known(&known, "Hund"));
+        assert!(is_known(&known, "Tokyo"));
+        assert!(is_known(&known, "tokyo"));
+        assert!(!is_known(&known, "maus"));
+    }
+}
@@ -0,0 +1,109 @@
+#![cfg(feature = "cli")]
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn transdoc(args: &[&str]) -> Output {
+    transdoc_stdin(args, "")
+}
+
+fn transdoc_stdin(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_transdoc"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn empty_comment_token() {
+    for token in ["", "  "] {
+        let out = transdoc(&["--comment", token, "check", "sample.chapter"]);
+        assert_eq!(out.status.code(), Some(2));
+        let err = String::from_utf8_lossy(&out.stderr);
+        assert!(err.contains("can't be empty or whitespace"), "{err}");
+    }
+    let out = transdoc(&["--comment", "//", "check", "sample.chapter"]);
+    assert!(out.status.success());
+}
+
+#[test]
+fn embed_stdin() {
+    let dir = std::env::temp_dir().join(format!("transdoc-embed-{}", std::process::id()));
+    let out = dir.to_str().unwrap();
+    let chapter = "@ 1\na\n---\nb\n";
+    let res = transdoc_stdin(&["embed", "-", "-o", out], chapter);
+    assert!(!res.status.success());
+    assert!(String::from_utf8_lossy(&res.stderr).contains("--name"));
+    assert!(!dir.join("-.json").exists());
+
+    let res = transdoc_stdin(&["embed", "-", "-o", out, "--name", "ch1"], chapter);
+    assert!(res.status.success());
+    assert!(dir.join("ch1.json").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn check_stdin() {
+    let dir = std::env::temp_dir().join(format!("transdoc-check-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let bad = dir.join("bad.chapter");
+    let good = dir.join("good.chapter");
+    std::fs::write(&bad, "@ 1\na\n---\nb\n\n@ 1\nc\n---\nd\n").unwrap();
+    std::fs::write(&good, "@ 1\na\n---\nb\n").unwrap();
+    let names = format!("{}\n\n{}\n", bad.display(), good.display());
+    let res = transdoc_stdin(&["check", "--stdin"], &names);
+    assert_eq!(res.status.code(), Some(1));
+    let out = String::from_utf8_lossy(&res.stderr);
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(
+        lines,
+        [format!(
+            "{}:6:1: error: Duplicate label \"1\", first used at line 1 [duplicate-label]",
+            bad.display()
+        )]
+    );
+
+    let res = transdoc_stdin(&["check", "--stdin"], &format!("{}\n", good.display()));
+    assert_eq!(res.status.code(), Some(0));
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    // the chapter itself on stdin
+    let res = transdoc_stdin(&["check", "-"], "@ 1\na\n\n@ 1\nb\n");
+    assert_eq!(res.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&res.stderr).starts_with("<stdin>:4:1: error: "));
+}
+
+#[test]
+fn fmt_stdin() {
+    for chapter in ["@ 1\na\n---\nb\n", "@ 1\r\na\r\n---\r\nb\r\n"] {
+        let res = transdoc_stdin(&["fmt", "-"], chapter);
+        assert!(res.status.success());
+        assert_eq!(String::from_utf8_lossy(&res.stdout), "@ 1\na\n---\nb\n");
+    }
+}
+
+#[test]
+fn strict_build() {
+    let dir = std::env::temp_dir().join(format!("transdoc-strict-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let chapter = dir.join("c.chapter");
+    let page = dir.join("c.html");
+    std::fs::write(&chapter, "translations = german\n\n@ a\nx\n--- de\nz\n").unwrap();
+    let (chapter, page) = (chapter.to_str().unwrap(), page.to_str().unwrap());
+    assert!(transdoc(&["build", chapter, "-o", page]).status.success());
+    for flag in ["--strict", "--strict-dict"] {
+        let out = transdoc(&["build", flag, chapter, "-o", page]);
+        assert!(!out.status.success());
+        assert!(String::from_utf8_lossy(&out.stderr).contains("unknown-translation-label"));
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,136 @@
+//! Every file of `tests/corpus/` and the samples at the top of the repo
+//! parse back to the same chapter from the source they are written to
+
+use proptest::collection::vec;
+use proptest::option;
+use proptest::prelude::*;
+use proptest::sample::select;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use transdoc::syntax::Chapter;
+
+fn corpus() -> Vec<PathBuf> {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut files: Vec<PathBuf> = std::fs::read_dir(root.join("tests/corpus"))
+        .unwrap()
+        .chain(std::fs::read_dir(root).unwrap())
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.extension().is_some_and(|e| e == "chapter"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn corpus_round_trip() {
+    let files = corpus();
+    assert!(files.len() >= 3, "no corpus files found");
+    for file in files {
+        let src = std::fs::read_to_string(&file).unwrap();
+        let name = file.display();
+        let chap = Chapter::from_str(&src).unwrap_or_else(|e| panic!("{name}: {e:?}"));
+        let written = chap.source();
+        let again = Chapter::from_str(&written)
+            .unwrap_or_else(|e| panic!("{name}: written source doesn't parse: {e:?}\n{written}"));
+        assert_eq!(again, chap, "{name}");
+        assert_eq!(again.source(), written, "{name}");
+    }
+}
+
+const WORDS: [&str; 10] = [
+    "word",
+    "C#",
+    "#3",
+    "\\#",
+    "नमस्ते",
+    "漢字",
+    "it's",
+    "a-b",
+    "(x)",
+    "Haus",
+];
+const LANGUAGES: [&str; 3] = ["english", "german", "nepali"];
+
+fn text(max: usize) -> impl Strategy<Value = String> {
+    vec(select(&WORDS[..]), 1..=max).prop_map(|words| {
+        let text = words.join(" ");
+        // a line starting with `#` is a comment
+        match text.starts_with('#') {
+            true => format!("x {text}"),
+            false => text,
+        }
+    })
+}
+
+fn original() -> impl Strategy<Value = String> {
+    let gloss = (0..4usize, text(1), text(2), select(&LANGUAGES[..])).prop_map(
+        |(kind, word, meaning, lang)| match kind {
+            0 => format!("<< {word} = {meaning} >>"),
+            1 => format!("<< {word} >>"),
+            2 => format!("{{{{ruby:{word}|x}}}}"),
+            _ => format!("<<?{lang} {word}>>"),
+        },
+    );
+    (text(3), vec((gloss, text(2)), 0..4)).prop_map(|(first, rest)| {
+        let mut parts = vec![first];
+        for (gloss, text) in rest {
+            parts.push(gloss);
+            parts.push(text);
+        }
+        parts.join(" ")
+    })
+}
+
+fn attrs() -> impl Strategy<Value = String> {
+    vec(text(2), 0..3).prop_map(|values| {
+        values
+            .iter()
+            .enumerate()
+            .map(|(i, v)| format!("key{i} = {v}\n"))
+            .collect()
+    })
+}
+
+fn translation() -> impl Strategy<Value = String> {
+    (option::of(select(&LANGUAGES[..])), text(4), attrs()).prop_map(|(lang, text, attrs)| {
+        match lang {
+            Some(l) => format!("--- {l}\n{text}\n{attrs}"),
+            None => format!("---\n{text}\n{attrs}"),
+        }
+    })
+}
+
+fn document() -> impl Strategy<Value = String> {
+    let sentence = (original(), attrs(), vec(translation(), 0..3));
+    (text(3), any::<bool>(), vec(sentence, 1..5)).prop_map(|(title, tls, sentences)| {
+        let mut doc = format!("title = {title}\n");
+        if tls {
+            doc.push_str("tranlations = english, german\n");
+        }
+        for (label, (original, attrs, translations)) in sentences.into_iter().enumerate() {
+            doc.push_str(&format!("\n@ {label}\n{original}\n{attrs}"));
+            doc.push_str(&translations.concat());
+        }
+        doc
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(500))]
+
+    #[test]
+    fn generated_round_trip(src in document()) {
+        let chap = Chapter::from_str(&src).unwrap_or_else(|e| panic!("{e:?}\n{src}"));
+        let written = chap.source();
+        let again = Chapter::from_str(&written)
+            .unwrap_or_else(|e| panic!("written source doesn't parse: {e:?}\n{src}\n{written}"));
+        // sentences compare without their spans, which are checked against
+        // the written source on their own
+        prop_assert_eq!(&again, &chap, "\n{}\n{}", src, written);
+        for sent in &again.sentences {
+            let line = written.lines().nth(sent.span.line - 1).unwrap_or_default();
+            prop_assert_eq!(line, format!("@ {}", sent.label));
+            prop_assert!(written[sent.span.start..].starts_with(line));
+        }
+    }
+}